@@ -1,6 +1,7 @@
 use axum::{
-    extract::{Multipart, State, Query},
-    response::IntoResponse,
+    extract::{Multipart, Path, State, Query},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
     Json,
 };
 use std::sync::Arc;
@@ -9,12 +10,15 @@ use tokio::io::AsyncWriteExt;
 use uuid::Uuid;
 
 use crate::{
-    core::hash,
+    core::{hash, jobs::{Job, JobProgress, JobStore}, store::{AuditEntry, DirManifestStore, ManifestStore}, similarity::{compute_color_histogram, diff_images, histogram_similarity, embedding_similarity, image_in_video, pdq_distance_matrix, video_sequence_similarity, video_similarity_score, ColorSignature, DEFAULT_HISTOGRAM_BINS, MAX_COMPARED_FRAMES}},
     error::{AppError, Result},
-    models::manifest::{MediaManifest, MediaType},
+    models::manifest::{FrameSource, ManifestDetail, MediaManifest, MediaType, VerificationReport, VerifyOptions, DEFAULT_FRAME_TOLERANCE, PARTIAL_DECODE_KEY, TOO_SMALL_FOR_PDQ_KEY, WEBP_LOSSLESS_KEY},
+    state::{Config, ProcessingProfile},
     AppState,
 };
-use serde::Deserialize;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tokio_stream::StreamExt;
 
 use super::responses::ApiResponse;
 
@@ -23,31 +27,105 @@ use super::responses::ApiResponse;
 /// This endpoint accepts multipart form data with a "file" field.
 /// It computes cryptographic and perceptual hashes for images and videos,
 /// and returns a `MediaManifest` upon success.
-#[derive(Debug, Deserialize, Default)]
+#[derive(Debug, Clone, Deserialize, Default, JsonSchema)]
 pub struct UploadParams {
     pub include_embeddings: Option<bool>,
+    pub model: Option<String>,
     pub frame_interval_secs: Option<f64>,
     pub max_frames: Option<usize>,
     pub extract_frames: Option<bool>,
+    pub embed_keyframes_only: Option<bool>,
+    pub video_stream_index: Option<usize>,
+    pub min_entropy: Option<f32>,
+    pub algorithm: Option<String>,
+    pub detail: Option<ManifestDetail>,
+    pub profile: Option<String>,
+    pub meta_json: Option<String>,
+}
+
+/// Largest `options` or `meta` field [`upload_file`] accepts; it is buffered in memory.
+const MAX_OPTIONS_FIELD_SIZE: usize = 64 * 1024;
+
+impl UploadParams {
+    /// Fills every option left unset here from `fallback`.
+    fn or(self, fallback: Self) -> Self {
+        Self {
+            include_embeddings: self.include_embeddings.or(fallback.include_embeddings),
+            model: self.model.or(fallback.model),
+            frame_interval_secs: self.frame_interval_secs.or(fallback.frame_interval_secs),
+            max_frames: self.max_frames.or(fallback.max_frames),
+            extract_frames: self.extract_frames.or(fallback.extract_frames),
+            embed_keyframes_only: self.embed_keyframes_only.or(fallback.embed_keyframes_only),
+            video_stream_index: self.video_stream_index.or(fallback.video_stream_index),
+            min_entropy: self.min_entropy.or(fallback.min_entropy),
+            algorithm: self.algorithm.or(fallback.algorithm),
+            detail: self.detail.or(fallback.detail),
+            profile: self.profile.or(fallback.profile),
+            meta_json: self.meta_json.or(fallback.meta_json),
+        }
+    }
+}
+
+impl From<&ProcessingProfile> for UploadParams {
+    fn from(profile: &ProcessingProfile) -> Self {
+        Self {
+            include_embeddings: profile.include_embeddings,
+            model: None,
+            frame_interval_secs: profile.frame_interval_secs,
+            max_frames: profile.max_frames,
+            extract_frames: profile.extract_frames,
+            embed_keyframes_only: profile.embed_keyframes_only,
+            video_stream_index: profile.video_stream_index,
+            min_entropy: None,
+            algorithm: profile.algorithm.clone(),
+            detail: profile.detail,
+            profile: None,
+            meta_json: None,
+        }
+    }
 }
 
 /// Upload endpoint: accepts multipart form with a `file` field and optional query params.
 ///
+/// The same options can instead be sent as JSON in an `options` field, before
+/// or after `file`; options given there override the query string.
+///
 /// Query parameters:
 /// - `include_embeddings` (bool, default: false) — include image/frame embeddings.
+/// - `model` (string, default: `default`) — registered embedding provider computing them, recorded as `embedding_model`; implies `include_embeddings` unless that is set to false. Unknown names are rejected with `400 Bad Request`.
 /// - `extract_frames` (bool, default: true; video only) — enable/disable frame extraction.
 /// - `frame_interval_secs` (f64, default: 1.0; video only) — seconds between frames.
 /// - `max_frames` (usize, optional; video only) — cap number of processed frames; if omitted, the full video is processed.
+/// - `embed_keyframes_only` (bool, default: false; video only) — embed only frames that start a new scene.
+/// - `video_stream_index` (usize, default: 0; video only) — which video stream to extract frames from.
+/// - `min_entropy` (f32, optional; video only) — drop frames whose luminance entropy (0–8 bits, see `frame_entropy`) is below this, such as black intros and outros.
+/// - `algorithm` (string, default: `pdq`; image only) — registered perceptual algorithm used for `pdq_hash`.
+/// - `detail` (`full` | `minimal`, default: `full`; video only) — `minimal` keeps only the frame count and Merkle root of the frame hashes.
+/// - `profile` (string, optional) — name of a `Config::profiles` entry whose options fill in any of the above left unset; unknown names are rejected with `400 Bad Request`.
+/// - `meta_json` (string, optional) — JSON object of client metadata (album, owner, license, ...) stored under `metadata.user`.
+///
+/// Client metadata may instead be sent as a JSON object in a `meta` field,
+/// which wins over `meta_json`. Anything but an object is rejected with
+/// `400 Bad Request`.
 ///
 /// Returns a `MediaManifest` JSON with hashes, and optional embeddings
 /// (for videos, embeddings are per frame; for images, embedding is in `metadata.embedding`).
 pub async fn upload_file(
-    State(_state): State<Arc<AppState>>,
-    Query(params): Query<UploadParams>,
+    State(state): State<Arc<AppState>>,
+    Query(query_params): Query<UploadParams>,
     mut multipart: Multipart,
 ) -> Result<impl IntoResponse> {
+    let _upload = state.track_upload();
+    // Bound concurrent processing; the slot is held until the response is built
+    let _job = state.acquire_job_slot().await?;
+    let config = state.config();
+
     let mut file_name = None;
-    let mut temp_path = None;
+    let mut temp_path: Option<std::path::PathBuf> = None;
+    let mut file_hash = None;
+    let mut body_params = None;
+    let mut body_meta = None;
+    let mut progressive = None;
 
     // Process the multipart form data
     while let Some(field) = multipart.next_field().await.map_err(|e| {
@@ -56,9 +134,7 @@ pub async fn upload_file(
         let name = field.name().unwrap_or("").to_string();
         
         if name == "file" {
-            let file_name_field = field.file_name()
-                .ok_or_else(|| AppError::UploadError("No filename provided".to_string()))?
-                .to_string();
+            let file_name_field = safe_upload_name(field.file_name())?;
             
             let extension = std::path::Path::new(&file_name_field)
                 .extension()
@@ -67,45 +143,130 @@ pub async fn upload_file(
                 .to_lowercase();
             
             let temp_file_name = format!("{}.{}", Uuid::new_v4(), extension);
-            let temp_file_path = std::env::temp_dir().join(&temp_file_name);
+            tokio::fs::create_dir_all(&config.upload_temp_dir).await?;
+            let temp_file_path = config.upload_temp_dir.join(&temp_file_name);
             
+            // Videos start decoding with the options known so far; an
+            // `options` field sent after the file may still change them
+            progressive = match MediaType::from_path(&file_name_field) {
+                MediaType::Video if cfg!(feature = "video") && !config.fast_stub_hashes => {
+                    resolve_params(body_params.clone(), query_params.clone(), &config)
+                        .ok()
+                        .and_then(|params| FrameSettings::of(&params))
+                        .map(|settings| ProgressiveExtraction::start(&state, settings, &temp_file_path, &config))
+                }
+                _ => None,
+            };
+
             let mut temp_file = File::create(&temp_file_path).await?;
-            // Stream the field content to disk to avoid buffering the whole file in memory
+            // Stream the field content to disk to avoid buffering the whole file in memory,
+            // checking the size before each write so an oversized field never fills the disk.
+            // The SHA3 file hash is computed as the bytes arrive, so it is ready once they are in,
+            // and video frames are extracted from them as they arrive too
             let mut field_stream = field;
-            while let Some(chunk) = field_stream
-                .chunk()
-                .await
-                .map_err(|e| AppError::UploadError(format!("Failed to read file content: {}", e)))?
-            {
-                temp_file.write_all(&chunk).await?;
+            let mut hasher = hash::IncrementalFileHash::new();
+            let streamed: Result<()> = async {
+                while let Some(chunk) = field_stream
+                    .chunk()
+                    .await
+                    .map_err(|e| AppError::UploadError(format!("Failed to read file content: {}", e)))?
+                {
+                    ensure_within_upload_limit(hasher.len() + chunk.len() as u64, config.max_upload_size)?;
+                    hasher.update(&chunk);
+                    temp_file.write_all(&chunk).await?;
+                    if let Some(extraction) = &mut progressive {
+                        extraction.feed(chunk).await;
+                    }
+                }
+                temp_file.flush().await?;
+                Ok(())
+            }
+            .await;
+            if let Err(e) = streamed {
+                drop(temp_file);
+                let _ = tokio::fs::remove_file(&temp_file_path).await;
+                return Err(e);
             }
-            temp_file.flush().await?;
             
             file_name = Some(file_name_field);
             temp_path = Some(temp_file_path);
+            file_hash = Some(hasher.finalize());
+        } else if name == "options" {
+            // Small enough to buffer, so it may arrive on either side of the file
+            match read_options_field(field).await {
+                Ok(options) => body_params = Some(options),
+                Err(e) => {
+                    if let Some(path) = &temp_path {
+                        let _ = tokio::fs::remove_file(path).await;
+                    }
+                    return Err(e);
+                }
+            }
+        } else if name == "meta" {
+            match read_small_field(field, "meta").await.and_then(|data| parse_user_metadata(&data)) {
+                Ok(meta) => body_meta = Some(meta),
+                Err(e) => {
+                    if let Some(path) = &temp_path {
+                        let _ = tokio::fs::remove_file(path).await;
+                    }
+                    return Err(e);
+                }
+            }
         }
     }
 
+    // A named profile fills in whatever the request left unset
+    let params = match resolve_params(body_params, query_params, &config) {
+        Ok(params) => params,
+        Err(e) => {
+            if let Some(path) = &temp_path {
+                let _ = tokio::fs::remove_file(path).await;
+            }
+            return Err(e);
+        }
+    };
+
+    let embedding_provider = state
+        .embedding_providers
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(params.model.as_deref());
+    let embedding_provider = match embedding_provider {
+        Ok(provider) => provider,
+        Err(e) => {
+            if let Some(path) = &temp_path {
+                let _ = tokio::fs::remove_file(path).await;
+            }
+            return Err(AppError::Validation(e.to_string()));
+        }
+    };
+
+    let user_metadata = match (body_meta, params.meta_json.as_deref()) {
+        (Some(meta), _) => Some(meta),
+        (None, Some(json)) => match parse_user_metadata(json.as_bytes()) {
+            Ok(meta) => Some(meta),
+            Err(e) => {
+                if let Some(path) = &temp_path {
+                    let _ = tokio::fs::remove_file(path).await;
+                }
+                return Err(e);
+            }
+        },
+        (None, None) => None,
+    };
+
     let temp_path = temp_path.ok_or_else(|| AppError::UploadError("No file provided".to_string()))?;
     let file_name = file_name.unwrap_or_else(|| "unknown".to_string());
+    let file_hash = file_hash.unwrap_or_default();
+
+    if tokio::fs::metadata(&temp_path).await?.len() == 0 {
+        let err = AppError::Validation("empty file".to_string());
+        set_aside(&config, &temp_path, &file_name, &err).await;
+        return Err(err);
+    }
     
     // Process the file based on its type
-    let extension = std::path::Path::new(&file_name)
-        .extension()
-        .and_then(|s| s.to_str())
-        .unwrap_or("")
-        .to_lowercase();
-    
-    let media_type = if ["jpg", "jpeg", "png", "gif", "bmp", "webp"].contains(&extension.as_str()) {
-        MediaType::Image
-    } else if ["mp4", "avi", "mov", "mkv", "webm"].contains(&extension.as_str()) {
-        MediaType::Video
-    } else {
-        MediaType::Other
-    };
-    
-    // Compute file hash
-    let file_hash = hash::compute_file_hash(&temp_path)?;
+    let media_type = MediaType::from_path(&file_name);
     
     // Process based on media type
     let new_file_name = temp_path
@@ -115,75 +276,179 @@ pub async fn upload_file(
         .to_string();
 
     // Resolve flags with defaults
-    let include_embeddings = params.include_embeddings.unwrap_or(false);
+    let include_embeddings = params.include_embeddings.unwrap_or(params.model.is_some());
+    let embedding_provider = include_embeddings.then_some(embedding_provider);
+    let embedding_model = embedding_provider.as_ref().map(|provider| provider.name().to_string());
+    let frame_settings = FrameSettings::of(&params);
     let frame_interval = params.frame_interval_secs.unwrap_or(1.0);
     let frame_interval = if frame_interval > 0.0 { frame_interval } else { 1.0 };
     let max_frames = params.max_frames;
-    let extract_frames_flag = params.extract_frames.unwrap_or(true);
+    let extract_frames_flag = frame_settings.is_some();
+    let embed_keyframes_only = params.embed_keyframes_only.unwrap_or(false);
+    let video_stream_index = params.video_stream_index.unwrap_or(0);
+    let min_entropy = params.min_entropy;
+    let (frame_width, frame_height) = (config.video.target_width, config.video.target_height);
 
-    let manifest = match media_type {
+    let mut manifest = match media_type {
+        // Placeholder hashes for pipeline testing; nothing is decoded
+        _ if config.fast_stub_hashes => {
+            MediaManifest::stub(new_file_name, &temp_path, media_type, &file_name)?
+        }
         MediaType::Image => {
-            // Process image
-            let img = image::open(&temp_path)?;
-            let pdq_hash = hash::compute_pdq_hash(&img)?;
+            // Process image, salvaging what a damaged file still holds
+            let decoded = tokio::fs::read(&temp_path)
+                .await
+                .map_err(anyhow::Error::from)
+                .and_then(|data| {
+                    // Animations record their frame count, which verification expects back
+                    let frame_count = hash::image_frame_count(&data).ok().filter(|&count| count > 1);
+                    Ok((hash::decode_lenient(&data)?, hash::webp_is_lossless(&data), frame_count))
+                });
+            let ((img, partial), webp_lossless, frame_count) = match decoded {
+                Ok(decoded) => decoded,
+                Err(e) => {
+                    let err = AppError::InvalidInput(format!("Failed to decode image {}: {}", file_name, e));
+                    set_aside(&config, &temp_path, &file_name, &err).await;
+                    return Err(err);
+                }
+            };
+            let algorithm = crate::core::perceptual::algorithm(params.algorithm.as_deref())
+                .map_err(|e| AppError::Validation(e.to_string()))?;
+            let custom_algorithm = (algorithm.name() != crate::core::perceptual::DEFAULT_ALGORITHM)
+                .then_some(algorithm);
+            let digest = digest_image(
+                &temp_path,
+                file_hash,
+                Arc::new(img),
+                custom_algorithm.clone(),
+                embedding_provider,
+                &config,
+            )
+            .await?;
 
             // Optional embedding for image stored in metadata
-            let mut metadata: Option<serde_json::Value> = None;
-            if include_embeddings {
-                let embedding_opt = crate::core::embeddings::compute_image_embedding(&img).await?;
-                if let Some(embedding) = embedding_opt {
-                    metadata = Some(serde_json::json!({ "embedding": embedding }));
-                }
+            let embedding_dim = digest.embedding.as_ref().map(Vec::len);
+            let mut metadata = serde_json::Map::new();
+            if let Some(embedding) = digest.embedding {
+                metadata.insert("embedding".to_string(), serde_json::json!(embedding));
+            }
+            if partial {
+                log::warn!("Image {} was only partially decoded", file_name);
+                metadata.insert(PARTIAL_DECODE_KEY.to_string(), serde_json::Value::Bool(true));
+            }
+            if let Some(lossless) = webp_lossless {
+                metadata.insert(WEBP_LOSSLESS_KEY.to_string(), serde_json::Value::Bool(lossless));
             }
+            if digest.pixels.perceptual_hash.is_none() {
+                log::warn!("Image {} is too small for a perceptual hash", file_name);
+                metadata.insert(TOO_SMALL_FOR_PDQ_KEY.to_string(), serde_json::Value::Bool(true));
+            }
+            let metadata = (!metadata.is_empty()).then_some(serde_json::Value::Object(metadata));
 
-            MediaManifest::new(
+            let mut manifest = MediaManifest::new(
                 new_file_name,
                 &temp_path,
                 MediaType::Image,
-                file_hash,
-                Some(pdq_hash),
+                digest.file_hash,
+                digest.pixels.perceptual_hash,
                 None,
                 metadata,
-            )?
+            )?;
+            if let Some(algorithm) = custom_algorithm {
+                manifest.pdq_format = None;
+                manifest.perceptual_algorithm = Some(algorithm.name().to_string());
+                manifest.algorithm_versions =
+                    crate::models::manifest::current_algorithm_versions(Some(algorithm.name()));
+            }
+            manifest.color_signature = Some(digest.pixels.color_signature);
+            manifest.color_histogram = Some(digest.pixels.color_histogram);
+            manifest.content_hash = Some(digest.pixels.content_hash);
+            manifest.stream_hash = digest.pixels.stream_hash;
+            manifest.frame_count = frame_count;
+            manifest.embedding_dim = embedding_dim;
+            manifest.embedding_model = embedding_dim.and(embedding_model);
+            manifest
         }
         MediaType::Video => {
-            // Extract frames and compute PDQ per frame, with optional embeddings
+            // Extract frames and compute PDQ per frame, with optional embeddings. Frames
+            // decoded while the upload streamed in are used when ffmpeg could read it that way
             let mut frames_info: Vec<crate::models::manifest::FrameInfo> = Vec::new();
-            if extract_frames_flag {
-                let mut frames_images = crate::core::video::extract_frames(&temp_path, frame_interval)?;
+            if let Some(settings) = &frame_settings {
+                let extractor = settings.extractor(&temp_path, &config);
+                let streamed = match progressive {
+                    Some(extraction) => extraction.finish(settings).await,
+                    None => None,
+                };
+                let mut frames_images = match streamed {
+                    Some(frames) => {
+                        // The duration limit needs the complete file to probe
+                        extractor.check_budget().map_err(frame_extraction_error)?;
+                        frames
+                    }
+                    None => {
+                        let mut frames = Vec::new();
+                        extractor
+                            .extract_frames(|frame, timestamp| {
+                                frames.push((frame, timestamp));
+                                Ok(())
+                            })
+                            .map_err(frame_extraction_error)?;
+                        frames
+                    }
+                };
                 if let Some(limit) = max_frames {
                     if frames_images.len() > limit {
                         frames_images.truncate(limit);
                     }
                 }
-                for (i, img) in frames_images.iter().enumerate() {
-                    let pdq = hash::compute_pdq_hash(img)?;
-                    let embedding = if include_embeddings {
-                        crate::core::embeddings::compute_image_embedding(img).await?
-                    } else {
-                        None
+                let pdq_hashes = frames_images
+                    .iter()
+                    .map(|(img, _)| hash::compute_pdq_hash_with(img, config.parallel_hashing))
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                let keyframes = if embed_keyframes_only {
+                    crate::core::video::keyframe_indices(&pdq_hashes, crate::core::video::DEFAULT_KEYFRAME_THRESHOLD)
+                } else {
+                    (0..pdq_hashes.len()).collect()
+                };
+                // Timestamps come from the extractor, since dropped frames leave gaps
+                for (i, ((img, timestamp), pdq)) in frames_images.iter().zip(pdq_hashes).enumerate() {
+                    let embedding = match &embedding_provider {
+                        Some(provider) if keyframes.binary_search(&i).is_ok() => {
+                            crate::core::embeddings::embed_checked(provider.as_ref(), img).await?
+                        }
+                        _ => None,
                     };
                     frames_info.push(crate::models::manifest::FrameInfo {
-                        timestamp_secs: (i as f64) * frame_interval,
+                        timestamp_secs: *timestamp,
                         pdq_hash: pdq,
                         embedding,
                     });
                 }
             }
 
+            let embedding_dim = frames_info.iter().find_map(|f| f.embedding.as_ref().map(Vec::len));
+            let video_embedding = crate::core::similarity::pool_frame_embeddings(&frames_info);
+            let frame_source = (!frames_info.is_empty()).then_some(FrameSource::VideoFfmpeg);
+
             // Include basic metadata
             let metadata = serde_json::json!({
                 "frame_interval_secs": frame_interval,
                 "frame_count": frames_info.len(),
                 "max_frames": max_frames,
                 "extracted_frames": extract_frames_flag,
+                "embed_keyframes_only": embed_keyframes_only,
+                "video_stream_index": video_stream_index,
+                "min_entropy": min_entropy,
+                "frame_width": frame_width,
+                "frame_height": frame_height,
+                "embedded_frame_count": frames_info.iter().filter(|f| f.embedding.is_some()).count(),
                 "original_extension": std::path::Path::new(&file_name)
                     .extension()
                     .and_then(|s| s.to_str())
                     .unwrap_or("")
             });
 
-            MediaManifest::new(
+            let mut manifest = MediaManifest::new(
                 new_file_name,
                 &temp_path,
                 MediaType::Video,
@@ -191,7 +456,45 @@ pub async fn upload_file(
                 None, // No single PDQ hash for video
                 Some(frames_info),
                 Some(metadata),
-            )?
+            )?;
+            manifest.frame_source = frame_source;
+            manifest.embedding_dim = embedding_dim;
+            manifest.embedding_model = embedding_dim.and(embedding_model);
+            manifest.video_embedding = video_embedding;
+            match params.detail.unwrap_or_default() {
+                ManifestDetail::Full => {
+                    manifest.seal_frames()?;
+                    manifest
+                }
+                ManifestDetail::Minimal => manifest.into_minimal()?,
+            }
+        }
+        MediaType::Document => {
+            let data = tokio::fs::read(&temp_path).await?;
+            let document_name = file_name.clone();
+            let pages = state
+                .spawn_blocking_job(move || crate::core::document::page_frames(&document_name, &data))
+                .await?;
+            let (frame_source, pages) = match pages {
+                Ok(pages) => pages,
+                Err(err) => {
+                    set_aside(&config, &temp_path, &file_name, &err).await;
+                    return Err(err);
+                }
+            };
+            let metadata = serde_json::json!({ "page_count": pages.len() });
+
+            let mut manifest = MediaManifest::new(
+                new_file_name,
+                &temp_path,
+                MediaType::Document,
+                file_hash,
+                None, // Documents are hashed per page
+                Some(pages),
+                Some(metadata),
+            )?;
+            manifest.frame_source = Some(frame_source);
+            manifest
         }
         MediaType::Other => {
             // Create a basic manifest for other file types
@@ -207,35 +510,1863 @@ pub async fn upload_file(
         }
     };
     
+    if let Some(user_metadata) = user_metadata {
+        manifest.set_user_metadata(user_metadata);
+    }
+    
     // Create uploads directory if it doesn't exist
-    let uploads_dir = std::env::current_dir()?.join("uploads");
+    let uploads_dir = state.config().upload_dir.clone();
     tokio::fs::create_dir_all(&uploads_dir).await?;
 
+    // Store the file under its content fingerprint, so identical uploads share a key
+    manifest.file_name = match std::path::Path::new(&manifest.file_name).extension().and_then(|e| e.to_str()) {
+        Some(extension) if !extension.is_empty() => format!("{}.{}", manifest.fingerprint(), extension),
+        _ => manifest.fingerprint(),
+    };
+
     // Move the file to the uploads directory
     let new_file_name = manifest.file_name.clone();
     let dest_path = uploads_dir.join(&new_file_name);
-    tokio::fs::rename(&temp_path, dest_path).await?;
-    
-    // In a real application, you'd save the manifest to a database
+    if dest_path.parent() != Some(uploads_dir.as_path()) {
+        let _ = tokio::fs::remove_file(&temp_path).await;
+        return Err(AppError::Validation(format!("invalid file name: {:?}", new_file_name)));
+    }
+    move_file(&temp_path, &dest_path).await?;
+
+    // Persist the manifest next to the file; startup reconciliation catches a crash in between
+    if let Some(manifest_dir) = &config.manifest_dir {
+        DirManifestStore::new(manifest_dir).save(&manifest)?;
+    }
+    state.index_manifest(&manifest)?;
     
     Ok(Json(ApiResponse::success(manifest)))
 }
 
-/// Verifies the integrity of a file against a provided `MediaManifest`.
+/// Merges an upload's `options` field over its query, then fills the gaps from the named profile.
+fn resolve_params(body: Option<UploadParams>, query: UploadParams, config: &Config) -> Result<UploadParams> {
+    let params = match body {
+        Some(options) => options.or(query),
+        None => query,
+    };
+    match params.profile.clone() {
+        None => Ok(params),
+        Some(name) => match config.profiles.get(&name) {
+            Some(profile) => Ok(params.or(UploadParams::from(profile))),
+            None => Err(AppError::Validation(format!("unknown processing profile: {}", name))),
+        },
+    }
+}
+
+/// The upload options that shape video frame extraction.
+#[derive(Debug, Clone, PartialEq)]
+struct FrameSettings {
+    interval_secs: f64,
+    video_stream_index: usize,
+    min_entropy: Option<f32>,
+}
+
+impl FrameSettings {
+    /// The settings `params` ask for, or `None` when they turn extraction off.
+    fn of(params: &UploadParams) -> Option<Self> {
+        let interval_secs = params.frame_interval_secs.filter(|secs| *secs > 0.0).unwrap_or(1.0);
+        params.extract_frames.unwrap_or(true).then(|| Self {
+            interval_secs,
+            video_stream_index: params.video_stream_index.unwrap_or(0),
+            min_entropy: params.min_entropy,
+        })
+    }
+
+    /// An extractor for the video at `path`, sized and budgeted by `config`.
+    fn extractor(&self, path: &std::path::Path, config: &Config) -> crate::core::video::FrameExtractor {
+        let extractor = crate::core::video::FrameExtractor::new(path, self.interval_secs)
+            .with_video_stream(self.video_stream_index)
+            .with_target_size(config.video.target_width, config.video.target_height)
+            .with_budget(crate::core::video::FrameBudget {
+                max_duration_secs: Some(config.max_video_duration_secs),
+                max_frames: Some(config.max_total_frames),
+            });
+        match self.min_entropy {
+            Some(min_entropy) => extractor.with_min_entropy(min_entropy),
+            None => extractor,
+        }
+    }
+}
+
+/// Chunks of an upload queued for ffmpeg before the client is slowed down.
+const PROGRESSIVE_BACKLOG: usize = 64;
+
+/// Video frame extraction fed with an upload's bytes while they stream in.
 ///
-/// This endpoint checks if a file on disk matches the metadata and hashes
-/// stored in the manifest.
-pub async fn verify_manifest(
-    State(_state): State<Arc<AppState>>,
-    Json(manifest): Json<MediaManifest>,
+/// ffmpeg reads the upload from a pipe as the client sends it, so most
+/// frames are decoded by the time the last byte arrives. Containers that
+/// cannot be parsed front to back (such as MP4 files with the index at the
+/// end) make ffmpeg fail; [`finish`](Self::finish) then returns `None` and the
+/// frames are extracted from the complete file instead.
+struct ProgressiveExtraction {
+    settings: FrameSettings,
+    sender: Option<tokio::sync::mpsc::Sender<bytes::Bytes>>,
+    frames: tokio::task::JoinHandle<anyhow::Result<Vec<(image::DynamicImage, f64)>>>,
+}
+
+impl ProgressiveExtraction {
+    /// Starts ffmpeg on the bytes to come, extracting with `settings`.
+    fn start(state: &AppState, settings: FrameSettings, path: &std::path::Path, config: &Config) -> Self {
+        let (sender, receiver) = tokio::sync::mpsc::channel(PROGRESSIVE_BACKLOG);
+        let extractor = settings.extractor(path, config);
+        let frames = state.spawn_blocking_job(move || {
+            let mut frames = Vec::new();
+            let reader = ChannelReader { receiver, chunk: bytes::Bytes::new() };
+            extractor.extract_frames_from_reader(reader, |frame, timestamp| {
+                frames.push((frame, timestamp));
+                Ok(())
+            })?;
+            Ok(frames)
+        });
+        Self { settings, sender: Some(sender), frames }
+    }
+
+    /// Passes the next bytes of the upload to ffmpeg.
+    async fn feed(&mut self, chunk: bytes::Bytes) {
+        if let Some(sender) = &self.sender {
+            // ffmpeg gave up on the stream; the complete file is extracted instead
+            if sender.send(chunk).await.is_err() {
+                self.sender = None;
+            }
+        }
+    }
+
+    /// The extracted frames, if ffmpeg read the whole upload with the `settings` finally in effect.
+    async fn finish(mut self, settings: &FrameSettings) -> Option<Vec<(image::DynamicImage, f64)>> {
+        // Closing the channel is the end of ffmpeg's input
+        self.sender = None;
+        if *settings != self.settings {
+            return None;
+        }
+        match self.frames.await {
+            // No frames at all more likely means an unreadable stream than an empty video
+            Ok(Ok(frames)) if !frames.is_empty() => Some(frames),
+            Ok(Ok(_)) => None,
+            Ok(Err(e)) => {
+                log::debug!("Progressive frame extraction failed, extracting from the file: {}", e);
+                None
+            }
+            Err(_) => None,
+        }
+    }
+}
+
+/// Reads the chunks sent through a channel, blocking until each arrives.
+struct ChannelReader {
+    receiver: tokio::sync::mpsc::Receiver<bytes::Bytes>,
+    chunk: bytes::Bytes,
+}
+
+impl std::io::Read for ChannelReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.chunk.is_empty() {
+            match self.receiver.blocking_recv() {
+                Some(chunk) => self.chunk = chunk,
+                None => return Ok(0),
+            }
+        }
+        let len = buf.len().min(self.chunk.len());
+        buf[..len].copy_from_slice(&self.chunk.split_to(len));
+        Ok(len)
+    }
+}
+
+/// Maps a frame extraction error, reporting an exceeded budget as a bad request.
+fn frame_extraction_error(e: anyhow::Error) -> AppError {
+    match e.downcast::<crate::core::video::BudgetExceeded>() {
+        Ok(exceeded) => AppError::Validation(exceeded.to_string()),
+        Err(e) => e.into(),
+    }
+}
+
+/// Parses the JSON `options` field of an upload, capped at [`MAX_OPTIONS_FIELD_SIZE`].
+async fn read_options_field(field: axum::extract::multipart::Field<'_>) -> Result<UploadParams> {
+    let data = read_small_field(field, "options").await?;
+    serde_json::from_slice(&data).map_err(|e| AppError::Validation(format!("invalid options: {}", e)))
+}
+
+/// Buffers a small multipart field named `what`, capped at [`MAX_OPTIONS_FIELD_SIZE`].
+async fn read_small_field(mut field: axum::extract::multipart::Field<'_>, what: &str) -> Result<Vec<u8>> {
+    let mut data = Vec::new();
+    while let Some(chunk) = field
+        .chunk()
+        .await
+        .map_err(|e| AppError::UploadError(format!("Failed to read {}: {}", what, e)))?
+    {
+        if data.len() + chunk.len() > MAX_OPTIONS_FIELD_SIZE {
+            return Err(AppError::Validation(format!(
+                "{} field exceeds {} bytes",
+                what, MAX_OPTIONS_FIELD_SIZE
+            )));
+        }
+        data.extend_from_slice(&chunk);
+    }
+    Ok(data)
+}
+
+/// Parses client metadata for an upload, which must be a JSON object.
+fn parse_user_metadata(data: &[u8]) -> Result<serde_json::Map<String, serde_json::Value>> {
+    match serde_json::from_slice(data) {
+        Ok(serde_json::Value::Object(fields)) => Ok(fields),
+        Ok(other) => Err(AppError::InvalidInput(format!("metadata must be a JSON object, got {}", other))),
+        Err(e) => Err(AppError::InvalidInput(format!("invalid metadata JSON: {}", e))),
+    }
+}
+
+/// Moves `from` to `to`, copying across filesystems where a rename can't.
+///
+/// The temp directory is often a tmpfs while uploads live on a mounted
+/// volume, so renaming fails with `EXDEV`; the file is then streamed to a
+/// hidden `.part` file next to `to`, renamed into place and the original
+/// deleted, so `to` never holds a partial copy.
+async fn move_file(from: &std::path::Path, to: &std::path::Path) -> std::io::Result<()> {
+    move_file_with(from, to, tokio::fs::rename).await
+}
+
+/// [`move_file`] with the first rename done by `rename`, so tests can make it fail.
+async fn move_file_with<F, Fut>(from: &std::path::Path, to: &std::path::Path, rename: F) -> std::io::Result<()>
+where
+    F: FnOnce(std::path::PathBuf, std::path::PathBuf) -> Fut,
+    Fut: std::future::Future<Output = std::io::Result<()>>,
+{
+    match rename(from.to_path_buf(), to.to_path_buf()).await {
+        Err(e) if e.kind() == std::io::ErrorKind::CrossesDevices => {
+            log::debug!("{} is on another filesystem than {}; copying", from.display(), to.display());
+            let part = to.with_file_name(format!(".{}.part", to.file_name().unwrap_or_default().to_string_lossy()));
+            let copied = async {
+                tokio::fs::copy(from, &part).await?;
+                tokio::fs::rename(&part, to).await
+            }
+            .await;
+            if copied.is_err() {
+                let _ = tokio::fs::remove_file(&part).await;
+                return copied;
+            }
+            tokio::fs::remove_file(from).await
+        }
+        other => other,
+    }
+}
+
+/// Disposes of an upload that failed integrity checks.
+///
+/// With `Config::quarantine_dir` set, the file is moved there next to a
+/// `<name>.reason.txt` sidecar naming the original file and the failure, so it
+/// can be inspected later; otherwise it is deleted. Failures here are only
+/// logged, since the client is getting the original error either way.
+async fn set_aside(config: &Config, temp_path: &std::path::Path, file_name: &str, reason: &AppError) {
+    let Some(quarantine_dir) = &config.quarantine_dir else {
+        let _ = tokio::fs::remove_file(temp_path).await;
+        return;
+    };
+
+    let stored_name = temp_path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+    let dest = quarantine_dir.join(&stored_name);
+    let moved = async {
+        tokio::fs::create_dir_all(quarantine_dir).await?;
+        move_file(temp_path, &dest).await?;
+        let note = format!("file: {}\nreason: {}\n", file_name, reason);
+        tokio::fs::write(quarantine_dir.join(format!("{}.reason.txt", stored_name)), note).await
+    }
+    .await;
+
+    match moved {
+        Ok(()) => log::warn!("Quarantined {} as {}: {}", file_name, dest.display(), reason),
+        Err(e) => {
+            log::error!("Failed to quarantine {}: {}", file_name, e);
+            let _ = tokio::fs::remove_file(temp_path).await;
+        }
+    }
+}
+
+/// Sanitizes a multipart filename with [`sanitize_filename`](crate::utils::sanitize_filename).
+///
+/// Missing names and names with nothing left after sanitizing (`..`, `dir/`)
+/// are rejected.
+fn safe_upload_name(name: Option<&str>) -> Result<String> {
+    let name = name.ok_or_else(|| AppError::UploadError("No filename provided".to_string()))?;
+    let sanitized = crate::utils::sanitize_filename(name);
+    if sanitized.is_empty() {
+        return Err(AppError::Validation(format!("invalid file name: {:?}", name)));
+    }
+    Ok(sanitized)
+}
+
+/// Rejects an upload once `received` bytes exceed the configured `limit`.
+fn ensure_within_upload_limit(received: u64, limit: u64) -> Result<()> {
+    if received > limit {
+        return Err(AppError::Validation(format!(
+            "file exceeds the maximum upload size of {} bytes",
+            limit
+        )));
+    }
+    Ok(())
+}
+
+/// Hashes and optional embedding of an uploaded image.
+#[derive(Debug)]
+struct ImageDigest {
+    file_hash: String,
+    pixels: PixelDigest,
+    embedding: Option<Vec<f32>>,
+}
+
+/// Everything derived from an image's pixels and encoded stream.
+#[derive(Debug)]
+struct PixelDigest {
+    /// `None` for PDQ on images smaller than `Config::min_pdq_dimension`
+    perceptual_hash: Option<String>,
+    color_signature: ColorSignature,
+    color_histogram: Vec<f32>,
+    content_hash: String,
+    stream_hash: Option<String>,
+}
+
+/// Computes everything an image manifest needs from the stored file and its decoded pixels.
+///
+/// `file_hash` is the SHA3 hash taken while the upload streamed in. The
+/// perceptual hashes (CPU-bound) and the embedding (a model or remote call)
+/// are independent, so with `config.concurrent_pipeline` the former run on a
+/// blocking thread while the embedding is awaited. Otherwise they run one after
+/// another. `algorithm` replaces the built-in PDQ hash when set; the image is
+/// only embedded when `embedding_provider` is set.
+async fn digest_image(
+    path: &std::path::Path,
+    file_hash: String,
+    img: Arc<image::DynamicImage>,
+    algorithm: Option<Arc<dyn crate::core::perceptual::PerceptualAlgorithm>>,
+    embedding_provider: Option<Arc<dyn crate::core::embeddings::EmbeddingProvider>>,
+    config: &Config,
+) -> Result<ImageDigest> {
+    let pixels = {
+        let (path, img, parallel) = (path.to_path_buf(), img.clone(), config.parallel_hashing);
+        let min_dimension = config.min_pdq_dimension;
+        move || pixel_digest(&path, &img, algorithm.as_deref(), parallel, min_dimension)
+    };
+    let embedding = async {
+        match &embedding_provider {
+            Some(provider) => crate::core::embeddings::embed_checked(provider.as_ref(), &img).await,
+            None => Ok(None),
+        }
+    };
+
+    let (pixels, embedding) = if config.concurrent_pipeline {
+        let (pixels, embedding) = tokio::join!(tokio::task::spawn_blocking(pixels), embedding);
+        (pixels??, embedding?)
+    } else {
+        (pixels()?, embedding.await?)
+    };
+
+    Ok(ImageDigest { file_hash, pixels, embedding })
+}
+
+/// Perceptual hash, colour summaries, content hash and stream hash of an image.
+fn pixel_digest(
+    path: &std::path::Path,
+    img: &image::DynamicImage,
+    algorithm: Option<&dyn crate::core::perceptual::PerceptualAlgorithm>,
+    parallel: bool,
+    min_dimension: u32,
+) -> anyhow::Result<PixelDigest> {
+    let perceptual_hash = match algorithm {
+        Some(algorithm) => Some(algorithm.hash(img)?),
+        None if hash::too_small_for_pdq(img, min_dimension) => None,
+        None => Some(hash::compute_pdq_hash_with(img, parallel)?),
+    };
+    let data = std::fs::read(path)?;
+    let content_hash = match hash::exif_orientation(&data) {
+        1 => hash::compute_content_hash(img)?,
+        orientation => hash::compute_content_hash(&hash::apply_orientation(img.clone(), orientation))?,
+    };
+
+    Ok(PixelDigest {
+        perceptual_hash,
+        color_signature: ColorSignature::compute(img),
+        color_histogram: compute_color_histogram(img, DEFAULT_HISTOGRAM_BINS),
+        content_hash,
+        stream_hash: hash::stream_hash(&data),
+    })
+}
+
+/// Reads the `file` field of a multipart upload into memory
+///
+/// Fails as soon as the field grows past `limit`, and rejects empty files.
+async fn read_upload_in_memory(multipart: &mut Multipart, limit: u64) -> Result<(String, Vec<u8>)> {
+    let (file_name, data) = read_upload_with(multipart, limit, |_| Vec::new(), |data, chunk| {
+        data.extend_from_slice(chunk)
+    })
+    .await?;
+    Ok((file_name, data))
+}
+
+/// Streams the `file` field of a multipart upload through `feed` chunk by chunk
+///
+/// `start` is given the file name and makes the state `feed` folds the
+/// chunks into, so callers choose how much of the file to hold. Fails as soon
+/// as the field grows past `limit`, and rejects empty files.
+async fn read_upload_with<T>(
+    multipart: &mut Multipart,
+    limit: u64,
+    mut start: impl FnMut(&str) -> T,
+    mut feed: impl FnMut(&mut T, &[u8]),
+) -> Result<(String, T)> {
+    let mut upload = None;
+    while let Some(mut field) = multipart.next_field().await.map_err(|e| {
+        AppError::UploadError(format!("Failed to read multipart field: {}", e))
+    })? {
+        if field.name() != Some("file") {
+            continue;
+        }
+        let file_name = safe_upload_name(field.file_name())?;
+
+        let mut state = start(&file_name);
+        let mut len = 0u64;
+        while let Some(chunk) = field
+            .chunk()
+            .await
+            .map_err(|e| AppError::UploadError(format!("Failed to read file content: {}", e)))?
+        {
+            len += chunk.len() as u64;
+            ensure_within_upload_limit(len, limit)?;
+            feed(&mut state, &chunk);
+        }
+        upload = Some((file_name, state, len));
+    }
+
+    let (file_name, state, len) = upload.ok_or_else(|| AppError::UploadError("No file provided".to_string()))?;
+    if len == 0 {
+        return Err(AppError::Validation("empty file".to_string()));
+    }
+
+    Ok((file_name, state))
+}
+
+/// Hashes computed by [`hash_file`].
+#[derive(Debug, Serialize, JsonSchema)]
+pub(crate) struct HashResult {
+    /// SHA3-256 of the file bytes.
+    pub sha3_256: String,
+    /// PDQ perceptual hash (images at least `MIN_PDQ_DIMENSION` pixels across only).
+    pub pdq: Option<String>,
+}
+
+/// Hash-only endpoint: accepts multipart form with a `file` field and returns
+/// its SHA3-256 and, for images, PDQ hash.
+///
+/// Nothing is persisted: the file is never written to the uploads directory
+/// and not added to the similarity index. The SHA3-256 is computed as the
+/// upload streams in, and only images, which must be decoded for PDQ, are
+/// held in memory. Files larger than `max_upload_size` are rejected as soon
+/// as the limit is crossed.
+pub async fn hash_file(
+    State(state): State<Arc<AppState>>,
+    mut multipart: Multipart,
 ) -> Result<impl IntoResponse> {
-    // In a real application, you'd look up the file path based on the manifest
-    // For this example, we'll assume the file is in the uploads directory
-    let uploads_dir = std::env::current_dir()?.join("uploads");
-    let file_path = uploads_dir.join(&manifest.file_name);
-    
-    let is_valid = manifest.verify(&file_path)?;
-    
-    Ok(Json(ApiResponse::success(serde_json::json!({ "is_valid": is_valid }))))
+    let _job = state.acquire_job_slot().await?;
+    let (file_name, (hasher, image_data)) = read_upload_with(
+        &mut multipart,
+        state.config().max_upload_size,
+        |name| (hash::IncrementalFileHash::new(), matches!(MediaType::from_path(name), MediaType::Image).then(Vec::new)),
+        |(hasher, image_data), chunk| {
+            hasher.update(chunk);
+            if let Some(data) = image_data {
+                data.extend_from_slice(chunk);
+            }
+        },
+    )
+    .await?;
+
+    let sha3_256 = hasher.finalize();
+    let pdq = match image_data {
+        Some(data) => {
+            let img = image::load_from_memory(&data).map_err(|e| {
+                AppError::InvalidInput(format!("Failed to decode image {}: {}", file_name, e))
+            })?;
+            let config = state.config();
+            (!hash::too_small_for_pdq(&img, config.min_pdq_dimension))
+                .then(|| hash::compute_pdq_hash_with(&img, config.parallel_hashing))
+                .transpose()?
+        }
+        None => None,
+    };
+
+    Ok(Json(ApiResponse::success(HashResult { sha3_256, pdq })))
+}
+
+/// Perceptual-evaluation endpoint: accepts multipart form with an image in
+/// the `file` field and returns its hash under every registered perceptual
+/// algorithm, keyed by name.
+///
+/// Like [`hash_file`], nothing is persisted.
+pub async fn perceptual_all(
+    State(state): State<Arc<AppState>>,
+    mut multipart: Multipart,
+) -> Result<impl IntoResponse> {
+    let _job = state.acquire_job_slot().await?;
+    let (file_name, data) = read_upload_in_memory(&mut multipart, state.config().max_upload_size).await?;
+
+    let img = image::load_from_memory(&data)
+        .map_err(|e| AppError::InvalidInput(format!("Failed to decode image {}: {}", file_name, e)))?;
+    let hashes = crate::core::perceptual::compute_all_perceptual(&img)?;
+
+    Ok(Json(ApiResponse::success(hashes)))
+}
+
+/// Page size used by [`list_manifests`] when the request gives no `limit`.
+const DEFAULT_LIST_LIMIT: usize = 50;
+
+/// Largest page [`list_manifests`] returns, whatever `limit` asks for.
+const MAX_LIST_LIMIT: usize = 1000;
+
+/// Query parameters for listing manifests.
+#[derive(Debug, Deserialize, Default, JsonSchema)]
+pub struct ListParams {
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+}
+
+/// Lists stored manifests in file-name order, a page at a time.
+///
+/// Manifests come from `Config::manifest_dir` when it is set, and from the
+/// in-memory image index otherwise. The envelope's `meta` carries the
+/// `total` number of manifests and the `limit` and `offset` applied.
+pub async fn list_manifests(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<ListParams>,
+) -> Result<impl IntoResponse> {
+    let mut manifests = match &state.config().manifest_dir {
+        Some(dir) => DirManifestStore::new(dir).manifests()?,
+        None => state
+            .indexed_manifests
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .values()
+            .cloned()
+            .collect(),
+    };
+    manifests.sort_by(|a, b| a.file_name.cmp(&b.file_name));
+
+    let total = manifests.len();
+    let limit = params.limit.unwrap_or(DEFAULT_LIST_LIMIT).min(MAX_LIST_LIMIT);
+    let offset = params.offset.unwrap_or(0);
+    let page: Vec<MediaManifest> = manifests.into_iter().skip(offset).take(limit).collect();
+
+    let meta = serde_json::json!({ "total": total, "limit": limit, "offset": offset });
+    Ok(Json(ApiResponse::success_with_meta(page, meta)))
+}
+
+/// Body of a batch job submission.
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct JobRequest {
+    pub source: String,
+}
+
+/// A batch job and its progress, as reported by the jobs endpoints.
+#[derive(Debug, Serialize, JsonSchema)]
+pub(crate) struct JobReport {
+    /// Counts of the job's items by status.
+    pub progress: JobProgress,
+    /// Whether the job is being processed right now.
+    pub running: bool,
+    /// The job, with every item and, once done, its manifest.
+    #[serde(flatten)]
+    pub job: Job,
+}
+
+impl JobReport {
+    fn new(state: &AppState, job: Job) -> Self {
+        let running = state.running_jobs.lock().unwrap_or_else(|e| e.into_inner()).contains(&job.id);
+        Self { progress: job.progress(), running, job }
+    }
+}
+
+/// Returns `Config::jobs_dir`, or an error when batch jobs are disabled
+fn jobs_dir(config: &Config) -> Result<&std::path::Path> {
+    config
+        .jobs_dir
+        .as_deref()
+        .ok_or_else(|| AppError::Config("batch jobs are disabled - set JOBS_DIR".to_string()))
+}
+
+/// Batch job endpoint: accepts a JSON body `{ "source": <path> }` naming a
+/// directory or `.zip` archive inside `Config::jobs_dir` and processes every
+/// file in it in the background.
+///
+/// Submitting a source that already has a job returns that job instead of
+/// planning a new one, and restarts it if any items are unfinished; items
+/// already done are never processed again. Answers `202 Accepted` with the
+/// job's id and progress.
+pub async fn submit_job(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<JobRequest>,
+) -> Result<impl IntoResponse> {
+    let config = state.config();
+    let root = jobs_dir(&config)?;
+    let job = JobStore::new(root).submit(root, &request.source)?;
+    if !job.is_complete() {
+        state.start_job(job.clone());
+    }
+    Ok((StatusCode::ACCEPTED, Json(ApiResponse::success(JobReport::new(&state, job)))))
+}
+
+/// Reports a batch job's progress and the manifests of its finished items.
+pub async fn job_status(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse> {
+    let job = JobStore::new(jobs_dir(&state.config())?)
+        .load(&id)?
+        .ok_or_else(|| AppError::NotFound(format!("job {}", id)))?;
+    Ok(Json(ApiResponse::success(JobReport::new(&state, job))))
+}
+
+/// Query parameters for the closest-match endpoint.
+#[derive(Debug, Deserialize, Default, JsonSchema)]
+pub struct ClosestParams {
+    pub threshold: Option<u32>,
+    pub include_embeddings: Option<bool>,
+}
+
+/// Nearest stored image found by [`closest`].
+#[derive(Debug, Serialize, JsonSchema)]
+pub(crate) struct ClosestMatch {
+    /// Manifest of the stored image.
+    pub manifest: MediaManifest,
+    /// PDQ Hamming distance between the query and the stored image.
+    pub distance: u32,
+    /// Cosine similarity of the embeddings, when both images have one.
+    pub cosine_similarity: Option<f32>,
+}
+
+/// Closest-match endpoint: accepts multipart form with an image in the `file`
+/// field and returns the stored image nearest to it by PDQ distance.
+///
+/// Query parameters:
+/// - `threshold` (u32, default: `Config::closest_match_threshold`) — maximum
+///   PDQ distance for a stored image to count as a match.
+/// - `include_embeddings` (bool, default: false) — embed the query and break
+///   distance ties by cosine similarity to the stored embeddings.
+///
+/// Like [`hash_file`], nothing is persisted. Returns `404 Not Found` when no
+/// stored image is within the threshold.
+pub async fn closest(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<ClosestParams>,
+    mut multipart: Multipart,
+) -> Result<impl IntoResponse> {
+    let _job = state.acquire_job_slot().await?;
+    let (file_name, data) = read_upload_in_memory(&mut multipart, state.config().max_upload_size).await?;
+
+    let img = image::load_from_memory(&data)
+        .map_err(|e| AppError::InvalidInput(format!("Failed to decode image {}: {}", file_name, e)))?;
+    let pdq = hash::compute_pdq_hash_with(&img, state.config().parallel_hashing)?;
+    let embedding = if params.include_embeddings.unwrap_or(false) {
+        crate::core::embeddings::compute_image_embedding(&img).await?
+    } else {
+        None
+    };
+    let threshold = params.threshold.unwrap_or(state.config().closest_match_threshold);
+
+    let matches = state.pdq_index.read().unwrap_or_else(|e| e.into_inner()).within(&pdq, threshold)?;
+    let manifests = state.indexed_manifests.read().unwrap_or_else(|e| e.into_inner());
+    let cosine = |manifest: &MediaManifest| -> anyhow::Result<Option<f32>> {
+        let (Some(query), Some(stored)) = (embedding.as_deref(), manifest.metadata.get("embedding")) else {
+            return Ok(None);
+        };
+        let Ok(stored) = serde_json::from_value::<Vec<f32>>(stored.clone()) else {
+            return Ok(None);
+        };
+        embedding_similarity(query, &stored)
+    };
+
+    // `within` sorts by distance, so ties for the closest come first
+    let best = matches
+        .iter()
+        .take_while(|(_, distance)| Some(distance) == matches.first().map(|(_, d)| d))
+        .filter_map(|(id, distance)| manifests.get(id).map(|manifest| (manifest, *distance)))
+        .map(|(manifest, distance)| {
+            Ok(ClosestMatch {
+                manifest: manifest.clone(),
+                distance,
+                cosine_similarity: cosine(manifest)?,
+            })
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?
+        .into_iter()
+        .max_by(|a, b| {
+            a.cosine_similarity
+                .partial_cmp(&b.cosine_similarity)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then_with(|| b.manifest.file_name.cmp(&a.manifest.file_name))
+        })
+        .ok_or_else(|| AppError::NotFound(format!("no stored image within distance {}", threshold)))?;
+
+    Ok(Json(ApiResponse::success(best)))
 }
 
+/// Result lines a streaming endpoint may buffer before waiting for the client.
+const NDJSON_BUFFER: usize = 256;
+
+/// Query parameters for the streaming similarity endpoints.
+#[derive(Debug, Deserialize, Default, JsonSchema)]
+pub struct SimilarParams {
+    pub threshold: Option<u32>,
+    pub limit: Option<usize>,
+    pub min_histogram_similarity: Option<f32>,
+}
+
+impl SimilarParams {
+    /// The requested threshold, or `Config::closest_match_threshold` when unset.
+    ///
+    /// No two hashes are more than [`hash::PDQ_HASH_BITS`] apart, so larger
+    /// thresholds are clamped rather than searched distance by distance.
+    fn threshold(&self, config: &Config) -> u32 {
+        self.threshold
+            .unwrap_or(config.closest_match_threshold)
+            .min(hash::PDQ_HASH_BITS as u32)
+    }
+}
+
+/// Streams the lines `produce` emits as newline-delimited JSON.
+///
+/// `produce` runs as a blocking job; its `emit` callback waits while
+/// [`NDJSON_BUFFER`] lines are pending and returns `false` once the client has
+/// gone, so memory stays bounded however many results there are. An error
+/// ends the stream with an `{"error": ...}` line.
+fn stream_ndjson<F>(state: &AppState, produce: F) -> impl IntoResponse
+where
+    F: FnOnce(&mut dyn FnMut(serde_json::Value) -> bool) -> Result<()> + Send + 'static,
+{
+    let (tx, rx) = tokio::sync::mpsc::channel::<serde_json::Value>(NDJSON_BUFFER);
+    state.spawn_blocking_job(move || {
+        let mut emit = |line| tx.blocking_send(line).is_ok();
+        if let Err(e) = produce(&mut emit) {
+            emit(serde_json::json!({ "error": e.to_string() }));
+        }
+    });
+
+    let lines = tokio_stream::wrappers::ReceiverStream::new(rx)
+        .map(|line| Ok::<_, std::convert::Infallible>(format!("{}\n", line)));
+    ([(header::CONTENT_TYPE, "application/x-ndjson")], axum::body::Body::from_stream(lines))
+}
+
+/// Similarity search endpoint: accepts multipart form with an image in the
+/// `file` field and streams the stored images near it as newline-delimited
+/// JSON `{"file_name": ..., "distance": ...}` lines, closest first.
+///
+/// Query parameters:
+/// - `threshold` (u32, default: `Config::closest_match_threshold`) — maximum PDQ
+///   distance; values above [`hash::PDQ_HASH_BITS`] match every hash.
+/// - `limit` (usize, optional) — return only this many closest images. They are
+///   kept in a bounded heap and sent once the search ends. Without a limit the
+///   index is searched one distance at a time and each distance's matches are
+///   sent as soon as they are found.
+/// - `min_histogram_similarity` (f32, optional) — also skip stored images whose
+///   colour histogram is less similar than this to the query's (see
+///   [`find_similar`](crate::core::similarity::find_similar)), which separates
+///   recoloured versions of the same picture. Images without a histogram are kept.
+///   The search then always goes one distance at a time, stopping at `limit`.
+///
+/// The index is only locked while searching, never while waiting for the client.
+pub async fn search_similar(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<SimilarParams>,
+    mut multipart: Multipart,
+) -> Result<impl IntoResponse> {
+    let threshold = params.threshold(&state.config());
+    let (pdq, histogram) = {
+        let _job = state.acquire_job_slot().await?;
+        let (file_name, data) = read_upload_in_memory(&mut multipart, state.config().max_upload_size).await?;
+        let img = image::load_from_memory(&data)
+            .map_err(|e| AppError::InvalidInput(format!("Failed to decode image {}: {}", file_name, e)))?;
+        let histogram = params
+            .min_histogram_similarity
+            .map(|min| (min, compute_color_histogram(&img, DEFAULT_HISTOGRAM_BINS)));
+        (hash::compute_pdq_hash_with(&img, state.config().parallel_hashing)?, histogram)
+    };
+    let index = state.pdq_index.clone();
+    let manifests = state.indexed_manifests.clone();
+
+    Ok(stream_ndjson(&state, move |emit| {
+        let read = || index.read().unwrap_or_else(|e| e.into_inner());
+        let mut emit = |file_name: String, distance: u32| {
+            emit(serde_json::json!({ "file_name": file_name, "distance": distance }))
+        };
+        let Some((min_similarity, histogram)) = histogram else {
+            match params.limit {
+                Some(limit) => {
+                    let nearest = read().nearest(&pdq, threshold, limit)?;
+                    for (file_name, distance) in nearest {
+                        if !emit(file_name, distance) {
+                            break;
+                        }
+                    }
+                }
+                None => {
+                    'search: for distance in 0..=threshold {
+                        let band = read().exactly(&pdq, distance)?;
+                        for file_name in band {
+                            if !emit(file_name, distance) {
+                                break 'search;
+                            }
+                        }
+                    }
+                }
+            }
+            return Ok(());
+        };
+
+        // Filtering drops matches, so a limit can't be handed to the index
+        let similar_colours = |file_name: &str| {
+            let manifests = manifests.read().unwrap_or_else(|e| e.into_inner());
+            manifests
+                .get(file_name)
+                .and_then(|manifest| manifest.color_histogram.as_deref())
+                .is_none_or(|stored| histogram_similarity(&histogram, stored) >= min_similarity)
+        };
+        let mut remaining = params.limit.unwrap_or(usize::MAX);
+        'search: for distance in 0..=threshold {
+            let band = read().exactly(&pdq, distance)?;
+            for file_name in band.into_iter().filter(|file_name| similar_colours(file_name)) {
+                if remaining == 0 || !emit(file_name, distance) {
+                    break 'search;
+                }
+                remaining -= 1;
+            }
+        }
+        Ok(())
+    }))
+}
+
+/// Duplicate search endpoint: streams every pair of stored images within the
+/// threshold of each other as newline-delimited JSON
+/// `{"a": ..., "b": ..., "distance": ...}` lines, closest pairs first.
+///
+/// Each pair is reported once, with the smaller file name as `a`. Query
+/// parameters are those of [`search_similar`], with `limit` counting pairs
+/// and `min_histogram_similarity` comparing the two images of a pair.
+/// Without a limit, the index is walked once for every pair within the
+/// threshold, and the pairs are sent grouped by distance.
+pub async fn find_duplicates(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<SimilarParams>,
+) -> Result<impl IntoResponse> {
+    let threshold = params.threshold(&state.config());
+    let index = state.pdq_index.clone();
+    let manifests = state.indexed_manifests.clone();
+
+    Ok(stream_ndjson(&state, move |emit| {
+        let read = || index.read().unwrap_or_else(|e| e.into_inner());
+        let mut emit = |a: String, b: String, distance: u32| {
+            emit(serde_json::json!({ "a": a, "b": b, "distance": distance }))
+        };
+        if let Some(min_similarity) = params.min_histogram_similarity {
+            let similar_colours = |a: &str, b: &str| {
+                let manifests = manifests.read().unwrap_or_else(|e| e.into_inner());
+                let histogram = |name: &str| manifests.get(name).and_then(|manifest| manifest.color_histogram.as_deref());
+                match (histogram(a), histogram(b)) {
+                    (Some(a), Some(b)) => histogram_similarity(a, b) >= min_similarity,
+                    _ => true,
+                }
+            };
+            let mut remaining = params.limit.unwrap_or(usize::MAX);
+            let bands = read().pairs_by_distance(threshold)?;
+            'filtered: for (distance, band) in (0..).zip(bands) {
+                for (a, b) in band.into_iter().filter(|(a, b)| similar_colours(a, b)) {
+                    if remaining == 0 || !emit(a, b, distance) {
+                        break 'filtered;
+                    }
+                    remaining -= 1;
+                }
+            }
+            return Ok(());
+        }
+        match params.limit {
+            Some(limit) => {
+                let nearest = read().nearest_pairs(threshold, limit)?;
+                for (a, b, distance) in nearest {
+                    if !emit(a, b, distance) {
+                        break;
+                    }
+                }
+            }
+            None => {
+                let bands = read().pairs_by_distance(threshold)?;
+                'search: for (distance, band) in (0..).zip(bands) {
+                    for (a, b) in band {
+                        if !emit(a, b, distance) {
+                            break 'search;
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }))
+}
+
+/// Query parameters for the verify endpoint.
+#[derive(Debug, Deserialize, Default, JsonSchema)]
+pub struct VerifyParams {
+    pub explain: Option<bool>,
+}
+
+/// Verifies the integrity of a file against a provided `MediaManifest`.
+///
+/// This endpoint checks if a file on disk matches the metadata and hashes
+/// stored in the manifest. Every call is recorded in the audit log when
+/// `Config::manifest_dir` is set (see [`audit`]).
+///
+/// Query parameters:
+/// - `explain` (bool, default: false) — also return the failure `reason` and
+///   an `explanation` with the expected and recomputed size and hashes.
+pub async fn verify_manifest(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<VerifyParams>,
+    Json(manifest): Json<MediaManifest>,
+) -> Result<impl IntoResponse> {
+    let config = state.config();
+    check_frame_count(&manifest, &config)?;
+    let explain = params.explain.unwrap_or(false);
+
+    // Verifying and writing the audit log both block, so neither runs on the runtime
+    let job_state = state.clone();
+    let report = state
+        .spawn_blocking_job(move || {
+            // Files are looked up by name in the configured uploads directory
+            let uploads_dir = &config.upload_dir;
+            let report = if explain {
+                manifest.verify_report_explained_in(uploads_dir)
+            } else {
+                manifest.verify_report_in_cached(uploads_dir, &job_state.decode_cache)
+            };
+            record_verification(&config, &manifest, uploads_dir, report.as_ref());
+            report
+        })
+        .await??;
+
+    if explain {
+        return Ok(Json(ApiResponse::success(serde_json::json!({
+            "is_valid": report.is_valid,
+            "reason": report.reason,
+            "explanation": report.explanation,
+        }))));
+    }
+    Ok(Json(ApiResponse::success(serde_json::json!({ "is_valid": report.is_valid }))))
+}
+
+/// Appends a verification of `manifest` to the audit log, if there is one
+///
+/// The log lives in `Config::manifest_dir`. Failing to write it is logged
+/// and otherwise ignored, so it never fails the verification itself. This
+/// writes to disk, so call it from blocking work rather than on the runtime.
+fn record_verification(
+    config: &Config,
+    manifest: &MediaManifest,
+    uploads_dir: &std::path::Path,
+    report: std::result::Result<&VerificationReport, &AppError>,
+) {
+    let Some(manifest_dir) = &config.manifest_dir else {
+        return;
+    };
+    let file_path = uploads_dir.join(manifest.relative_path.as_deref().unwrap_or(&manifest.file_name));
+    let entry = AuditEntry::new(manifest, &file_path, report);
+    if let Err(e) = DirManifestStore::new(manifest_dir).append_audit(&entry) {
+        log::error!("Failed to record verification of {} in the audit log: {}", manifest.file_name, e);
+    }
+}
+
+/// Query parameters for the audit endpoint.
+#[derive(Debug, Deserialize, Default, JsonSchema)]
+pub struct AuditParams {
+    pub manifest_id: Option<String>,
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+}
+
+/// Lists recorded verifications, oldest first.
+///
+/// Every `/api/verify` and `/api/verify/progress` call is appended to an
+/// audit log kept in `Config::manifest_dir`; this endpoint fails when that is unset.
+///
+/// Query parameters:
+/// - `manifest_id` (string, optional) — only verifications of the manifest for
+///   this stored file name.
+/// - `limit` (usize, default: 50, at most 1000) — entries per page.
+/// - `offset` (usize, default: 0) — matching entries to skip.
+///
+/// The envelope's `meta` carries the `limit` and `offset` applied.
+pub async fn audit(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<AuditParams>,
+) -> Result<impl IntoResponse> {
+    let config = state.config();
+    let manifest_dir = config
+        .manifest_dir
+        .as_deref()
+        .ok_or_else(|| AppError::Config("the audit log is disabled - set MANIFEST_DIR".to_string()))?;
+    let store = DirManifestStore::new(manifest_dir);
+    let manifest_id = params.manifest_id;
+    let limit = params.limit.unwrap_or(DEFAULT_LIST_LIMIT).min(MAX_LIST_LIMIT);
+    let offset = params.offset.unwrap_or(0);
+    let entries =
+        tokio::task::spawn_blocking(move || store.audit_entries(manifest_id.as_deref(), offset, limit)).await??;
+    let meta = serde_json::json!({ "limit": limit, "offset": offset });
+    Ok(Json(ApiResponse::success_with_meta(entries, meta)))
+}
+
+/// Query parameters for verification with progress.
+#[derive(Debug, Deserialize, Default, JsonSchema)]
+pub struct VerifyProgressParams {
+    pub frame_tolerance: Option<u32>,
+    pub frame_interval_secs: Option<f64>,
+    pub timestamp_tolerance_secs: Option<f64>,
+}
+
+/// Verifies a manifest frame by frame, streaming progress as it goes.
+///
+/// Accepts the same JSON manifest as `/api/verify`. The response is
+/// newline-delimited JSON: one `{"event":"progress",...}` line per checked
+/// frame, followed by a final `{"event":"result","is_valid":...}` line, or an
+/// `{"event":"error","message":...}` line if verification could not complete.
+///
+/// Query parameters:
+/// - `frame_tolerance` (u32, default: 8) — maximum PDQ distance per frame.
+/// - `frame_interval_secs` (f64, optional) — override the manifest's frame interval.
+/// - `timestamp_tolerance_secs` (f64, optional) — match frames by timestamp
+///   within this many seconds instead of by position, absorbing ffmpeg drift.
+pub async fn verify_manifest_progress(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<VerifyProgressParams>,
+    Json(manifest): Json<MediaManifest>,
+) -> Result<impl IntoResponse> {
+    check_frame_count(&manifest, &state.config())?;
+    let job = state.acquire_job_slot().await?;
+    let uploads_dir = state.config().upload_dir.clone();
+    let opts = VerifyOptions {
+        frame_tolerance: params.frame_tolerance.unwrap_or(DEFAULT_FRAME_TOLERANCE),
+        frame_interval_secs: params.frame_interval_secs,
+        timestamp_tolerance_secs: params.timestamp_tolerance_secs,
+    };
+
+    let config = state.config();
+    let (tx, rx) = tokio::sync::mpsc::unbounded_channel::<serde_json::Value>();
+    state.spawn_blocking_job(move || {
+        // Hold the job slot for as long as verification runs
+        let _job = job;
+        let progress_tx = tx.clone();
+        let report = manifest.verify_report_in_with_progress(&uploads_dir, &opts, |progress| {
+            let mut line = serde_json::json!({ "event": "progress" });
+            if let (Some(line), Ok(serde_json::Value::Object(fields))) =
+                (line.as_object_mut(), serde_json::to_value(&progress))
+            {
+                line.extend(fields);
+            }
+            let _ = progress_tx.send(line);
+        });
+        record_verification(&config, &manifest, &uploads_dir, report.as_ref());
+        let last = match report {
+            Ok(report) => serde_json::json!({ "event": "result", "is_valid": report.is_valid }),
+            Err(e) => serde_json::json!({ "event": "error", "message": e.to_string() }),
+        };
+        let _ = tx.send(last);
+    });
+
+    let lines = tokio_stream::wrappers::UnboundedReceiverStream::new(rx).map(|line| {
+        Ok::<_, std::convert::Infallible>(format!("{}\n", line))
+    });
+
+    Ok((
+        [(header::CONTENT_TYPE, "application/x-ndjson")],
+        axum::body::Body::from_stream(lines),
+    ))
+}
+
+/// Default maximum PDQ distance for a frame to count as a match.
+const DEFAULT_FRAME_MATCH_THRESHOLD: u32 = 10;
+
+/// Query parameters for the frame-match endpoint.
+#[derive(Debug, Deserialize, Default, JsonSchema)]
+pub struct FrameMatchParams {
+    pub threshold: Option<u32>,
+}
+
+/// Checks whether an uploaded still image appears in a video.
+///
+/// This endpoint accepts multipart form data with a "file" field holding the
+/// image and a "manifest" field holding the video's `MediaManifest` as JSON.
+///
+/// Query parameters:
+/// - `threshold` (u32, default: 10) — maximum PDQ distance for a frame to match.
+///
+/// Returns the best-matching frame and its distance, or `matched: false`.
+/// Manifests whose frames were hashed by another PDQ version are rejected
+/// with `409 Conflict`.
+pub async fn frame_match(
+    State(_state): State<Arc<AppState>>,
+    Query(params): Query<FrameMatchParams>,
+    mut multipart: Multipart,
+) -> Result<impl IntoResponse> {
+    let mut image_data = None;
+    let mut image_name = None;
+    let mut video = None;
+
+    while let Some(field) = multipart.next_field().await.map_err(|e| {
+        AppError::UploadError(format!("Failed to read multipart field: {}", e))
+    })? {
+        let name = field.name().unwrap_or("").to_string();
+
+        match name.as_str() {
+            "file" => {
+                image_name = field.file_name().map(String::from);
+                let data = field.bytes().await.map_err(|e| {
+                    AppError::UploadError(format!("Failed to read file content: {}", e))
+                })?;
+                image_data = Some(data);
+            }
+            "manifest" => {
+                let text = field.text().await.map_err(|e| {
+                    AppError::UploadError(format!("Failed to read manifest: {}", e))
+                })?;
+                video = Some(MediaManifest::from_json(&text)?);
+            }
+            _ => {}
+        }
+    }
+
+    let image_data = image_data.ok_or_else(|| AppError::UploadError("No file provided".to_string()))?;
+    let video = video.ok_or_else(|| AppError::UploadError("No manifest provided".to_string()))?;
+
+    if video.media_type != MediaType::Video {
+        return Err(AppError::Validation("manifest must describe a video".to_string()));
+    }
+    video.check_current()?;
+
+    let image_name = image_name.unwrap_or_else(|| "unknown".to_string());
+    if image_data.is_empty() {
+        return Err(AppError::Validation("empty file".to_string()));
+    }
+    let img = image::load_from_memory(&image_data).map_err(|e| {
+        AppError::InvalidInput(format!("Failed to decode image {}: {}", image_name, e))
+    })?;
+    let image_pdq = hash::compute_pdq_hash(&img)?;
+    let threshold = params.threshold.unwrap_or(DEFAULT_FRAME_MATCH_THRESHOLD);
+
+    let frame = image_in_video(&image_pdq, &video, threshold);
+    let distance = match &frame {
+        Some(frame) => Some(hash::pdq_distance(&image_pdq, &frame.pdq_hash)?),
+        None => None,
+    };
+
+    Ok(Json(ApiResponse::success(serde_json::json!({
+        "matched": frame.is_some(),
+        "frame": frame,
+        "distance": distance,
+        "threshold": threshold,
+    }))))
+}
+
+/// Query parameters for the video-compare endpoint.
+#[derive(Debug, Deserialize, Default, JsonSchema)]
+pub struct VideoCompareParams {
+    pub threshold: Option<u32>,
+}
+
+/// The two video manifests to compare.
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct VideoCompareRequest {
+    pub a: MediaManifest,
+    pub b: MediaManifest,
+}
+
+/// Compares two videos by aligning their frame sequences.
+///
+/// Accepts a JSON body `{ "a": <manifest>, "b": <manifest> }` of two video
+/// manifests with frames.
+///
+/// Query parameters:
+/// - `threshold` (u32, default: 10) — maximum PDQ distance for two frames to match.
+///
+/// Returns the in-order alignment, its similarity and the order-insensitive
+/// set overlap, so reordered or reversed copies can be told apart. Manifests
+/// hashed by different PDQ versions are rejected with `409 Conflict`, and
+/// videos of more than [`MAX_COMPARED_FRAMES`] frames with 400.
+pub async fn video_compare(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<VideoCompareParams>,
+    Json(request): Json<VideoCompareRequest>,
+) -> Result<impl IntoResponse> {
+    require_comparable_videos(&request)?;
+    let threshold = params.threshold.unwrap_or(DEFAULT_FRAME_MATCH_THRESHOLD);
+
+    let result = state
+        .spawn_blocking_job(move || {
+            let a = request.a.frames.as_deref().unwrap_or_default();
+            let b = request.b.frames.as_deref().unwrap_or_default();
+            video_sequence_similarity(a, b, threshold)
+        })
+        .await?;
+    Ok(Json(ApiResponse::success(result)))
+}
+
+/// Scores how similar two videos are as a single number.
+///
+/// Accepts the same `{ "a": <manifest>, "b": <manifest> }` body as
+/// [`video_compare`] and returns `{ "score": ... }` in the `0.0..=1.0` range,
+/// computed by [`video_similarity_score`]: frame overlap, blended with frame
+/// embedding similarity when both videos have embeddings. Manifests hashed by
+/// different PDQ versions are rejected with `409 Conflict`, and videos of more
+/// than [`MAX_COMPARED_FRAMES`] frames with 400.
+pub async fn video_similarity(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<VideoCompareRequest>,
+) -> Result<impl IntoResponse> {
+    require_comparable_videos(&request)?;
+
+    let score = state
+        .spawn_blocking_job(move || video_similarity_score(&request.a, &request.b))
+        .await?;
+    Ok(Json(ApiResponse::success(serde_json::json!({ "score": score }))))
+}
+
+/// Checks that both manifests are videos hashed alike, with few enough frames to compare
+fn require_comparable_videos(request: &VideoCompareRequest) -> Result<()> {
+    for manifest in [&request.a, &request.b] {
+        require_video(manifest)?;
+        let frames = manifest.frames.as_ref().map_or(0, Vec::len);
+        if frames > MAX_COMPARED_FRAMES {
+            return Err(AppError::Validation(format!(
+                "manifest {} has {} frames; at most {} can be compared",
+                manifest.file_name, frames, MAX_COMPARED_FRAMES
+            )));
+        }
+    }
+    request.a.check_comparable(&request.b)
+}
+
+/// Rejects client-supplied manifests recording more frames than an upload may extract
+///
+/// Verification re-extracts and hashes up to `frame_count` frames, so the
+/// count is bounded by `Config::max_total_frames` like uploads are.
+fn check_frame_count(manifest: &MediaManifest, config: &Config) -> Result<()> {
+    match manifest.frame_count {
+        Some(count) if count > config.max_total_frames => Err(AppError::Validation(format!(
+            "manifest {} records {} frames, more than the limit of {}",
+            manifest.file_name, count, config.max_total_frames
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Rejects manifests that don't describe a video
+fn require_video(manifest: &MediaManifest) -> Result<()> {
+    if manifest.media_type != MediaType::Video {
+        return Err(AppError::Validation(format!(
+            "manifest {} must describe a video",
+            manifest.file_name
+        )));
+    }
+    Ok(())
+}
+
+/// Image formats the bundled `image` crate is built to decode.
+const IMAGE_FORMATS: &[&str] = &["jpeg", "png", "gif", "webp"];
+
+/// Describes what this server build is able to process.
+#[derive(Debug, Serialize)]
+pub(crate) struct Capabilities {
+    pub image_formats: Vec<String>,
+    pub video_supported: bool,
+    pub embeddings_supported: bool,
+    pub perceptual_algorithms: Vec<String>,
+    pub hash_algorithms: Vec<String>,
+    pub max_upload_size: u64,
+}
+
+impl Capabilities {
+    /// Builds the capabilities from the compiled features and configuration.
+    pub(crate) fn from_config(config: &Config) -> Self {
+        let mut perceptual_algorithms = Vec::new();
+        let mut hash_algorithms = Vec::new();
+        if cfg!(feature = "hashing") {
+            perceptual_algorithms = crate::core::perceptual::AlgorithmRegistry::global()
+                .read()
+                .unwrap_or_else(|e| e.into_inner())
+                .names();
+            hash_algorithms.push("sha3-256".to_string());
+        }
+
+        Self {
+            image_formats: IMAGE_FORMATS.iter().map(|f| f.to_string()).collect(),
+            video_supported: cfg!(feature = "video"),
+            embeddings_supported: cfg!(feature = "embeddings"),
+            perceptual_algorithms,
+            hash_algorithms,
+            max_upload_size: config.max_upload_size,
+        }
+    }
+}
+
+/// Reports supported formats, compiled-in features and upload limits.
+pub async fn capabilities(State(state): State<Arc<AppState>>) -> Result<impl IntoResponse> {
+    Ok(Json(ApiResponse::success(Capabilities::from_config(&state.config()))))
+}
+
+/// Reports live load: held and queued job slots, uploads in flight and uptime.
+pub async fn status(State(state): State<Arc<AppState>>) -> Result<impl IntoResponse> {
+    Ok(Json(ApiResponse::success(state.load_status())))
+}
+
+/// Serves the OpenAPI document describing this API.
+pub async fn openapi() -> impl IntoResponse {
+    Json(super::openapi::openapi_document())
+}
+
+/// Query parameters carried by a signed download link.
+#[derive(Debug, Deserialize)]
+pub struct DownloadParams {
+    pub expires: i64,
+    pub signature: String,
+}
+
+/// Serves a stored file through a signed, time-limited link.
+///
+/// Links are created with `AppState::sign_download_url` using the configured
+/// download secret. Invalid, tampered or expired links are rejected with
+/// `AppError::Auth`.
+pub async fn download_file(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Query(params): Query<DownloadParams>,
+) -> Result<impl IntoResponse> {
+    let path = authorized_download_path(&state, &id, &params)?;
+    let data = tokio::fs::read(&path).await.map_err(|e| not_found_as(e, &id))?;
+    let mime = mime_guess::from_path(&id).first_or_octet_stream();
+
+    Ok(([(header::CONTENT_TYPE, mime.to_string())], data))
+}
+
+/// Streams a stored file with HTTP `Range` support, e.g. for `<video>` seeking.
+///
+/// Takes the same signed-link query parameters as [`download_file`]. Without
+/// a `Range` header the whole file is streamed with `200 OK`; a single
+/// `bytes=` range gets `206 Partial Content` with a `Content-Range` header.
+/// Malformed, multi-part or unsatisfiable ranges get
+/// `416 Range Not Satisfiable`.
+pub async fn stream_file(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Query(params): Query<DownloadParams>,
+    headers: HeaderMap,
+) -> Result<Response> {
+    use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+    let path = authorized_download_path(&state, &id, &params)?;
+    let mut file = File::open(&path).await.map_err(|e| not_found_as(e, &id))?;
+    let len = file.metadata().await?.len();
+    let mime = mime_guess::from_path(&id).first_or_octet_stream().to_string();
+
+    let range = match headers.get(header::RANGE) {
+        None => None,
+        Some(value) => match value.to_str().ok().and_then(|value| byte_range(value, len)) {
+            Some(range) => Some(range),
+            None => {
+                return Ok((
+                    StatusCode::RANGE_NOT_SATISFIABLE,
+                    [(header::CONTENT_RANGE, format!("bytes */{}", len))],
+                )
+                    .into_response())
+            }
+        },
+    };
+
+    let (status, start, end) = match range {
+        Some((start, end)) => (StatusCode::PARTIAL_CONTENT, start, end),
+        None => (StatusCode::OK, 0, len.saturating_sub(1)),
+    };
+    let body_len = if len == 0 { 0 } else { end - start + 1 };
+    file.seek(std::io::SeekFrom::Start(start)).await?;
+    let body = axum::body::Body::from_stream(tokio_util::io::ReaderStream::new(file.take(body_len)));
+
+    let mut response = (
+        status,
+        [
+            (header::CONTENT_TYPE, mime),
+            (header::ACCEPT_RANGES, "bytes".to_string()),
+            (header::CONTENT_LENGTH, body_len.to_string()),
+        ],
+        body,
+    )
+        .into_response();
+    if range.is_some() {
+        let content_range = format!("bytes {}-{}/{}", start, end, len);
+        if let Ok(value) = content_range.parse() {
+            response.headers_mut().insert(header::CONTENT_RANGE, value);
+        }
+    }
+    Ok(response)
+}
+
+/// Checks a signed download link and resolves `id` inside the uploads directory.
+fn authorized_download_path(state: &AppState, id: &str, params: &DownloadParams) -> Result<std::path::PathBuf> {
+    let config = state.config();
+    let secret = config
+        .download_secret
+        .as_deref()
+        .ok_or_else(|| AppError::Auth("signed downloads are disabled".to_string()))?;
+
+    super::signing::verify_download_signature(
+        id,
+        params.expires,
+        &params.signature,
+        secret.as_bytes(),
+        chrono::Utc::now(),
+    )?;
+
+    // Ids are bare file names inside the uploads directory
+    if id.is_empty() || id.contains(['/', '\\']) || id.starts_with('.') {
+        return Err(AppError::Validation(format!("invalid download id: {}", id)));
+    }
+
+    Ok(config.upload_dir.join(id))
+}
+
+fn not_found_as(e: std::io::Error, id: &str) -> AppError {
+    match e.kind() {
+        std::io::ErrorKind::NotFound => AppError::NotFound(format!("file {}", id)),
+        _ => AppError::Io(e),
+    }
+}
+
+/// Parses a single `bytes=` range against a file of `len` bytes.
+///
+/// Returns the inclusive `(start, end)` offsets, clamping `end` to the file,
+/// or `None` if the header is malformed, lists several ranges, or starts past
+/// the end of the file.
+fn byte_range(value: &str, len: u64) -> Option<(u64, u64)> {
+    let spec = value.trim().strip_prefix("bytes=")?;
+    if spec.contains(',') || len == 0 {
+        return None;
+    }
+    let (start, end) = spec.trim().split_once('-')?;
+    let (start, end) = (start.trim(), end.trim());
+
+    if start.is_empty() {
+        // Suffix range: the last `end` bytes
+        let suffix: u64 = end.parse().ok().filter(|&n| n > 0)?;
+        return Some((len.saturating_sub(suffix), len - 1));
+    }
+
+    let start: u64 = start.parse().ok()?;
+    let end: u64 = if end.is_empty() { len - 1 } else { end.parse().ok()? };
+    (start <= end && start < len).then(|| (start, end.min(len - 1)))
+}
+
+/// Produces a pixel-level diff between two uploaded images.
+///
+/// This endpoint accepts multipart form data with two image fields, "a" and
+/// "b". The response body is a PNG heatmap of the differences; the changed
+/// pixel ratio and bounding box are returned in the `x-changed-pixel-ratio`
+/// and `x-diff-bounding-box` (`x,y,width,height`) headers.
+pub async fn image_diff(
+    State(_state): State<Arc<AppState>>,
+    multipart: Multipart,
+) -> Result<impl IntoResponse> {
+    let (image_a, image_b) = read_image_pair(multipart).await?;
+
+    let report = diff_images(&image_a, &image_b)?;
+
+    let mut png = Vec::new();
+    report
+        .heatmap
+        .write_to(&mut std::io::Cursor::new(&mut png), image::ImageOutputFormat::Png)?;
+
+    let bounding_box = report
+        .bounding_box
+        .map(|(x, y, w, h)| format!("{},{},{},{}", x, y, w, h))
+        .unwrap_or_default();
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "image/png".to_string()),
+            (header::HeaderName::from_static("x-changed-pixel-ratio"), report.changed_pixel_ratio.to_string()),
+            (header::HeaderName::from_static("x-diff-bounding-box"), bounding_box),
+        ],
+        png,
+    ))
+}
+
+/// Reads and decodes the two images in the "a" and "b" multipart fields.
+async fn read_image_pair(mut multipart: Multipart) -> Result<(image::DynamicImage, image::DynamicImage)> {
+    let mut image_a = None;
+    let mut image_b = None;
+
+    while let Some(field) = multipart.next_field().await.map_err(|e| {
+        AppError::UploadError(format!("Failed to read multipart field: {}", e))
+    })? {
+        let name = field.name().unwrap_or("").to_string();
+        if name != "a" && name != "b" {
+            continue;
+        }
+
+        let file_name = field.file_name().unwrap_or("unknown").to_string();
+        let data = field.bytes().await.map_err(|e| {
+            AppError::UploadError(format!("Failed to read file content: {}", e))
+        })?;
+        if data.is_empty() {
+            return Err(AppError::Validation("empty file".to_string()));
+        }
+        let img = image::load_from_memory(&data).map_err(|e| {
+            AppError::InvalidInput(format!("Failed to decode image {}: {}", file_name, e))
+        })?;
+
+        if name == "a" {
+            image_a = Some(img);
+        } else {
+            image_b = Some(img);
+        }
+    }
+
+    let image_a = image_a.ok_or_else(|| AppError::UploadError("No image \"a\" provided".to_string()))?;
+    let image_b = image_b.ok_or_else(|| AppError::UploadError("No image \"b\" provided".to_string()))?;
+    Ok((image_a, image_b))
+}
+
+/// Query parameters for the PDQ compare endpoint.
+#[derive(Debug, Deserialize, Default, JsonSchema)]
+pub struct CompareParams {
+    pub verbose: Option<bool>,
+}
+
+/// PDQ comparison of two images returned by [`compare`].
+#[derive(Debug, Serialize, JsonSchema)]
+pub(crate) struct PdqComparison {
+    /// PDQ Hamming distance between the two images.
+    pub distance: u32,
+    /// The raw hashes and the bits they differ in, with `verbose=true`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<PdqComparisonDetail>,
+}
+
+/// Bit-level view of a [`PdqComparison`], for tuning match thresholds.
+#[derive(Debug, Serialize, JsonSchema)]
+pub(crate) struct PdqComparisonDetail {
+    /// PDQ hash of image "a" in hex.
+    pub a_hex: String,
+    /// PDQ hash of image "a" as a `'0'`/`'1'` string.
+    pub a_binary: String,
+    /// PDQ hash of image "b" in hex.
+    pub b_hex: String,
+    /// PDQ hash of image "b" as a `'0'`/`'1'` string.
+    pub b_binary: String,
+    /// XOR of the two hashes as a `'0'`/`'1'` string; each `'1'` is a differing bit.
+    pub diff_mask: String,
+}
+
+/// Compares the PDQ hashes of two uploaded images.
+///
+/// This endpoint accepts multipart form data with two image fields, "a" and
+/// "b", and returns their PDQ Hamming distance. Nothing is stored.
+///
+/// Query parameters:
+/// - `verbose` (bool, default: false) — also return both hashes in hex and
+///   binary and the XOR mask of the bits that differ.
+pub async fn compare(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<CompareParams>,
+    multipart: Multipart,
+) -> Result<impl IntoResponse> {
+    let (image_a, image_b) = read_image_pair(multipart).await?;
+    let _job = state.acquire_job_slot().await?;
+
+    let parallel = state.config().parallel_hashing;
+    let a = hash::compute_pdq_hash_with(&image_a, parallel)?;
+    let b = hash::compute_pdq_hash_with(&image_b, parallel)?;
+    let detail = if params.verbose.unwrap_or(false) {
+        Some(PdqComparisonDetail {
+            a_hex: hash::pdq_to_hex(&a)?,
+            a_binary: hash::pdq_to_binary(&a)?,
+            b_hex: hash::pdq_to_hex(&b)?,
+            b_binary: hash::pdq_to_binary(&b)?,
+            diff_mask: hash::pdq_diff_mask(&a, &b)?,
+        })
+    } else {
+        None
+    };
+
+    Ok(Json(ApiResponse::success(PdqComparison { distance: hash::pdq_distance(&a, &b)?, detail })))
+}
+
+/// Pairwise PDQ distances returned by [`distance_matrix`].
+#[derive(Debug, Serialize, JsonSchema)]
+pub(crate) struct DistanceMatrix {
+    /// File name of each uploaded image or id of each stored one, in row order.
+    pub order: Vec<String>,
+    /// Symmetric matrix of PDQ Hamming distances; `distances[i][j]` compares
+    /// `order[i]` with `order[j]`, so the diagonal is zero.
+    pub distances: Vec<Vec<u32>>,
+}
+
+/// Distance-matrix endpoint: accepts multipart form data with any number of
+/// `file` fields holding images and `id` fields naming stored images, and
+/// returns the PDQ Hamming distance between every pair of them.
+///
+/// Rows follow the order of the fields. At most
+/// `Config::max_distance_matrix_size` images are accepted, since the number of
+/// comparisons grows with the square; uploads are decoded and hashed off the
+/// async runtime. Nothing is stored. Returns
+/// `404 Not Found` for an id with no indexed PDQ hash.
+pub async fn distance_matrix(
+    State(state): State<Arc<AppState>>,
+    mut multipart: Multipart,
+) -> Result<impl IntoResponse> {
+    let _job = state.acquire_job_slot().await?;
+    let config = state.config();
+    let mut order = Vec::new();
+    let mut hashes = Vec::new();
+
+    while let Some(field) = multipart.next_field().await.map_err(|e| {
+        AppError::UploadError(format!("Failed to read multipart field: {}", e))
+    })? {
+        if !matches!(field.name(), Some("file" | "id")) {
+            continue;
+        }
+        if order.len() == config.max_distance_matrix_size {
+            return Err(AppError::Validation(format!(
+                "at most {} images can be compared at once",
+                config.max_distance_matrix_size
+            )));
+        }
+        let (label, pdq) = match field.name() {
+            Some("file") => {
+                let file_name = field.file_name().unwrap_or("unknown").to_string();
+                let data = field.bytes().await.map_err(|e| {
+                    AppError::UploadError(format!("Failed to read file content: {}", e))
+                })?;
+                if data.is_empty() {
+                    return Err(AppError::Validation("empty file".to_string()));
+                }
+                let (min_dimension, parallel) = (config.min_pdq_dimension, config.parallel_hashing);
+                state
+                    .spawn_blocking_job(move || {
+                        let img = image::load_from_memory(&data).map_err(|e| {
+                            AppError::InvalidInput(format!("Failed to decode image {}: {}", file_name, e))
+                        })?;
+                        if hash::too_small_for_pdq(&img, min_dimension) {
+                            return Err(AppError::InvalidInput(format!("image {} is too small for a PDQ hash", file_name)));
+                        }
+                        let pdq = hash::compute_pdq_hash_with(&img, parallel)?;
+                        Ok((file_name, pdq))
+                    })
+                    .await??
+            }
+            Some("id") => {
+                let id = String::from_utf8(read_small_field(field, "id").await?)
+                    .map_err(|_| AppError::InvalidInput("id must be UTF-8".to_string()))?;
+                let manifests = state.indexed_manifests.read().unwrap_or_else(|e| e.into_inner());
+                let pdq = manifests
+                    .get(&id)
+                    .and_then(|manifest| manifest.pdq_hash.clone())
+                    .ok_or_else(|| AppError::NotFound(format!("no stored image {} with a PDQ hash", id)))?;
+                (id, pdq)
+            }
+            _ => continue,
+        };
+        order.push(label);
+        hashes.push(pdq);
+    }
+    if order.is_empty() {
+        return Err(AppError::UploadError("No \"file\" or \"id\" fields provided".to_string()));
+    }
+
+    let distances = state.spawn_blocking_job(move || pdq_distance_matrix(&hashes)).await??;
+    Ok(Json(ApiResponse::success(DistanceMatrix { order, distances })))
+}
+
+/// Processes every image and video in an uploaded ZIP archive.
+///
+/// This endpoint accepts multipart form data with a "file" field holding the
+/// archive. Nothing is persisted; the response lists one entry per processed
+/// media file with either its `manifest` or an `error`, keyed by archive path.
+pub async fn upload_archive(
+    State(state): State<Arc<AppState>>,
+    mut multipart: Multipart,
+) -> Result<impl IntoResponse> {
+    let _upload = state.track_upload();
+    let _job = state.acquire_job_slot().await?;
+
+    let mut archive = None;
+    while let Some(field) = multipart.next_field().await.map_err(|e| {
+        AppError::UploadError(format!("Failed to read multipart field: {}", e))
+    })? {
+        if field.name() == Some("file") {
+            let data = field.bytes().await.map_err(|e| {
+                AppError::UploadError(format!("Failed to read file content: {}", e))
+            })?;
+            archive = Some(data);
+        }
+    }
+
+    let archive = archive.ok_or_else(|| AppError::UploadError("No file provided".to_string()))?;
+    if archive.is_empty() {
+        return Err(AppError::Validation("empty file".to_string()));
+    }
+
+    let results = state.spawn_blocking_job(move || {
+        crate::core::archive::process_zip(std::io::Cursor::new(archive))
+    })
+    .await??;
+
+    let entries: Vec<serde_json::Value> = results
+        .into_iter()
+        .map(|(path, result)| match result {
+            Ok(manifest) => serde_json::json!({ "path": path, "manifest": manifest }),
+            Err(e) => serde_json::json!({ "path": path, "error": e.to_string() }),
+        })
+        .collect();
+
+    Ok(Json(ApiResponse::success(entries)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_channel_reader_yields_chunks_in_order() {
+        use std::io::Read;
+        let (sender, receiver) = tokio::sync::mpsc::channel(2);
+        let writer = std::thread::spawn(move || {
+            for chunk in [&b"stream"[..], b"", b"ed in ", b"pieces"] {
+                sender.blocking_send(bytes::Bytes::from_static(chunk)).unwrap();
+            }
+        });
+        let mut read = String::new();
+        ChannelReader { receiver, chunk: bytes::Bytes::new() }.read_to_string(&mut read).unwrap();
+        writer.join().unwrap();
+        assert_eq!(read, "streamed in pieces");
+    }
+
+    #[cfg(feature = "video")]
+    #[tokio::test]
+    async fn test_progressive_extraction_matches_extraction_from_the_file() {
+        if crate::core::video::check_ffmpeg_installed().is_err() {
+            return;
+        }
+        let dir = tempfile::tempdir().unwrap();
+        let encode = |name: &str, args: &[&str]| {
+            let path = dir.path().join(name);
+            let status = std::process::Command::new("ffmpeg")
+                .args(["-hide_banner", "-loglevel", "error"])
+                .args(["-f", "lavfi", "-i", "testsrc=s=64x64:d=3:r=10"])
+                .args(args)
+                .arg(&path)
+                .status()
+                .unwrap();
+            assert!(status.success());
+            path
+        };
+        let state = AppState::new();
+        let config = state.config();
+        let settings = FrameSettings::of(&UploadParams::default()).unwrap();
+        let stream = |path: std::path::PathBuf| {
+            let state = state.clone();
+            let (settings, config) = (settings.clone(), config.clone());
+            async move {
+                let mut extraction = ProgressiveExtraction::start(&state, settings.clone(), &path, &config);
+                for chunk in std::fs::read(&path).unwrap().chunks(4096) {
+                    extraction.feed(bytes::Bytes::copy_from_slice(chunk)).await;
+                }
+                extraction.finish(&settings).await
+            }
+        };
+
+        // Streamable containers are decoded while they arrive
+        let mkv = encode("clip.mkv", &["-c:v", "ffv1"]);
+        let streamed = stream(mkv.clone()).await.expect("mkv decodes from a pipe");
+        let mut from_file = Vec::new();
+        settings
+            .extractor(&mkv, &config)
+            .extract_frames(|frame, timestamp| {
+                from_file.push((frame, timestamp));
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(streamed.len(), from_file.len());
+        for ((a, ta), (b, tb)) in streamed.iter().zip(&from_file) {
+            assert_eq!(ta, tb);
+            assert_eq!(a.to_rgb8(), b.to_rgb8());
+        }
+
+        // An MP4 indexed at the end needs the whole file, so it is left to extraction afterwards
+        let mp4 = encode("clip.mp4", &["-c:v", "mpeg4"]);
+        assert!(stream(mp4).await.is_none());
+
+        // Options that changed after the upload started discard the streamed frames
+        let mut extraction = ProgressiveExtraction::start(&state, settings.clone(), &mkv, &config);
+        extraction.feed(std::fs::read(&mkv).unwrap().into()).await;
+        let slower = FrameSettings { interval_secs: 2.0, ..settings };
+        assert!(extraction.finish(&slower).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_move_falls_back_to_copy_across_filesystems() {
+        let temp = tempfile::tempdir().unwrap();
+        let uploads = tempfile::tempdir().unwrap();
+        let from = temp.path().join("upload.png");
+        let to = uploads.path().join("stored.png");
+        std::fs::write(&from, b"uploaded bytes").unwrap();
+
+        let cross_device = |_, _| async { Err(std::io::Error::from(std::io::ErrorKind::CrossesDevices)) };
+        move_file_with(&from, &to, cross_device).await.unwrap();
+
+        assert_eq!(std::fs::read(&to).unwrap(), b"uploaded bytes");
+        assert!(!from.exists());
+        assert_eq!(std::fs::read_dir(uploads.path()).unwrap().count(), 1, "partial copy left behind");
+
+        // Other rename failures are not papered over
+        std::fs::write(&from, b"again").unwrap();
+        let denied = |_, _| async { Err(std::io::Error::from(std::io::ErrorKind::PermissionDenied)) };
+        assert!(move_file_with(&from, &to, denied).await.is_err());
+        assert!(from.exists());
+    }
+
+    #[test]
+    fn test_byte_range_parsing() {
+        assert_eq!(byte_range("bytes=0-99", 1000), Some((0, 99)));
+        assert_eq!(byte_range("bytes=900-", 1000), Some((900, 999)));
+        assert_eq!(byte_range("bytes=900-5000", 1000), Some((900, 999)));
+        assert_eq!(byte_range("bytes=-5000", 1000), Some((0, 999)));
+        assert_eq!(byte_range("bytes=1000-1001", 1000), None);
+        assert_eq!(byte_range("bytes=5-2", 1000), None);
+        assert_eq!(byte_range("bytes=0-1,5-6", 1000), None);
+        assert_eq!(byte_range("items=0-1", 1000), None);
+        assert_eq!(byte_range("bytes=0-", 0), None);
+    }
+
+    #[test]
+    fn test_capabilities_reflect_features() {
+        let config = Config {
+            max_upload_size: 1234,
+            ..Default::default()
+        };
+        let json = serde_json::to_value(Capabilities::from_config(&config)).unwrap();
+
+        assert_eq!(json["video_supported"], cfg!(feature = "video"));
+        assert_eq!(json["embeddings_supported"], cfg!(feature = "embeddings"));
+        assert_eq!(json["max_upload_size"], 1234);
+        assert!(json["image_formats"].as_array().unwrap().contains(&"png".into()));
+        assert_eq!(
+            json["perceptual_algorithms"].as_array().unwrap().contains(&"pdq".into()),
+            cfg!(feature = "hashing")
+        );
+        assert_eq!(
+            json["hash_algorithms"].as_array().unwrap().contains(&"sha3-256".into()),
+            cfg!(feature = "hashing")
+        );
+    }
+}
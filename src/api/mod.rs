@@ -3,44 +3,211 @@
 #[cfg(feature = "web")]
 pub(crate) mod handlers;
 #[cfg(feature = "web")]
+pub(crate) mod openapi;
+#[cfg(feature = "web")]
 pub(crate) mod responses;
+#[cfg(feature = "web")]
+pub(crate) mod signing;
 
 #[cfg(feature = "web")]
 use axum::{
+    extract::DefaultBodyLimit,
     routing::{get, post},
     Router,
 };
 #[cfg(feature = "web")]
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
+#[cfg(feature = "web")]
+use axum::http::HeaderValue;
 #[cfg(feature = "web")]
 use tower_http::{
-    cors::{Any, CorsLayer},
+    cors::{AllowHeaders, AllowMethods, AllowOrigin, Any, CorsLayer},
+    limit::RequestBodyLimitLayer,
+    timeout::TimeoutLayer,
     trace::TraceLayer,
 };
 #[cfg(feature = "web")]
-use crate::state::AppState;
+use crate::{
+    error::{AppError, Result},
+    state::{AppState, Config},
+};
 
 #[cfg(feature = "web")]
 pub(crate) use handlers::*;
 
 #[cfg(feature = "web")]
-/// Create the application router with all routes
+/// Create the application router with all routes, permissive CORS and the
+/// default body limits
+///
+/// This is the router [`create_router_with_config`] builds for
+/// `Config::default()`, whose empty origin list allows any origin.
 pub fn create_router() -> Router<Arc<AppState>> {
-    // Configure CORS
-    let cors = CorsLayer::new()
+    routes(&Config::default()).layer(TraceLayer::new_for_http()).layer(permissive_cors())
+}
+
+#[cfg(feature = "web")]
+/// Create the application router with CORS, body limits and request timeouts configured from `config`
+///
+/// `Config::request_timeout` applies to every route except uploads, which
+/// may legitimately stream for a long time. Routes taking JSON bodies are
+/// capped at `Config::max_json_body_size` and the rest at
+/// `Config::max_upload_size`. Fails if the CORS settings are invalid (see
+/// [`cors_layer`]).
+pub fn create_router_with_config(config: &Config) -> Result<Router<Arc<AppState>>> {
+    Ok(routes(config)
+        .layer(TraceLayer::new_for_http())
+        .layer(cors_layer(config)?))
+}
+
+#[cfg(feature = "web")]
+/// Build the CORS layer described by `config`
+///
+/// With no `cors_allowed_origins` every origin, method and header is allowed.
+/// Otherwise only the listed origins are, and requested methods and headers
+/// are mirrored back, which keeps credentialed requests valid. Allowing
+/// credentials requires an explicit origin list, since browsers reject
+/// credentials combined with a wildcard origin.
+pub fn cors_layer(config: &Config) -> Result<CorsLayer> {
+    if config.cors_allowed_origins.is_empty() {
+        if config.cors_allow_credentials {
+            return Err(AppError::Config(
+                "CORS credentials require an explicit list of allowed origins".to_string(),
+            ));
+        }
+        return Ok(permissive_cors());
+    }
+
+    let origins = config
+        .cors_allowed_origins
+        .iter()
+        .map(|origin| {
+            if origin == "*" {
+                return Err(AppError::Config(
+                    "use an empty CORS origin list instead of \"*\" to allow any origin".to_string(),
+                ));
+            }
+            HeaderValue::from_str(origin)
+                .map_err(|_| AppError::Config(format!("invalid CORS origin: {}", origin)))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(CorsLayer::new()
+        .allow_origin(AllowOrigin::list(origins))
+        .allow_methods(AllowMethods::mirror_request())
+        .allow_headers(AllowHeaders::mirror_request())
+        .allow_credentials(config.cors_allow_credentials))
+}
+
+#[cfg(feature = "web")]
+fn permissive_cors() -> CorsLayer {
+    CorsLayer::new()
         .allow_origin(Any)
         .allow_methods(Any)
-        .allow_headers(Any);
+        .allow_headers(Any)
+}
+
+#[cfg(feature = "web")]
+/// All API routes, with the request timeout applied to everything but
+/// uploads and each route group capped at its body limit
+fn routes(config: &Config) -> Router<Arc<AppState>> {
+    let upload_limit = usize::try_from(config.max_upload_size).unwrap_or(usize::MAX);
+    let timed = with_body_limit(json_routes(), config.max_json_body_size)
+        .merge(with_body_limit(timed_routes(), upload_limit));
+    with_request_timeout(timed, with_body_limit(upload_routes(), upload_limit), config.request_timeout)
+}
+
+#[cfg(feature = "web")]
+/// Rejects request bodies over `limit` bytes on every route of `router` with `413 Payload Too Large`
+fn with_body_limit<S>(router: Router<S>, limit: usize) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    router
+        .layer(DefaultBodyLimit::max(limit))
+        .layer(RequestBodyLimitLayer::new(limit))
+}
 
+#[cfg(feature = "web")]
+/// Routes that receive file bodies and are exempt from the request timeout
+fn upload_routes() -> Router<Arc<AppState>> {
     Router::new()
-        // Public health check (no rate limiting)
-        .route("/api/health", get(health_check))
         // Upload endpoint
         .route("/api/upload", post(upload_file))
+        // Stateless hashing: nothing is stored
+        .route("/api/hash", post(hash_file))
+        // Every registered perceptual hash of one image, for comparing algorithms
+        .route("/api/perceptual-all", post(perceptual_all))
+        // Nearest stored image to a query image
+        .route("/api/closest", post(closest))
+        // Stream stored images near an uploaded one, closest first
+        .route("/api/search-similar", post(search_similar))
+        // Pairwise PDQ distances between uploaded and stored images
+        .route("/api/distance-matrix", post(distance_matrix))
+        // Archive upload: one manifest per contained media file
+        .route("/api/upload-archive", post(upload_archive))
+}
+
+#[cfg(feature = "web")]
+/// Applies `timeout` to the `timed` routes only, then merges in `untimed`
+///
+/// Requests on a timed route that run longer get a `408 Request Timeout`.
+fn with_request_timeout<S>(timed: Router<S>, untimed: Router<S>, timeout: Option<Duration>) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    let timed = match timeout {
+        Some(timeout) => timed.layer(TimeoutLayer::new(timeout)),
+        None => timed,
+    };
+    timed.merge(untimed)
+}
+
+#[cfg(feature = "web")]
+/// Every route that is not an upload and takes no JSON body, without middleware
+fn timed_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        // Public health check (no rate limiting)
+        .route("/api/health", get(health_check))
+        // Machine-readable API contract
+        .route("/api/openapi.json", get(openapi))
+        // Supported formats and compiled-in features
+        .route("/api/capabilities", get(capabilities))
+        // Live job queue and upload counts
+        .route("/api/status", get(status))
+        // Stored manifests, paginated
+        .route("/api/manifests", get(list_manifests))
+        // Stream near-duplicate pairs among stored images, closest first
+        .route("/api/find-duplicates", get(find_duplicates))
+        // Progress of a batch job
+        .route("/api/jobs/:id", get(job_status))
+        // Audit log of verifications
+        .route("/api/audit", get(audit))
+        // Signed, time-limited file download
+        .route("/api/manifest/:id/download", get(download_file))
+        // Signed file streaming with Range support for seeking
+        .route("/api/manifest/:id/file", get(stream_file))
+        // Pixel-level diff between two images
+        .route("/api/image-diff", post(image_diff))
+        // PDQ distance between two images, optionally with the differing bits
+        .route("/api/compare", post(compare))
+        // Frame membership endpoint
+        .route("/api/frame-match", post(frame_match))
+}
+
+#[cfg(feature = "web")]
+/// Timed routes whose bodies are JSON documents, which get the small body limit
+fn json_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        // Resumable batch jobs over directories and archives
+        .route("/api/jobs", post(submit_job))
         // Verification endpoint
         .route("/api/verify", post(verify_manifest))
-        .layer(TraceLayer::new_for_http())
-        .layer(cors)
+        // Frame-by-frame verification streaming NDJSON progress
+        .route("/api/verify/progress", post(verify_manifest_progress))
+        // Order-aware comparison of two videos
+        .route("/api/video-compare", post(video_compare))
+        // Single-number similarity score of two videos
+        .route("/api/video-similarity", post(video_similarity))
 }
 
 #[cfg(feature = "web")]
@@ -49,3 +216,37 @@ pub async fn health_check() -> &'static str {
     "OK"
 }
 
+
+#[cfg(all(test, feature = "web"))]
+mod tests {
+    use super::*;
+    use axum::{body::Body, http::{Request, StatusCode}};
+    use tower::ServiceExt;
+
+    async fn slow() -> &'static str {
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        "done"
+    }
+
+    #[tokio::test]
+    async fn test_timeout_applies_only_to_timed_routes() {
+        let app: Router = with_request_timeout(
+            Router::new().route("/slow", get(slow)),
+            Router::new().route("/upload", post(slow)),
+            Some(Duration::from_millis(50)),
+        );
+
+        let timed = app
+            .clone()
+            .oneshot(Request::get("/slow").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(timed.status(), StatusCode::REQUEST_TIMEOUT);
+
+        let upload = app
+            .oneshot(Request::post("/upload").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(upload.status(), StatusCode::OK);
+    }
+}
@@ -0,0 +1,360 @@
+//! OpenAPI document describing the HTTP API
+
+use schemars::{
+    gen::{SchemaGenerator, SchemaSettings},
+    schema::Schema,
+    JsonSchema,
+};
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+
+use super::{
+    handlers::{AuditParams, ClosestMatch, ClosestParams, CompareParams, DistanceMatrix, FrameMatchParams, HashResult, PdqComparison, JobReport, JobRequest, ListParams, SimilarParams, UploadParams, VerifyParams, VerifyProgressParams, VideoCompareParams, VideoCompareRequest},
+    responses::ApiResponse,
+};
+use crate::{core::{similarity::SequenceMatch, store::AuditEntry}, error::ErrorResponse, models::manifest::MediaManifest};
+
+/// Builds the OpenAPI 3.0 document for the routes in [`create_router`](super::create_router).
+pub(crate) fn openapi_document() -> Value {
+    let mut gen = SchemaSettings::openapi3().into_generator();
+
+    let manifest = schema_json::<MediaManifest>(&mut gen);
+    let manifest_response = schema_json::<ApiResponse<MediaManifest>>(&mut gen);
+    let json_response = schema_json::<ApiResponse<Value>>(&mut gen);
+    let hash_response = schema_json::<ApiResponse<HashResult>>(&mut gen);
+    let perceptual_response = schema_json::<ApiResponse<BTreeMap<String, String>>>(&mut gen);
+    let closest_response = schema_json::<ApiResponse<ClosestMatch>>(&mut gen);
+    let compare_response = schema_json::<ApiResponse<PdqComparison>>(&mut gen);
+    let matrix_response = schema_json::<ApiResponse<DistanceMatrix>>(&mut gen);
+    let list_response = schema_json::<ApiResponse<Vec<MediaManifest>>>(&mut gen);
+    let sequence_response = schema_json::<ApiResponse<SequenceMatch>>(&mut gen);
+    let video_compare_body = schema_json::<VideoCompareRequest>(&mut gen);
+    let job_body = schema_json::<JobRequest>(&mut gen);
+    let job_response = schema_json::<ApiResponse<JobReport>>(&mut gen);
+    let audit_response = schema_json::<ApiResponse<Vec<AuditEntry>>>(&mut gen);
+    let error = schema_json::<ErrorResponse>(&mut gen);
+
+    let upload_params = query_parameters::<UploadParams>(&mut gen);
+    let verify_params = query_parameters::<VerifyParams>(&mut gen);
+    let verify_progress_params = query_parameters::<VerifyProgressParams>(&mut gen);
+    let audit_params = query_parameters::<AuditParams>(&mut gen);
+    let closest_params = query_parameters::<ClosestParams>(&mut gen);
+    let compare_params = query_parameters::<CompareParams>(&mut gen);
+    let list_params = query_parameters::<ListParams>(&mut gen);
+    let similar_params = query_parameters::<SimilarParams>(&mut gen);
+    let frame_match_params = query_parameters::<FrameMatchParams>(&mut gen);
+    let video_compare_params = query_parameters::<VideoCompareParams>(&mut gen);
+
+    let ok_json = |description: &str, schema: &Value| {
+        json!({
+            "200": {
+                "description": description,
+                "content": { "application/json": { "schema": schema } }
+            },
+            "default": {
+                "description": "Error",
+                "content": { "application/json": { "schema": error } }
+            }
+        })
+    };
+    let ok_binary = |description: &str, content_type: &str| {
+        json!({
+            "200": {
+                "description": description,
+                "content": { content_type: { "schema": { "type": "string", "format": "binary" } } }
+            },
+            "default": {
+                "description": "Error",
+                "content": { "application/json": { "schema": error } }
+            }
+        })
+    };
+    let binary = json!({ "type": "string", "format": "binary" });
+    let multipart = |fields: &[(&str, &Value)]| {
+        let properties: Map<String, Value> = fields
+            .iter()
+            .map(|(name, schema)| (name.to_string(), (*schema).clone()))
+            .collect();
+        let required: Vec<&str> = fields.iter().map(|(name, _)| *name).collect();
+        json!({
+            "required": true,
+            "content": {
+                "multipart/form-data": {
+                    "schema": { "type": "object", "properties": properties, "required": required }
+                }
+            }
+        })
+    };
+    let manifest_text = json!({ "type": "string", "description": "MediaManifest JSON of the video" });
+    let manifest_body = json!({
+        "required": true,
+        "content": { "application/json": { "schema": manifest } }
+    });
+
+    let definitions: Map<String, Value> = gen
+        .definitions()
+        .iter()
+        .map(|(name, schema)| (name.clone(), to_value(schema)))
+        .collect();
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": env!("CARGO_PKG_NAME"),
+            "version": env!("CARGO_PKG_VERSION"),
+            "description": env!("CARGO_PKG_DESCRIPTION"),
+        },
+        "paths": {
+            "/api/health": {
+                "get": {
+                    "summary": "Health check",
+                    "responses": { "200": { "description": "Server is up" } }
+                }
+            },
+            "/api/capabilities": {
+                "get": {
+                    "summary": "Supported formats, compiled-in features and upload limits",
+                    "responses": ok_json("Server capabilities", &json_response)
+                }
+            },
+            "/api/status": {
+                "get": {
+                    "summary": "Active and queued jobs, uploads in progress and uptime",
+                    "responses": ok_json("Load status", &json_response)
+                }
+            },
+            "/api/upload": {
+                "post": {
+                    "summary": "Upload a file and generate its manifest",
+                    "parameters": upload_params,
+                    "requestBody": multipart(&[("file", &binary)]),
+                    "responses": ok_json("Generated manifest", &manifest_response)
+                }
+            },
+            "/api/hash": {
+                "post": {
+                    "summary": "Hash a file without storing it",
+                    "requestBody": multipart(&[("file", &binary)]),
+                    "responses": ok_json("SHA3-256 and PDQ hashes", &hash_response)
+                }
+            },
+            "/api/perceptual-all": {
+                "post": {
+                    "summary": "Hash an image with every registered perceptual algorithm",
+                    "requestBody": multipart(&[("file", &binary)]),
+                    "responses": ok_json("Perceptual hashes keyed by algorithm name", &perceptual_response)
+                }
+            },
+            "/api/closest": {
+                "post": {
+                    "summary": "Find the stored image closest to an image by PDQ distance",
+                    "parameters": closest_params,
+                    "requestBody": multipart(&[("file", &binary)]),
+                    "responses": ok_json("Closest stored image; 404 when none is within the threshold", &closest_response)
+                }
+            },
+            "/api/search-similar": {
+                "post": {
+                    "summary": "Stream stored images within a PDQ distance of an image, closest first",
+                    "parameters": similar_params,
+                    "requestBody": multipart(&[("file", &binary)]),
+                    "responses": ok_binary("Newline-delimited JSON file_name/distance lines", "application/x-ndjson")
+                }
+            },
+            "/api/distance-matrix": {
+                "post": {
+                    "summary": "Pairwise PDQ distances between uploaded and stored images",
+                    "requestBody": {
+                        "required": true,
+                        "content": {
+                            "multipart/form-data": {
+                                "schema": {
+                                    "type": "object",
+                                    "properties": {
+                                        "file": { "type": "array", "items": binary },
+                                        "id": { "type": "array", "items": { "type": "string" } }
+                                    }
+                                }
+                            }
+                        }
+                    },
+                    "responses": ok_json("Row order and symmetric distance matrix", &matrix_response)
+                }
+            },
+            "/api/find-duplicates": {
+                "get": {
+                    "summary": "Stream pairs of stored images within a PDQ distance of each other, closest first",
+                    "parameters": similar_params,
+                    "responses": ok_binary("Newline-delimited JSON a/b/distance lines", "application/x-ndjson")
+                }
+            },
+            "/api/manifests": {
+                "get": {
+                    "summary": "List stored manifests by file name; meta carries total, limit and offset",
+                    "parameters": list_params,
+                    "responses": ok_json("A page of manifests", &list_response)
+                }
+            },
+            "/api/jobs": {
+                "post": {
+                    "summary": "Start or resume a batch job over a directory or ZIP archive in the jobs directory",
+                    "requestBody": {
+                        "required": true,
+                        "content": { "application/json": { "schema": job_body } }
+                    },
+                    "responses": {
+                        "202": {
+                            "description": "The job and its progress",
+                            "content": { "application/json": { "schema": job_response } }
+                        },
+                        "default": {
+                            "description": "Error",
+                            "content": { "application/json": { "schema": error } }
+                        }
+                    }
+                }
+            },
+            "/api/jobs/{id}": {
+                "get": {
+                    "summary": "Report a batch job's progress and the manifests of its finished items",
+                    "parameters": [
+                        { "name": "id", "in": "path", "required": true, "schema": { "type": "string" } }
+                    ],
+                    "responses": ok_json("The job and its progress", &job_response)
+                }
+            },
+            "/api/upload-archive": {
+                "post": {
+                    "summary": "Upload a ZIP archive and generate a manifest per media entry",
+                    "requestBody": multipart(&[("file", &binary)]),
+                    "responses": ok_json("Per-entry manifests or errors", &json_response)
+                }
+            },
+            "/api/verify": {
+                "post": {
+                    "summary": "Verify a file against its manifest",
+                    "parameters": verify_params,
+                    "requestBody": manifest_body,
+                    "responses": ok_json("Verification result", &json_response)
+                }
+            },
+            "/api/verify/progress": {
+                "post": {
+                    "summary": "Verify a manifest frame by frame, streaming progress",
+                    "parameters": verify_progress_params,
+                    "requestBody": manifest_body,
+                    "responses": ok_binary("Newline-delimited JSON progress events", "application/x-ndjson")
+                }
+            },
+            "/api/audit": {
+                "get": {
+                    "summary": "List recorded verifications, oldest first",
+                    "parameters": audit_params,
+                    "responses": ok_json("Audit log entries", &audit_response)
+                }
+            },
+            "/api/manifest/{id}/download": {
+                "get": {
+                    "summary": "Download an uploaded file through a signed link",
+                    "parameters": [
+                        { "name": "id", "in": "path", "required": true, "schema": { "type": "string" } },
+                        { "name": "expires", "in": "query", "required": true, "schema": { "type": "integer", "format": "int64" } },
+                        { "name": "signature", "in": "query", "required": true, "schema": { "type": "string" } }
+                    ],
+                    "responses": ok_binary("File contents", "application/octet-stream")
+                }
+            },
+            "/api/manifest/{id}/file": {
+                "get": {
+                    "summary": "Stream an uploaded file through a signed link, honouring Range requests",
+                    "parameters": [
+                        { "name": "id", "in": "path", "required": true, "schema": { "type": "string" } },
+                        { "name": "expires", "in": "query", "required": true, "schema": { "type": "integer", "format": "int64" } },
+                        { "name": "signature", "in": "query", "required": true, "schema": { "type": "string" } },
+                        { "name": "Range", "in": "header", "required": false, "schema": { "type": "string" } }
+                    ],
+                    "responses": {
+                        "200": { "description": "Whole file", "content": { "application/octet-stream": { "schema": binary } } },
+                        "206": { "description": "Requested byte range", "content": { "application/octet-stream": { "schema": binary } } },
+                        "416": { "description": "Range not satisfiable" },
+                        "default": {
+                            "description": "Error",
+                            "content": { "application/json": { "schema": error } }
+                        }
+                    }
+                }
+            },
+            "/api/image-diff": {
+                "post": {
+                    "summary": "Pixel-level diff heatmap between two images",
+                    "requestBody": multipart(&[("a", &binary), ("b", &binary)]),
+                    "responses": ok_binary("PNG heatmap of changed pixels", "image/png")
+                }
+            },
+            "/api/compare": {
+                "post": {
+                    "summary": "PDQ distance between two images, with the raw hashes and differing bits when verbose",
+                    "parameters": compare_params,
+                    "requestBody": multipart(&[("a", &binary), ("b", &binary)]),
+                    "responses": ok_json("PDQ distance, plus hashes and XOR mask with verbose=true", &compare_response)
+                }
+            },
+            "/api/frame-match": {
+                "post": {
+                    "summary": "Find the video frame closest to an image",
+                    "parameters": frame_match_params,
+                    "requestBody": multipart(&[("file", &binary), ("manifest", &manifest_text)]),
+                    "responses": ok_json("Closest matching frame, if any", &json_response)
+                }
+            },
+            "/api/video-compare": {
+                "post": {
+                    "summary": "Compare two videos by aligning their frame sequences",
+                    "parameters": video_compare_params,
+                    "requestBody": {
+                        "required": true,
+                        "content": { "application/json": { "schema": video_compare_body } }
+                    },
+                    "responses": ok_json("Sequence alignment and set overlap", &sequence_response)
+                }
+            },
+            "/api/video-similarity": {
+                "post": {
+                    "summary": "Score how similar two videos are from 0 to 1",
+                    "requestBody": {
+                        "required": true,
+                        "content": { "application/json": { "schema": video_compare_body } }
+                    },
+                    "responses": ok_json("Similarity score", &json_response)
+                }
+            }
+        },
+        "components": { "schemas": definitions }
+    })
+}
+
+/// Returns the schema for `T`, registering named types as components.
+fn schema_json<T: JsonSchema>(gen: &mut SchemaGenerator) -> Value {
+    to_value(&gen.subschema_for::<T>())
+}
+
+/// Describes each field of a query-string struct as an optional query parameter.
+fn query_parameters<T: JsonSchema>(gen: &mut SchemaGenerator) -> Value {
+    let root = gen.root_schema_for::<T>();
+    let properties = root
+        .schema
+        .object
+        .map(|object| object.properties)
+        .unwrap_or_default();
+
+    properties
+        .into_iter()
+        .map(|(name, schema)| {
+            json!({ "name": name, "in": "query", "required": false, "schema": to_value(&schema) })
+        })
+        .collect()
+}
+
+fn to_value(schema: &Schema) -> Value {
+    serde_json::to_value(schema).unwrap_or(Value::Null)
+}
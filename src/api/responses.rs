@@ -3,14 +3,18 @@ use axum::{
     response::{IntoResponse, Response},
     Json,
 };
+use schemars::JsonSchema;
 use serde::Serialize;
 use serde_json::json;
 
-#[derive(Serialize)]
+#[derive(Serialize, JsonSchema)]
 pub(crate) struct ApiResponse<T: Serialize> {
     pub success: bool,
     pub data: Option<T>,
     pub error: Option<String>,
+    /// Extra information about `data`, such as paging for list endpoints
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub meta: Option<serde_json::Value>,
 }
 
 impl<T: Serialize> ApiResponse<T> {
@@ -19,6 +23,14 @@ impl<T: Serialize> ApiResponse<T> {
             success: true,
             data: Some(data),
             error: None,
+            meta: None,
+        }
+    }
+
+    pub(crate) fn success_with_meta(data: T, meta: serde_json::Value) -> Self {
+        Self {
+            meta: Some(meta),
+            ..Self::success(data)
         }
     }
 
@@ -28,6 +40,7 @@ impl<T: Serialize> ApiResponse<T> {
             success: false,
             data: None,
             error: Some(message.to_string()),
+            meta: None,
         }
     }
 }
@@ -41,11 +54,14 @@ impl<T: Serialize> IntoResponse for ApiResponse<T> {
             StatusCode::BAD_REQUEST
         };
 
-        let body = json!({
+        let mut body = json!({
             "success": self.success,
             "data": self.data,
             "error": self.error,
         });
+        if let Some(meta) = self.meta {
+            body["meta"] = meta;
+        }
 
         (status, Json(body)).into_response()
     }
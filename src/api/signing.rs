@@ -0,0 +1,111 @@
+//! HMAC-signed, time-limited download links
+
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use sha3::Sha3_256;
+
+use crate::error::{AppError, Result};
+
+type HmacSha3 = Hmac<Sha3_256>;
+
+/// Builds the HMAC over a download id and its expiry timestamp.
+fn download_mac(id: &str, expires: i64, secret: &[u8]) -> HmacSha3 {
+    // HMAC accepts keys of any length, so this cannot fail
+    let mut mac = HmacSha3::new_from_slice(secret).expect("HMAC accepts any key length");
+    mac.update(id.as_bytes());
+    mac.update(b":");
+    mac.update(expires.to_string().as_bytes());
+    mac
+}
+
+/// Builds a signed download path for `id` that is valid until `expires_at`.
+pub(crate) fn signed_download_url(id: &str, expires_at: DateTime<Utc>, secret: &[u8]) -> String {
+    let expires = expires_at.timestamp();
+    let signature = hex::encode(download_mac(id, expires, secret).finalize().into_bytes());
+    format!(
+        "/api/manifest/{}/download?expires={}&signature={}",
+        id, expires, signature
+    )
+}
+
+/// Checks a download signature and expiry.
+///
+/// Returns `AppError::Auth` if the link has expired or the signature does not
+/// match `id` and `expires` under `secret`.
+pub(crate) fn verify_download_signature(
+    id: &str,
+    expires: i64,
+    signature: &str,
+    secret: &[u8],
+    now: DateTime<Utc>,
+) -> Result<()> {
+    if now.timestamp() > expires {
+        return Err(AppError::Auth("download link has expired".to_string()));
+    }
+
+    let signature = hex::decode(signature)
+        .map_err(|_| AppError::Auth("invalid download signature".to_string()))?;
+    download_mac(id, expires, secret)
+        .verify_slice(&signature)
+        .map_err(|_| AppError::Auth("invalid download signature".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    const SECRET: &[u8] = b"test-secret";
+
+    /// Splits a signed URL into its expiry and signature query values
+    fn parse(url: &str) -> (i64, String) {
+        let query = url.split_once('?').unwrap().1;
+        let mut expires = 0;
+        let mut signature = String::new();
+        for pair in query.split('&') {
+            match pair.split_once('=').unwrap() {
+                ("expires", v) => expires = v.parse().unwrap(),
+                ("signature", v) => signature = v.to_string(),
+                _ => {}
+            }
+        }
+        (expires, signature)
+    }
+
+    #[test]
+    fn test_valid_signed_url() {
+        let now = Utc::now();
+        let url = signed_download_url("abc.png", now + Duration::minutes(5), SECRET);
+        assert!(url.starts_with("/api/manifest/abc.png/download?"));
+
+        let (expires, signature) = parse(&url);
+        assert!(verify_download_signature("abc.png", expires, &signature, SECRET, now).is_ok());
+    }
+
+    #[test]
+    fn test_expired_signed_url() {
+        let now = Utc::now();
+        let url = signed_download_url("abc.png", now - Duration::seconds(1), SECRET);
+
+        let (expires, signature) = parse(&url);
+        let result = verify_download_signature("abc.png", expires, &signature, SECRET, now);
+        assert!(matches!(result, Err(AppError::Auth(_))));
+    }
+
+    #[test]
+    fn test_tampered_signed_url() {
+        let now = Utc::now();
+        let url = signed_download_url("abc.png", now + Duration::minutes(5), SECRET);
+        let (expires, signature) = parse(&url);
+
+        // A different file, a later expiry or another secret must all be rejected
+        for (id, expires, secret) in [
+            ("other.png", expires, SECRET),
+            ("abc.png", expires + 3600, SECRET),
+            ("abc.png", expires, b"wrong-secret".as_slice()),
+        ] {
+            let result = verify_download_signature(id, expires, &signature, secret, now);
+            assert!(matches!(result, Err(AppError::Auth(_))));
+        }
+    }
+}
@@ -0,0 +1,276 @@
+use std::io::{Read, Seek};
+
+use crate::error::{AppError, Result};
+use crate::models::manifest::{MediaManifest, MediaType};
+
+/// Bounds on how much an archive may decompress to
+///
+/// Entry sizes in the ZIP headers are whatever the archive's author wrote, so
+/// they are not trusted: entries are read through these limits instead, which
+/// keeps a zip bomb from exhausting memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ZipLimits {
+    /// Largest decompressed entry, in bytes; larger entries fail on their own
+    pub max_entry_bytes: u64,
+    /// Most bytes decompressed from one archive; past it the whole archive fails
+    pub max_total_bytes: u64,
+}
+
+impl Default for ZipLimits {
+    fn default() -> Self {
+        Self {
+            max_entry_bytes: 256 * 1024 * 1024,
+            max_total_bytes: 1024 * 1024 * 1024,
+        }
+    }
+}
+
+/// Processes every image, video and document inside a ZIP archive
+///
+/// Entries are read in archive order and processed in memory; videos are
+/// spooled to a temporary file for frame extraction. Directories and entries
+/// that are not media are skipped. Each processed entry is
+/// returned with its archive path, alongside its own result, so one bad file
+/// does not fail the whole archive.
+///
+/// # Errors
+///
+/// Returns an error only if the archive itself cannot be read.
+pub fn process_zip<R: Read + Seek>(reader: R) -> Result<Vec<(String, Result<MediaManifest>)>> {
+    process_zip_with(reader, &ZipLimits::default())
+}
+
+/// Like [`process_zip`], decompressing no more than `limits` allow
+///
+/// An entry over `max_entry_bytes` gets an `AppError::Validation` entry of its
+/// own; the rest of the archive is still processed.
+///
+/// # Errors
+///
+/// Returns an error if the archive cannot be read, or `AppError::Validation`
+/// once its entries decompress to more than `max_total_bytes`.
+pub fn process_zip_with<R: Read + Seek>(reader: R, limits: &ZipLimits) -> Result<Vec<(String, Result<MediaManifest>)>> {
+    let mut archive = zip::ZipArchive::new(reader)
+        .map_err(|e| AppError::InvalidInput(format!("Invalid ZIP archive: {}", e)))?;
+
+    let mut results = Vec::new();
+    let mut total = 0u64;
+    for index in 0..archive.len() {
+        let mut entry = archive
+            .by_index(index)
+            .map_err(|e| AppError::InvalidInput(format!("Invalid ZIP entry: {}", e)))?;
+        if entry.is_dir() {
+            continue;
+        }
+
+        let path = entry.name().to_string();
+        let media_type = MediaType::from_path(&path);
+        if media_type == MediaType::Other {
+            log::debug!("Skipping unsupported archive entry: {}", path);
+            continue;
+        }
+
+        let remaining = limits.max_total_bytes.saturating_sub(total);
+        let data = match read_entry(&mut entry, &path, limits.max_entry_bytes.min(remaining)) {
+            Err(e) if remaining < limits.max_entry_bytes && matches!(e, AppError::Validation(_)) => {
+                return Err(AppError::Validation(format!(
+                    "archive decompresses to more than {} bytes",
+                    limits.max_total_bytes
+                )));
+            }
+            other => other,
+        };
+        total += data.as_ref().map_or(0, |data| data.len() as u64);
+        let result = data.and_then(|data| process_entry(&path, media_type, &data));
+        results.push((path, result));
+    }
+
+    Ok(results)
+}
+
+/// Lists the paths of the media entries [`process_zip`] would process, in archive order
+///
+/// # Errors
+///
+/// Returns an error if the archive cannot be read.
+pub fn zip_media_entries<R: Read + Seek>(reader: R) -> Result<Vec<String>> {
+    let mut archive = zip::ZipArchive::new(reader)
+        .map_err(|e| AppError::InvalidInput(format!("Invalid ZIP archive: {}", e)))?;
+
+    let mut paths = Vec::new();
+    for index in 0..archive.len() {
+        let entry = archive
+            .by_index(index)
+            .map_err(|e| AppError::InvalidInput(format!("Invalid ZIP entry: {}", e)))?;
+        if !entry.is_dir() && MediaType::from_path(entry.name()) != MediaType::Other {
+            paths.push(entry.name().to_string());
+        }
+    }
+    Ok(paths)
+}
+
+/// Processes the single entry at `path` of a ZIP archive, like [`process_zip`] would
+///
+/// To process several entries of one archive, open it once with [`ZipEntries`].
+///
+/// # Errors
+///
+/// Returns `AppError::NotFound` if the archive has no such entry, and the
+/// entry's own error if it cannot be processed.
+pub fn process_zip_entry<R: Read + Seek>(reader: R, path: &str) -> Result<MediaManifest> {
+    ZipEntries::new(reader)?.process(path)
+}
+
+/// An open ZIP archive whose entries are processed by path, like [`process_zip`] would
+pub struct ZipEntries<R> {
+    archive: zip::ZipArchive<R>,
+}
+
+impl<R: Read + Seek> std::fmt::Debug for ZipEntries<R> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ZipEntries").field("entries", &self.archive.len()).finish()
+    }
+}
+
+impl<R: Read + Seek> ZipEntries<R> {
+    /// Opens the archive, reading its central directory once
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError::InvalidInput` if the archive cannot be read.
+    pub fn new(reader: R) -> Result<Self> {
+        let archive = zip::ZipArchive::new(reader)
+            .map_err(|e| AppError::InvalidInput(format!("Invalid ZIP archive: {}", e)))?;
+        Ok(Self { archive })
+    }
+
+    /// Processes the entry at `path`
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError::NotFound` if the archive has no such entry, and the
+    /// entry's own error if it cannot be processed.
+    pub fn process(&mut self, path: &str) -> Result<MediaManifest> {
+        let mut entry = self.archive.by_name(path).map_err(|e| match e {
+            zip::result::ZipError::FileNotFound => AppError::NotFound(format!("archive entry {}", path)),
+            e => AppError::InvalidInput(format!("Invalid ZIP entry: {}", e)),
+        })?;
+
+        let data = read_entry(&mut entry, path, ZipLimits::default().max_entry_bytes)?;
+        process_entry(path, MediaType::from_path(path), &data)
+    }
+}
+
+/// Reads an entry in full, failing with `AppError::Validation` past `limit` bytes
+///
+/// The header's size is not trusted, neither for allocating nor for stopping.
+fn read_entry(entry: &mut impl Read, path: &str, limit: u64) -> Result<Vec<u8>> {
+    let mut data = Vec::new();
+    entry.take(limit.saturating_add(1)).read_to_end(&mut data)?;
+    if data.len() as u64 > limit {
+        return Err(AppError::Validation(format!("archive entry {} is larger than {} bytes", path, limit)));
+    }
+    Ok(data)
+}
+
+/// Processes a single archive entry's bytes
+fn process_entry(path: &str, media_type: MediaType, data: &[u8]) -> Result<MediaManifest> {
+    let file_name = std::path::Path::new(path)
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or(path);
+
+    match media_type {
+        MediaType::Image => crate::process_image_bytes(file_name, data),
+        #[cfg(feature = "video")]
+        MediaType::Video => {
+            let dir = tempfile::tempdir()?;
+            let temp_path = dir.path().join(file_name);
+            std::fs::write(&temp_path, data)?;
+            crate::process_video_file(&temp_path)
+        }
+        #[cfg(not(feature = "video"))]
+        MediaType::Video => Err(AppError::Config(
+            "Video processing not available - enable 'video' feature".to_string(),
+        )),
+        MediaType::Document => crate::process_document_bytes(file_name, data),
+        MediaType::Other => Err(AppError::InvalidInput(format!("Unsupported file: {}", path))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Write};
+
+    fn png(color: [u8; 3]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        image::DynamicImage::ImageRgb8(image::RgbImage::from_pixel(8, 8, image::Rgb(color)))
+            .write_to(&mut Cursor::new(&mut bytes), image::ImageOutputFormat::Png)
+            .unwrap();
+        bytes
+    }
+
+    #[test]
+    fn test_process_zip_with_two_images() {
+        let mut buffer = Cursor::new(Vec::new());
+        {
+            let mut writer = zip::ZipWriter::new(&mut buffer);
+            let options = zip::write::FileOptions::default();
+            writer.add_directory("photos/", options).unwrap();
+            writer.start_file("photos/red.png", options).unwrap();
+            writer.write_all(&png([255, 0, 0])).unwrap();
+            writer.start_file("readme.txt", options).unwrap();
+            writer.write_all(b"not media").unwrap();
+            writer.start_file("blue.png", options).unwrap();
+            writer.write_all(&png([0, 0, 255])).unwrap();
+            writer.finish().unwrap();
+        }
+        buffer.set_position(0);
+
+        let results = process_zip(buffer).unwrap();
+
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].0, "photos/red.png");
+        assert_eq!(results[1].0, "blue.png");
+        let red = results[0].1.as_ref().unwrap();
+        assert_eq!(red.file_name, "red.png");
+        assert_eq!(red.media_type, MediaType::Image);
+        assert!(results[1].1.is_ok());
+    }
+
+    #[test]
+    fn test_oversized_entries_and_archives_are_rejected() {
+        let zip_of = |entries: &[(&str, Vec<u8>)]| {
+            let mut buffer = Cursor::new(Vec::new());
+            {
+                let mut writer = zip::ZipWriter::new(&mut buffer);
+                for (name, data) in entries {
+                    writer.start_file(*name, zip::write::FileOptions::default()).unwrap();
+                    writer.write_all(data).unwrap();
+                }
+                writer.finish().unwrap();
+            }
+            buffer.set_position(0);
+            buffer
+        };
+        let small = png([0, 255, 0]);
+        let limits = ZipLimits { max_entry_bytes: 4096, max_total_bytes: 8192 };
+
+        // Compresses to almost nothing, but decompresses past the entry limit
+        let archive = zip_of(&[("bomb.png", vec![0; 1 << 20]), ("ok.png", small)]);
+        let results = process_zip_with(archive, &limits).unwrap();
+        assert!(matches!(&results[0].1, Err(AppError::Validation(message)) if message.contains("bomb.png")));
+        assert!(results[1].1.is_ok());
+
+        // Each entry fits on its own, but together they pass the archive limit
+        let archive = zip_of(&[("a.png", vec![0; 3000]), ("b.png", vec![0; 3000]), ("c.png", vec![0; 3000])]);
+        assert!(matches!(process_zip_with(archive, &limits), Err(AppError::Validation(_))));
+    }
+
+    #[test]
+    fn test_process_zip_rejects_non_archive() {
+        let result = process_zip(Cursor::new(b"not a zip".to_vec()));
+        assert!(matches!(result, Err(AppError::InvalidInput(_))));
+    }
+}
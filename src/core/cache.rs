@@ -0,0 +1,119 @@
+use anyhow::Result;
+use image::DynamicImage;
+use lru::LruCache;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+/// A decoded image with the modification time of the file it came from
+type Entry = (SystemTime, Arc<DynamicImage>);
+
+/// A bounded LRU cache of decoded images, keyed by path and modification time
+///
+/// Consecutive operations on the same file (say, verifying it and then
+/// hashing a thumbnail of it) reuse a single decode. An entry is only reused
+/// while the file's modification time is unchanged, so a rewritten file is
+/// decoded again. A capacity of zero disables caching entirely.
+#[derive(Debug)]
+pub struct DecodeCache {
+    entries: Option<Mutex<LruCache<PathBuf, Entry>>>,
+    decodes: AtomicUsize,
+}
+
+impl DecodeCache {
+    /// Creates a cache holding up to `capacity` decoded images.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: NonZeroUsize::new(capacity).map(|cap| Mutex::new(LruCache::new(cap))),
+            decodes: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns the decoded image at `path`, decoding it only on a cache miss.
+    pub fn open(&self, path: &Path) -> Result<Arc<DynamicImage>> {
+        let Some(entries) = &self.entries else {
+            return self.decode(path);
+        };
+
+        let modified = std::fs::metadata(path)?.modified()?;
+        if let Some((cached_at, image)) = lock(entries).get(path) {
+            if *cached_at == modified {
+                return Ok(image.clone());
+            }
+        }
+
+        // Decode without holding the lock so other files are not blocked
+        let image = self.decode(path)?;
+        lock(entries).put(path.to_path_buf(), (modified, image.clone()));
+        Ok(image)
+    }
+
+    /// Number of images currently cached.
+    pub fn len(&self) -> usize {
+        self.entries.as_ref().map_or(0, |entries| lock(entries).len())
+    }
+
+    /// Returns true if no images are cached.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Number of times an image was actually decoded, cached or not.
+    pub fn decodes(&self) -> usize {
+        self.decodes.load(Ordering::Relaxed)
+    }
+
+    fn decode(&self, path: &Path) -> Result<Arc<DynamicImage>> {
+        self.decodes.fetch_add(1, Ordering::Relaxed);
+        Ok(Arc::new(image::open(path)?))
+    }
+}
+
+/// Locks the cache, recovering it if another thread panicked while holding it
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_repeated_operations_decode_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("photo.png");
+        image::RgbImage::from_fn(32, 32, |x, y| image::Rgb([(x * 8) as u8, (y * 8) as u8, 60]))
+            .save(&path)
+            .unwrap();
+        let manifest = crate::process_image(&path).unwrap();
+
+        let cache = DecodeCache::new(4);
+        assert!(manifest.verify_report_cached(&path, &cache).unwrap().is_valid);
+        let image = cache.open(&path).unwrap();
+        assert_eq!(image.width(), 32);
+        assert_eq!(cache.decodes(), 1);
+
+        // A rewritten file must be decoded again
+        let file = std::fs::File::options().write(true).open(&path).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(60)).unwrap();
+        cache.open(&path).unwrap();
+        assert_eq!(cache.decodes(), 2);
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn test_zero_capacity_disables_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("dot.png");
+        image::RgbImage::new(2, 2).save(&path).unwrap();
+
+        let cache = DecodeCache::new(0);
+        cache.open(&path).unwrap();
+        cache.open(&path).unwrap();
+        assert_eq!(cache.decodes(), 2);
+        assert!(cache.is_empty());
+    }
+}
@@ -0,0 +1,294 @@
+use anyhow::{Context, Result};
+use std::borrow::Cow;
+
+use crate::models::manifest::MediaManifest;
+
+/// PNG file signature
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// Keyword identifying the manifest in PNG text chunks and container tags
+pub const MANIFEST_KEYWORD: &str = "imagechain-manifest";
+
+/// Serializes a manifest as canonical (compact, key-sorted) JSON
+fn canonical_json(manifest: &MediaManifest) -> Result<String> {
+    // Going through `Value` sorts object keys, giving a stable byte form
+    let value = serde_json::to_value(manifest)?;
+    Ok(serde_json::to_string(&value)?)
+}
+
+/// A raw PNG chunk located within a byte buffer
+struct Chunk<'a> {
+    kind: &'a [u8],
+    data: &'a [u8],
+    /// Byte range of the whole chunk, including length, type and CRC
+    span: std::ops::Range<usize>,
+}
+
+/// Splits a PNG file into its chunks, validating the overall structure
+fn png_chunks(bytes: &[u8]) -> Result<Vec<Chunk<'_>>> {
+    if !bytes.starts_with(PNG_SIGNATURE) {
+        return Err(anyhow::anyhow!("not a PNG file"));
+    }
+
+    let mut chunks = Vec::new();
+    let mut pos = PNG_SIGNATURE.len();
+    while pos < bytes.len() {
+        let header = bytes
+            .get(pos..pos + 8)
+            .context("truncated PNG chunk header")?;
+        let len = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+        let end = pos + 12 + len;
+        if end > bytes.len() {
+            return Err(anyhow::anyhow!("truncated PNG chunk"));
+        }
+
+        chunks.push(Chunk {
+            kind: &bytes[pos + 4..pos + 8],
+            data: &bytes[pos + 8..pos + 8 + len],
+            span: pos..end,
+        });
+        pos = end;
+    }
+
+    Ok(chunks)
+}
+
+/// Builds the payload of an uncompressed iTXt chunk
+fn itxt_payload(keyword: &str, text: &str) -> Vec<u8> {
+    let mut data = Vec::with_capacity(keyword.len() + text.len() + 5);
+    data.extend_from_slice(keyword.as_bytes());
+    // Null separator, compression flag, compression method
+    data.extend_from_slice(&[0, 0, 0]);
+    // Empty language tag and translated keyword
+    data.extend_from_slice(&[0, 0]);
+    data.extend_from_slice(text.as_bytes());
+    data
+}
+
+/// Returns the text of an uncompressed iTXt chunk if it carries `keyword`
+fn itxt_text<'a>(data: &'a [u8], keyword: &str) -> Option<&'a [u8]> {
+    let rest = data.strip_prefix(keyword.as_bytes())?.strip_prefix(&[0, 0, 0])?;
+    // Skip the language tag and translated keyword
+    let lang_end = rest.iter().position(|&b| b == 0)?;
+    let rest = &rest[lang_end + 1..];
+    let translated_end = rest.iter().position(|&b| b == 0)?;
+    Some(&rest[translated_end + 1..])
+}
+
+/// Stores a manifest inside a PNG file as an iTXt chunk
+///
+/// The manifest is written as canonical JSON into a chunk placed right before
+/// `IEND`. Any manifest embedded earlier is replaced. Pixel data is untouched,
+/// and [`strip_manifest_png`] recovers the original bytes exactly, so the
+/// embedded file still passes [`MediaManifest::verify`], which hashes the
+/// PNG without the chunk (see [`manifest_content`]).
+pub fn embed_manifest_png(bytes: &[u8], manifest: &MediaManifest) -> Result<Vec<u8>> {
+    let stripped = strip_manifest_png(bytes)?;
+    let chunks = png_chunks(&stripped)?;
+    let iend = chunks
+        .iter()
+        .find(|c| c.kind == b"IEND")
+        .context("PNG has no IEND chunk")?
+        .span
+        .start;
+
+    let payload = itxt_payload(MANIFEST_KEYWORD, &canonical_json(manifest)?);
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(b"iTXt");
+    hasher.update(&payload);
+
+    let mut out = Vec::with_capacity(stripped.len() + payload.len() + 12);
+    out.extend_from_slice(&stripped[..iend]);
+    out.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+    out.extend_from_slice(b"iTXt");
+    out.extend_from_slice(&payload);
+    out.extend_from_slice(&hasher.finalize().to_be_bytes());
+    out.extend_from_slice(&stripped[iend..]);
+    Ok(out)
+}
+
+/// Reads a manifest embedded by [`embed_manifest_png`], if any
+pub fn extract_manifest_png(bytes: &[u8]) -> Result<Option<MediaManifest>> {
+    for chunk in png_chunks(bytes)? {
+        if chunk.kind != b"iTXt" {
+            continue;
+        }
+        if let Some(text) = itxt_text(chunk.data, MANIFEST_KEYWORD) {
+            let json = std::str::from_utf8(text).context("manifest chunk is not UTF-8")?;
+            return Ok(Some(MediaManifest::from_json(json)?));
+        }
+    }
+    Ok(None)
+}
+
+/// Removes an embedded manifest chunk, returning the original PNG bytes
+pub fn strip_manifest_png(bytes: &[u8]) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(bytes.len());
+    out.extend_from_slice(PNG_SIGNATURE);
+    for chunk in png_chunks(bytes)? {
+        let is_manifest =
+            chunk.kind == b"iTXt" && itxt_text(chunk.data, MANIFEST_KEYWORD).is_some();
+        if !is_manifest {
+            out.extend_from_slice(&bytes[chunk.span]);
+        }
+    }
+    Ok(out)
+}
+
+/// Largest embedded manifest chunk verification looks past, in bytes
+pub const MAX_EMBEDDED_MANIFEST_SIZE: u64 = 1024 * 1024;
+
+/// Whether `bytes` begin like a PNG file
+pub fn is_png(bytes: &[u8]) -> bool {
+    bytes.starts_with(PNG_SIGNATURE)
+}
+
+/// The bytes a manifest's hashes cover
+///
+/// For a PNG carrying a manifest embedded by [`embed_manifest_png`] these are
+/// the bytes without that chunk; anything else is returned unchanged, so
+/// malformed files still fail verification the usual way.
+pub fn manifest_content(bytes: &[u8]) -> Cow<'_, [u8]> {
+    let embedded = is_png(bytes)
+        && png_chunks(bytes).is_ok_and(|chunks| {
+            chunks.iter().any(|c| c.kind == b"iTXt" && itxt_text(c.data, MANIFEST_KEYWORD).is_some())
+        });
+    match embedded.then(|| strip_manifest_png(bytes)) {
+        Some(Ok(stripped)) => Cow::Owned(stripped),
+        _ => Cow::Borrowed(bytes),
+    }
+}
+
+#[cfg(feature = "video")]
+/// Writes a copy of a video with the manifest stored in its container metadata
+///
+/// Streams are copied without re-encoding; for MP4/MOV outputs the manifest
+/// lands in the `udta` box. The container bytes change, so the manifest's
+/// SHA3-256 hash describes `input`, not `output`.
+pub fn embed_manifest_video<P: AsRef<std::path::Path>, Q: AsRef<std::path::Path>>(
+    input: P,
+    output: Q,
+    manifest: &MediaManifest,
+) -> Result<()> {
+    crate::core::video::check_ffmpeg_installed()?;
+
+    let status = std::process::Command::new("ffmpeg")
+        .arg("-hide_banner")
+        .arg("-loglevel").arg("error")
+        .arg("-y")
+        .arg("-i").arg(input.as_ref())
+        .arg("-map").arg("0")
+        .arg("-c").arg("copy")
+        .arg("-metadata").arg(format!("{}={}", MANIFEST_KEYWORD, canonical_json(manifest)?))
+        .arg("-movflags").arg("use_metadata_tags")
+        .arg(output.as_ref())
+        .status()
+        .map_err(|e| anyhow::anyhow!("Failed to spawn ffmpeg: {}", e))?;
+
+    if !status.success() {
+        return Err(anyhow::anyhow!("ffmpeg failed to embed the manifest"));
+    }
+    Ok(())
+}
+
+#[cfg(feature = "video")]
+/// Reads a manifest embedded by [`embed_manifest_video`], if any
+pub fn extract_manifest_video<P: AsRef<std::path::Path>>(path: P) -> Result<Option<MediaManifest>> {
+    let output = std::process::Command::new("ffprobe")
+        .arg("-v").arg("error")
+        .arg("-show_entries").arg(format!("format_tags={}", MANIFEST_KEYWORD))
+        .arg("-of").arg("default=noprint_wrappers=1:nokey=1")
+        .arg(path.as_ref())
+        .output()
+        .map_err(|e| anyhow::anyhow!("Failed to spawn ffprobe: {}", e))?;
+
+    if !output.status.success() {
+        return Err(anyhow::anyhow!("ffprobe failed to read container metadata"));
+    }
+
+    let json = String::from_utf8(output.stdout).context("manifest tag is not UTF-8")?;
+    let json = json.trim();
+    if json.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(MediaManifest::from_json(json)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::manifest::MediaType;
+
+    fn png_bytes() -> Vec<u8> {
+        let img = image::RgbImage::from_fn(16, 16, |x, y| image::Rgb([x as u8 * 16, y as u8 * 16, 0]));
+        let mut bytes = Vec::new();
+        image::DynamicImage::ImageRgb8(img)
+            .write_to(&mut std::io::Cursor::new(&mut bytes), image::ImageOutputFormat::Png)
+            .unwrap();
+        bytes
+    }
+
+    #[test]
+    fn test_png_manifest_round_trip() {
+        let original = png_bytes();
+        let file = tempfile::Builder::new().suffix(".png").tempfile().unwrap();
+        std::fs::write(file.path(), &original).unwrap();
+
+        let manifest = MediaManifest::new(
+            "image.png".to_string(),
+            file.path(),
+            MediaType::Image,
+            crate::core::hash::compute_sha3_256(&original).unwrap(),
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+
+        let embedded = embed_manifest_png(&original, &manifest).unwrap();
+        assert_ne!(embedded, original);
+
+        // The file remains a valid image with the same pixels
+        let decoded = image::load_from_memory(&embedded).unwrap();
+        assert_eq!(decoded.to_rgb8(), image::load_from_memory(&original).unwrap().to_rgb8());
+
+        let extracted = extract_manifest_png(&embedded).unwrap().unwrap();
+        assert_eq!(extracted.file_name, manifest.file_name);
+        assert_eq!(extracted.sha3_256_hash, manifest.sha3_256_hash);
+
+        // Stripping the chunk restores the exact bytes the hash was computed over
+        let stripped = strip_manifest_png(&embedded).unwrap();
+        assert_eq!(stripped, original);
+
+        // Embedding twice replaces rather than duplicates the manifest
+        let twice = embed_manifest_png(&embedded, &manifest).unwrap();
+        assert_eq!(twice, embedded);
+    }
+
+    #[test]
+    fn test_png_carrying_its_manifest_verifies() {
+        let original = png_bytes();
+        let manifest = crate::process_image_bytes("image.png", &original).unwrap();
+        let embedded = embed_manifest_png(&original, &manifest).unwrap();
+
+        let file = tempfile::Builder::new().suffix(".png").tempfile().unwrap();
+        std::fs::write(file.path(), &embedded).unwrap();
+        assert!(manifest.verify(file.path()).unwrap());
+        assert!(manifest.verify_bytes(&embedded).unwrap().is_valid);
+
+        // Pixel edits are still caught with the manifest embedded
+        let mut tampered = Vec::new();
+        let edited = image::RgbImage::from_pixel(16, 16, image::Rgb([9, 9, 9]));
+        image::DynamicImage::ImageRgb8(edited)
+            .write_to(&mut std::io::Cursor::new(&mut tampered), image::ImageOutputFormat::Png)
+            .unwrap();
+        let tampered = embed_manifest_png(&tampered, &manifest).unwrap();
+        assert!(!manifest.verify_bytes(&tampered).unwrap().is_valid);
+    }
+
+    #[test]
+    fn test_extract_from_plain_png() {
+        assert!(extract_manifest_png(&png_bytes()).unwrap().is_none());
+        assert!(extract_manifest_png(b"not a png").is_err());
+    }
+}
@@ -0,0 +1,169 @@
+use std::io::Cursor;
+
+use image::{DynamicImage, ImageBuffer};
+use tiff::decoder::{Decoder, DecodingResult};
+use tiff::ColorType;
+
+use crate::error::{AppError, Result};
+use crate::models::manifest::{FrameInfo, FrameSource};
+
+/// Width in pixels PDF pages are rendered at before hashing.
+#[cfg(feature = "pdf")]
+pub const PDF_RENDER_WIDTH: i32 = 1024;
+
+/// Splits a multi-page document into one image per page, in page order
+///
+/// TIFF files yield every image file directory, not just the first one that
+/// `image::open` decodes. PDF pages are rendered with pdfium (requires the
+/// `pdf` feature and a pdfium library on the system). The format is chosen by
+/// `file_name`'s extension.
+///
+/// # Errors
+///
+/// Returns `AppError::InvalidInput` if the document cannot be decoded or uses
+/// an unsupported pixel layout, and `AppError::Config` for PDFs when the `pdf`
+/// feature is disabled.
+pub fn extract_pages(file_name: &str, data: &[u8]) -> Result<Vec<DynamicImage>> {
+    let extension = std::path::Path::new(file_name)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(str::to_lowercase)
+        .unwrap_or_default();
+
+    match extension.as_str() {
+        "pdf" => pdf_pages(file_name, data),
+        _ => tiff_pages(file_name, data),
+    }
+}
+
+/// Hashes each page of a document as a [`FrameInfo`]
+///
+/// A page's `timestamp_secs` is its zero-based page number, so page-level
+/// matching can reuse the video frame tooling.
+pub fn page_frames(file_name: &str, data: &[u8]) -> Result<(FrameSource, Vec<FrameInfo>)> {
+    let source = if file_name.to_lowercase().ends_with(".pdf") {
+        FrameSource::PdfPages
+    } else {
+        FrameSource::TiffPages
+    };
+
+    let frames = extract_pages(file_name, data)?
+        .iter()
+        .enumerate()
+        .map(|(page, img)| {
+            Ok(FrameInfo {
+                timestamp_secs: page as f64,
+                pdq_hash: crate::core::hash::compute_pdq_hash(img)?,
+                embedding: None,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok((source, frames))
+}
+
+/// Decodes every page of a TIFF file
+fn tiff_pages(file_name: &str, data: &[u8]) -> Result<Vec<DynamicImage>> {
+    let invalid = |e: tiff::TiffError| AppError::InvalidInput(format!("Failed to decode TIFF {}: {}", file_name, e));
+    let mut decoder = Decoder::new(Cursor::new(data)).map_err(invalid)?;
+
+    let mut pages = Vec::new();
+    loop {
+        let (width, height) = decoder.dimensions().map_err(invalid)?;
+        let color = decoder.colortype().map_err(invalid)?;
+        let page = match (color, decoder.read_image().map_err(invalid)?) {
+            (ColorType::Gray(8), DecodingResult::U8(buf)) => {
+                ImageBuffer::from_raw(width, height, buf).map(DynamicImage::ImageLuma8)
+            }
+            (ColorType::GrayA(8), DecodingResult::U8(buf)) => {
+                ImageBuffer::from_raw(width, height, buf).map(DynamicImage::ImageLumaA8)
+            }
+            (ColorType::RGB(8), DecodingResult::U8(buf)) => {
+                ImageBuffer::from_raw(width, height, buf).map(DynamicImage::ImageRgb8)
+            }
+            (ColorType::RGBA(8), DecodingResult::U8(buf)) => {
+                ImageBuffer::from_raw(width, height, buf).map(DynamicImage::ImageRgba8)
+            }
+            (ColorType::Gray(16), DecodingResult::U16(buf)) => {
+                ImageBuffer::from_raw(width, height, buf).map(DynamicImage::ImageLuma16)
+            }
+            (ColorType::RGB(16), DecodingResult::U16(buf)) => {
+                ImageBuffer::from_raw(width, height, buf).map(DynamicImage::ImageRgb16)
+            }
+            (ColorType::RGBA(16), DecodingResult::U16(buf)) => {
+                ImageBuffer::from_raw(width, height, buf).map(DynamicImage::ImageRgba16)
+            }
+            _ => None,
+        }
+        .ok_or_else(|| {
+            AppError::InvalidInput(format!(
+                "Unsupported TIFF page {} in {}: {:?}",
+                pages.len() + 1,
+                file_name,
+                color
+            ))
+        })?;
+        pages.push(page);
+
+        if !decoder.more_images() {
+            return Ok(pages);
+        }
+        decoder.next_image().map_err(invalid)?;
+    }
+}
+
+/// Renders every page of a PDF file
+#[cfg(feature = "pdf")]
+fn pdf_pages(file_name: &str, data: &[u8]) -> Result<Vec<DynamicImage>> {
+    use pdfium_render::prelude::*;
+
+    let invalid = |e: PdfiumError| AppError::InvalidInput(format!("Failed to render PDF {}: {}", file_name, e));
+    let bindings = Pdfium::bind_to_system_library()
+        .map_err(|e| AppError::Config(format!("pdfium library not available: {}", e)))?;
+    let pdfium = Pdfium::new(bindings);
+    let document = pdfium.load_pdf_from_byte_slice(data, None).map_err(invalid)?;
+    let config = PdfRenderConfig::new().set_target_width(PDF_RENDER_WIDTH);
+
+    document
+        .pages()
+        .iter()
+        .map(|page| Ok(page.render_with_config(&config).map_err(invalid)?.as_image()))
+        .collect()
+}
+
+#[cfg(not(feature = "pdf"))]
+fn pdf_pages(_file_name: &str, _data: &[u8]) -> Result<Vec<DynamicImage>> {
+    Err(AppError::Config(
+        "PDF rendering not available - enable 'pdf' feature".to_string(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::manifest::MediaType;
+    use tiff::encoder::{colortype, TiffEncoder};
+
+    #[test]
+    fn test_two_page_tiff_yields_two_page_entries() {
+        let mut data = Cursor::new(Vec::new());
+        let mut encoder = TiffEncoder::new(&mut data).unwrap();
+        let stripes: Vec<u8> = (0..64 * 64).map(|i| if (i % 64) / 8 % 2 == 0 { 0 } else { 255 }).collect();
+        let gradient: Vec<u8> = (0..64 * 64).map(|i| (i / 64 * 4) as u8).collect();
+        encoder.write_image::<colortype::Gray8>(64, 64, &stripes).unwrap();
+        encoder.write_image::<colortype::Gray8>(64, 64, &gradient).unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("scan.tiff");
+        std::fs::write(&path, data.into_inner()).unwrap();
+
+        let manifest = crate::process_file(&path).unwrap();
+
+        assert_eq!(manifest.media_type, MediaType::Document);
+        assert_eq!(manifest.frame_source, Some(FrameSource::TiffPages));
+        let pages = manifest.frames.unwrap();
+        assert_eq!(pages.len(), 2);
+        assert_eq!(pages[1].timestamp_secs, 1.0);
+        assert_ne!(pages[0].pdq_hash, pages[1].pdq_hash);
+    }
+}
@@ -1,24 +1,36 @@
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Context, Result};
 use image::{DynamicImage, ImageOutputFormat};
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
 use std::io::Cursor;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use crate::error::AppError;
 
 #[cfg(feature = "embeddings")]
 use ndarray::Array1;
 #[cfg(feature = "embeddings")]
 use tch::{Device, Kind, Tensor};
 
+/// Dimension of the vectors produced by the bundled embedding model.
+pub const EMBEDDING_DIM: usize = 512;
+
 /// Represents the embedding model and its state
 #[cfg(feature = "embeddings")]
 #[derive(Debug, Clone)]
 #[allow(dead_code)]
 pub struct EmbeddingModel {
     device: Device,
+    dim: usize,
 }
 
 #[cfg(not(feature = "embeddings"))]
 #[derive(Clone, Debug)]
 pub struct EmbeddingModel {
     // Placeholder for when embeddings are disabled
+    dim: usize,
 }
 
 #[cfg(feature = "embeddings")]
@@ -51,15 +63,33 @@ pub async fn compute_image_embedding(_img: &DynamicImage) -> Result<Option<Vec<f
     Ok(None)
 }
 
+/// Name reported in `AppError::ExternalService` for embedding service failures
+const EMBEDDING_SERVICE: &str = "embedding service";
+
 /// Try to obtain an embedding by calling an external HTTP service.
 /// The service URL is read from the EMBEDDING_SERVICE_URL env var and
 /// is expected to expose a POST /embed endpoint accepting multipart "image".
 async fn http_embedding(img: &DynamicImage) -> Result<Option<Vec<f32>>> {
-    let base = match std::env::var("EMBEDDING_SERVICE_URL") {
-        Ok(v) if !v.trim().is_empty() => v,
-        _ => return Ok(None),
-    };
+    match embedding_service_url() {
+        Some(base) => http_embedding_at(&base, img).await,
+        None => Ok(None),
+    }
+}
+
+/// The embedding service URL from EMBEDDING_SERVICE_URL, unless unset or blank
+fn embedding_service_url() -> Option<String> {
+    std::env::var("EMBEDDING_SERVICE_URL").ok().filter(|base| !base.trim().is_empty())
+}
 
+/// Calls the embedding service at `base`
+///
+/// A service that answers with a non-success status is skipped (`Ok(None)`);
+/// one that cannot be reached or sends back something unreadable fails with
+/// `AppError::ExternalService`.
+async fn http_embedding_at(base: &str, img: &DynamicImage) -> Result<Option<Vec<f32>>> {
+    let service_failed = |detail: String| {
+        anyhow::Error::from(AppError::ExternalService { service: EMBEDDING_SERVICE.to_string(), detail })
+    };
     let url = format!("{}/embed", base.trim_end_matches('/'));
 
     // Encode image to PNG
@@ -82,7 +112,7 @@ async fn http_embedding(img: &DynamicImage) -> Result<Option<Vec<f32>>> {
         .multipart(form)
         .send()
         .await
-        .context("embedding service request failed")?;
+        .map_err(|e| service_failed(format!("request failed: {}", e)))?;
 
     if !resp.status().is_success() {
         // Do not fail the whole pipeline; just skip embeddings
@@ -92,7 +122,7 @@ async fn http_embedding(img: &DynamicImage) -> Result<Option<Vec<f32>>> {
     let v: serde_json::Value = resp
         .json()
         .await
-        .context("failed to parse embedding JSON")?;
+        .map_err(|e| service_failed(format!("failed to parse embedding JSON: {}", e)))?;
     let arr = match v.get("embedding").and_then(|e| e.as_array()) {
         Some(a) => a,
         None => return Ok(None),
@@ -106,6 +136,119 @@ async fn http_embedding(img: &DynamicImage) -> Result<Option<Vec<f32>>> {
     Ok(Some(out))
 }
 
+/// Name of the provider used when an upload does not pick one
+pub const DEFAULT_EMBEDDING_MODEL: &str = "default";
+
+/// Future returned by [`EmbeddingProvider::embed`]
+pub type EmbeddingFuture<'a> = Pin<Box<dyn Future<Output = Result<Option<Vec<f32>>>> + Send + 'a>>;
+
+/// An embedding model that uploads can select by name
+///
+/// Implementations are registered in an [`EmbeddingRegistry`]; manifests
+/// record the name in `embedding_model`, since vectors from different models
+/// are not comparable.
+pub trait EmbeddingProvider: Send + Sync {
+    /// Unique name clients use to select the provider, e.g. `"clip"`.
+    fn name(&self) -> &str;
+
+    /// Computes the embedding of a decoded image; `None` when the provider has nothing to offer.
+    fn embed<'a>(&'a self, img: &'a DynamicImage) -> EmbeddingFuture<'a>;
+
+    /// Dimension of the vectors [`embed`](Self::embed) returns, when known up front.
+    ///
+    /// [`embed_checked`] rejects vectors of any other dimension. The default
+    /// is `None`: the dimension is whatever the vectors turn out to have.
+    fn dim(&self) -> Option<usize> {
+        None
+    }
+}
+
+/// Runs `provider` on `img`, failing if the vector's dimension is not the one it declares
+///
+/// A mismatch is an `AppError::ExternalService` naming the provider, since
+/// such vectors could not be compared with the provider's others.
+pub async fn embed_checked(provider: &dyn EmbeddingProvider, img: &DynamicImage) -> Result<Option<Vec<f32>>> {
+    let embedding = provider.embed(img).await?;
+    match (provider.dim(), &embedding) {
+        (Some(dim), Some(vector)) if vector.len() != dim => Err(AppError::ExternalService {
+            service: format!("embedding model {}", provider.name()),
+            detail: format!("returned a {}-dimensional embedding, expected {}", vector.len(), dim),
+        }
+        .into()),
+        _ => Ok(embedding),
+    }
+}
+
+/// The built-in provider: the external service when `EMBEDDING_SERVICE_URL`
+/// is set, the bundled model otherwise (see [`compute_image_embedding`])
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DefaultEmbedding;
+
+impl EmbeddingProvider for DefaultEmbedding {
+    fn name(&self) -> &str {
+        DEFAULT_EMBEDDING_MODEL
+    }
+
+    fn embed<'a>(&'a self, img: &'a DynamicImage) -> EmbeddingFuture<'a> {
+        Box::pin(compute_image_embedding(img))
+    }
+
+    /// The bundled model's dimension; unknown when the external service answers instead.
+    fn dim(&self) -> Option<usize> {
+        if embedding_service_url().is_some() {
+            return None;
+        }
+        #[cfg(feature = "embeddings")]
+        return EmbeddingModel::global().ok().map(EmbeddingModel::dim);
+        #[cfg(not(feature = "embeddings"))]
+        None
+    }
+}
+
+/// Embedding providers available to uploads, by name
+#[derive(Clone)]
+pub struct EmbeddingRegistry {
+    providers: HashMap<String, Arc<dyn EmbeddingProvider>>,
+}
+
+impl fmt::Debug for EmbeddingRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EmbeddingRegistry").field("providers", &self.names()).finish()
+    }
+}
+
+impl Default for EmbeddingRegistry {
+    /// A registry holding the built-in provider.
+    fn default() -> Self {
+        let mut registry = Self { providers: HashMap::new() };
+        registry.register(DefaultEmbedding);
+        registry
+    }
+}
+
+impl EmbeddingRegistry {
+    /// Adds `provider` under its name, replacing any provider of the same name.
+    pub fn register<P: EmbeddingProvider + 'static>(&mut self, provider: P) {
+        self.providers.insert(provider.name().to_string(), Arc::new(provider));
+    }
+
+    /// Looks up a provider by name, falling back to [`DEFAULT_EMBEDDING_MODEL`] when `name` is `None`.
+    pub fn get(&self, name: Option<&str>) -> Result<Arc<dyn EmbeddingProvider>> {
+        let name = name.unwrap_or(DEFAULT_EMBEDDING_MODEL);
+        self.providers
+            .get(name)
+            .cloned()
+            .ok_or_else(|| anyhow!("unknown embedding model: {}", name))
+    }
+
+    /// Names of the registered providers, sorted.
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.providers.keys().cloned().collect();
+        names.sort();
+        names
+    }
+}
+
 #[cfg(not(feature = "embeddings"))]
 impl Default for EmbeddingModel {
     fn default() -> Self {
@@ -117,7 +260,12 @@ impl Default for EmbeddingModel {
 impl EmbeddingModel {
     /// Create a new instance of the embedding model (placeholder)
     pub fn new() -> Self {
-        Self {}
+        Self::with_dim(EMBEDDING_DIM)
+    }
+
+    /// Create an instance of a model producing `dim`-dimensional vectors (placeholder)
+    pub fn with_dim(dim: usize) -> Self {
+        Self { dim }
     }
 
     /// Get a global instance of the embedding model (placeholder)
@@ -126,6 +274,11 @@ impl EmbeddingModel {
         Ok(INSTANCE.get_or_init(Self::new))
     }
 
+    /// Dimension of the vectors this model produces
+    pub fn dim(&self) -> usize {
+        self.dim
+    }
+
     /// Compute an embedding for an image (placeholder)
     pub fn compute_embedding(&self, _data: &[u8]) -> Result<Option<Vec<f32>>> {
         Ok(None)
@@ -136,8 +289,13 @@ impl EmbeddingModel {
 impl EmbeddingModel {
     /// Create a new instance of the embedding model
     pub fn new() -> Self {
+        Self::with_dim(EMBEDDING_DIM)
+    }
+
+    /// Create an instance of a model whose output layer has `dim` units
+    pub fn with_dim(dim: usize) -> Self {
         let device = Device::cuda_if_available();
-        Self { device }
+        Self { device, dim }
     }
 
     /// Get a global instance of the embedding model (placeholder)
@@ -151,11 +309,16 @@ impl EmbeddingModel {
         Ok(None)
     }
 
+    /// Dimension of the vectors this model produces
+    pub fn dim(&self) -> usize {
+        self.dim
+    }
+
     /// Compute an embedding for an image
     pub fn compute_embedding(&self, _img: &DynamicImage) -> Result<Array1<f32>> {
         // For now, return a dummy embedding
         // In a real implementation, you'd use a pre-trained model
-        let dummy_embedding = vec![0.0f32; 512]; // 512-dimensional dummy embedding
+        let dummy_embedding = vec![0.0f32; self.dim()];
         Ok(Array1::from(dummy_embedding))
     }
     
@@ -203,15 +366,20 @@ impl EmbeddingModel {
     }
     
     /// Compute cosine similarity between two embeddings
-    pub fn cosine_similarity(a: &Array1<f32>, b: &Array1<f32>) -> f32 {
+    ///
+    /// Fails when the embeddings have different dimensions.
+    pub fn cosine_similarity(a: &Array1<f32>, b: &Array1<f32>) -> Result<f32> {
+        if a.len() != b.len() {
+            anyhow::bail!("embedding dimension mismatch: {} vs {}", a.len(), b.len());
+        }
         let dot_product = a.dot(b);
         let norm_a = a.dot(a).sqrt();
         let norm_b = b.dot(b).sqrt();
         
         if norm_a > 0.0 && norm_b > 0.0 {
-            (dot_product / (norm_a * norm_b)).clamp(-1.0, 1.0)
+            Ok((dot_product / (norm_a * norm_b)).clamp(-1.0, 1.0))
         } else {
-            0.0
+            Ok(0.0)
         }
     }
 }
@@ -221,6 +389,7 @@ mod tests {
     use super::*;
     use image::RgbImage;
     
+    #[cfg(feature = "embeddings")]
     #[test]
     fn test_embedding_computation() {
         // Skip this test in CI since it requires downloading the model
@@ -249,24 +418,67 @@ mod tests {
         let embedding = model.compute_embedding(&img).unwrap();
         
         // Check embedding dimensions
-        assert_eq!(embedding.len(), 512);
+        assert_eq!(embedding.len(), model.dim());
     }
     
+    #[tokio::test]
+    async fn test_unreachable_embedding_service_is_an_external_service_error() {
+        // Bind and release a port so nothing is listening on it
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        drop(listener);
+
+        let img = DynamicImage::ImageRgb8(RgbImage::new(4, 4));
+        let err = AppError::from(http_embedding_at(&base, &img).await.unwrap_err());
+        assert!(matches!(err, AppError::ExternalService { ref service, .. } if service == EMBEDDING_SERVICE), "{:?}", err);
+        #[cfg(feature = "web")]
+        assert_eq!(err.status_code(), axum::http::StatusCode::BAD_GATEWAY);
+    }
+
+    #[test]
+    fn test_model_reports_its_own_dimension() {
+        assert_eq!(EmbeddingModel::new().dim(), EMBEDDING_DIM);
+        assert_eq!(EmbeddingModel::with_dim(768).dim(), 768);
+    }
+
+    #[tokio::test]
+    async fn test_vectors_of_an_undeclared_dimension_are_rejected() {
+        struct Fixed(Option<usize>);
+        impl EmbeddingProvider for Fixed {
+            fn name(&self) -> &str {
+                "fixed"
+            }
+            fn embed<'a>(&'a self, _img: &'a DynamicImage) -> EmbeddingFuture<'a> {
+                Box::pin(async { Ok(Some(vec![0.5; 3])) })
+            }
+            fn dim(&self) -> Option<usize> {
+                self.0
+            }
+        }
+
+        let img = DynamicImage::ImageRgb8(RgbImage::new(4, 4));
+        assert_eq!(embed_checked(&Fixed(Some(3)), &img).await.unwrap().unwrap().len(), 3);
+        assert_eq!(embed_checked(&Fixed(None), &img).await.unwrap().unwrap().len(), 3);
+        let err = AppError::from(embed_checked(&Fixed(Some(4)), &img).await.unwrap_err());
+        assert!(matches!(err, AppError::ExternalService { ref detail, .. } if detail.contains("expected 4")), "{:?}", err);
+    }
+
+    #[cfg(feature = "embeddings")]
     #[test]
     fn test_cosine_similarity() {
         // Test with identical vectors
         let a = Array1::from(vec![1.0, 0.0, 0.0]);
         let b = Array1::from(vec![1.0, 0.0, 0.0]);
-        assert!((EmbeddingModel::cosine_similarity(&a, &b) - 1.0).abs() < 1e-6);
+        assert!((EmbeddingModel::cosine_similarity(&a, &b).unwrap() - 1.0).abs() < 1e-6);
         
         // Test with orthogonal vectors
         let a = Array1::from(vec![1.0, 0.0]);
         let b = Array1::from(vec![0.0, 1.0]);
-        assert!((EmbeddingModel::cosine_similarity(&a, &b) - 0.0).abs() < 1e-6);
+        assert!((EmbeddingModel::cosine_similarity(&a, &b).unwrap() - 0.0).abs() < 1e-6);
         
         // Test with opposite vectors
         let a = Array1::from(vec![1.0, 0.0]);
         let b = Array1::from(vec![-1.0, 0.0]);
-        assert!((EmbeddingModel::cosine_similarity(&a, &b) - (-1.0)).abs() < 1e-6);
+        assert!((EmbeddingModel::cosine_similarity(&a, &b).unwrap() - (-1.0)).abs() < 1e-6);
     }
 }
@@ -0,0 +1,271 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
+
+use log::debug;
+use reqwest::header::CONTENT_TYPE;
+use reqwest::Url;
+
+use crate::error::{AppError, Result};
+
+/// Content types accepted by default, matching the image formats the hashing pipeline decodes
+pub const DEFAULT_ALLOWED_CONTENT_TYPES: &[&str] =
+    &["image/png", "image/jpeg", "image/webp", "image/gif", "image/tiff"];
+
+/// Limits applied when downloading media from a remote URL
+///
+/// The URL is attacker-controlled in general, so every download is bounded in
+/// size and time, and its content type is checked before any of the body is read.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FetchLimits {
+    /// Largest body accepted, in bytes; the download is aborted past it
+    pub max_bytes: u64,
+    /// Time limit for the whole download, from connecting to the last byte
+    pub timeout: Duration,
+    /// Accepted `Content-Type`s, without parameters
+    pub allowed_content_types: Vec<String>,
+    /// Refuse hosts resolving to loopback, private, link-local or otherwise
+    /// non-public addresses; redirects are not followed when set
+    pub block_private_addresses: bool,
+}
+
+impl Default for FetchLimits {
+    fn default() -> Self {
+        Self {
+            max_bytes: 100 * 1024 * 1024,
+            timeout: Duration::from_secs(30),
+            allowed_content_types: DEFAULT_ALLOWED_CONTENT_TYPES.iter().map(|t| t.to_string()).collect(),
+            block_private_addresses: true,
+        }
+    }
+}
+
+/// Downloads `url` within `limits`, returning the file name taken from its path and the body
+///
+/// # Errors
+///
+/// Returns `AppError::Validation` for a URL that is not http(s), a disallowed
+/// content type or a body over `max_bytes`; `AppError::PermissionDenied` for a
+/// host on a blocked address; and `AppError::InvalidInput` if the download fails
+/// or times out.
+pub async fn fetch_media(url: &str, limits: &FetchLimits) -> Result<(String, Vec<u8>)> {
+    let url = Url::parse(url).map_err(|e| AppError::Validation(format!("invalid URL {:?}: {}", url, e)))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(AppError::Validation(format!("unsupported URL scheme: {}", url.scheme())));
+    }
+    let host = url
+        .host_str()
+        .ok_or_else(|| AppError::Validation(format!("URL has no host: {}", url)))?
+        .to_string();
+
+    let mut client = reqwest::Client::builder().timeout(limits.timeout);
+    if limits.block_private_addresses {
+        // Pin the connection to the address checked here, so a second DNS
+        // answer can't swap in a private one
+        let address = public_address(&url, &host).await?;
+        // A proxy would make its own connection, to wherever the host resolves then
+        client = client
+            .resolve(host.trim_start_matches('[').trim_end_matches(']'), address)
+            .redirect(reqwest::redirect::Policy::none())
+            .no_proxy();
+    }
+    let client = client.build().map_err(|e| AppError::Internal(e.to_string()))?;
+
+    let download_failed = |e: reqwest::Error| AppError::InvalidInput(format!("failed to download {}: {}", url, e));
+    let mut response = client.get(url.clone()).send().await.map_err(download_failed)?;
+    if !response.status().is_success() {
+        return Err(AppError::InvalidInput(format!("{} returned {}", url, response.status())));
+    }
+
+    let content_type = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .map(|value| value.trim().to_ascii_lowercase())
+        .unwrap_or_default();
+    if !limits.allowed_content_types.iter().any(|allowed| allowed.eq_ignore_ascii_case(&content_type)) {
+        return Err(AppError::Validation(format!("content type {:?} is not allowed", content_type)));
+    }
+
+    let too_large = || AppError::Validation(format!("{} is larger than {} bytes", url, limits.max_bytes));
+    if response.content_length().is_some_and(|length| length > limits.max_bytes) {
+        return Err(too_large());
+    }
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await.map_err(download_failed)? {
+        if (body.len() + chunk.len()) as u64 > limits.max_bytes {
+            return Err(too_large());
+        }
+        body.extend_from_slice(&chunk);
+    }
+    debug!("Downloaded {} bytes from {}", body.len(), url);
+
+    let file_name = url
+        .path_segments()
+        .and_then(|mut segments| segments.next_back())
+        .map(crate::utils::sanitize_filename)
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "download".to_string());
+    Ok((file_name, body))
+}
+
+/// Resolves the URL's host, failing unless every address it resolves to is public
+async fn public_address(url: &Url, host: &str) -> Result<SocketAddr> {
+    let port = url.port_or_known_default().unwrap_or(80);
+    let addresses: Vec<SocketAddr> = tokio::net::lookup_host((host.trim_start_matches('[').trim_end_matches(']'), port))
+        .await
+        .map_err(|e| AppError::InvalidInput(format!("failed to resolve {}: {}", host, e)))?
+        .collect();
+    if let Some(blocked) = addresses.iter().find(|address| !is_public(address.ip())) {
+        return Err(AppError::PermissionDenied(format!("{} resolves to non-public address {}", host, blocked.ip())));
+    }
+    addresses
+        .first()
+        .copied()
+        .ok_or_else(|| AppError::InvalidInput(format!("{} did not resolve", host)))
+}
+
+/// Whether `ip` is reachable on the public internet
+///
+/// IPv6 addresses that embed an IPv4 one (mapped, IPv4-compatible, NAT64 and
+/// 6to4) are judged by the IPv4 address they lead to.
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                || ip.is_multicast()
+                || a == 0
+                // Shared address space (carrier-grade NAT)
+                || (a == 100 && (64..128).contains(&b))
+                // Benchmarking (198.18.0.0/15) and reserved (240.0.0.0/4)
+                || (a == 198 && (b & 0xfe) == 18)
+                || a >= 240)
+        }
+        IpAddr::V6(ip) => match embedded_ipv4(ip) {
+            Some(v4) => is_public(IpAddr::V4(v4)),
+            None => {
+                let first = ip.segments()[0];
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_multicast()
+                    // Unique local (fc00::/7) and link-local (fe80::/10)
+                    || (first & 0xfe00) == 0xfc00
+                    || (first & 0xffc0) == 0xfe80)
+            }
+        },
+    }
+}
+
+/// The IPv4 address an IPv6 address leads to, if it embeds one
+///
+/// Covers IPv4-mapped (`::ffff:a.b.c.d`), IPv4-compatible (`::a.b.c.d`),
+/// NAT64 (`64:ff9b::/96`) and 6to4 (`2002::/16`) addresses.
+fn embedded_ipv4(ip: Ipv6Addr) -> Option<Ipv4Addr> {
+    let [s0, s1, s2, s3, s4, s5, s6, s7] = ip.segments();
+    let from_segments = |high: u16, low: u16| Ipv4Addr::from((u32::from(high) << 16) | u32::from(low));
+    match [s0, s1, s2, s3, s4, s5] {
+        // The loopback and unspecified addresses are not IPv4-compatible ones
+        [0, 0, 0, 0, 0, 0] if ip.is_loopback() || ip.is_unspecified() => None,
+        [0, 0, 0, 0, 0, 0] | [0, 0, 0, 0, 0, 0xffff] | [0x64, 0xff9b, 0, 0, 0, 0] => Some(from_segments(s6, s7)),
+        [0x2002, ..] => Some(from_segments(s1, s2)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// Serves one request with `head` (status line and headers) followed by `body_len` bytes
+    async fn serve_once(head: &'static str, body_len: usize) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = [0u8; 1024];
+            let _ = socket.read(&mut request).await;
+            if socket.write_all(head.as_bytes()).await.is_err() {
+                return;
+            }
+            let chunk = vec![0u8; 64 * 1024];
+            let mut sent = 0;
+            while sent < body_len {
+                let n = chunk.len().min(body_len - sent);
+                if socket.write_all(&chunk[..n]).await.is_err() {
+                    return;
+                }
+                sent += n;
+            }
+        });
+        format!("http://{}/files/photo.png", address)
+    }
+
+    fn local_limits() -> FetchLimits {
+        FetchLimits { max_bytes: 1024 * 1024, block_private_addresses: false, ..FetchLimits::default() }
+    }
+
+    #[tokio::test]
+    async fn test_oversized_response_is_aborted() {
+        // No Content-Length, so the limit can only be enforced while streaming
+        let url = serve_once("HTTP/1.1 200 OK\r\nContent-Type: image/png\r\nConnection: close\r\n\r\n", 64 * 1024 * 1024).await;
+        let result = fetch_media(&url, &local_limits()).await;
+        assert!(matches!(result, Err(AppError::Validation(ref message)) if message.contains("larger than")), "{:?}", result);
+
+        let url = serve_once("HTTP/1.1 200 OK\r\nContent-Type: image/png\r\nContent-Length: 10000000\r\n\r\n", 0).await;
+        assert!(matches!(fetch_media(&url, &local_limits()).await, Err(AppError::Validation(_))));
+
+        let url = serve_once("HTTP/1.1 200 OK\r\nContent-Type: image/png\r\nContent-Length: 512\r\n\r\n", 512).await;
+        let (name, body) = fetch_media(&url, &local_limits()).await.unwrap();
+        assert_eq!((name.as_str(), body.len()), ("photo.png", 512));
+    }
+
+    #[tokio::test]
+    async fn test_disallowed_content_type_is_rejected() {
+        let url = serve_once("HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: 16\r\n\r\n", 16).await;
+        let result = fetch_media(&url, &local_limits()).await;
+        assert!(matches!(result, Err(AppError::Validation(ref message)) if message.contains("text/html")), "{:?}", result);
+    }
+
+    #[tokio::test]
+    async fn test_private_addresses_are_blocked_when_enabled() {
+        let url = serve_once("HTTP/1.1 200 OK\r\nContent-Type: image/png\r\nContent-Length: 16\r\n\r\n", 16).await;
+        let limits = FetchLimits { block_private_addresses: true, ..local_limits() };
+        assert!(matches!(fetch_media(&url, &limits).await, Err(AppError::PermissionDenied(_))));
+
+        assert!(!is_public("10.1.2.3".parse().unwrap()));
+        assert!(!is_public("169.254.169.254".parse().unwrap()));
+        assert!(!is_public("::ffff:192.168.0.1".parse().unwrap()));
+        assert!(!is_public("fd00::1".parse().unwrap()));
+        assert!(is_public("93.184.216.34".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_reserved_and_embedded_addresses_are_not_public() {
+        for address in [
+            "224.0.0.1",
+            "239.255.255.250",
+            "198.18.0.1",
+            "198.19.255.254",
+            "240.0.0.1",
+            "::127.0.0.1",
+            "::10.0.0.1",
+            "64:ff9b::a9fe:a9fe",
+            "64:ff9b::192.168.1.1",
+            "2002:7f00:1::",
+            "2002:a9fe:a9fe::1",
+            "ff02::1",
+        ] {
+            assert!(!is_public(address.parse().unwrap()), "{}", address);
+        }
+        for address in ["198.20.0.1", "64:ff9b::5db8:d822", "2002:5db8:d822::1", "2606:4700::1111"] {
+            assert!(is_public(address.parse().unwrap()), "{}", address);
+        }
+    }
+}
@@ -1,23 +1,31 @@
 use anyhow::Result;
-use image::DynamicImage;
-use std::{fs::File, io::Read, path::Path};
+use image::imageops::{self, FilterType};
+use image::{DynamicImage, GenericImageView, ImageBuffer, ImageFormat, Luma, Rgba};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::{borrow::Cow, fs::File, io::BufRead, path::Path};
+use subtle::ConstantTimeEq;
 
+#[cfg(feature = "hashing")]
+use rayon::prelude::*;
 #[cfg(feature = "hashing")]
 use sha3::{Digest, Sha3_256};
 
 #[cfg(feature = "hashing")]
 /// Computes SHA3-256 hash of a file
+///
+/// The file is streamed through a buffer, so memory use doesn't grow with its size.
 pub fn compute_file_hash<P: AsRef<Path>>(path: P) -> Result<String> {
-    let mut file = File::open(path)?;
+    let mut reader = std::io::BufReader::with_capacity(64 * 1024, File::open(path)?);
     let mut hasher = Sha3_256::new();
-    let mut buffer = [0; 1024];
-
     loop {
-        let count = file.read(&mut buffer)?;
-        if count == 0 {
+        let buffer = reader.fill_buf()?;
+        if buffer.is_empty() {
             break;
         }
-        hasher.update(&buffer[..count]);
+        hasher.update(buffer);
+        let count = buffer.len();
+        reader.consume(count);
     }
 
     Ok(format!("{:x}", hasher.finalize()))
@@ -48,28 +56,356 @@ pub fn sha3_256<P: AsRef<Path>>(path: P) -> Result<String> {
     compute_file_hash(path)
 }
 
+/// SHA3-256 file hash fed with a file's bytes as they arrive, e.g. from a streamed upload
+///
+/// All the hashing work happens in [`update`](Self::update); once the last
+/// bytes are in, [`finalize`](Self::finalize) only pads and permutes one block,
+/// so the hash is ready without reading the file back. The result equals
+/// [`compute_file_hash`] of the same bytes.
+#[derive(Clone, Default)]
+pub struct IncrementalFileHash {
+    #[cfg(feature = "hashing")]
+    hasher: Sha3_256,
+    len: u64,
+}
+
+impl std::fmt::Debug for IncrementalFileHash {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("IncrementalFileHash").field("len", &self.len).finish_non_exhaustive()
+    }
+}
+
+impl IncrementalFileHash {
+    /// A hash of no bytes yet
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Hashes the next bytes of the file
+    pub fn update(&mut self, data: &[u8]) {
+        #[cfg(feature = "hashing")]
+        self.hasher.update(data);
+        self.len += data.len() as u64;
+    }
+
+    /// Number of bytes hashed so far
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Whether no bytes have been hashed yet
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The hex SHA3-256 of every byte passed to [`update`](Self::update)
+    #[cfg(feature = "hashing")]
+    pub fn finalize(self) -> String {
+        format!("{:x}", self.hasher.finalize())
+    }
+
+    /// The hash of every byte passed to [`update`](Self::update) (placeholder)
+    #[cfg(not(feature = "hashing"))]
+    pub fn finalize(self) -> String {
+        "placeholder_hash".to_string()
+    }
+}
+
+/// Side length of the luminance grid that PDQ hashing operates on
+pub const PDQ_GRID_SIZE: usize = 64;
+
+/// Number of bits in a PDQ hash
+pub const PDQ_HASH_BITS: usize = 64;
+
+/// Version of the PDQ preprocessing and bit layout; bump it whenever the hash
+/// of the same image changes, so old and new hashes are not compared.
+pub const PDQ_VERSION: u32 = 1;
+
+/// Version of the SHA3-256 file hash recorded in manifests.
+pub const SHA3_256_VERSION: u32 = 1;
+
+/// Maximum distance between PDQ hashes of the same image downscaled with
+/// different [`PdqConfig::downscale_filter`]s, for photos and other images
+/// without fine pixel-level detail.
+pub const PDQ_FILTER_TOLERANCE: u32 = 8;
+
+/// Default smallest width and height, in pixels, of an image that gets a
+/// PDQ hash; see [`too_small_for_pdq`].
+pub const MIN_PDQ_DIMENSION: u32 = 16;
+
+/// Whether `image` is narrower or shorter than `min_dimension` pixels
+///
+/// PDQ works on a 64x64 grid, so a smaller image is upsampled by repeating
+/// pixels, and one only a few pixels across hashes to a near-meaningless
+/// pattern. Manifests leave such images without a PDQ hash.
+pub fn too_small_for_pdq(image: &DynamicImage, min_dimension: u32) -> bool {
+    image.width() < min_dimension || image.height() < min_dimension
+}
+
+/// Options for computing PDQ hashes
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct PdqConfig {
+    /// Filter used to shrink the image to the 64x64 grid.
+    ///
+    /// `None` (the default) averages every source pixel into its grid cell,
+    /// as PDQ prescribes: the most stable choice, streaming the image row by
+    /// row. `Some(FilterType::Nearest)` samples one pixel per cell and is much
+    /// faster on large images, at the cost of aliasing on fine detail;
+    /// `Triangle`, `CatmullRom`, `Gaussian` and `Lanczos3` resample a full
+    /// intermediate image and are slower. Hashes from different filters
+    /// differ by at most [`PDQ_FILTER_TOLERANCE`] bits on typical images, so
+    /// mix them only with a matching distance threshold.
+    pub downscale_filter: Option<FilterType>,
+    /// Spread the DCT across the rayon thread pool; hashes are identical either way.
+    pub parallel: bool,
+}
+
+/// Side length of the low-frequency DCT block kept for the hash
 #[cfg(feature = "hashing")]
-/// Computes PDQ hash of an image using a simple hash approach
+const PDQ_DCT_SIZE: usize = 8;
+
+/// Returns the half-open source range `[start, end)` covered by target cell `t`.
+///
+/// Every cell covers at least one source pixel, so images smaller than the
+/// grid are upsampled by repetition rather than leaving empty cells.
+fn cell_range(t: usize, source_len: usize) -> (usize, usize) {
+    let start = t * source_len / PDQ_GRID_SIZE;
+    let end = ((t + 1) * source_len / PDQ_GRID_SIZE).max(start + 1).min(source_len);
+    (start, end)
+}
+
+/// Computes the integer luminance of a pixel, scaled by 1000
+fn luma_milli(r: u8, g: u8, b: u8) -> u64 {
+    299 * r as u64 + 587 * g as u64 + 114 * b as u64
+}
+
+/// Computes the normalized luminance of a pixel, scaled by 1000
+///
+/// Translucent pixels are composited over white, so the hidden colour of a
+/// fully transparent pixel never affects the hash. Decoders disagree on what
+/// they return there (and whether they keep an alpha channel at all), which
+/// otherwise makes the same picture hash differently per container format.
+fn pixel_luma_milli(pixel: Rgba<u8>) -> u64 {
+    let [r, g, b, a] = pixel.0;
+    let alpha = a as u64;
+    (luma_milli(r, g, b) * alpha + 255_000 * (255 - alpha)) / 255
+}
+
+/// Computes [`pixel_luma_milli`] for one pixel's 8-bit samples
+///
+/// Gray samples stand for equal red, green and blue, and a missing alpha
+/// channel for full opacity, as in [`GenericImageView::get_pixel`].
+fn sample_luma_milli(samples: &[u8]) -> u64 {
+    let pixel = match *samples {
+        [l] => Rgba([l, l, l, 255]),
+        [l, a] => Rgba([l, l, l, a]),
+        [r, g, b] => Rgba([r, g, b, 255]),
+        [r, g, b, a] => Rgba([r, g, b, a]),
+        _ => unreachable!("8-bit pixels have one to four samples"),
+    };
+    pixel_luma_milli(pixel)
+}
+
+/// Returns an image's 8-bit samples, row-major, with the number per pixel
+///
+/// 8-bit buffers are borrowed as decoded; other bit depths are converted to
+/// 8-bit RGBA first.
+fn luma_samples(image: &DynamicImage) -> (Cow<'_, [u8]>, usize) {
+    match image {
+        DynamicImage::ImageLuma8(buffer) => (Cow::Borrowed(buffer.as_raw()), 1),
+        DynamicImage::ImageLumaA8(buffer) => (Cow::Borrowed(buffer.as_raw()), 2),
+        DynamicImage::ImageRgb8(buffer) => (Cow::Borrowed(buffer.as_raw()), 3),
+        DynamicImage::ImageRgba8(buffer) => (Cow::Borrowed(buffer.as_raw()), 4),
+        other => (Cow::Owned(other.to_rgba8().into_raw()), 4),
+    }
+}
+
+/// Downscales an image to a 64x64 luminance grid with a box filter
+///
+/// Rows are read straight from the decoded buffer and accumulated directly
+/// into the grid, so beyond the image itself peak memory is a single band of
+/// cell sums rather than a full-resolution grayscale copy. Images with more
+/// than 8 bits per sample are converted to 8-bit RGBA first. Pixels are
+/// composited over white, so every source format goes through the same
+/// path. Values are in the `0.0..=255.0` range and stored row-major.
+pub fn downscale_luminance(image: &DynamicImage) -> Vec<f32> {
+    downscale_luminance_with(image, None)
+}
+
+/// Downscales an image to a 64x64 luminance grid with the given filter
+///
+/// `None` is the box filter of [`downscale_luminance`]; see
+/// [`PdqConfig::downscale_filter`] for the tradeoffs. Pixels are reduced to
+/// luminance and composited over white before any resampling, so a filter's
+/// taps never blend in the hidden colour of transparent pixels.
+pub fn downscale_luminance_with(image: &DynamicImage, filter: Option<FilterType>) -> Vec<f32> {
+    let (width, height) = image.dimensions();
+    let mut grid = vec![0.0f32; PDQ_GRID_SIZE * PDQ_GRID_SIZE];
+    if width == 0 || height == 0 {
+        return grid;
+    }
+
+    let side = PDQ_GRID_SIZE as u32;
+    if filter == Some(FilterType::Nearest) {
+        // Each cell takes one source pixel, so nothing blends and the
+        // full-resolution luminance pass below can be skipped
+        return image
+            .resize_exact(side, side, FilterType::Nearest)
+            .pixels()
+            .map(|(_, _, pixel)| pixel_luma_milli(pixel) as f32 / 1000.0)
+            .collect();
+    }
+
+    let (samples, channels) = luma_samples(image);
+    let stride = width as usize * channels;
+    if let Some(filter) = filter {
+        // Float pixels are resampled in the 0.0..=1.0 range
+        let values = samples[..stride * height as usize]
+            .chunks_exact(channels)
+            .map(|pixel| sample_luma_milli(pixel) as f32 / 255_000.0)
+            .collect();
+        let luma: ImageBuffer<Luma<f32>, Vec<f32>> =
+            ImageBuffer::from_raw(width, height, values).expect("one value per pixel");
+        return imageops::resize(&luma, side, side, filter)
+            .into_raw()
+            .into_iter()
+            .map(|value| value * 255.0)
+            .collect();
+    }
+
+    let (width, height) = (width as usize, height as usize);
+
+    let columns: Vec<(usize, usize)> = (0..PDQ_GRID_SIZE)
+        .map(|tx| cell_range(tx, width))
+        .collect();
+    let mut band = [0u64; PDQ_GRID_SIZE];
+
+    for ty in 0..PDQ_GRID_SIZE {
+        let (y0, y1) = cell_range(ty, height);
+        band.iter_mut().for_each(|sum| *sum = 0);
+
+        for row in samples[y0 * stride..y1 * stride].chunks_exact(stride) {
+            for (tx, &(x0, x1)) in columns.iter().enumerate() {
+                band[tx] += row[x0 * channels..x1 * channels]
+                    .chunks_exact(channels)
+                    .map(sample_luma_milli)
+                    .sum::<u64>();
+            }
+        }
+
+        for (tx, &(x0, x1)) in columns.iter().enumerate() {
+            let count = ((x1 - x0) * (y1 - y0)) as u64;
+            grid[ty * PDQ_GRID_SIZE + tx] = band[tx] as f32 / (count * 1000) as f32;
+        }
+    }
+
+    grid
+}
+
+/// Cosine basis for the low-frequency DCT-II terms, stored as `basis[u][x]`
+#[cfg(feature = "hashing")]
+fn dct_basis() -> Vec<f32> {
+    let n = PDQ_GRID_SIZE;
+    (0..PDQ_DCT_SIZE)
+        .flat_map(|u| {
+            (0..n).map(move |x| {
+                (std::f64::consts::PI * (2 * x + 1) as f64 * u as f64 / (2 * n) as f64).cos() as f32
+            })
+        })
+        .collect()
+}
+
+/// Transforms one grid row: `out[u] = sum_x row[x] * basis[u][x]`
+#[cfg(feature = "hashing")]
+fn dct_row(row: &[f32], basis: &[f32], out: &mut [f32]) {
+    let n = PDQ_GRID_SIZE;
+    for (u, value) in out.iter_mut().enumerate() {
+        *value = (0..n).map(|x| row[x] * basis[u * n + x]).sum();
+    }
+}
+
+/// Transforms the row results along one output frequency `v`:
+/// `out[u] = sum_y rows[y][u] * basis[v][y]`
+#[cfg(feature = "hashing")]
+fn dct_column(rows: &[f32], basis: &[f32], v: usize, out: &mut [f32]) {
+    let n = PDQ_GRID_SIZE;
+    for (u, value) in out.iter_mut().enumerate() {
+        *value = (0..n).map(|y| rows[y * PDQ_DCT_SIZE + u] * basis[v * n + y]).sum();
+    }
+}
+
+/// Computes the low-frequency 8x8 block of the 2D DCT-II of a 64x64 grid
+///
+/// With `parallel`, the row and column passes are spread across the rayon
+/// thread pool. Every output value is still summed in the same order as the
+/// scalar path, so both produce bit-identical coefficients.
+#[cfg(feature = "hashing")]
+fn dct_low_frequencies(grid: &[f32], parallel: bool) -> Vec<f32> {
+    let n = PDQ_GRID_SIZE;
+    let basis = dct_basis();
+
+    let mut rows = vec![0.0f32; n * PDQ_DCT_SIZE];
+    let mut coeffs = vec![0.0f32; PDQ_DCT_SIZE * PDQ_DCT_SIZE];
+
+    if parallel {
+        rows.par_chunks_mut(PDQ_DCT_SIZE)
+            .enumerate()
+            .for_each(|(y, out)| dct_row(&grid[y * n..(y + 1) * n], &basis, out));
+        coeffs
+            .par_chunks_mut(PDQ_DCT_SIZE)
+            .enumerate()
+            .for_each(|(v, out)| dct_column(&rows, &basis, v, out));
+    } else {
+        rows.chunks_mut(PDQ_DCT_SIZE)
+            .enumerate()
+            .for_each(|(y, out)| dct_row(&grid[y * n..(y + 1) * n], &basis, out));
+        coeffs
+            .chunks_mut(PDQ_DCT_SIZE)
+            .enumerate()
+            .for_each(|(v, out)| dct_column(&rows, &basis, v, out));
+    }
+
+    coeffs
+}
+
+#[cfg(feature = "hashing")]
+/// Computes a PDQ-style perceptual hash of an image
+///
+/// The image is downscaled to a 64x64 luminance grid, transformed with a DCT,
+/// and the 64 lowest-frequency coefficients are thresholded against their
+/// median. The 64 bits are returned hex-encoded ([`PdqFormat::Hex`]).
+///
+/// Translucent pixels are composited over white, so only what is visible on
+/// a white page counts. Grayscale pixels contribute their grey level as the
+/// luminance, so a grayscale image hashes exactly like its RGB expansion.
 pub fn compute_pdq_hash(image: &DynamicImage) -> Result<String> {
-    // Convert to RGB8 and compute a simple hash based on image properties
-    let rgb_image = image.to_rgb8();
-    let (width, height) = rgb_image.dimensions();
-    let pixels = rgb_image.as_raw();
-    
-    // Simple hash based on image dimensions and pixel data
-    let mut hash_value = 0u64;
-    hash_value ^= width as u64;
-    hash_value ^= (height as u64) << 16;
-    
-    // Sample some pixels for hash computation
-    let step = (pixels.len() / 64).max(1);
-    for (i, &pixel) in pixels.iter().step_by(step).enumerate() {
-        if i >= 64 { break; }
-        hash_value ^= (pixel as u64) << (i % 64);
-    }
-
-    // Return hash as a binary string (64 bits → 64 chars "0"/"1")
-    Ok(format!("{:064b}", hash_value))
+    compute_pdq_hash_with(image, false)
+}
+
+#[cfg(feature = "hashing")]
+/// Computes a PDQ-style perceptual hash, optionally parallelizing the DCT
+///
+/// The result is identical to [`compute_pdq_hash`] regardless of `parallel`.
+pub fn compute_pdq_hash_with(image: &DynamicImage, parallel: bool) -> Result<String> {
+    compute_pdq_hash_config(image, &PdqConfig { parallel, ..PdqConfig::default() })
+}
+
+#[cfg(feature = "hashing")]
+/// Computes a PDQ-style perceptual hash with the given options
+pub fn compute_pdq_hash_config(image: &DynamicImage, config: &PdqConfig) -> Result<String> {
+    let grid = downscale_luminance_with(image, config.downscale_filter);
+    let coeffs = dct_low_frequencies(&grid, config.parallel);
+
+    let mut sorted = coeffs.clone();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let median = (sorted[sorted.len() / 2 - 1] + sorted[sorted.len() / 2]) / 2.0;
+
+    // Threshold into 64 bits, then store them as 16 hex chars
+    let bits: String = coeffs
+        .iter()
+        .map(|&c| if c > median { '1' } else { '0' })
+        .collect();
+    pdq_to_hex(&bits)
 }
 
 #[cfg(not(feature = "hashing"))]
@@ -78,6 +414,552 @@ pub fn compute_pdq_hash(_image: &DynamicImage) -> Result<String> {
     Ok("placeholder_pdq_hash".to_string())
 }
 
+#[cfg(not(feature = "hashing"))]
+/// Computes PDQ hash of an image (placeholder)
+pub fn compute_pdq_hash_with(_image: &DynamicImage, _parallel: bool) -> Result<String> {
+    Ok("placeholder_pdq_hash".to_string())
+}
+
+#[cfg(not(feature = "hashing"))]
+/// Computes PDQ hash of an image (placeholder)
+pub fn compute_pdq_hash_config(_image: &DynamicImage, _config: &PdqConfig) -> Result<String> {
+    Ok("placeholder_pdq_hash".to_string())
+}
+
+/// Reads the EXIF orientation (1-8) of an encoded image
+///
+/// Returns 1 (upright) when the image has no EXIF block, no orientation tag,
+/// or an out-of-range value.
+pub fn exif_orientation(data: &[u8]) -> u32 {
+    let mut cursor = std::io::Cursor::new(data);
+    exif::Reader::new()
+        .read_from_container(&mut cursor)
+        .ok()
+        .and_then(|exif| {
+            exif.get_field(exif::Tag::Orientation, exif::In::PRIMARY)
+                .and_then(|field| field.value.get_uint(0))
+        })
+        .filter(|orientation| (1..=8).contains(orientation))
+        .unwrap_or(1)
+}
+
+/// Rotates and flips a decoded image so it displays upright for the given EXIF orientation
+pub fn apply_orientation(image: DynamicImage, orientation: u32) -> DynamicImage {
+    match orientation {
+        2 => image.fliph(),
+        3 => image.rotate180(),
+        4 => image.flipv(),
+        5 => image.rotate90().fliph(),
+        6 => image.rotate90(),
+        7 => image.rotate270().fliph(),
+        8 => image.rotate270(),
+        _ => image,
+    }
+}
+
+/// Decodes an encoded image and applies its EXIF orientation
+pub fn decode_oriented(data: &[u8]) -> Result<DynamicImage> {
+    let image = image::load_from_memory(data)?;
+    Ok(apply_orientation(image, exif_orientation(data)))
+}
+
+/// Decodes an encoded image, recovering what a truncated or slightly corrupt file still holds
+///
+/// Returns the image and whether it was only partly decoded. When normal
+/// decoding fails, a JPEG is retried with its end-of-image marker restored,
+/// which leaves the blocks past the damage filled in by the decoder, and an
+/// 8-bit PNG keeps the rows decoded before the error, with the rest black.
+///
+/// # Errors
+///
+/// Returns the original decoding error if nothing can be recovered.
+pub fn decode_lenient(data: &[u8]) -> Result<(DynamicImage, bool)> {
+    let error = match image::load_from_memory(data) {
+        Ok(image) => return Ok((image, false)),
+        Err(e) => e,
+    };
+    let recovered = match image::guess_format(data) {
+        Ok(ImageFormat::Jpeg) if !data.ends_with(&[0xFF, 0xD9]) => {
+            let mut terminated = data.to_vec();
+            terminated.extend_from_slice(&[0xFF, 0xD9]);
+            image::load_from_memory_with_format(&terminated, ImageFormat::Jpeg).ok()
+        }
+        Ok(ImageFormat::Png) => decode_png_rows(data),
+        _ => None,
+    };
+    recovered.map(|image| (image, true)).ok_or_else(|| error.into())
+}
+
+/// The rows of an 8-bit PNG decoded before it failed, or `None` if there are none
+fn decode_png_rows(data: &[u8]) -> Option<DynamicImage> {
+    use image::{codecs::png::PngDecoder, ColorType, ImageDecoder};
+
+    let decoder = PngDecoder::new(std::io::Cursor::new(data)).ok()?;
+    let (width, height) = decoder.dimensions();
+    let color = decoder.color_type();
+    let mut buffer = vec![0u8; usize::try_from(decoder.total_bytes()).ok()?];
+    if decoder.read_image(&mut buffer).is_ok() || buffer.iter().all(|&byte| byte == 0) {
+        return None;
+    }
+    match color {
+        ColorType::L8 => ImageBuffer::from_raw(width, height, buffer).map(DynamicImage::ImageLuma8),
+        ColorType::La8 => ImageBuffer::from_raw(width, height, buffer).map(DynamicImage::ImageLumaA8),
+        ColorType::Rgb8 => ImageBuffer::from_raw(width, height, buffer).map(DynamicImage::ImageRgb8),
+        ColorType::Rgba8 => ImageBuffer::from_raw(width, height, buffer).map(DynamicImage::ImageRgba8),
+        _ => None,
+    }
+}
+
+/// Counts the frames of an encoded image
+///
+/// Animated GIF, WebP and APNG files report every frame; still images and
+/// formats without animation report 1. All frames are decoded, so this costs
+/// as much as reading the whole animation.
+pub fn image_frame_count(data: &[u8]) -> Result<usize> {
+    use image::codecs::{gif::GifDecoder, png::PngDecoder, webp::WebPDecoder};
+    use image::AnimationDecoder;
+
+    let cursor = std::io::Cursor::new(data);
+    let count = match image::guess_format(data) {
+        Ok(ImageFormat::Gif) => GifDecoder::new(cursor)?.into_frames().count(),
+        Ok(ImageFormat::WebP) => {
+            let decoder = WebPDecoder::new(cursor)?;
+            if decoder.has_animation() { decoder.into_frames().count() } else { 1 }
+        }
+        Ok(ImageFormat::Png) => {
+            let decoder = PngDecoder::new(cursor)?;
+            if decoder.is_apng() { decoder.apng().into_frames().count() } else { 1 }
+        }
+        _ => 1,
+    };
+    Ok(count)
+}
+
+/// Tells lossless WebP files from lossy ones by their bitstream chunks
+///
+/// Returns `Some(true)` when every image bitstream is lossless VP8L and
+/// `Some(false)` when any is lossy VP8, including lossy frames of an
+/// animation and lossy images with a separate alpha chunk. Returns `None` for
+/// data that is not WebP or holds no image bitstream. Only headers are read.
+pub fn webp_is_lossless(data: &[u8]) -> Option<bool> {
+    /// Calls `visit` with the id and payload of each RIFF chunk in `bytes`
+    fn each_chunk<'a>(mut bytes: &'a [u8], mut visit: impl FnMut(&[u8], &'a [u8])) {
+        while bytes.len() >= 8 {
+            let size = u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]) as usize;
+            visit(&bytes[..4], &bytes[8..bytes.len().min(8 + size)]);
+            // Chunks are padded to an even size
+            bytes = bytes.get(8 + size + (size & 1)..).unwrap_or_default();
+        }
+    }
+
+    if data.len() < 12 || &data[..4] != b"RIFF" || &data[8..12] != b"WEBP" {
+        return None;
+    }
+    let (mut lossy, mut lossless) = (false, false);
+    let mut bitstream = |id: &[u8]| match id {
+        b"VP8 " => lossy = true,
+        b"VP8L" => lossless = true,
+        _ => {}
+    };
+    let mut frames = Vec::new();
+    each_chunk(&data[12..], |id, payload| {
+        bitstream(id);
+        // Animation frames carry a 16-byte frame header before their chunks;
+        // they are only valid at the top level, so nested ones are not entered
+        if id == b"ANMF" && payload.len() > 16 {
+            frames.push(&payload[16..]);
+        }
+    });
+    for frame in frames {
+        each_chunk(frame, |id, _| bitstream(id));
+    }
+    (lossy || lossless).then_some(!lossy)
+}
+
+#[cfg(feature = "hashing")]
+/// Computes a SHA3-256 hash over decoded pixels rather than file bytes
+///
+/// The image is converted to 8-bit RGBA and hashed together with its
+/// dimensions, so two files share a content hash exactly when they decode to
+/// the same picture, whatever container or lossless encoder produced them: a
+/// lossless WebP (see [`webp_is_lossless`]) hashes the same as a PNG of the
+/// same pixels. A lossy file's hash only stands for its own decoded pixels.
+/// Callers should pass an already oriented image (see [`decode_oriented`]).
+pub fn compute_content_hash(image: &DynamicImage) -> Result<String> {
+    let rgba = image.to_rgba8();
+    let mut hasher = Sha3_256::new();
+    hasher.update(rgba.width().to_le_bytes());
+    hasher.update(rgba.height().to_le_bytes());
+    hasher.update(rgba.as_raw());
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+#[cfg(not(feature = "hashing"))]
+/// Computes a SHA3-256 hash over decoded pixels (placeholder)
+pub fn compute_content_hash(_image: &DynamicImage) -> Result<String> {
+    Ok("placeholder_hash".to_string())
+}
+
+#[cfg(feature = "hashing")]
+/// Computes the SHA3-256 Merkle root of `leaves`, in order
+///
+/// Leaves are hashed as `SHA3(0x00 || leaf)` and inner nodes as
+/// `SHA3(0x01 || left || right)`, so a leaf can never pass for a node. An odd
+/// node at the end of a level is promoted unchanged. The root of no leaves is
+/// the hash of the empty string.
+pub fn merkle_root<S: AsRef<[u8]>>(leaves: &[S]) -> Result<String> {
+    let mut level: Vec<Vec<u8>> = leaves
+        .iter()
+        .map(|leaf| {
+            let mut hasher = Sha3_256::new();
+            hasher.update([0x00]);
+            hasher.update(leaf.as_ref());
+            hasher.finalize().to_vec()
+        })
+        .collect();
+    if level.is_empty() {
+        return Ok(format!("{:x}", Sha3_256::digest([])));
+    }
+
+    while level.len() > 1 {
+        level = level
+            .chunks(2)
+            .map(|pair| match pair {
+                [left, right] => {
+                    let mut hasher = Sha3_256::new();
+                    hasher.update([0x01]);
+                    hasher.update(left);
+                    hasher.update(right);
+                    hasher.finalize().to_vec()
+                }
+                [odd] => odd.clone(),
+                _ => unreachable!("chunks(2) yields one or two nodes"),
+            })
+            .collect();
+    }
+    Ok(hex::encode(&level[0]))
+}
+
+#[cfg(not(feature = "hashing"))]
+/// Computes the SHA3-256 Merkle root of `leaves` (placeholder)
+pub fn merkle_root<S: AsRef<[u8]>>(_leaves: &[S]) -> Result<String> {
+    Ok("placeholder_hash".to_string())
+}
+
+/// PNG chunks that only carry metadata
+const PNG_METADATA_CHUNKS: &[&[u8; 4]] = &[b"tEXt", b"zTXt", b"iTXt", b"eXIf", b"tIME"];
+
+/// VP8X flag bits announcing EXIF and XMP chunks
+const WEBP_METADATA_FLAGS: u8 = 0x08 | 0x04;
+
+/// Feeds the parts of an encoded image that describe the picture to `sink`.
+///
+/// Metadata is skipped: JPEG APPn and COM segments, PNG text, EXIF and time
+/// chunks, and WebP EXIF and XMP chunks (with their VP8X flags masked).
+fn for_each_stream_segment<F: FnMut(&[u8])>(bytes: &[u8], format: ImageFormat, mut sink: F) -> Result<()> {
+    let truncated = || anyhow::anyhow!("truncated {:?} stream", format);
+    match format {
+        ImageFormat::Jpeg => {
+            if !bytes.starts_with(&[0xFF, 0xD8]) {
+                anyhow::bail!("missing JPEG start-of-image marker");
+            }
+            sink(&bytes[..2]);
+            let mut pos = 2;
+            while pos < bytes.len() {
+                if bytes[pos] != 0xFF {
+                    anyhow::bail!("expected a JPEG marker at offset {}", pos);
+                }
+                let marker = *bytes.get(pos + 1).ok_or_else(truncated)?;
+                match marker {
+                    // Fill byte before a marker
+                    0xFF => pos += 1,
+                    // Start of scan: the rest is entropy-coded image data
+                    0xDA => {
+                        sink(&bytes[pos..]);
+                        return Ok(());
+                    }
+                    // Markers without a length
+                    0x01 | 0xD0..=0xD9 => {
+                        sink(&bytes[pos..pos + 2]);
+                        pos += 2;
+                    }
+                    _ => {
+                        let length = bytes.get(pos + 2..pos + 4).ok_or_else(truncated)?;
+                        let end = pos + 2 + u16::from_be_bytes([length[0], length[1]]) as usize;
+                        let segment = bytes.get(pos..end).ok_or_else(truncated)?;
+                        if !matches!(marker, 0xE0..=0xEF | 0xFE) {
+                            sink(segment);
+                        }
+                        pos = end;
+                    }
+                }
+            }
+            Ok(())
+        }
+        ImageFormat::Png => {
+            const SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+            if !bytes.starts_with(SIGNATURE) {
+                anyhow::bail!("missing PNG signature");
+            }
+            let mut pos = SIGNATURE.len();
+            while pos < bytes.len() {
+                let header = bytes.get(pos..pos + 8).ok_or_else(truncated)?;
+                let length = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+                let end = pos + 8 + length;
+                let chunk = bytes.get(pos + 4..end).ok_or_else(truncated)?;
+                if !PNG_METADATA_CHUNKS.iter().any(|kind| chunk.starts_with(*kind)) {
+                    sink(chunk);
+                }
+                // Skip the CRC, which only covers the chunk type and data hashed above
+                pos = end + 4;
+            }
+            Ok(())
+        }
+        ImageFormat::WebP => {
+            if bytes.len() < 12 || &bytes[..4] != b"RIFF" || &bytes[8..12] != b"WEBP" {
+                anyhow::bail!("missing WebP RIFF header");
+            }
+            let mut pos = 12;
+            while pos < bytes.len() {
+                let header = bytes.get(pos..pos + 8).ok_or_else(truncated)?;
+                let size = u32::from_le_bytes([header[4], header[5], header[6], header[7]]) as usize;
+                let data = bytes.get(pos + 8..pos + 8 + size).ok_or_else(truncated)?;
+                match &header[..4] {
+                    b"EXIF" | b"XMP " => {}
+                    b"VP8X" if !data.is_empty() => {
+                        sink(&header[..4]);
+                        sink(&[data[0] & !WEBP_METADATA_FLAGS]);
+                        sink(&data[1..]);
+                    }
+                    kind => {
+                        sink(kind);
+                        sink(data);
+                    }
+                }
+                // Chunks are padded to an even size
+                pos += 8 + size + (size & 1);
+            }
+            Ok(())
+        }
+        other => anyhow::bail!("stream hashing is not supported for {:?}", other),
+    }
+}
+
+#[cfg(feature = "hashing")]
+/// Computes a SHA3-256 hash over an image's encoded picture data, ignoring metadata
+///
+/// Unlike [`compute_sha3_256`] this hash survives EXIF, XMP, comment and text
+/// edits, and unlike [`compute_content_hash`] it needs no decoding and still
+/// changes when the picture is re-encoded. JPEG, PNG and WebP are supported.
+pub fn compute_content_region_hash(bytes: &[u8], format: ImageFormat) -> Result<String> {
+    let mut hasher = Sha3_256::new();
+    for_each_stream_segment(bytes, format, |segment| hasher.update(segment))?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+#[cfg(not(feature = "hashing"))]
+/// Computes a SHA3-256 hash over an image's encoded picture data (placeholder)
+pub fn compute_content_region_hash(bytes: &[u8], format: ImageFormat) -> Result<String> {
+    for_each_stream_segment(bytes, format, |_| {})?;
+    Ok("placeholder_hash".to_string())
+}
+
+/// Computes [`compute_content_region_hash`] for an image of any supported format
+///
+/// Returns `None` when the format cannot be detected or is not supported.
+pub fn stream_hash(bytes: &[u8]) -> Option<String> {
+    let format = image::guess_format(bytes).ok()?;
+    compute_content_region_hash(bytes, format).ok()
+}
+
+/// Compares two hash strings in constant time
+///
+/// Hex-encoded hashes (such as SHA3-256 digests) are compared on their decoded
+/// bytes, so letter case does not matter; when neither is hex, they are
+/// compared byte for byte. A hex hash never equals a non-hex one, even if its
+/// decoded bytes spell the other out. The comparison time does not depend on
+/// where the inputs differ.
+pub fn secure_hash_eq(a: &str, b: &str) -> bool {
+    match (hex::decode(a), hex::decode(b)) {
+        (Ok(a), Ok(b)) => bool::from(a.as_slice().ct_eq(b.as_slice())),
+        (Err(_), Err(_)) => bool::from(a.as_bytes().ct_eq(b.as_bytes())),
+        _ => false,
+    }
+}
+
+/// String representation of a PDQ hash
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum PdqFormat {
+    /// One `'0'`/`'1'` character per bit, as stored by older manifests
+    Binary,
+    /// Lowercase hex, four bits per character; the default
+    Hex,
+}
+
+impl PdqFormat {
+    /// Detects the representation of `hash`
+    ///
+    /// A string of exactly [`PDQ_HASH_BITS`] `'0'`/`'1'` characters is binary;
+    /// anything else must be valid hex. The two never collide for a full hash,
+    /// since its hex form is a quarter of the length. When the format is known
+    /// up front (e.g. from a manifest's `pdq_format`), prefer [`pdq_bits_as`].
+    pub fn detect(hash: &str) -> Result<Self> {
+        if hash.is_empty() {
+            return Err(anyhow::anyhow!("PDQ hash is empty"));
+        }
+        if hash.len() == PDQ_HASH_BITS && hash.bytes().all(|b| matches!(b, b'0' | b'1')) {
+            Ok(PdqFormat::Binary)
+        } else if hash.bytes().all(|b| b.is_ascii_hexdigit()) {
+            Ok(PdqFormat::Hex)
+        } else {
+            Err(anyhow::anyhow!("PDQ hash must be a binary or hex string"))
+        }
+    }
+}
+
+/// Decodes a PDQ hash stored in `format` into its bits, most significant first
+pub fn pdq_bits_as(hash: &str, format: PdqFormat) -> Result<Vec<bool>> {
+    match format {
+        PdqFormat::Binary => hash
+            .bytes()
+            .map(|b| match b {
+                b'0' => Ok(false),
+                b'1' => Ok(true),
+                _ => Err(anyhow::anyhow!("PDQ hash must be a binary string")),
+            })
+            .collect(),
+        PdqFormat::Hex => {
+            let mut bits = Vec::with_capacity(hash.len() * 4);
+            for c in hash.chars() {
+                let nibble = c
+                    .to_digit(16)
+                    .ok_or_else(|| anyhow::anyhow!("PDQ hash must be a hex string"))?;
+                bits.extend((0..4).rev().map(|i| (nibble >> i) & 1 == 1));
+            }
+            Ok(bits)
+        }
+    }
+}
+
+/// Decodes a PDQ hash in either representation into its bits
+pub fn pdq_bits(hash: &str) -> Result<Vec<bool>> {
+    pdq_bits_as(hash, PdqFormat::detect(hash)?)
+}
+
+/// Returns the number of bits a PDQ hash represents, whatever its format
+///
+/// Unparseable hashes count as zero bits.
+pub fn pdq_bit_count(hash: &str) -> usize {
+    pdq_bits(hash).map(|bits| bits.len()).unwrap_or(0)
+}
+
+/// Packs a PDQ hash into bytes; the bit count must be a multiple of eight
+fn pdq_bytes(hash: &str) -> Result<Vec<u8>> {
+    let bits = pdq_bits(hash)?;
+    if bits.len() % 8 != 0 {
+        return Err(anyhow::anyhow!("PDQ hash of {} bits is not whole bytes", bits.len()));
+    }
+    Ok(bits
+        .chunks(8)
+        .map(|byte| byte.iter().fold(0u8, |acc, &bit| (acc << 1) | u8::from(bit)))
+        .collect())
+}
+
+/// Converts a PDQ hash to lowercase hex
+///
+/// Accepts binary or hex input, so it also normalizes hex hashes.
+pub fn pdq_to_hex(hash_bits: &str) -> Result<String> {
+    Ok(hex::encode(pdq_bytes(hash_bits)?))
+}
+
+/// Converts a PDQ hash to standard, padded base64
+pub fn pdq_to_base64(hash_bits: &str) -> Result<String> {
+    use base64::Engine;
+    Ok(base64::engine::general_purpose::STANDARD.encode(pdq_bytes(hash_bits)?))
+}
+
+/// Converts a PDQ hash to a binary string of `'0'`/`'1'` characters
+pub fn pdq_to_binary(hash: &str) -> Result<String> {
+    Ok(pdq_bits(hash)?
+        .into_iter()
+        .map(|bit| if bit { '1' } else { '0' })
+        .collect())
+}
+
+/// Computes the Hamming distance between two PDQ hashes
+///
+/// Either hash may be binary or hex (see [`PdqFormat::detect`]), so hashes
+/// from older binary-string manifests compare correctly against hex ones.
+/// Both must represent the same number of bits.
+pub fn pdq_distance(a: &str, b: &str) -> Result<u32> {
+    let (a, b) = (pdq_bits(a)?, pdq_bits(b)?);
+    if a.len() != b.len() {
+        return Err(anyhow::anyhow!(
+            "PDQ hash length mismatch: {} vs {} bits",
+            a.len(),
+            b.len()
+        ));
+    }
+
+    Ok(a.iter().zip(&b).filter(|(x, y)| x != y).count() as u32)
+}
+
+/// Returns the bitwise XOR of two PDQ hashes as a binary string
+///
+/// Each `'1'` marks a bit where the hashes differ, so the mask's popcount is
+/// their [`pdq_distance`]. Either hash may be binary or hex, but both must
+/// represent the same number of bits.
+pub fn pdq_diff_mask(a: &str, b: &str) -> Result<String> {
+    let (a, b) = (pdq_bits(a)?, pdq_bits(b)?);
+    if a.len() != b.len() {
+        return Err(anyhow::anyhow!(
+            "PDQ hash length mismatch: {} vs {} bits",
+            a.len(),
+            b.len()
+        ));
+    }
+
+    Ok(a.iter().zip(&b).map(|(x, y)| if x != y { '1' } else { '0' }).collect())
+}
+
+/// Returns a short, sortable bucket key for a PDQ hash: its first
+/// `prefix_bits` bits, most stable first, as a `'0'`/`'1'` string
+///
+/// The hash's bits are DCT coefficient signs laid out row by row over an
+/// 8x8 frequency grid. They are reordered along a Z-order curve (the row and
+/// column indices bit-interleaved), which puts the low frequencies, the bits
+/// that survive re-encoding and resizing best, at the front. A near-duplicate
+/// that differs in `d` random bits keeps the same key with probability of
+/// roughly `(1 - d / 64)^prefix_bits`, while unrelated hashes collide with
+/// probability `2^-prefix_bits`; shorter keys give coarser buckets that are
+/// more likely to hold an image's near-duplicates. Keys of one length sort
+/// and compare as plain strings.
+///
+/// `prefix_bits` is capped at the hash length. Hashes whose bits don't form
+/// a square grid keep their order; unparseable hashes yield an empty key.
+pub fn pdq_locality_key(hash: &str, prefix_bits: usize) -> String {
+    let Ok(bits) = pdq_bits(hash) else {
+        return String::new();
+    };
+    let side = (bits.len() as f64).sqrt() as usize;
+    let mut order: Vec<usize> = (0..bits.len()).collect();
+    if side * side == bits.len() && side.is_power_of_two() {
+        let z_order = |index: usize| {
+            let (row, column) = (index / side, index % side);
+            (0..side.trailing_zeros()).fold(0, |code, bit| {
+                code | ((column >> bit) & 1) << (2 * bit) | ((row >> bit) & 1) << (2 * bit + 1)
+            })
+        };
+        order.sort_by_key(|&index| z_order(index));
+    }
+    order
+        .into_iter()
+        .take(prefix_bits)
+        .map(|index| if bits[index] { '1' } else { '0' })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -97,4 +979,421 @@ mod tests {
         assert_eq!(hash.len(), 64);
         assert!(!hash.is_empty());
     }
+
+    #[test]
+    fn test_streamed_file_hash_matches_in_memory_hash() {
+        let data: Vec<u8> = (0..200_000u32).map(|i| (i.wrapping_mul(2654435761) >> 24) as u8).collect();
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(&data).unwrap();
+        assert_eq!(compute_file_hash(file.path()).unwrap(), compute_sha3_256(&data).unwrap());
+    }
+
+    #[test]
+    fn test_incremental_hash_is_finalized_with_negligible_work() {
+        let data: Vec<u8> = (0..1024u32 * 1024).map(|i| (i.wrapping_mul(2654435761) >> 24) as u8).collect();
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(&data).unwrap();
+        let expected = compute_file_hash(file.path()).unwrap();
+
+        // Every chunk is absorbed as it arrives: the hash so far is always
+        // that of the bytes streamed so far
+        let mut incremental = IncrementalFileHash::new();
+        let mut streamed = 0;
+        for chunk in data.chunks(64 * 1024 + 7) {
+            incremental.update(chunk);
+            streamed += chunk.len();
+            assert_eq!(incremental.len(), streamed as u64);
+            assert_eq!(incremental.clone().finalize(), compute_sha3_256(&data[..streamed]).unwrap());
+        }
+
+        // Finalizing reads nothing back: the file can be gone by then
+        drop(file);
+        assert_eq!(incremental.finalize(), expected);
+    }
+
+    #[test]
+    fn test_downscale_filters_stay_within_tolerance() {
+        // A photo-like scene: smooth gradients with a few soft-edged shapes
+        let image = DynamicImage::ImageRgb8(image::RgbImage::from_fn(1200, 900, |x, y| {
+            let (fx, fy) = (x as f32 / 1200.0, y as f32 / 900.0);
+            let disc = (((fx - 0.3).powi(2) + (fy - 0.4).powi(2)).sqrt() < 0.2) as u8 * 90;
+            let band = ((fy * 6.0).sin() * 40.0 + 40.0) as u8;
+            image::Rgb([(fx * 160.0) as u8 + disc, band + (fy * 100.0) as u8, 200 - (fx * 120.0) as u8])
+        }));
+
+        let default = compute_pdq_hash(&image).unwrap();
+        for filter in [FilterType::Nearest, FilterType::Triangle, FilterType::Lanczos3] {
+            let config = PdqConfig { downscale_filter: Some(filter), ..PdqConfig::default() };
+            let hash = compute_pdq_hash_config(&image, &config).unwrap();
+            let distance = pdq_distance(&default, &hash).unwrap();
+            assert!(distance <= PDQ_FILTER_TOLERANCE, "{:?}: {}", filter, distance);
+        }
+        assert_eq!(compute_pdq_hash_config(&image, &PdqConfig::default()).unwrap(), default);
+    }
+
+    #[test]
+    fn test_pdq_distance() {
+        let a = "0".repeat(64);
+        let b = format!("{}{}", "1".repeat(3), "0".repeat(61));
+        assert_eq!(pdq_distance(&a, &a).unwrap(), 0);
+        assert_eq!(pdq_distance(&a, &b).unwrap(), 3);
+        assert!(pdq_distance(&a, "0101").is_err());
+
+        // Binary and hex forms of the same bits compare as equal
+        assert_eq!(pdq_distance(&b, "e000000000000000").unwrap(), 0);
+        assert_eq!(pdq_distance(&a, "e000000000000000").unwrap(), 3);
+    }
+
+    #[test]
+    fn test_pdq_conversions() {
+        let bits = "1010010100001111001111001111111100000000100000010111111000010010";
+        let hex = "a50f3cff00817e12";
+
+        assert_eq!(PdqFormat::detect(bits).unwrap(), PdqFormat::Binary);
+        assert_eq!(PdqFormat::detect(hex).unwrap(), PdqFormat::Hex);
+        // An all-zero hex hash is not mistaken for binary
+        assert_eq!(PdqFormat::detect("0000000000000000").unwrap(), PdqFormat::Hex);
+        assert!(PdqFormat::detect("xyz").is_err());
+
+        assert_eq!(pdq_to_hex(bits).unwrap(), hex);
+        assert_eq!(pdq_to_hex(&hex.to_uppercase()).unwrap(), hex);
+        assert_eq!(pdq_to_base64(bits).unwrap(), "pQ88/wCBfhI=");
+        assert_eq!(pdq_to_base64(hex).unwrap(), "pQ88/wCBfhI=");
+        assert_eq!(pdq_to_binary(hex).unwrap(), bits);
+        assert_eq!(pdq_to_binary(&pdq_to_hex(bits).unwrap()).unwrap(), bits);
+
+        assert_eq!(pdq_bit_count(bits), 64);
+        assert_eq!(pdq_bit_count(hex), 64);
+        assert_eq!(pdq_bit_count("not a hash"), 0);
+        assert_eq!(pdq_bits(hex).unwrap(), pdq_bits_as(bits, PdqFormat::Binary).unwrap());
+    }
+
+    /// Reference downscale that materializes the full grayscale image first
+    fn downscale_luminance_naive(image: &DynamicImage) -> Vec<f32> {
+        let rgba = image.to_rgba8();
+        let (width, height) = (rgba.width() as usize, rgba.height() as usize);
+        let luma: Vec<u64> = rgba.pixels().map(|&p| pixel_luma_milli(p)).collect();
+
+        let mut grid = vec![0.0f32; PDQ_GRID_SIZE * PDQ_GRID_SIZE];
+        for ty in 0..PDQ_GRID_SIZE {
+            let (y0, y1) = cell_range(ty, height);
+            for tx in 0..PDQ_GRID_SIZE {
+                let (x0, x1) = cell_range(tx, width);
+                let mut sum = 0u64;
+                for y in y0..y1 {
+                    for x in x0..x1 {
+                        sum += luma[y * width + x];
+                    }
+                }
+                let count = ((x1 - x0) * (y1 - y0)) as u64;
+                grid[ty * PDQ_GRID_SIZE + tx] = sum as f32 / (count * 1000) as f32;
+            }
+        }
+        grid
+    }
+
+    #[test]
+    fn test_streaming_downscale_matches_naive() {
+        let img = image::RgbImage::from_fn(1021, 767, |x, y| {
+            image::Rgb([(x * 7 % 256) as u8, (y * 13 % 256) as u8, ((x ^ y) % 256) as u8])
+        });
+        let img = DynamicImage::ImageRgb8(img);
+        // Gray, alpha and 16-bit buffers are read through different paths
+        let variants = [
+            DynamicImage::ImageLuma8(img.to_luma8()),
+            DynamicImage::ImageLumaA8(img.to_luma_alpha8()),
+            DynamicImage::ImageRgba8(img.to_rgba8()),
+            DynamicImage::ImageRgba16(img.to_rgba16()),
+            img,
+        ];
+
+        for img in &variants {
+            let streaming = downscale_luminance(img);
+            let naive = downscale_luminance_naive(img);
+
+            assert_eq!(streaming.len(), PDQ_GRID_SIZE * PDQ_GRID_SIZE);
+            let streaming_bits: Vec<u32> = streaming.iter().map(|v| v.to_bits()).collect();
+            let naive_bits: Vec<u32> = naive.iter().map(|v| v.to_bits()).collect();
+            assert_eq!(streaming_bits, naive_bits, "{:?}", img.color());
+        }
+    }
+
+    #[test]
+    fn test_secure_hash_eq() {
+        let hash = "a".repeat(64);
+        assert!(secure_hash_eq(&hash, &hash));
+        assert!(secure_hash_eq(&hash, &hash.to_uppercase()));
+
+        // Differences at the start, middle and end are all detected
+        for i in [0, 31, 63] {
+            let mut other = hash.clone().into_bytes();
+            other[i] = b'b';
+            assert!(!secure_hash_eq(&hash, std::str::from_utf8(&other).unwrap()));
+        }
+
+        assert!(!secure_hash_eq(&hash, &hash[..62]));
+        assert!(secure_hash_eq("0101", "0101"));
+        assert!(!secure_hash_eq("0101", "0111"));
+
+        // "30" decodes to the byte b'0', but hex and non-hex hashes never match
+        assert!(!secure_hash_eq("30", "0"));
+        assert!(!secure_hash_eq("0", "30"));
+        assert!(secure_hash_eq("xyz", "xyz"));
+        assert!(!secure_hash_eq("xyz", "xy"));
+    }
+
+    #[test]
+    fn test_parallel_dct_matches_scalar() {
+        let images = [
+            image::RgbImage::from_fn(640, 480, |x, y| image::Rgb([(x % 256) as u8, (y % 256) as u8, 90])),
+            image::RgbImage::from_fn(97, 131, |x, y| image::Rgb([((x * y) % 256) as u8, 17, ((x + y) % 256) as u8])),
+            image::RgbImage::from_fn(64, 64, |x, y| {
+                let v = if (x / 8 + y / 8) % 2 == 0 { 255 } else { 0 };
+                image::Rgb([v, v, v])
+            }),
+        ];
+
+        for img in images {
+            let grid = downscale_luminance(&DynamicImage::ImageRgb8(img.clone()));
+            let scalar: Vec<u32> = dct_low_frequencies(&grid, false).iter().map(|c| c.to_bits()).collect();
+            let parallel: Vec<u32> = dct_low_frequencies(&grid, true).iter().map(|c| c.to_bits()).collect();
+            assert_eq!(scalar, parallel);
+
+            let img = DynamicImage::ImageRgb8(img);
+            assert_eq!(
+                compute_pdq_hash_with(&img, false).unwrap(),
+                compute_pdq_hash_with(&img, true).unwrap()
+            );
+        }
+    }
+
+    /// Blocky pseudo-random texture with a transparent band and a translucent band
+    #[cfg(feature = "hashing")]
+    fn translucent_source(hidden_seed: u32) -> image::RgbaImage {
+        image::RgbaImage::from_fn(200, 150, |x, y| {
+            let cell = (x / 10) * 16 + (y / 10);
+            let v = ((cell.wrapping_mul(2654435761) >> 24) % 256) as u8;
+            if y < 30 {
+                // Fully transparent: the stored colour is arbitrary
+                let hidden = ((x * 31 + y * 17 + hidden_seed * 97) % 256) as u8;
+                image::Rgba([hidden, hidden.wrapping_mul(3), 255 - hidden, 0])
+            } else if y < 60 {
+                image::Rgba([v, v / 2, 255 - v, 128])
+            } else {
+                image::Rgba([v, 255 - v, v / 3, 255])
+            }
+        })
+    }
+
+    #[cfg(feature = "hashing")]
+    #[test]
+    fn test_transparent_pixels_do_not_affect_hash() {
+        let a = compute_pdq_hash(&DynamicImage::ImageRgba8(translucent_source(0))).unwrap();
+        let b = compute_pdq_hash(&DynamicImage::ImageRgba8(translucent_source(1))).unwrap();
+        assert_eq!(a, b);
+
+        // Resampling filters see the composited luminance, not the hidden colours
+        for filter in [FilterType::Triangle, FilterType::Lanczos3] {
+            let config = PdqConfig { downscale_filter: Some(filter), ..PdqConfig::default() };
+            let a = compute_pdq_hash_config(&DynamicImage::ImageRgba8(translucent_source(0)), &config).unwrap();
+            let b = compute_pdq_hash_config(&DynamicImage::ImageRgba8(translucent_source(1)), &config).unwrap();
+            assert_eq!(a, b, "{:?}", filter);
+        }
+    }
+
+    #[cfg(feature = "hashing")]
+    #[test]
+    fn test_grayscale_png_hashes_like_its_rgb_expansion() {
+        let gray = image::GrayImage::from_fn(90, 70, |x, y| {
+            let cell = (x / 9) * 8 + (y / 7);
+            Luma([((cell.wrapping_mul(2654435761) >> 24) % 256) as u8])
+        });
+        let encode = |image: DynamicImage| {
+            let mut png = std::io::Cursor::new(Vec::new());
+            image.write_to(&mut png, ImageFormat::Png).unwrap();
+            image::load_from_memory(&png.into_inner()).unwrap()
+        };
+        let gray_png = encode(DynamicImage::ImageLuma8(gray.clone()));
+        let rgb_png = encode(DynamicImage::ImageRgb8(DynamicImage::ImageLuma8(gray).to_rgb8()));
+        assert!(matches!(gray_png, DynamicImage::ImageLuma8(_)));
+        assert!(matches!(rgb_png, DynamicImage::ImageRgb8(_)));
+
+        assert_eq!(downscale_luminance(&gray_png), downscale_luminance(&rgb_png));
+        assert_eq!(compute_pdq_hash(&gray_png).unwrap(), compute_pdq_hash(&rgb_png).unwrap());
+        let config = PdqConfig { downscale_filter: Some(FilterType::Triangle), ..PdqConfig::default() };
+        assert_eq!(
+            compute_pdq_hash_config(&gray_png, &config).unwrap(),
+            compute_pdq_hash_config(&rgb_png, &config).unwrap()
+        );
+    }
+
+    /// Inserts an EXIF APP1 segment holding a single IFD entry after the JPEG SOI marker
+    fn with_exif_entry(jpeg: &[u8], entry: [u8; 12]) -> Vec<u8> {
+        let mut tiff = b"II*\0\x08\0\0\0\x01\0".to_vec();
+        tiff.extend_from_slice(&entry);
+        tiff.extend_from_slice(&[0, 0, 0, 0]);
+
+        let mut out = jpeg[..2].to_vec();
+        out.extend_from_slice(&[0xFF, 0xE1]);
+        out.extend_from_slice(&((2 + 6 + tiff.len()) as u16).to_be_bytes());
+        out.extend_from_slice(b"Exif\0\0");
+        out.extend_from_slice(&tiff);
+        out.extend_from_slice(&jpeg[2..]);
+        out
+    }
+
+    /// Inserts an EXIF orientation tag into a JPEG
+    fn with_exif_orientation(jpeg: &[u8], orientation: u8) -> Vec<u8> {
+        with_exif_entry(jpeg, [0x12, 0x01, 0x03, 0x00, 0x01, 0, 0, 0, orientation, 0, 0, 0])
+    }
+
+    /// Inserts an EXIF `Software` tag holding a two-character version into a JPEG
+    fn with_exif_software(jpeg: &[u8], version: [u8; 2]) -> Vec<u8> {
+        with_exif_entry(jpeg, [0x31, 0x01, 0x02, 0x00, 0x03, 0, 0, 0, version[0], version[1], 0, 0])
+    }
+
+    #[cfg(feature = "hashing")]
+    #[test]
+    fn test_content_hash_applies_exif_orientation() {
+        let source = DynamicImage::ImageRgb8(image::RgbImage::from_fn(48, 32, |x, y| {
+            image::Rgb([(x * 5) as u8, (y * 7) as u8, 90])
+        }));
+        let mut jpeg = std::io::Cursor::new(Vec::new());
+        source.write_to(&mut jpeg, image::ImageOutputFormat::Jpeg(90)).unwrap();
+        let jpeg = jpeg.into_inner();
+        let rotated = with_exif_orientation(&jpeg, 6);
+
+        assert_eq!(exif_orientation(&jpeg), 1);
+        assert_eq!(exif_orientation(&rotated), 6);
+
+        let upright = decode_oriented(&rotated).unwrap();
+        assert_eq!(upright.dimensions(), (32, 48));
+        assert_eq!(
+            compute_content_hash(&upright).unwrap(),
+            compute_content_hash(&image::load_from_memory(&jpeg).unwrap().rotate90()).unwrap()
+        );
+        assert_ne!(
+            compute_content_hash(&upright).unwrap(),
+            compute_content_hash(&image::load_from_memory(&jpeg).unwrap()).unwrap()
+        );
+    }
+
+    #[cfg(feature = "hashing")]
+    #[test]
+    fn test_stream_hash_ignores_exif_edits() {
+        let source = DynamicImage::ImageRgb8(image::RgbImage::from_fn(40, 30, |x, y| {
+            image::Rgb([(x * 6) as u8, (y * 8) as u8, 30])
+        }));
+        let mut jpeg = std::io::Cursor::new(Vec::new());
+        source.write_to(&mut jpeg, image::ImageOutputFormat::Jpeg(85)).unwrap();
+        let jpeg = jpeg.into_inner();
+
+        let original = crate::process_image_bytes("photo.jpg", &with_exif_software(&jpeg, *b"v1")).unwrap();
+        let edited_bytes = with_exif_software(&jpeg, *b"v2");
+        let edited = crate::process_image_bytes("photo.jpg", &edited_bytes).unwrap();
+
+        assert_ne!(original.sha3_256_hash, edited.sha3_256_hash);
+        assert!(original.stream_hash.is_some());
+        assert_eq!(original.stream_hash, edited.stream_hash);
+
+        // Verification tells a metadata edit apart from changed pixels
+        let file = tempfile::Builder::new().suffix(".jpg").tempfile().unwrap();
+        std::fs::write(file.path(), &edited_bytes).unwrap();
+        let report = original.verify_report(file.path()).unwrap();
+        assert!(!report.is_valid);
+        assert!(report.reason.unwrap().contains("only metadata changed"));
+
+        let mut recoded = std::io::Cursor::new(Vec::new());
+        source.write_to(&mut recoded, image::ImageOutputFormat::Jpeg(60)).unwrap();
+        assert_ne!(stream_hash(&recoded.into_inner()), original.stream_hash);
+    }
+
+    #[cfg(feature = "hashing")]
+    #[test]
+    fn test_stream_hash_ignores_png_text_chunks() {
+        let mut png = std::io::Cursor::new(Vec::new());
+        DynamicImage::ImageRgb8(image::RgbImage::from_pixel(8, 8, image::Rgb([10, 20, 30])))
+            .write_to(&mut png, image::ImageOutputFormat::Png)
+            .unwrap();
+        let png = png.into_inner();
+
+        // Insert a tEXt chunk right after IHDR (8-byte signature + 25-byte chunk)
+        let data = b"Comment\0edited";
+        let mut chunk = (data.len() as u32).to_be_bytes().to_vec();
+        chunk.extend_from_slice(b"tEXt");
+        chunk.extend_from_slice(data);
+        chunk.extend_from_slice(&crc32fast::hash(&chunk[4..]).to_be_bytes());
+        let mut tagged = png[..33].to_vec();
+        tagged.extend_from_slice(&chunk);
+        tagged.extend_from_slice(&png[33..]);
+
+        assert!(image::load_from_memory(&tagged).is_ok());
+        assert_eq!(stream_hash(&png), stream_hash(&tagged));
+        assert!(stream_hash(&png).is_some());
+        assert!(compute_content_region_hash(&png[..20], ImageFormat::Png).is_err());
+    }
+
+    #[cfg(feature = "hashing")]
+    #[test]
+    fn test_pdq_hash_stable_across_formats() {
+        use image::codecs::{bmp::BmpEncoder, png::PngEncoder, webp::WebPEncoder};
+        use image::{ColorType, ImageEncoder};
+
+        let source = translucent_source(0);
+        let (w, h) = source.dimensions();
+        let reference = compute_pdq_hash(&DynamicImage::ImageRgba8(source.clone())).unwrap();
+
+        let mut png = Vec::new();
+        PngEncoder::new(&mut png)
+            .write_image(source.as_raw(), w, h, ColorType::Rgba8)
+            .unwrap();
+        let mut bmp = Vec::new();
+        BmpEncoder::new(&mut bmp).encode(source.as_raw(), w, h, ColorType::Rgba8).unwrap();
+        let mut webp = Vec::new();
+        WebPEncoder::new_lossless(&mut webp)
+            .encode(source.as_raw(), w, h, ColorType::Rgba8)
+            .unwrap();
+
+        for (format, bytes) in [("png", png), ("bmp", bmp), ("webp", webp)] {
+            let decoded = image::load_from_memory(&bytes).unwrap();
+            let hash = compute_pdq_hash(&decoded).unwrap();
+            let distance = pdq_distance(&reference, &hash).unwrap();
+            assert!(distance <= 2, "{} hash is {} bits from the source", format, distance);
+        }
+    }
+
+    #[test]
+    fn test_near_duplicates_share_locality_keys_more_often_than_random_hashes() {
+        let mut state = 0x9E37_79B9_7F4A_7C15u64;
+        let mut next = || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        let hex = |bits: u64| format!("{:016x}", bits);
+
+        let (mut near_shared, mut random_shared) = (0, 0);
+        for _ in 0..1000 {
+            let original = next();
+            // A near-duplicate: three bits flipped
+            let mut near = original;
+            for _ in 0..3 {
+                near ^= 1 << (next() % 64);
+            }
+            let key = pdq_locality_key(&hex(original), 8);
+            assert_eq!(key.len(), 8);
+            near_shared += usize::from(pdq_locality_key(&hex(near), 8) == key);
+            random_shared += usize::from(pdq_locality_key(&hex(next()), 8) == key);
+        }
+        assert!(near_shared > 500, "{} near-duplicates shared a key", near_shared);
+        assert!(random_shared < 50, "{} random hashes shared a key", random_shared);
+
+        // The lowest frequencies lead: row 0 and 1 of columns 0 and 1 come first
+        let corner = hex(0xC0C0_0000_0000_0000);
+        assert_eq!(pdq_locality_key(&corner, 6), "111100");
+        assert_eq!(pdq_locality_key(&pdq_to_binary(&corner).unwrap(), 4), "1111");
+        assert_eq!(pdq_locality_key(&corner, 100).len(), 64);
+        assert_eq!(pdq_locality_key("not-a-hash", 8), "");
+    }
 }
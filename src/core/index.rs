@@ -0,0 +1,485 @@
+use anyhow::{bail, Result};
+use std::cmp::Ordering;
+use std::collections::btree_map::{BTreeMap, Entry};
+use std::collections::BinaryHeap;
+use std::io::{Read, Write};
+
+use crate::core::hash::pdq_distance;
+use crate::models::manifest::MediaManifest;
+
+/// A BK-tree over PDQ hashes for Hamming-radius queries
+///
+/// Each child edge is labelled with its distance to the parent, so by the
+/// triangle inequality a radius query only has to descend into children whose
+/// label lies within `max_distance` of the query's distance to the parent.
+/// That prunes most of the tree for small radii instead of scanning every hash.
+#[derive(Debug)]
+pub struct BkTree<I> {
+    root: Option<Node<I>>,
+    len: usize,
+}
+
+#[derive(Debug)]
+struct Node<I> {
+    id: I,
+    hash: String,
+    children: BTreeMap<u32, Node<I>>,
+}
+
+impl<I> Default for BkTree<I> {
+    fn default() -> Self {
+        Self { root: None, len: 0 }
+    }
+}
+
+impl<I: Clone> BkTree<I> {
+    /// Creates an empty tree.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Builds a tree from stored manifests, indexing each one that has a PDQ hash.
+    ///
+    /// Used to rebuild the index on startup; video manifests without a single
+    /// PDQ hash, and manifests hashed with another perceptual algorithm or PDQ
+    /// version, are skipped.
+    pub fn from_manifests<'a, It>(manifests: It) -> Result<Self>
+    where
+        It: IntoIterator<Item = (I, &'a MediaManifest)>,
+    {
+        let mut tree = Self::new();
+        for (id, manifest) in manifests {
+            if manifest.custom_perceptual_algorithm().is_some() || manifest.check_current().is_err() {
+                continue;
+            }
+            if let Some(hash) = &manifest.pdq_hash {
+                tree.insert(id, hash.clone())?;
+            }
+        }
+        Ok(tree)
+    }
+
+    /// Number of hashes in the tree.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if the tree holds no hashes.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Adds `hash` under `id`. Fails if the hash is malformed or differs in length from the others.
+    pub fn insert(&mut self, id: I, hash: String) -> Result<()> {
+        let mut node = match &mut self.root {
+            Some(root) => root,
+            None => {
+                pdq_distance(&hash, &hash)?;
+                self.root = Some(Node { id, hash, children: BTreeMap::new() });
+                self.len = 1;
+                return Ok(());
+            }
+        };
+
+        loop {
+            let distance = pdq_distance(&node.hash, &hash)?;
+            match node.children.entry(distance) {
+                Entry::Occupied(child) => node = child.into_mut(),
+                Entry::Vacant(slot) => {
+                    slot.insert(Node { id, hash, children: BTreeMap::new() });
+                    self.len += 1;
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    /// Returns every `(id, distance)` whose hash is within `max_distance` of `query`.
+    ///
+    /// Results are sorted by distance, closest first.
+    pub fn within(&self, query: &str, max_distance: u32) -> Result<Vec<(I, u32)>> {
+        let mut matches = Vec::new();
+        self.visit_within(query, max_distance, |id, distance| matches.push((id.clone(), distance)))?;
+        matches.sort_by_key(|&(_, distance)| distance);
+        Ok(matches)
+    }
+
+    /// Calls `visit` with every id whose hash is within `max_distance` of
+    /// `query` and its distance, in no particular order, without collecting them.
+    pub fn visit_within<F>(&self, query: &str, max_distance: u32, mut visit: F) -> Result<()>
+    where
+        F: FnMut(&I, u32),
+    {
+        let mut pending: Vec<&Node<I>> = self.root.iter().collect();
+
+        while let Some(node) = pending.pop() {
+            let distance = pdq_distance(&node.hash, query)?;
+            if distance <= max_distance {
+                visit(&node.id, distance);
+            }
+            let low = distance.saturating_sub(max_distance);
+            let high = distance.saturating_add(max_distance);
+            pending.extend(node.children.range(low..=high).map(|(_, child)| child));
+        }
+        Ok(())
+    }
+
+    /// Returns the ids whose hash is exactly `distance` from `query`.
+    ///
+    /// Asking for each distance in turn yields matches in ascending order
+    /// while holding only one distance's worth of them at a time.
+    pub fn exactly(&self, query: &str, distance: u32) -> Result<Vec<I>> {
+        let mut matches = Vec::new();
+        self.visit_within(query, distance, |id, d| {
+            if d == distance {
+                matches.push(id.clone());
+            }
+        })?;
+        Ok(matches)
+    }
+
+    /// Returns the `k` closest `(id, distance)` within `max_distance` of `query`, closest first.
+    ///
+    /// Only `k` candidates are held at any time, however many hashes match.
+    pub fn nearest(&self, query: &str, max_distance: u32, k: usize) -> Result<Vec<(I, u32)>> {
+        let mut heap = TopK::new(k);
+        self.visit_within(query, max_distance, |id, distance| heap.push(distance, id.clone()))?;
+        Ok(heap.into_sorted().into_iter().map(|(distance, id)| (id, distance)).collect())
+    }
+
+    /// Every `(id, hash)` in the tree, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (&I, &str)> {
+        let mut pending: Vec<&Node<I>> = self.root.iter().collect();
+        std::iter::from_fn(move || {
+            let node = pending.pop()?;
+            pending.extend(node.children.values());
+            Some((&node.id, node.hash.as_str()))
+        })
+    }
+}
+
+impl<I: Clone + Ord> BkTree<I> {
+    /// Returns every pair of ids whose hashes are exactly `distance` apart.
+    ///
+    /// Each pair is reported once, with the smaller id first.
+    pub fn pairs_exactly(&self, distance: u32) -> Result<Vec<(I, I)>> {
+        let mut pairs = Vec::new();
+        for (id, hash) in self.iter() {
+            self.visit_within(hash, distance, |other, d| {
+                if d == distance && id < other {
+                    pairs.push((id.clone(), other.clone()));
+                }
+            })?;
+        }
+        Ok(pairs)
+    }
+
+    /// Returns every pair of ids at most `max_distance` apart, grouped by distance.
+    ///
+    /// Element `d` of the result holds the pairs exactly `d` apart, as
+    /// [`BkTree::pairs_exactly`] would return them, but the tree is walked once
+    /// rather than once per distance.
+    pub fn pairs_by_distance(&self, max_distance: u32) -> Result<Vec<Vec<(I, I)>>> {
+        let mut bands = vec![Vec::new(); max_distance as usize + 1];
+        for (id, hash) in self.iter() {
+            self.visit_within(hash, max_distance, |other, distance| {
+                if id < other {
+                    bands[distance as usize].push((id.clone(), other.clone()));
+                }
+            })?;
+        }
+        Ok(bands)
+    }
+
+    /// Returns the `k` closest pairs of ids at most `max_distance` apart, closest first.
+    ///
+    /// Each pair is reported once, with the smaller id first, and only `k`
+    /// candidates are held at any time.
+    pub fn nearest_pairs(&self, max_distance: u32, k: usize) -> Result<Vec<(I, I, u32)>> {
+        let mut heap = TopK::new(k);
+        for (id, hash) in self.iter() {
+            self.visit_within(hash, max_distance, |other, distance| {
+                if id < other {
+                    heap.push(distance, (id.clone(), other.clone()));
+                }
+            })?;
+        }
+        Ok(heap.into_sorted().into_iter().map(|(distance, (a, b))| (a, b, distance)).collect())
+    }
+}
+
+/// Leading bytes of a saved [`BkTree`], ending in the format version
+const SAVED_TREE_MAGIC: &[u8; 4] = b"BKT\x01";
+
+/// Parent index of the root node in a saved [`BkTree`]
+const NO_PARENT: u32 = u32::MAX;
+
+impl BkTree<String> {
+    /// Writes the tree in a compact binary format that [`BkTree::load`] reads back.
+    ///
+    /// After a magic header and the node count, each node is written in
+    /// pre-order as its parent's index, the edge distance to the parent, and
+    /// its length-prefixed id and hash. The shape of the tree is kept, so
+    /// loading it back computes no distances.
+    pub fn save<W: Write>(&self, mut writer: W) -> Result<()> {
+        writer.write_all(SAVED_TREE_MAGIC)?;
+        writer.write_all(&(self.len as u64).to_le_bytes())?;
+
+        let mut pending: Vec<(u32, u32, &Node<String>)> = self.root.iter().map(|root| (NO_PARENT, 0, root)).collect();
+        let mut next = 0u32;
+        while let Some((parent, distance, node)) = pending.pop() {
+            writer.write_all(&parent.to_le_bytes())?;
+            writer.write_all(&distance.to_le_bytes())?;
+            write_str(&mut writer, &node.id)?;
+            write_str(&mut writer, &node.hash)?;
+            pending.extend(node.children.iter().rev().map(|(distance, child)| (next, *distance, child)));
+            next += 1;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Reads a tree written by [`BkTree::save`].
+    ///
+    /// Fails if the data is truncated, has another format version, or does
+    /// not describe a single tree. Hashes are not checked against each other.
+    pub fn load<R: Read>(mut reader: R) -> Result<Self> {
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != SAVED_TREE_MAGIC {
+            bail!("not a saved BK-tree, or saved by another version");
+        }
+        let len = usize::try_from(read_u64(&mut reader)?)?;
+
+        let mut nodes = Vec::with_capacity(len.min(1 << 16));
+        let mut edges = Vec::with_capacity(len.min(1 << 16));
+        for index in 0..len {
+            let parent = read_u32(&mut reader)?;
+            let distance = read_u32(&mut reader)?;
+            let id = read_str(&mut reader)?;
+            let hash = read_str(&mut reader)?;
+            // Pre-order puts every parent before its children, and the root first
+            let root = index == 0;
+            if (parent == NO_PARENT) != root || (!root && parent as usize >= index) {
+                bail!("saved BK-tree node {} has an invalid parent", index);
+            }
+            nodes.push(Some(Node { id, hash, children: BTreeMap::new() }));
+            edges.push((parent as usize, distance));
+        }
+
+        // Attach children last to first, so each node is complete before it moves
+        for index in (1..len).rev() {
+            let node = nodes[index].take().expect("each node is attached once");
+            let (parent, distance) = edges[index];
+            let parent = nodes[parent].as_mut().expect("parents come before their children");
+            if parent.children.insert(distance, node).is_some() {
+                bail!("saved BK-tree has two children at distance {} under one node", distance);
+            }
+        }
+        Ok(Self { root: nodes.first_mut().and_then(Option::take), len })
+    }
+}
+
+fn write_str<W: Write>(writer: &mut W, value: &str) -> Result<()> {
+    writer.write_all(&u32::try_from(value.len())?.to_le_bytes())?;
+    writer.write_all(value.as_bytes())?;
+    Ok(())
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> Result<u32> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> Result<u64> {
+    let mut bytes = [0u8; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn read_str<R: Read>(reader: &mut R) -> Result<String> {
+    let len = read_u32(reader)? as usize;
+    let mut bytes = Vec::with_capacity(len.min(1 << 16));
+    reader.take(len as u64).read_to_end(&mut bytes)?;
+    if bytes.len() != len {
+        bail!("saved BK-tree is truncated");
+    }
+    Ok(String::from_utf8(bytes)?)
+}
+
+/// Keeps the `k` entries with the smallest distance pushed into it
+struct TopK<T> {
+    k: usize,
+    heap: BinaryHeap<Ranked<T>>,
+}
+
+impl<T> TopK<T> {
+    fn new(k: usize) -> Self {
+        Self { k, heap: BinaryHeap::with_capacity(k.saturating_add(1).min(1024)) }
+    }
+
+    fn push(&mut self, distance: u32, item: T) {
+        if self.heap.len() < self.k {
+            self.heap.push(Ranked(distance, item));
+        } else if self.heap.peek().is_some_and(|worst| distance < worst.0) {
+            self.heap.pop();
+            self.heap.push(Ranked(distance, item));
+        }
+    }
+
+    fn into_sorted(self) -> Vec<(u32, T)> {
+        self.heap.into_sorted_vec().into_iter().map(|Ranked(distance, item)| (distance, item)).collect()
+    }
+}
+
+/// Heap entry ordered by distance alone, so the max-heap's top is the worst kept entry
+struct Ranked<T>(u32, T);
+
+impl<T> PartialEq for Ranked<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl<T> Eq for Ranked<T> {}
+
+impl<T> PartialOrd for Ranked<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for Ranked<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.cmp(&other.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Deterministic xorshift generator so the test needs no extra dependencies
+    fn random_hashes(count: usize, mut state: u64) -> Vec<String> {
+        (0..count)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                format!("{:064b}", state)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_within_matches_brute_force() {
+        let hashes = random_hashes(1000, 0x9E37_79B9_7F4A_7C15);
+        let mut tree = BkTree::new();
+        for (id, hash) in hashes.iter().enumerate() {
+            tree.insert(id, hash.clone()).unwrap();
+        }
+        assert_eq!(tree.len(), 1000);
+
+        for (query, radius) in [(&hashes[0], 0), (&hashes[17], 20), (&hashes[500], 26)] {
+            let mut found: Vec<(usize, u32)> = tree.within(query, radius).unwrap();
+            found.sort();
+
+            let expected: Vec<(usize, u32)> = hashes
+                .iter()
+                .enumerate()
+                .map(|(id, hash)| (id, pdq_distance(query, hash).unwrap()))
+                .filter(|&(_, distance)| distance <= radius)
+                .collect();
+
+            assert_eq!(found, expected);
+        }
+    }
+
+    #[test]
+    fn test_bounded_queries_match_sorted_brute_force() {
+        let hashes = random_hashes(300, 0x2545_F491_4F6C_DD1D);
+        let mut tree = BkTree::new();
+        for (id, hash) in hashes.iter().enumerate() {
+            tree.insert(id, hash.clone()).unwrap();
+        }
+        let query = &hashes[7];
+
+        let banded: Vec<(usize, u32)> = (0..=24)
+            .flat_map(|d| tree.exactly(query, d).unwrap().into_iter().map(move |id| (id, d)))
+            .collect();
+        assert!(banded.windows(2).all(|w| w[0].1 <= w[1].1));
+        let mut expected = tree.within(query, 24).unwrap();
+        expected.sort_by_key(|&(id, d)| (d, id));
+        let mut sorted = banded.clone();
+        sorted.sort_by_key(|&(id, d)| (d, id));
+        assert_eq!(sorted, expected);
+
+        let nearest = tree.nearest(query, 24, 5).unwrap();
+        assert_eq!(nearest.iter().map(|&(_, d)| d).collect::<Vec<_>>(), expected.iter().take(5).map(|&(_, d)| d).collect::<Vec<_>>());
+
+        let mut pairs: Vec<(usize, usize, u32)> = Vec::new();
+        for a in 0..hashes.len() {
+            for b in a + 1..hashes.len() {
+                let distance = pdq_distance(&hashes[a], &hashes[b]).unwrap();
+                if distance <= 22 {
+                    pairs.push((a, b, distance));
+                }
+            }
+        }
+        pairs.sort_by_key(|&(a, b, d)| (d, a, b));
+        let mut banded_pairs: Vec<(usize, usize, u32)> = (0..=22)
+            .flat_map(|d| tree.pairs_exactly(d).unwrap().into_iter().map(move |(a, b)| (a, b, d)))
+            .collect();
+        banded_pairs.sort_by_key(|&(a, b, d)| (d, a, b));
+        assert_eq!(banded_pairs, pairs);
+        let mut grouped_pairs: Vec<(usize, usize, u32)> = (0..)
+            .zip(tree.pairs_by_distance(22).unwrap())
+            .flat_map(|(d, band)| band.into_iter().map(move |(a, b)| (a, b, d)))
+            .collect();
+        grouped_pairs.sort_by_key(|&(a, b, d)| (d, a, b));
+        assert_eq!(grouped_pairs, pairs);
+        let nearest_pairs = tree.nearest_pairs(22, 3).unwrap();
+        assert_eq!(
+            nearest_pairs.iter().map(|&(_, _, d)| d).collect::<Vec<_>>(),
+            pairs.iter().take(3).map(|&(_, _, d)| d).collect::<Vec<_>>()
+        );
+        assert_eq!(tree.iter().count(), 300);
+    }
+
+    #[test]
+    fn test_insert_rejects_mismatched_hash() {
+        let mut tree = BkTree::new();
+        tree.insert("a", "0".repeat(64)).unwrap();
+        assert!(tree.insert("b", "0101".to_string()).is_err());
+        assert!(BkTree::<&str>::new().insert("c", "not-a-hash".to_string()).is_err());
+        assert_eq!(tree.len(), 1);
+    }
+
+    #[test]
+    fn test_saved_tree_loads_with_identical_query_results() {
+        let hashes = random_hashes(500, 0x2545_F491_4F6C_DD1D);
+        let mut tree = BkTree::new();
+        for (i, hash) in hashes.iter().enumerate() {
+            tree.insert(format!("img-{}.png", i), hash.clone()).unwrap();
+        }
+
+        let mut saved = Vec::new();
+        tree.save(&mut saved).unwrap();
+        let loaded = BkTree::load(saved.as_slice()).unwrap();
+
+        assert_eq!(loaded.len(), tree.len());
+        for query in hashes.iter().step_by(50) {
+            assert_eq!(loaded.within(query, 24).unwrap(), tree.within(query, 24).unwrap());
+            assert_eq!(loaded.nearest(query, 30, 5).unwrap(), tree.nearest(query, 30, 5).unwrap());
+        }
+        assert_eq!(loaded.nearest_pairs(20, 10).unwrap(), tree.nearest_pairs(20, 10).unwrap());
+
+        let mut empty = Vec::new();
+        BkTree::<String>::new().save(&mut empty).unwrap();
+        assert!(BkTree::load(empty.as_slice()).unwrap().is_empty());
+
+        assert!(BkTree::load(&saved[..saved.len() - 3]).is_err());
+        assert!(BkTree::load(&b"BKT\x02"[..]).is_err());
+    }
+}
@@ -0,0 +1,426 @@
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Mutex;
+
+use log::warn;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::error::{AppError, Result};
+use crate::models::manifest::MediaManifest;
+
+/// Subdirectory of the jobs directory where [`JobStore`] keeps job state
+pub const JOB_STATE_DIR: &str = ".jobs";
+
+/// Items recorded in a job's log before [`run_job`] folds them into its saved state
+const ITEMS_PER_SAVE: usize = 256;
+
+/// Id of the job submitted for each (state directory, source), so
+/// [`JobStore::submit`] never plans two jobs for one source
+static SUBMITTED: Mutex<BTreeMap<(PathBuf, String), String>> = Mutex::new(BTreeMap::new());
+
+/// Where processing of a [`JobItem`] stands
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ItemStatus {
+    /// Not processed yet, or interrupted while being processed
+    Pending,
+    /// Processed; the manifest is kept on the item
+    Done,
+    /// Processing failed; the item is retried when the job is resubmitted
+    Failed,
+}
+
+/// One file of a [`Job`]'s source
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct JobItem {
+    /// Path of the file within the source directory or archive
+    pub path: String,
+    /// Where processing of this item stands
+    pub status: ItemStatus,
+    /// Manifest of the item, once done
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub manifest: Option<MediaManifest>,
+    /// Why processing failed, for failed items
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Counts of a job's items by status
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, JsonSchema)]
+pub struct JobProgress {
+    /// Number of items in the job
+    pub total: usize,
+    /// Items processed successfully
+    pub done: usize,
+    /// Items not processed yet
+    pub pending: usize,
+    /// Items whose processing failed
+    pub failed: usize,
+}
+
+/// A resumable batch job processing every file of a directory or ZIP archive
+///
+/// The item list is fixed when the job is planned; files added to the source
+/// afterwards are not picked up. Progress is recorded after every item, so a
+/// job that is interrupted picks up where it stopped when it runs again.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Job {
+    /// Unique job id
+    pub id: String,
+    /// Source directory or `.zip` archive, relative to the jobs directory
+    pub source: String,
+    /// Every file of the source, in processing order
+    pub items: Vec<JobItem>,
+}
+
+impl Job {
+    /// Plans a job over `source`, a directory or `.zip` archive inside `root`
+    ///
+    /// A directory contributes the regular, non-hidden files directly inside
+    /// it, sorted by name; an archive contributes its media entries in archive
+    /// order. Every item starts out pending.
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError::Validation` if `source` is not a relative path inside
+    /// `root`, `AppError::NotFound` if it does not exist, and
+    /// `AppError::InvalidInput` if an archive cannot be read.
+    pub fn plan(root: &Path, source: &str) -> Result<Self> {
+        let path = source_path(root, source)?;
+        let paths = if is_archive(&path) {
+            let file = File::open(&path).map_err(|e| AppError::from_io_at(e, &path))?;
+            crate::core::archive::zip_media_entries(BufReader::new(file))?
+        } else {
+            crate::directory_files(&path)?
+                .iter()
+                .filter_map(|file| file.file_name()?.to_str().map(String::from))
+                .collect()
+        };
+
+        Ok(Self {
+            id: Uuid::new_v4().to_string(),
+            source: source.to_string(),
+            items: paths
+                .into_iter()
+                .map(|path| JobItem { path, status: ItemStatus::Pending, manifest: None, error: None })
+                .collect(),
+        })
+    }
+
+    /// Counts the items by status
+    pub fn progress(&self) -> JobProgress {
+        let count = |status| self.items.iter().filter(|item| item.status == status).count();
+        JobProgress {
+            total: self.items.len(),
+            done: count(ItemStatus::Done),
+            pending: count(ItemStatus::Pending),
+            failed: count(ItemStatus::Failed),
+        }
+    }
+
+    /// Whether every item has been processed successfully
+    pub fn is_complete(&self) -> bool {
+        self.items.iter().all(|item| item.status == ItemStatus::Done)
+    }
+}
+
+/// Resolves a job source against the jobs directory, rejecting paths that leave it
+fn source_path(root: &Path, source: &str) -> Result<PathBuf> {
+    let relative = Path::new(source);
+    let inside = relative.components().all(|c| matches!(c, Component::Normal(_)));
+    if source.is_empty() || !inside || relative.starts_with(JOB_STATE_DIR) {
+        return Err(AppError::Validation(format!(
+            "job source must be a path inside the jobs directory: {:?}",
+            source
+        )));
+    }
+    Ok(root.join(relative))
+}
+
+/// Whether a job source is a ZIP archive rather than a directory
+fn is_archive(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("zip"))
+}
+
+/// Persists [`Job`]s as `<id>.json` files in a jobs directory's [`JOB_STATE_DIR`]
+///
+/// Finished items are appended to a `<id>.items.jsonl` log next to the job
+/// rather than rewriting the whole job each time; [`save`](Self::save) folds
+/// the log back into the job file.
+#[derive(Debug, Clone)]
+pub struct JobStore {
+    dir: PathBuf,
+}
+
+/// One finished item, as a line of a job's item log
+#[derive(Serialize, Deserialize)]
+struct ItemRecord {
+    index: usize,
+    item: JobItem,
+}
+
+impl JobStore {
+    /// Creates a store for the jobs directory `root`
+    pub fn new<P: AsRef<Path>>(root: P) -> Self {
+        Self { dir: root.as_ref().join(JOB_STATE_DIR) }
+    }
+
+    /// Loads the job with `id`, or `None` if there is no such job
+    pub fn load(&self, id: &str) -> Result<Option<Job>> {
+        // Ids are UUIDs, which also keeps them from naming paths outside the store
+        if Uuid::parse_str(id).is_err() {
+            return Ok(None);
+        }
+        match self.read(&self.dir.join(format!("{}.json", id))) {
+            Err(AppError::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            job => job.map(Some),
+        }
+    }
+
+    /// Saves `job`, replacing its previous state and item log atomically
+    pub fn save(&self, job: &Job) -> Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        let temp = self.dir.join(format!(".{}.json.tmp", job.id));
+        std::fs::write(&temp, serde_json::to_vec(job)?)?;
+        std::fs::rename(&temp, self.dir.join(format!("{}.json", job.id)))?;
+        // The log is replayed idempotently, so a crash before this is harmless
+        match std::fs::remove_file(self.log_path(&job.id)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Appends the item at `index` of `job` to the job's item log
+    pub fn record(&self, job: &Job, index: usize) -> Result<()> {
+        let record = ItemRecord { index, item: job.items[index].clone() };
+        let mut line = serde_json::to_vec(&record)?;
+        line.push(b'\n');
+        std::fs::create_dir_all(&self.dir)?;
+        OpenOptions::new().create(true).append(true).open(self.log_path(&job.id))?.write_all(&line)?;
+        Ok(())
+    }
+
+    /// Every saved job; files that don't parse are logged and skipped
+    pub fn jobs(&self) -> Result<Vec<Job>> {
+        let paths = match crate::directory_files(&self.dir) {
+            Err(AppError::NotFound(_)) => return Ok(Vec::new()),
+            paths => paths?,
+        };
+        Ok(paths
+            .into_iter()
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+            .filter_map(|path| {
+                self.read(&path).map_err(|e| warn!("Skipping unreadable job {}: {}", path.display(), e)).ok()
+            })
+            .collect())
+    }
+
+    /// Returns the job for `source`, planning and saving a new one if there is none
+    ///
+    /// Submitting the same source again returns the saved job, so its
+    /// finished items are not processed twice. Concurrent submissions of one
+    /// source are serialized and share a single job.
+    pub fn submit(&self, root: &Path, source: &str) -> Result<Job> {
+        source_path(root, source)?;
+        let mut submitted = SUBMITTED.lock().unwrap_or_else(|e| e.into_inner());
+        let id = submitted.entry((self.dir.clone(), source.to_string())).or_default();
+        if let Some(job) = self.load(id)? {
+            return Ok(job);
+        }
+        let job = match self.jobs()?.into_iter().find(|job| job.source == source) {
+            Some(job) => job,
+            None => {
+                let job = Job::plan(root, source)?;
+                self.save(&job)?;
+                job
+            }
+        };
+        *id = job.id.clone();
+        Ok(job)
+    }
+
+    /// Path of the item log of the job with `id`
+    fn log_path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}.items.jsonl", id))
+    }
+
+    /// Reads the job file at `path` and replays its item log over it
+    ///
+    /// A torn last line, left by a crash while it was written, is ignored.
+    fn read(&self, path: &Path) -> Result<Job> {
+        let mut job: Job = serde_json::from_slice(&std::fs::read(path)?)?;
+        let log = match File::open(self.log_path(&job.id)) {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(job),
+            log => BufReader::new(log?),
+        };
+        for line in log.lines() {
+            let Ok(ItemRecord { index, item }) = serde_json::from_str(&line?) else {
+                warn!("Ignoring a torn record in the item log of job {}", job.id);
+                break;
+            };
+            if let Some(slot) = job.items.get_mut(index) {
+                *slot = item;
+            }
+        }
+        Ok(job)
+    }
+}
+
+/// Processes every item of `job` that is not done yet
+///
+/// Items run one after another and each is recorded as it finishes, so an
+/// interrupted run loses at most the item in progress. Items that fail are
+/// recorded as failed and the job moves on. An archive is opened once for
+/// the whole run.
+///
+/// # Errors
+///
+/// Returns an error if the job's source is invalid or its state cannot be saved.
+pub fn run_job(root: &Path, store: &JobStore, job: &mut Job) -> Result<()> {
+    let source = source_path(root, &job.source)?;
+    if !is_archive(&source) {
+        return run_job_with(store, job, |item| crate::process_file(source.join(item)));
+    }
+    if job.is_complete() {
+        return Ok(());
+    }
+    let file = File::open(&source).map_err(|e| AppError::from_io_at(e, &source))?;
+    let mut archive = crate::core::archive::ZipEntries::new(BufReader::new(file))?;
+    run_job_with(store, job, |item| archive.process(item))
+}
+
+/// Runs `process` on the unfinished items of `job`, recording each as it finishes
+///
+/// Finished items are appended to the job's item log, which is folded into
+/// the saved job every [`ITEMS_PER_SAVE`] items and once the run is over.
+fn run_job_with<F>(store: &JobStore, job: &mut Job, mut process: F) -> Result<()>
+where
+    F: FnMut(&str) -> Result<MediaManifest>,
+{
+    let mut recorded = 0;
+    for index in 0..job.items.len() {
+        let item = &mut job.items[index];
+        if item.status == ItemStatus::Done {
+            continue;
+        }
+        match process(&item.path) {
+            Ok(manifest) => {
+                item.status = ItemStatus::Done;
+                item.manifest = Some(manifest);
+                item.error = None;
+            }
+            Err(e) => {
+                warn!("Job {} failed to process {}: {}", job.id, item.path, e);
+                item.status = ItemStatus::Failed;
+                item.error = Some(e.to_string());
+            }
+        }
+        store.record(job, index)?;
+        recorded += 1;
+        if recorded % ITEMS_PER_SAVE == 0 {
+            store.save(job)?;
+        }
+    }
+    if recorded % ITEMS_PER_SAVE != 0 {
+        store.save(job)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resubmitted_job_skips_completed_items() {
+        let root = tempfile::tempdir().unwrap();
+        let batch = root.path().join("batch");
+        std::fs::create_dir(&batch).unwrap();
+        for name in ["a.bin", "b.bin", "c.bin"] {
+            std::fs::write(batch.join(name), name.as_bytes()).unwrap();
+        }
+        let store = JobStore::new(root.path());
+        let process = |path: &str| crate::process_file(batch.join(path));
+
+        // The first run dies while processing the second item
+        let mut job = store.submit(root.path(), "batch").unwrap();
+        let mut first_run = Vec::new();
+        let interrupted = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            run_job_with(&store, &mut job, |path| {
+                assert_ne!(path, "b.bin", "interrupted");
+                first_run.push(path.to_string());
+                process(path)
+            })
+        }));
+        assert!(interrupted.is_err());
+
+        let mut resumed = store.submit(root.path(), "batch").unwrap();
+        assert_eq!(resumed.id, job.id);
+        assert_eq!(resumed.progress(), JobProgress { total: 3, done: 1, pending: 2, failed: 0 });
+
+        let mut second_run = Vec::new();
+        run_job_with(&store, &mut resumed, |path| {
+            second_run.push(path.to_string());
+            process(path)
+        })
+        .unwrap();
+        assert_eq!(first_run, ["a.bin"]);
+        assert_eq!(second_run, ["b.bin", "c.bin"]);
+
+        let saved = store.load(&job.id).unwrap().unwrap();
+        assert!(saved.is_complete());
+        assert_eq!(saved.items[1].manifest.as_ref().unwrap().file_name, "b.bin");
+
+        // Resubmitting a finished job processes nothing
+        let mut finished = store.submit(root.path(), "batch").unwrap();
+        run_job_with(&store, &mut finished, |path| panic!("reprocessed {}", path)).unwrap();
+
+        assert!(matches!(store.submit(root.path(), "../batch"), Err(AppError::Validation(_))));
+    }
+
+    #[test]
+    fn test_concurrent_submissions_share_one_job() {
+        let root = tempfile::tempdir().unwrap();
+        std::fs::create_dir(root.path().join("batch")).unwrap();
+        std::fs::write(root.path().join("batch/a.bin"), b"a").unwrap();
+
+        let ids: Vec<String> = std::thread::scope(|scope| {
+            let submissions: Vec<_> = (0..8)
+                .map(|_| scope.spawn(|| JobStore::new(root.path()).submit(root.path(), "batch").unwrap().id))
+                .collect();
+            submissions.into_iter().map(|handle| handle.join().unwrap()).collect()
+        });
+        assert!(ids.iter().all(|id| *id == ids[0]));
+        assert_eq!(JobStore::new(root.path()).jobs().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_finished_run_folds_item_log_into_job() {
+        let root = tempfile::tempdir().unwrap();
+        let batch = root.path().join("batch");
+        std::fs::create_dir(&batch).unwrap();
+        for name in ["a.bin", "b.bin"] {
+            std::fs::write(batch.join(name), name.as_bytes()).unwrap();
+        }
+        let store = JobStore::new(root.path());
+        let mut job = store.submit(root.path(), "batch").unwrap();
+        run_job(root.path(), &store, &mut job).unwrap();
+
+        assert!(!store.log_path(&job.id).exists());
+        let saved: Job = serde_json::from_slice(
+            &std::fs::read(root.path().join(JOB_STATE_DIR).join(format!("{}.json", job.id))).unwrap(),
+        )
+        .unwrap();
+        assert!(saved.is_complete());
+
+        // A torn record at the end of the log is ignored
+        store.record(&saved, 0).unwrap();
+        let mut log = OpenOptions::new().append(true).open(store.log_path(&job.id)).unwrap();
+        log.write_all(br#"{"index":1,"item":{"pa"#).unwrap();
+        assert!(store.load(&job.id).unwrap().unwrap().is_complete());
+    }
+}
@@ -1,10 +1,34 @@
 //! Core functionality for image and video processing
 
+/// Processes media stored in archives such as ZIP files.
+pub mod archive;
+/// Caches decoded images across consecutive operations on the same file.
+pub mod cache;
+/// Stores manifests inside media containers (PNG text chunks, MP4 metadata).
+pub mod container;
+/// Splits multi-page documents (TIFF, PDF) into per-page images.
+pub mod document;
 /// Manages deep learning embeddings for media files.
 pub mod embeddings;
+/// Downloads media from remote URLs within size, time and address limits.
+pub mod fetch;
 /// Provides functions for computing cryptographic and perceptual hashes.
 pub mod hash;
+/// Indexes perceptual hashes for fast nearest-neighbour lookup.
+pub mod index;
+/// Runs resumable batch jobs over directories and archives, persisting their progress.
+pub mod jobs;
+/// Registers perceptual hash algorithms that can be selected by name.
+pub mod perceptual;
+/// Verifies and re-hashes directories of media and renders CI-friendly reports.
+pub mod report;
+/// Persists manifests and reconciles them with the uploaded files.
+pub mod store;
+/// Compares media by perceptual hash, e.g. locating an image within a video.
+pub mod similarity;
 /// Contains tools for video processing, such as frame extraction.
 pub mod video;
+/// Watches a directory and processes media files as they appear.
+pub mod watch;
 
 
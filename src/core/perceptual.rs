@@ -0,0 +1,326 @@
+use anyhow::{anyhow, Result};
+use image::DynamicImage;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::sync::{Arc, OnceLock, RwLock};
+
+use crate::core::hash::{compute_pdq_hash, downscale_luminance, pdq_distance, pdq_to_hex, PDQ_GRID_SIZE};
+
+/// Name of the algorithm used when none is requested
+pub const DEFAULT_ALGORITHM: &str = "pdq";
+
+/// A perceptual hash that can be computed and compared by name
+///
+/// Implementations are registered in an [`AlgorithmRegistry`]; manifests
+/// record the name so verification recomputes with the same algorithm.
+pub trait PerceptualAlgorithm: Send + Sync {
+    /// Unique name clients use to select the algorithm, e.g. `"pdq"`.
+    fn name(&self) -> &str;
+
+    /// Computes the hash of a decoded image.
+    fn hash(&self, img: &DynamicImage) -> Result<String>;
+
+    /// Returns the distance between two hashes; 0 means identical.
+    fn compare(&self, a: &str, b: &str) -> Result<u32>;
+
+    /// Version of the hash output; bump it whenever the hash of the same image changes.
+    fn version(&self) -> u32 {
+        1
+    }
+}
+
+/// The built-in PDQ-style hash from [`crate::core::hash`]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Pdq;
+
+impl PerceptualAlgorithm for Pdq {
+    fn name(&self) -> &str {
+        DEFAULT_ALGORITHM
+    }
+
+    fn hash(&self, img: &DynamicImage) -> Result<String> {
+        compute_pdq_hash(img)
+    }
+
+    fn compare(&self, a: &str, b: &str) -> Result<u32> {
+        pdq_distance(a, b)
+    }
+
+    fn version(&self) -> u32 {
+        crate::core::hash::PDQ_VERSION
+    }
+}
+
+/// Average hash: 8x8 cell means thresholded at their overall mean
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AHash;
+
+impl PerceptualAlgorithm for AHash {
+    fn name(&self) -> &str {
+        "ahash"
+    }
+
+    fn hash(&self, img: &DynamicImage) -> Result<String> {
+        let cells = shrink(&downscale_luminance(img), 8, 8);
+        let mean = cells.iter().sum::<f32>() / cells.len() as f32;
+        bits_to_hex(cells.iter().map(|&c| c > mean))
+    }
+
+    fn compare(&self, a: &str, b: &str) -> Result<u32> {
+        pdq_distance(a, b)
+    }
+}
+
+/// Difference hash: whether each of 9x8 cells is brighter than its right neighbour
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DHash;
+
+impl PerceptualAlgorithm for DHash {
+    fn name(&self) -> &str {
+        "dhash"
+    }
+
+    fn hash(&self, img: &DynamicImage) -> Result<String> {
+        let cells = shrink(&downscale_luminance(img), 9, 8);
+        bits_to_hex(cells.chunks(9).flat_map(|row| row.windows(2).map(|pair| pair[0] > pair[1])))
+    }
+
+    fn compare(&self, a: &str, b: &str) -> Result<u32> {
+        pdq_distance(a, b)
+    }
+}
+
+/// Wavelet hash: the 8x8 low-low band of a Haar decomposition, thresholded at its median
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WHash;
+
+impl PerceptualAlgorithm for WHash {
+    fn name(&self) -> &str {
+        "whash"
+    }
+
+    fn hash(&self, img: &DynamicImage) -> Result<String> {
+        // Each Haar level's low-low band is the mean of 2x2 blocks, halving the grid
+        let mut band = downscale_luminance(img);
+        let mut size = PDQ_GRID_SIZE;
+        while size > 8 {
+            band = shrink_square(&band, size, size / 2);
+            size /= 2;
+        }
+        let threshold = median(&band);
+        bits_to_hex(band.iter().map(|&c| c > threshold))
+    }
+
+    fn compare(&self, a: &str, b: &str) -> Result<u32> {
+        pdq_distance(a, b)
+    }
+}
+
+/// Block hash: 8x8 block means, each compared with the median of its horizontal band
+///
+/// The grid is split into four bands of two block rows, so a bright top half
+/// does not saturate every bit of a dark bottom half.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BlockHash;
+
+impl PerceptualAlgorithm for BlockHash {
+    fn name(&self) -> &str {
+        "blockhash"
+    }
+
+    fn hash(&self, img: &DynamicImage) -> Result<String> {
+        let cells = shrink(&downscale_luminance(img), 8, 8);
+        bits_to_hex(cells.chunks(16).flat_map(|band| {
+            let threshold = median(band);
+            band.iter().map(move |&c| c > threshold)
+        }))
+    }
+
+    fn compare(&self, a: &str, b: &str) -> Result<u32> {
+        pdq_distance(a, b)
+    }
+}
+
+/// Averages a square luminance grid into `width` x `height` cells, row-major
+fn shrink(grid: &[f32], width: usize, height: usize) -> Vec<f32> {
+    let range = |t: usize, cells: usize| {
+        let start = t * PDQ_GRID_SIZE / cells;
+        (start, ((t + 1) * PDQ_GRID_SIZE / cells).max(start + 1))
+    };
+    let mut cells = Vec::with_capacity(width * height);
+    for ty in 0..height {
+        let (y0, y1) = range(ty, height);
+        for tx in 0..width {
+            let (x0, x1) = range(tx, width);
+            let sum: f32 = (y0..y1)
+                .flat_map(|y| grid[y * PDQ_GRID_SIZE + x0..y * PDQ_GRID_SIZE + x1].iter())
+                .sum();
+            cells.push(sum / ((x1 - x0) * (y1 - y0)) as f32);
+        }
+    }
+    cells
+}
+
+/// Averages a `size` x `size` grid into `half` x `half` by 2x2 blocks
+fn shrink_square(grid: &[f32], size: usize, half: usize) -> Vec<f32> {
+    (0..half * half)
+        .map(|i| {
+            let (y, x) = (2 * (i / half), 2 * (i % half));
+            (grid[y * size + x] + grid[y * size + x + 1] + grid[(y + 1) * size + x] + grid[(y + 1) * size + x + 1]) / 4.0
+        })
+        .collect()
+}
+
+fn median(values: &[f32]) -> f32 {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let mid = sorted.len() / 2;
+    (sorted[(sorted.len() - 1) / 2] + sorted[mid]) / 2.0
+}
+
+fn bits_to_hex(bits: impl Iterator<Item = bool>) -> Result<String> {
+    let bits: String = bits.map(|bit| if bit { '1' } else { '0' }).collect();
+    pdq_to_hex(&bits)
+}
+
+/// Perceptual algorithms by name
+#[derive(Clone)]
+pub struct AlgorithmRegistry {
+    algorithms: HashMap<String, Arc<dyn PerceptualAlgorithm>>,
+}
+
+impl fmt::Debug for AlgorithmRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AlgorithmRegistry").field("algorithms", &self.names()).finish()
+    }
+}
+
+impl Default for AlgorithmRegistry {
+    /// A registry holding the built-in algorithms.
+    fn default() -> Self {
+        let mut registry = Self { algorithms: HashMap::new() };
+        registry.register(Pdq);
+        registry.register(AHash);
+        registry.register(DHash);
+        registry.register(WHash);
+        registry.register(BlockHash);
+        registry
+    }
+}
+
+impl AlgorithmRegistry {
+    /// Adds `algorithm` under its name, replacing any algorithm of the same name.
+    pub fn register<A: PerceptualAlgorithm + 'static>(&mut self, algorithm: A) {
+        self.algorithms.insert(algorithm.name().to_string(), Arc::new(algorithm));
+    }
+
+    /// Looks up an algorithm by name, falling back to [`DEFAULT_ALGORITHM`] when `name` is `None`.
+    pub fn get(&self, name: Option<&str>) -> Result<Arc<dyn PerceptualAlgorithm>> {
+        let name = name.unwrap_or(DEFAULT_ALGORITHM);
+        self.algorithms
+            .get(name)
+            .cloned()
+            .ok_or_else(|| anyhow!("unknown perceptual algorithm: {}", name))
+    }
+
+    /// Names of the registered algorithms, sorted.
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.algorithms.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// The process-wide registry used by the upload handler and manifest verification.
+    pub fn global() -> &'static RwLock<AlgorithmRegistry> {
+        static INSTANCE: OnceLock<RwLock<AlgorithmRegistry>> = OnceLock::new();
+        INSTANCE.get_or_init(|| RwLock::new(AlgorithmRegistry::default()))
+    }
+}
+
+/// Registers `algorithm` in the [global](AlgorithmRegistry::global) registry.
+pub fn register<A: PerceptualAlgorithm + 'static>(algorithm: A) {
+    AlgorithmRegistry::global()
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .register(algorithm);
+}
+
+/// Looks up an algorithm in the [global](AlgorithmRegistry::global) registry.
+pub fn algorithm(name: Option<&str>) -> Result<Arc<dyn PerceptualAlgorithm>> {
+    AlgorithmRegistry::global()
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(name)
+}
+
+/// Hashes `img` with every algorithm in the [global](AlgorithmRegistry::global) registry
+///
+/// Returns the hashes keyed by algorithm name, which makes it easy to compare
+/// how each algorithm sees the same image.
+pub fn compute_all_perceptual(img: &DynamicImage) -> Result<BTreeMap<String, String>> {
+    let algorithms: Vec<Arc<dyn PerceptualAlgorithm>> = {
+        let registry = AlgorithmRegistry::global().read().unwrap_or_else(|e| e.into_inner());
+        registry.algorithms.values().cloned().collect()
+    };
+    algorithms
+        .iter()
+        .map(|algorithm| Ok((algorithm.name().to_string(), algorithm.hash(img)?)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Hashes an image to its mean luma, compared by absolute difference
+    #[derive(Debug)]
+    struct MeanLuma;
+
+    impl PerceptualAlgorithm for MeanLuma {
+        fn name(&self) -> &str {
+            "mean-luma"
+        }
+
+        fn hash(&self, img: &DynamicImage) -> Result<String> {
+            let luma = img.to_luma8();
+            let sum: u64 = luma.pixels().map(|p| p.0[0] as u64).sum();
+            Ok(format!("{}", sum / (luma.len() as u64).max(1)))
+        }
+
+        fn compare(&self, a: &str, b: &str) -> Result<u32> {
+            Ok(a.parse::<u32>()?.abs_diff(b.parse::<u32>()?))
+        }
+    }
+
+    #[test]
+    fn test_registry_selects_by_name() {
+        let mut registry = AlgorithmRegistry::default();
+        registry.register(MeanLuma);
+        assert!(registry.names().contains(&"mean-luma".to_string()));
+
+        let img = DynamicImage::ImageRgb8(image::RgbImage::from_pixel(4, 4, image::Rgb([100, 100, 100])));
+        let mean = registry.get(Some("mean-luma")).unwrap();
+        assert_eq!(mean.hash(&img).unwrap(), "100");
+        assert_eq!(mean.compare("100", "97").unwrap(), 3);
+
+        assert_eq!(registry.get(None).unwrap().name(), DEFAULT_ALGORITHM);
+        assert!(registry.get(Some("phash")).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "hashing")]
+    fn test_compute_all_perceptual_covers_builtin_algorithms() {
+        let img = DynamicImage::ImageRgb8(image::RgbImage::from_fn(48, 32, |x, y| {
+            image::Rgb([(x * 5) as u8, (y * 7) as u8, ((x * y) % 256) as u8])
+        }));
+        let hashes = compute_all_perceptual(&img).unwrap();
+        for name in ["pdq", "ahash", "dhash", "whash", "blockhash"] {
+            let hash = hashes.get(name).unwrap_or_else(|| panic!("missing {}", name));
+            assert_eq!(hash.len(), 16, "{} hash {}", name, hash);
+        }
+
+        let flipped = compute_all_perceptual(&img.fliph()).unwrap();
+        let dhash = algorithm(Some("dhash")).unwrap();
+        assert!(dhash.compare(&hashes["dhash"], &flipped["dhash"]).unwrap() > 16);
+    }
+}
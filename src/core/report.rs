@@ -0,0 +1,158 @@
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+use crate::error::Result;
+use crate::models::manifest::{MediaManifest, VerificationReport};
+
+/// Verifies every manifest in `manifests_dir` against the files in `files_dir`.
+///
+/// Each `*.json` file in `manifests_dir` is loaded as a manifest and its file
+/// is resolved inside `files_dir` as [`MediaManifest::verify_in`] does.
+/// Results are keyed by the manifest's file path (or the manifest file name
+/// if it cannot be loaded) and sorted by manifest file name. A failure for one
+/// entry does not stop the others; only an unreadable `manifests_dir` fails
+/// the whole call.
+pub fn verify_dir<P, Q>(files_dir: P, manifests_dir: Q) -> Result<Vec<(String, Result<VerificationReport>)>>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    Ok(manifest_paths(manifests_dir.as_ref())?
+        .into_iter()
+        .map(|manifest_path| match MediaManifest::load(&manifest_path) {
+            Ok(manifest) => {
+                let name = manifest
+                    .relative_path
+                    .clone()
+                    .unwrap_or_else(|| manifest.file_name.clone());
+                (name, manifest.verify_report_in(files_dir.as_ref()))
+            }
+            Err(e) => (manifest_path.display().to_string(), Err(e)),
+        })
+        .collect())
+}
+
+/// Recomputes the perceptual hashes of every manifest in `manifests_dir`.
+///
+/// Files are resolved inside `files_dir` as in [`verify_dir`], each manifest
+/// is updated with [`MediaManifest::recompute_perceptual_with`] (images
+/// smaller than `min_pdq_dimension` get no PDQ hash) and saved back in
+/// place. Results are keyed by manifest file path and sorted; a failure for one
+/// entry leaves that manifest unchanged and does not stop the others.
+pub fn rehash_dir<P, Q>(files_dir: P, manifests_dir: Q, min_pdq_dimension: u32) -> Result<Vec<(String, Result<()>)>>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    Ok(manifest_paths(manifests_dir.as_ref())?
+        .into_iter()
+        .map(|manifest_path| {
+            let result = MediaManifest::load(&manifest_path).and_then(|mut manifest| {
+                manifest.recompute_perceptual_in(files_dir.as_ref(), min_pdq_dimension)?;
+                manifest.save(&manifest_path)
+            });
+            (manifest_path.display().to_string(), result)
+        })
+        .collect())
+}
+
+/// Lists the `*.json` files directly inside `dir`, sorted by path
+fn manifest_paths(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut paths: Vec<_> = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    paths.sort();
+    Ok(paths)
+}
+
+/// Renders verification results as a JUnit XML report.
+///
+/// Each entry becomes a testcase. Mismatches are reported as failures
+/// carrying the report's reason; entries that could not be verified at all
+/// (unreadable manifest, I/O errors) are reported as errors.
+pub fn junit_xml(suite: &str, results: &[(String, Result<VerificationReport>)]) -> String {
+    let failures = results
+        .iter()
+        .filter(|(_, result)| matches!(result, Ok(report) if !report.is_valid))
+        .count();
+    let errors = results.iter().filter(|(_, result)| result.is_err()).count();
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let _ = writeln!(
+        xml,
+        "<testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"{}\">",
+        escape_xml(suite),
+        results.len(),
+        failures,
+        errors
+    );
+
+    for (name, result) in results {
+        let _ = write!(xml, "  <testcase classname=\"{}\" name=\"{}\"", escape_xml(suite), escape_xml(name));
+        match result {
+            Ok(report) if report.is_valid => xml.push_str("/>\n"),
+            Ok(report) => {
+                let reason = report.reason.as_deref().unwrap_or("verification failed");
+                let _ = writeln!(
+                    xml,
+                    ">\n    <failure message=\"{}\"/>\n  </testcase>",
+                    escape_xml(reason)
+                );
+            }
+            Err(e) => {
+                let _ = writeln!(
+                    xml,
+                    ">\n    <error message=\"{}\"/>\n  </testcase>",
+                    escape_xml(&e.to_string())
+                );
+            }
+        }
+    }
+
+    xml.push_str("</testsuite>\n");
+    xml
+}
+
+/// Escapes the characters that are not allowed verbatim in XML attributes
+fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::AppError;
+
+    #[test]
+    fn test_junit_xml() {
+        let results = vec![
+            ("ok.png".to_string(), Ok(VerificationReport { is_valid: true, reason: None, explanation: None })),
+            (
+                "bad <1>.png".to_string(),
+                Ok(VerificationReport { is_valid: false, reason: Some("SHA3 hash mismatch".to_string()), explanation: None }),
+            ),
+            ("gone.json".to_string(), Err(AppError::NotFound("gone.png".to_string()))),
+        ];
+
+        let xml = junit_xml("imagechain.verify", &results);
+
+        assert!(xml.contains("tests=\"3\" failures=\"1\" errors=\"1\""));
+        assert!(xml.contains("<testcase classname=\"imagechain.verify\" name=\"ok.png\"/>"));
+        assert!(xml.contains("name=\"bad &lt;1&gt;.png\""));
+        assert!(xml.contains("<failure message=\"SHA3 hash mismatch\"/>"));
+        assert!(xml.contains("<error message=\"Not found: gone.png\"/>"));
+    }
+}
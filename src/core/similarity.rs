@@ -0,0 +1,746 @@
+use anyhow::Result;
+use image::{imageops::FilterType, DynamicImage, GenericImageView, Rgb, RgbImage};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::core::hash::{pdq_bits, pdq_distance};
+use crate::models::manifest::{FrameInfo, MediaManifest};
+
+/// Per-channel difference below which pixels are considered unchanged
+const DIFF_TOLERANCE: u8 = 8;
+
+/// Finds the frame of a video manifest that best matches an image's PDQ hash.
+///
+/// Returns the frame with the smallest PDQ Hamming distance to `image_pdq`,
+/// provided that distance is at most `threshold`. Frames whose hashes cannot
+/// be compared (e.g. malformed or of a different length) are skipped.
+/// Returns `None` if the manifest has no frames or no frame is close enough.
+pub fn image_in_video(image_pdq: &str, video: &MediaManifest, threshold: u32) -> Option<FrameInfo> {
+    let frames = video.frames.as_ref()?;
+
+    frames
+        .iter()
+        .filter_map(|frame| {
+            pdq_distance(image_pdq, &frame.pdq_hash)
+                .ok()
+                .map(|distance| (distance, frame))
+        })
+        .filter(|(distance, _)| *distance <= threshold)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, frame)| frame.clone())
+}
+
+/// Most frames per video the API compares; [`video_sequence_similarity`]
+/// holds a table of both lengths' product, so longer videos are refused.
+pub const MAX_COMPARED_FRAMES: usize = 2_000;
+
+/// A frame's PDQ hash decoded once, so comparing every pair of frames
+/// doesn't decode each hash again per pair
+struct PackedHash {
+    bits: usize,
+    words: Vec<u64>,
+}
+
+impl PackedHash {
+    /// Packs each frame's hash, or `None` for hashes that can't be decoded
+    fn frames(frames: &[FrameInfo]) -> Vec<Option<PackedHash>> {
+        frames
+            .iter()
+            .map(|frame| {
+                let bits = pdq_bits(&frame.pdq_hash).ok()?;
+                let words = bits
+                    .chunks(64)
+                    .map(|chunk| chunk.iter().fold(0u64, |word, &bit| (word << 1) | u64::from(bit)))
+                    .collect();
+                Some(PackedHash { bits: bits.len(), words })
+            })
+            .collect()
+    }
+
+    /// Whether the hashes have the same length and are at most `threshold` bits apart
+    fn within(a: &Option<PackedHash>, b: &Option<PackedHash>, threshold: u32) -> bool {
+        match (a, b) {
+            (Some(a), Some(b)) if a.bits == b.bits => {
+                a.words.iter().zip(&b.words).map(|(x, y)| (x ^ y).count_ones()).sum::<u32>() <= threshold
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Result of aligning two videos' frame sequences.
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+pub struct SequenceMatch {
+    /// Length of the longest in-order alignment of near-identical frames.
+    pub matched: usize,
+    /// Aligned `(index_in_a, index_in_b)` frame pairs, in order.
+    pub alignment: Vec<(usize, usize)>,
+    /// `matched` over the length of the longer sequence, in the `0.0..=1.0` range.
+    pub similarity: f32,
+    /// Fraction of the shorter sequence's frames that have a match anywhere in
+    /// the other, ignoring order.
+    pub set_overlap: f32,
+}
+
+/// Compares two frame sequences by order, not just by which frames they share.
+///
+/// Two frames match when their PDQ distance is at most `threshold`; frames
+/// whose hashes cannot be compared never match. The alignment is the longest
+/// common subsequence of matching frames, so a reversed or reshuffled copy of
+/// a video scores a high `set_overlap` but a low `similarity`.
+pub fn video_sequence_similarity(a: &[FrameInfo], b: &[FrameInfo], threshold: u32) -> SequenceMatch {
+    let (a_hashes, b_hashes) = (PackedHash::frames(a), PackedHash::frames(b));
+    let matches = |i: usize, j: usize| PackedHash::within(&a_hashes[i], &b_hashes[j], threshold);
+
+    // lengths[i][j] is the LCS length of a[i..] and b[j..]
+    let width = b.len() + 1;
+    let mut lengths = vec![0usize; (a.len() + 1) * width];
+    let mut near = vec![false; a.len() * b.len()];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            near[i * b.len() + j] = matches(i, j);
+            lengths[i * width + j] = if near[i * b.len() + j] {
+                lengths[(i + 1) * width + j + 1] + 1
+            } else {
+                lengths[(i + 1) * width + j].max(lengths[i * width + j + 1])
+            };
+        }
+    }
+
+    let mut alignment = Vec::with_capacity(lengths[0]);
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        if near[i * b.len() + j] {
+            alignment.push((i, j));
+            i += 1;
+            j += 1;
+        } else if lengths[(i + 1) * width + j] >= lengths[i * width + j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+
+    let longest = a.len().max(b.len());
+    let shortest = a.len().min(b.len());
+    let overlapping = if a.len() <= b.len() {
+        (0..a.len()).filter(|&i| (0..b.len()).any(|j| near[i * b.len() + j])).count()
+    } else {
+        (0..b.len()).filter(|&j| (0..a.len()).any(|i| near[i * b.len() + j])).count()
+    };
+    let ratio = |count: usize, total: usize| if total == 0 { 0.0 } else { count as f32 / total as f32 };
+
+    SequenceMatch {
+        matched: alignment.len(),
+        similarity: ratio(alignment.len(), longest),
+        set_overlap: ratio(overlapping, shortest),
+        alignment,
+    }
+}
+
+/// PDQ distance at which two frames count as the same in [`video_similarity_score`]
+pub const VIDEO_SCORE_FRAME_THRESHOLD: u32 = 10;
+
+/// Share of [`video_similarity_score`] given to embedding similarity when
+/// both videos carry frame embeddings; frame overlap gets the rest.
+pub const VIDEO_SCORE_EMBEDDING_WEIGHT: f32 = 0.3;
+
+/// Summarises how similar two videos are as one score in the `0.0..=1.0` range.
+///
+/// The frame score is the fraction of all frames of both videos that have a
+/// frame within [`VIDEO_SCORE_FRAME_THRESHOLD`] PDQ bits in the other video,
+/// ignoring order. When both videos carry frame embeddings, the cosine
+/// similarity of their mean frame embeddings (negative values count as 0) is
+/// blended in with weight [`VIDEO_SCORE_EMBEDDING_WEIGHT`]; otherwise, or if
+/// the embeddings have different dimensions, the score is the frame score
+/// alone. A manifest's `video_embedding` stands in for its mean frame
+/// embedding when present. Videos without frames score 0. The result depends
+/// only on the two manifests, so ranking by it is reproducible.
+pub fn video_similarity_score(a: &MediaManifest, b: &MediaManifest) -> f32 {
+    let a_frames = a.frames.as_deref().unwrap_or_default();
+    let b_frames = b.frames.as_deref().unwrap_or_default();
+    let total = a_frames.len() + b_frames.len();
+    if a_frames.is_empty() || b_frames.is_empty() {
+        return 0.0;
+    }
+
+    let (a_hashes, b_hashes) = (PackedHash::frames(a_frames), PackedHash::frames(b_frames));
+    let matched_in = |hashes: &[Option<PackedHash>], others: &[Option<PackedHash>]| {
+        hashes
+            .iter()
+            .filter(|hash| others.iter().any(|other| PackedHash::within(hash, other, VIDEO_SCORE_FRAME_THRESHOLD)))
+            .count()
+    };
+    let frame_score =
+        (matched_in(&a_hashes, &b_hashes) + matched_in(&b_hashes, &a_hashes)) as f32 / total as f32;
+
+    let video_embedding = |manifest: &MediaManifest, frames| {
+        manifest.video_embedding.clone().or_else(|| pool_frame_embeddings(frames))
+    };
+    let embedding_score = match (video_embedding(a, a_frames), video_embedding(b, b_frames)) {
+        (Some(x), Some(y)) => embedding_similarity(&x, &y).ok().flatten(),
+        _ => None,
+    };
+    match embedding_score {
+        Some(cosine) => {
+            let weight = VIDEO_SCORE_EMBEDDING_WEIGHT;
+            ((1.0 - weight) * frame_score + weight * cosine.max(0.0)).clamp(0.0, 1.0)
+        }
+        None => frame_score,
+    }
+}
+
+/// Pools a video's frame embeddings into one vector: their element-wise mean
+///
+/// Frames without an embedding are skipped, as are embeddings whose
+/// dimension differs from the first one's, so the result always has the
+/// model's dimension. Returns `None` when no frame has an embedding.
+pub fn pool_frame_embeddings(frames: &[FrameInfo]) -> Option<Vec<f32>> {
+    let mut embeddings = frames.iter().filter_map(|frame| frame.embedding.as_deref());
+    let first = embeddings.next()?;
+    let dim = first.len();
+    let mut sum = first.to_vec();
+    let mut count = 1usize;
+    for embedding in embeddings.filter(|embedding| embedding.len() == dim) {
+        sum.iter_mut().zip(embedding).for_each(|(total, x)| *total += x);
+        count += 1;
+    }
+    sum.iter_mut().for_each(|total| *total /= count as f32);
+    Some(sum)
+}
+
+/// Computes the symmetric matrix of PDQ Hamming distances between `hashes`
+///
+/// Entry `[i][j]` is the distance between `hashes[i]` and `hashes[j]`, so the
+/// diagonal is zero. Each pair is compared once. Fails if any two hashes
+/// cannot be compared (e.g. malformed or of different lengths).
+pub fn pdq_distance_matrix<S: AsRef<str>>(hashes: &[S]) -> Result<Vec<Vec<u32>>> {
+    let mut matrix = vec![vec![0; hashes.len()]; hashes.len()];
+    for i in 0..hashes.len() {
+        for j in i + 1..hashes.len() {
+            let distance = pdq_distance(hashes[i].as_ref(), hashes[j].as_ref())?;
+            matrix[i][j] = distance;
+            matrix[j][i] = distance;
+        }
+    }
+    Ok(matrix)
+}
+
+/// Default maximum per-channel average-colour difference for [`find_similar`]
+pub const DEFAULT_COLOR_PREFILTER: u8 = 48;
+
+/// Side length of the coarse colour grid in a [`ColorSignature`]
+const COLOR_GRID_SIZE: u32 = 4;
+
+/// Cheap colour summary of an image, used to pre-filter PDQ comparisons.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ColorSignature {
+    /// Mean RGB colour over all pixels.
+    pub average: [u8; 3],
+    /// 4x4 grid of cell colours, row-major.
+    pub grid: Vec<[u8; 3]>,
+}
+
+impl ColorSignature {
+    /// Computes the average colour and the coarse 4x4 colour grid of an image.
+    pub fn compute(image: &DynamicImage) -> Self {
+        let rgb = image.to_rgb8();
+        let mut sums = [0u64; 3];
+        for pixel in rgb.pixels() {
+            for (sum, &c) in sums.iter_mut().zip(pixel.0.iter()) {
+                *sum += c as u64;
+            }
+        }
+        let count = (rgb.width() as u64 * rgb.height() as u64).max(1);
+        let average = sums.map(|sum| (sum / count) as u8);
+
+        let grid = image::imageops::resize(&rgb, COLOR_GRID_SIZE, COLOR_GRID_SIZE, FilterType::Triangle)
+            .pixels()
+            .map(|p| p.0)
+            .collect();
+
+        Self { average, grid }
+    }
+
+    /// Largest per-channel difference between the two average colours.
+    pub fn average_distance(&self, other: &Self) -> u8 {
+        self.average
+            .iter()
+            .zip(other.average.iter())
+            .map(|(&a, &b)| a.abs_diff(b))
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+/// Default number of bins per channel in a colour histogram
+pub const DEFAULT_HISTOGRAM_BINS: usize = 8;
+
+/// Computes a per-channel colour histogram of an image
+///
+/// Returns `3 * bins` values: the red, green and blue histograms one after
+/// another, each normalized to sum to 1 so images of any size compare
+/// directly. Unlike PDQ, which only sees luminance, this tells a red shape
+/// from a green one. `bins` is clamped to `1..=256`.
+pub fn compute_color_histogram(img: &DynamicImage, bins: usize) -> Vec<f32> {
+    let bins = bins.clamp(1, 256);
+    let rgb = img.to_rgb8();
+    let mut histogram = vec![0u64; 3 * bins];
+    for pixel in rgb.pixels() {
+        for (channel, &value) in pixel.0.iter().enumerate() {
+            histogram[channel * bins + value as usize * bins / 256] += 1;
+        }
+    }
+
+    let count = (rgb.width() as u64 * rgb.height() as u64).max(1) as f32;
+    histogram.into_iter().map(|n| n as f32 / count).collect()
+}
+
+/// Histogram intersection of two colour histograms, in the `0.0..=1.0` range
+///
+/// 1.0 means identical colour distributions. The histograms should come from
+/// [`compute_color_histogram`] with the same bin count; if their lengths
+/// differ the similarity is 0.
+pub fn histogram_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() || a.is_empty() {
+        return 0.0;
+    }
+    let intersection: f32 = a.iter().zip(b).map(|(&x, &y)| x.min(y)).sum();
+    // Each of the three channels sums to 1
+    (intersection / 3.0).clamp(0.0, 1.0)
+}
+
+/// Cosine similarity of two embedding vectors, in the `-1.0..=1.0` range
+///
+/// Returns `None` when either vector is all zeros.
+///
+/// # Errors
+///
+/// Fails when the vectors have different dimensions, e.g. because they were
+/// produced by different embedding models and cannot be compared.
+pub fn embedding_similarity(a: &[f32], b: &[f32]) -> Result<Option<f32>> {
+    if a.len() != b.len() {
+        anyhow::bail!("embedding dimension mismatch: {} vs {}", a.len(), b.len());
+    }
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    Ok((norm_a > 0.0 && norm_b > 0.0).then(|| (dot / (norm_a * norm_b)).clamp(-1.0, 1.0)))
+}
+
+/// Finds the images among `candidates` within `max_distance` PDQ bits of `query`.
+///
+/// When `max_color_difference` is set and both manifests carry a
+/// [`ColorSignature`], candidates whose average colour differs by more than
+/// that are skipped before the Hamming distance is computed, which is much
+/// cheaper on large collections. When `min_histogram_similarity` is set and
+/// both manifests carry a `color_histogram`, candidates whose
+/// [`histogram_similarity`] falls below it are skipped too, which separates
+/// recoloured versions of the same picture. Candidates without a PDQ hash,
+/// hashed by another PDQ version (see [`MediaManifest::check_comparable`]) or
+/// whose hash cannot be compared are skipped. Results are sorted by distance.
+pub fn find_similar<'a, I>(
+    query: &MediaManifest,
+    candidates: I,
+    max_distance: u32,
+    max_color_difference: Option<u8>,
+    min_histogram_similarity: Option<f32>,
+) -> Vec<(&'a MediaManifest, u32)>
+where
+    I: IntoIterator<Item = &'a MediaManifest>,
+{
+    let Some(query_pdq) = query.pdq_hash.as_deref() else {
+        return Vec::new();
+    };
+
+    let mut matches: Vec<(&MediaManifest, u32)> = candidates
+        .into_iter()
+        .filter(|candidate| match query.check_comparable(candidate) {
+            Ok(()) => true,
+            Err(e) => {
+                log::warn!("Skipping {}: {}", candidate.file_name, e);
+                false
+            }
+        })
+        .filter(|candidate| {
+            match (max_color_difference, &query.color_signature, &candidate.color_signature) {
+                (Some(limit), Some(a), Some(b)) => a.average_distance(b) <= limit,
+                _ => true,
+            }
+        })
+        .filter(|candidate| {
+            match (min_histogram_similarity, &query.color_histogram, &candidate.color_histogram) {
+                (Some(limit), Some(a), Some(b)) => histogram_similarity(a, b) >= limit,
+                _ => true,
+            }
+        })
+        .filter_map(|candidate| {
+            let distance = pdq_distance(query_pdq, candidate.pdq_hash.as_deref()?).ok()?;
+            (distance <= max_distance).then_some((candidate, distance))
+        })
+        .collect();
+
+    matches.sort_by_key(|&(_, distance)| distance);
+    matches
+}
+
+/// Result of a pixel-level comparison between two images.
+#[derive(Debug, Clone)]
+pub struct DiffReport {
+    /// Fraction of pixels that differ, in the `0.0..=1.0` range.
+    pub changed_pixel_ratio: f32,
+    /// Smallest `(x, y, width, height)` box containing every changed pixel.
+    pub bounding_box: Option<(u32, u32, u32, u32)>,
+    /// Image where brighter red marks a larger difference.
+    pub heatmap: DynamicImage,
+}
+
+/// Compares two images pixel by pixel for forensic review.
+///
+/// Images of different sizes are compared after resizing the smaller one to
+/// the dimensions of the larger. A pixel counts as changed when any channel
+/// differs by more than a small tolerance, which absorbs encoder noise.
+pub fn diff_images(a: &DynamicImage, b: &DynamicImage) -> Result<DiffReport> {
+    let (a, b) = if a.dimensions() == b.dimensions() {
+        (a.to_rgb8(), b.to_rgb8())
+    } else {
+        let (aw, ah) = a.dimensions();
+        let (bw, bh) = b.dimensions();
+        if (aw as u64) * (ah as u64) >= (bw as u64) * (bh as u64) {
+            (a.to_rgb8(), b.resize_exact(aw, ah, FilterType::Triangle).to_rgb8())
+        } else {
+            (a.resize_exact(bw, bh, FilterType::Triangle).to_rgb8(), b.to_rgb8())
+        }
+    };
+
+    let (width, height) = a.dimensions();
+    if width == 0 || height == 0 {
+        return Err(anyhow::anyhow!("cannot diff empty images"));
+    }
+
+    let mut heatmap = RgbImage::new(width, height);
+    let mut changed = 0u64;
+    let mut bounds: Option<(u32, u32, u32, u32)> = None;
+
+    for (x, y, pa) in a.enumerate_pixels() {
+        let pb = b.get_pixel(x, y);
+        let delta = pa
+            .0
+            .iter()
+            .zip(pb.0.iter())
+            .map(|(&ca, &cb)| ca.abs_diff(cb))
+            .max()
+            .unwrap_or(0);
+        heatmap.put_pixel(x, y, Rgb([delta, 0, 0]));
+
+        if delta > DIFF_TOLERANCE {
+            changed += 1;
+            bounds = Some(match bounds {
+                None => (x, y, x, y),
+                Some((x0, y0, x1, y1)) => (x0.min(x), y0.min(y), x1.max(x), y1.max(y)),
+            });
+        }
+    }
+
+    Ok(DiffReport {
+        changed_pixel_ratio: changed as f32 / (width as u64 * height as u64) as f32,
+        bounding_box: bounds.map(|(x0, y0, x1, y1)| (x0, y0, x1 - x0 + 1, y1 - y0 + 1)),
+        heatmap: DynamicImage::ImageRgb8(heatmap),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::embeddings::EMBEDDING_DIM;
+    use crate::core::hash::compute_pdq_hash;
+    use crate::models::manifest::{FrameSource, MediaType};
+
+    /// Builds a distinct test pattern for each index
+    fn pattern_frame(index: u32) -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_fn(64, 64, |x, y| {
+            let v = match index {
+                0 => x * 4,
+                1 => y * 4,
+                2 => (x + y) * 2,
+                _ => if (x / 8 + y / 8) % 2 == 0 { 255 } else { 0 },
+            };
+            Rgb([v as u8; 3])
+        }))
+    }
+
+    fn video_manifest(frames: Vec<FrameInfo>) -> MediaManifest {
+        MediaManifest {
+            media_type: MediaType::Video,
+            file_name: "clip.mp4".to_string(),
+            relative_path: None,
+            file_size: 0,
+            created_at: chrono::Utc::now().to_rfc3339(),
+            modified_at: chrono::Utc::now().to_rfc3339(),
+            sha3_256_hash: "hash".to_string(),
+            pdq_hash: None,
+            perceptual_algorithm: None,
+            algorithm_versions: Default::default(),
+            pdq_format: None,
+            color_signature: None,
+            color_histogram: None,
+            content_hash: None,
+            stream_hash: None,
+            frame_source: Some(FrameSource::VideoFfmpeg),
+            frames_merkle_root: None,
+            frame_count: None,
+            frames: Some(frames),
+            metadata: serde_json::Value::Null,
+            derived_from: None,
+            embedding_dim: None,
+            embedding_model: None,
+            video_embedding: None,
+        }
+    }
+
+    #[test]
+    fn test_image_in_video_finds_known_frame() {
+        let frames = (0..4)
+            .map(|i| FrameInfo {
+                timestamp_secs: i as f64,
+                pdq_hash: compute_pdq_hash(&pattern_frame(i)).unwrap(),
+                embedding: None,
+            })
+            .collect();
+        let video = video_manifest(frames);
+
+        let query = compute_pdq_hash(&pattern_frame(2)).unwrap();
+        let found = image_in_video(&query, &video, 4).expect("frame should be found");
+
+        assert_eq!(found.timestamp_secs, 2.0);
+        assert!(pdq_distance(&query, &found.pdq_hash).unwrap() <= 4);
+    }
+
+    #[test]
+    fn test_image_in_video_respects_threshold() {
+        let frame = FrameInfo {
+            timestamp_secs: 0.0,
+            pdq_hash: "0".repeat(64),
+            embedding: None,
+        };
+        let video = video_manifest(vec![frame]);
+
+        let query = format!("{}{}", "1".repeat(8), "0".repeat(56));
+        assert!(image_in_video(&query, &video, 7).is_none());
+        assert!(image_in_video(&query, &video, 8).is_some());
+    }
+
+    #[test]
+    fn test_diff_images_bounds_changed_region() {
+        let a = RgbImage::from_pixel(100, 80, Rgb([40, 40, 40]));
+        let mut b = a.clone();
+        for y in 30..40 {
+            for x in 20..35 {
+                b.put_pixel(x, y, Rgb([220, 40, 40]));
+            }
+        }
+
+        let report = diff_images(&DynamicImage::ImageRgb8(a), &DynamicImage::ImageRgb8(b)).unwrap();
+
+        assert_eq!(report.bounding_box, Some((20, 30, 15, 10)));
+        assert!((report.changed_pixel_ratio - 150.0 / 8000.0).abs() < 1e-6);
+        assert_eq!(report.heatmap.dimensions(), (100, 80));
+    }
+
+    #[test]
+    fn test_diff_images_resizes_smaller_input() {
+        let a = DynamicImage::ImageRgb8(RgbImage::from_pixel(64, 64, Rgb([10, 10, 10])));
+        let b = DynamicImage::ImageRgb8(RgbImage::from_pixel(32, 32, Rgb([10, 10, 10])));
+
+        let report = diff_images(&a, &b).unwrap();
+
+        assert_eq!(report.heatmap.dimensions(), (64, 64));
+        assert_eq!(report.changed_pixel_ratio, 0.0);
+        assert!(report.bounding_box.is_none());
+    }
+
+    #[test]
+    fn test_color_prefilter_skips_different_colors() {
+        // Same structure, but the second image is heavily tinted
+        let texture = |tint: [u32; 3]| {
+            DynamicImage::ImageRgb8(RgbImage::from_fn(64, 64, |x, y| {
+                let cell = (x / 8) * 8 + (y / 8);
+                let v = (cell.wrapping_mul(2654435761) >> 24) % 100;
+                Rgb(tint.map(|t| (v + t) as u8))
+            }))
+        };
+        let image_manifest = |image: &DynamicImage, name: &str| {
+            let mut manifest = video_manifest(Vec::new());
+            manifest.media_type = MediaType::Image;
+            manifest.file_name = name.to_string();
+            manifest.frames = None;
+            manifest.pdq_hash = Some(compute_pdq_hash(image).unwrap());
+            manifest.color_signature = Some(ColorSignature::compute(image));
+            manifest
+        };
+
+        let query = image_manifest(&texture([20, 20, 20]), "query.png");
+        let same = image_manifest(&texture([25, 20, 20]), "same.png");
+        let tinted = image_manifest(&texture([150, 20, 150]), "tinted.png");
+        let candidates = [same, tinted];
+
+        // Perceptually both match; only the colour pre-filter tells them apart
+        let unfiltered = find_similar(&query, &candidates, 8, None, None);
+        assert_eq!(unfiltered.len(), 2);
+
+        let filtered = find_similar(&query, &candidates, 8, Some(DEFAULT_COLOR_PREFILTER), None);
+        let names: Vec<&str> = filtered.iter().map(|(m, _)| m.file_name.as_str()).collect();
+        assert_eq!(names, vec!["same.png"]);
+    }
+
+    #[test]
+    fn test_recolouring_drops_histogram_similarity_but_not_pdq() {
+        // The same disc on black, in green and in magenta of equal luminance
+        let disc = |colour: [u8; 3]| {
+            DynamicImage::ImageRgb8(RgbImage::from_fn(64, 64, |x, y| {
+                let (dx, dy) = (x as i32 - 24, y as i32 - 28);
+                if dx * dx + dy * dy < 300 { Rgb(colour) } else { Rgb([0, 0, 0]) }
+            }))
+        };
+        let green = disc([0, 150, 0]);
+        let magenta = disc([200, 0, 245]);
+
+        let pdq = |img: &DynamicImage| compute_pdq_hash(img).unwrap();
+        assert!(pdq_distance(&pdq(&green), &pdq(&magenta)).unwrap() <= 4);
+
+        let (a, b) = (
+            compute_color_histogram(&green, DEFAULT_HISTOGRAM_BINS),
+            compute_color_histogram(&magenta, DEFAULT_HISTOGRAM_BINS),
+        );
+        assert_eq!(a.len(), 3 * DEFAULT_HISTOGRAM_BINS);
+        assert!((histogram_similarity(&a, &a) - 1.0).abs() < 1e-6);
+        assert!(histogram_similarity(&a, &b) < 0.9);
+
+        let manifest = |img: &DynamicImage, name: &str| {
+            let mut manifest = video_manifest(Vec::new());
+            manifest.media_type = MediaType::Image;
+            manifest.file_name = name.to_string();
+            manifest.frames = None;
+            manifest.pdq_hash = Some(pdq(img));
+            manifest.color_histogram = Some(compute_color_histogram(img, DEFAULT_HISTOGRAM_BINS));
+            manifest
+        };
+        let query = manifest(&green, "green.png");
+        let candidates = [manifest(&green, "copy.png"), manifest(&magenta, "magenta.png")];
+        assert_eq!(find_similar(&query, &candidates, 8, None, None).len(), 2);
+        let names: Vec<&str> = find_similar(&query, &candidates, 8, None, Some(0.9))
+            .iter()
+            .map(|(m, _)| m.file_name.as_str())
+            .collect();
+        assert_eq!(names, vec!["copy.png"]);
+    }
+
+    #[test]
+    fn test_reversed_video_has_low_sequence_similarity() {
+        let mut state = 0x2545_F491_4F6C_DD1Du64;
+        let frames: Vec<FrameInfo> = (0..10)
+            .map(|i| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                FrameInfo { timestamp_secs: i as f64, pdq_hash: format!("{:016x}", state), embedding: None }
+            })
+            .collect();
+        let reversed: Vec<FrameInfo> = frames.iter().rev().cloned().collect();
+
+        let same = video_sequence_similarity(&frames, &frames, 8);
+        assert_eq!(same.matched, 10);
+        assert_eq!(same.similarity, 1.0);
+        assert_eq!(same.alignment[3], (3, 3));
+
+        let flipped = video_sequence_similarity(&frames, &reversed, 8);
+        assert_eq!(flipped.set_overlap, 1.0);
+        assert_eq!(flipped.matched, 1);
+        assert!(flipped.similarity <= 0.1);
+
+        // A trimmed copy still aligns in order
+        let trimmed = video_sequence_similarity(&frames, &frames[2..8], 8);
+        assert_eq!(trimmed.alignment, (2..8).map(|i| (i, i - 2)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_video_similarity_score_is_one_for_itself_and_near_zero_for_unrelated() {
+        let video = |seed: u64| {
+            let mut state = seed;
+            let frames = (0..8)
+                .map(|i| {
+                    let hash: String = (0..4)
+                        .map(|_| {
+                            state ^= state << 13;
+                            state ^= state >> 7;
+                            state ^= state << 17;
+                            format!("{:016x}", state)
+                        })
+                        .collect();
+                    FrameInfo { timestamp_secs: i as f64, pdq_hash: hash, embedding: None }
+                })
+                .collect();
+            video_manifest(frames)
+        };
+        let (a, b) = (video(0x2545_F491_4F6C_DD1D), video(0x9E37_79B9_7F4A_7C15));
+
+        assert_eq!(video_similarity_score(&a, &a), 1.0);
+        assert!(video_similarity_score(&a, &b) < 0.05);
+        assert_eq!(video_similarity_score(&a, &b), video_similarity_score(&b, &a));
+
+        // Shared frame embeddings lift an unrelated pair by the embedding weight only
+        let (mut a, mut b) = (a, b);
+        for frame in a.frames.iter_mut().chain(b.frames.iter_mut()).flatten() {
+            frame.embedding = Some(vec![0.5; 4]);
+        }
+        assert!((video_similarity_score(&a, &a) - 1.0).abs() < 1e-6);
+        let blended = video_similarity_score(&a, &b);
+        assert!((blended - VIDEO_SCORE_EMBEDDING_WEIGHT).abs() < 1e-6, "{}", blended);
+    }
+
+    #[test]
+    fn test_pooled_video_embedding_is_the_mean_of_its_frames() {
+        let frame = |i: usize, embedding: Option<Vec<f32>>| FrameInfo {
+            timestamp_secs: i as f64,
+            pdq_hash: "0".repeat(64),
+            embedding,
+        };
+        let first: Vec<f32> = (0..EMBEDDING_DIM).map(|i| i as f32).collect();
+        let second: Vec<f32> = (0..EMBEDDING_DIM).map(|i| 3.0 * i as f32 + 2.0).collect();
+        let frames = vec![
+            frame(0, Some(first)),
+            frame(1, None),
+            frame(2, Some(second)),
+            frame(3, Some(vec![1.0; 8])),
+        ];
+
+        let pooled = pool_frame_embeddings(&frames).unwrap();
+        assert_eq!(pooled.len(), EMBEDDING_DIM);
+        assert!(pooled.iter().enumerate().all(|(i, &x)| x == 2.0 * i as f32 + 1.0));
+
+        assert_eq!(pool_frame_embeddings(&frames[1..2]), None);
+    }
+
+    #[test]
+    fn test_color_signature() {
+        let image = DynamicImage::ImageRgb8(RgbImage::from_fn(40, 40, |x, _| {
+            if x < 20 { Rgb([200, 0, 0]) } else { Rgb([0, 0, 200]) }
+        }));
+        let signature = ColorSignature::compute(&image);
+
+        assert_eq!(signature.average, [100, 0, 100]);
+        assert_eq!(signature.grid.len(), 16);
+        assert!(signature.grid[0][0] > 150 && signature.grid[3][2] > 150);
+    }
+
+    #[test]
+    fn test_embedding_dimension_mismatch_is_an_error() {
+        let a = vec![0.5f32; 512];
+        let b = vec![0.5f32; 256];
+        let err = embedding_similarity(&a, &b).unwrap_err();
+        assert_eq!(err.to_string(), "embedding dimension mismatch: 512 vs 256");
+
+        let same = embedding_similarity(&a, &a).unwrap().unwrap();
+        assert!((same - 1.0).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,355 @@
+use std::collections::BTreeSet;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use log::warn;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::core::index::BkTree;
+use crate::error::{AppError, Result};
+use crate::models::manifest::{MediaManifest, VerificationReport};
+
+/// Persists manifests, keyed by the stored file name they describe
+pub trait ManifestStore {
+    /// Every manifest in the store
+    fn manifests(&self) -> Result<Vec<MediaManifest>>;
+
+    /// Stores `manifest`, replacing any manifest for the same file name
+    fn save(&self, manifest: &MediaManifest) -> Result<()>;
+
+    /// Deletes the manifest for `file_name`; a missing manifest is not an error
+    fn remove(&self, file_name: &str) -> Result<()>;
+}
+
+/// File in a [`DirManifestStore`]'s directory holding the saved PDQ index
+pub const PDQ_INDEX_FILE: &str = ".pdq-index.bin";
+
+/// File in a [`DirManifestStore`]'s directory holding the verification audit log
+pub const AUDIT_LOG_FILE: &str = ".audit.jsonl";
+
+/// How a verification recorded in the audit log ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditOutcome {
+    /// The file matched its manifest
+    Valid,
+    /// The file did not match its manifest
+    Invalid,
+    /// Verification could not be carried out
+    Error,
+}
+
+/// One verification, as recorded in the audit log
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct AuditEntry {
+    /// When the verification ran, in RFC 3339
+    pub timestamp: String,
+    /// Stored file name of the verified manifest
+    pub manifest_id: String,
+    /// Path of the file verified against the manifest
+    pub file_path: String,
+    /// How verification ended
+    pub outcome: AuditOutcome,
+    /// The verification report, unless verification failed with an error
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub report: Option<VerificationReport>,
+    /// Why verification could not be carried out, for errors
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl AuditEntry {
+    /// An entry for verifying `manifest` against `file_path` now, ending in `result`
+    pub fn new(
+        manifest: &MediaManifest,
+        file_path: &Path,
+        result: std::result::Result<&VerificationReport, &AppError>,
+    ) -> Self {
+        let (outcome, report, error) = match result {
+            Ok(report) if report.is_valid => (AuditOutcome::Valid, Some(report.clone()), None),
+            Ok(report) => (AuditOutcome::Invalid, Some(report.clone()), None),
+            Err(e) => (AuditOutcome::Error, None, Some(e.to_string())),
+        };
+        Self {
+            timestamp: crate::utils::now_timestamp(),
+            manifest_id: manifest.file_name.clone(),
+            file_path: file_path.display().to_string(),
+            outcome,
+            report,
+            error,
+        }
+    }
+}
+
+/// A [`ManifestStore`] keeping each manifest as `<file_name>.json` in a directory
+#[derive(Debug, Clone)]
+pub struct DirManifestStore {
+    dir: PathBuf,
+}
+
+impl DirManifestStore {
+    /// Creates a store over `dir`, which is created on the first save
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    fn path_for(&self, file_name: &str) -> Result<PathBuf> {
+        let path = self.dir.join(format!("{}.json", file_name));
+        if path.parent() != Some(self.dir.as_path()) {
+            return Err(AppError::Validation(format!("invalid file name: {:?}", file_name)));
+        }
+        Ok(path)
+    }
+
+    /// Saves the PDQ index next to the manifests, replacing the previous one atomically
+    pub fn save_index(&self, index: &BkTree<String>) -> Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        let temp = self.dir.join(format!("{}.tmp", PDQ_INDEX_FILE));
+        index.save(BufWriter::new(File::create(&temp)?))?;
+        std::fs::rename(&temp, self.dir.join(PDQ_INDEX_FILE))?;
+        Ok(())
+    }
+
+    /// Loads the PDQ index saved by [`DirManifestStore::save_index`], or `None` if there is none
+    ///
+    /// The index may predate later changes to the manifests; see
+    /// `AppState::restore_pdq_index` for checking it against them.
+    pub fn load_index(&self) -> Result<Option<BkTree<String>>> {
+        let file = match File::open(self.dir.join(PDQ_INDEX_FILE)) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Some(BkTree::load(BufReader::new(file))?))
+    }
+
+    /// Appends `entry` to the audit log, one JSON document per line
+    ///
+    /// The log is only ever appended to; each entry is written with a single
+    /// write, so concurrent verifications don't interleave their lines.
+    pub fn append_audit(&self, entry: &AuditEntry) -> Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        let mut line = serde_json::to_vec(entry)?;
+        line.push(b'\n');
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.dir.join(AUDIT_LOG_FILE))?
+            .write_all(&line)?;
+        Ok(())
+    }
+
+    /// Audit log entries, oldest first, only those for `manifest_id` when given
+    ///
+    /// The log is read line by line, skipping the first `offset` matching
+    /// entries and stopping after `limit` more, so only one page is ever held
+    /// in memory. Lines that don't parse are logged and skipped; a missing
+    /// log holds no entries.
+    pub fn audit_entries(&self, manifest_id: Option<&str>, offset: usize, limit: usize) -> Result<Vec<AuditEntry>> {
+        let path = self.dir.join(AUDIT_LOG_FILE);
+        let file = match File::open(&path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(AppError::from_io_at(e, &path)),
+        };
+        let mut entries = Vec::new();
+        let mut skipped = 0;
+        for (number, line) in BufReader::new(file).lines().enumerate() {
+            if entries.len() == limit {
+                break;
+            }
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str::<AuditEntry>(&line) {
+                Ok(entry) if manifest_id.is_none_or(|id| entry.manifest_id == id) => {
+                    if skipped < offset {
+                        skipped += 1;
+                    } else {
+                        entries.push(entry);
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => warn!("Skipping unreadable audit entry {} of {}: {}", number + 1, path.display(), e),
+            }
+        }
+        Ok(entries)
+    }
+}
+
+impl ManifestStore for DirManifestStore {
+    /// Loads every `*.json` file in the directory; files that don't parse are
+    /// logged and skipped, and a missing directory holds no manifests.
+    fn manifests(&self) -> Result<Vec<MediaManifest>> {
+        let entries = match std::fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(AppError::from_io_at(e, &self.dir)),
+        };
+
+        let mut paths: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == "json"))
+            .collect();
+        paths.sort();
+
+        Ok(paths
+            .into_iter()
+            .filter_map(|path| match MediaManifest::load(&path) {
+                Ok(manifest) => Some(manifest),
+                Err(e) => {
+                    warn!("Skipping unreadable manifest {}: {}", path.display(), e);
+                    None
+                }
+            })
+            .collect())
+    }
+
+    fn save(&self, manifest: &MediaManifest) -> Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        manifest.save(self.path_for(&manifest.file_name)?)
+    }
+
+    fn remove(&self, file_name: &str) -> Result<()> {
+        match std::fs::remove_file(self.path_for(file_name)?) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// What to do with orphans found by [`reconcile`] at startup
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OrphanPolicy {
+    /// Report orphans in the log and leave them in place.
+    #[default]
+    Log,
+    /// Delete orphaned files and manifests.
+    Delete,
+}
+
+/// Drift between a [`ManifestStore`] and the upload directory
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReconcileReport {
+    /// Uploaded files no manifest describes, by file name
+    pub orphan_files: Vec<String>,
+    /// Manifests whose file is missing, by the file name they describe
+    pub orphan_manifests: Vec<String>,
+}
+
+impl ReconcileReport {
+    /// Whether the store and the upload directory agree
+    pub fn is_clean(&self) -> bool {
+        self.orphan_files.is_empty() && self.orphan_manifests.is_empty()
+    }
+
+    /// Deletes the orphaned files from `upload_dir` and the orphaned manifests from `store`
+    pub fn delete_orphans(&self, store: &dyn ManifestStore, upload_dir: &Path) -> Result<()> {
+        for file_name in &self.orphan_files {
+            std::fs::remove_file(upload_dir.join(file_name))?;
+        }
+        for file_name in &self.orphan_manifests {
+            store.remove(file_name)?;
+        }
+        Ok(())
+    }
+}
+
+/// Finds uploads without a manifest and manifests without an upload
+///
+/// Only regular files directly inside `upload_dir` count as uploads; hidden
+/// files (such as in-progress temp files) are ignored. A manifest's file is
+/// looked up by its `relative_path`, falling back to its `file_name`. Both
+/// lists are sorted. Nothing is changed; see [`ReconcileReport::delete_orphans`].
+pub fn reconcile(store: &dyn ManifestStore, upload_dir: &Path) -> Result<ReconcileReport> {
+    let manifests = store.manifests()?;
+    let described: BTreeSet<&str> = manifests
+        .iter()
+        .map(|m| m.relative_path.as_deref().unwrap_or(&m.file_name))
+        .collect();
+
+    let mut orphan_files = Vec::new();
+    if upload_dir.is_dir() {
+        for entry in std::fs::read_dir(upload_dir)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if entry.file_type()?.is_file() && !name.starts_with('.') && !described.contains(name.as_str()) {
+                orphan_files.push(name);
+            }
+        }
+    }
+    orphan_files.sort();
+
+    let orphan_manifests = manifests
+        .iter()
+        .filter(|m| !upload_dir.join(m.relative_path.as_deref().unwrap_or(&m.file_name)).is_file())
+        .map(|m| m.file_name.clone())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+
+    Ok(ReconcileReport { orphan_files, orphan_manifests })
+}
+
+/// Runs [`reconcile`], logs every orphan and deletes them if `policy` says so
+pub fn reconcile_with_policy(
+    store: &dyn ManifestStore,
+    upload_dir: &Path,
+    policy: OrphanPolicy,
+) -> Result<ReconcileReport> {
+    let report = reconcile(store, upload_dir)?;
+    for file_name in &report.orphan_files {
+        warn!("Upload {} has no manifest", file_name);
+    }
+    for file_name in &report.orphan_manifests {
+        warn!("Manifest for {} has no uploaded file", file_name);
+    }
+    if policy == OrphanPolicy::Delete && !report.is_clean() {
+        report.delete_orphans(store, upload_dir)?;
+        warn!(
+            "Deleted {} orphaned uploads and {} orphaned manifests",
+            report.orphan_files.len(),
+            report.orphan_manifests.len()
+        );
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::manifest::MediaType;
+
+    #[test]
+    fn test_reconcile_finds_orphan_file_and_orphan_manifest() {
+        let uploads = tempfile::tempdir().unwrap();
+        let manifests = tempfile::tempdir().unwrap();
+        let store = DirManifestStore::new(manifests.path());
+        let manifest_for = |name: &str| {
+            let path = uploads.path().join(name);
+            std::fs::write(&path, name.as_bytes()).unwrap();
+            let hash = crate::core::hash::compute_file_hash(&path).unwrap();
+            MediaManifest::new(name.to_string(), &path, MediaType::Other, hash, None, None, None).unwrap()
+        };
+
+        store.save(&manifest_for("kept.bin")).unwrap();
+        store.save(&manifest_for("lost.bin")).unwrap();
+        std::fs::remove_file(uploads.path().join("lost.bin")).unwrap();
+        std::fs::write(uploads.path().join("stray.bin"), b"no manifest").unwrap();
+        std::fs::write(uploads.path().join(".upload.tmp"), b"in progress").unwrap();
+
+        let report = reconcile_with_policy(&store, uploads.path(), OrphanPolicy::Log).unwrap();
+        assert_eq!(report.orphan_files, vec!["stray.bin"]);
+        assert_eq!(report.orphan_manifests, vec!["lost.bin"]);
+        assert!(uploads.path().join("stray.bin").exists());
+
+        reconcile_with_policy(&store, uploads.path(), OrphanPolicy::Delete).unwrap();
+        assert!(reconcile(&store, uploads.path()).unwrap().is_clean());
+        assert!(!uploads.path().join("stray.bin").exists());
+        assert_eq!(store.manifests().unwrap().len(), 1);
+    }
+}
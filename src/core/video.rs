@@ -1,6 +1,6 @@
 use anyhow::Result;
 use image::DynamicImage;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 
 
@@ -30,12 +30,110 @@ pub fn init_ffmpeg() -> Result<()> {
     Ok(())
 }
 
+/// Which frames a [`FrameExtractor`] pulls from the video
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum FrameSelection {
+    /// One frame every `interval_secs`, decoded and resampled by ffmpeg.
+    #[default]
+    Interval,
+    /// Only the container's intra-coded keyframes, at their own timestamps.
+    ///
+    /// Non-key frames are skipped before decoding, so this is much faster than
+    /// interval extraction on long videos, and every frame is exactly seekable.
+    KeyframesOnly,
+    /// One frame at each start time listed in an EDL or chapter file (see [`parse_edl`]).
+    ///
+    /// Frames are tagged with the listed times. Each frame is found by
+    /// seeking, so this needs a file rather than a stream.
+    FromEdl(PathBuf),
+}
+
+/// Longest video, in seconds, processed unless configured otherwise
+pub const DEFAULT_MAX_VIDEO_DURATION_SECS: f64 = 4.0 * 60.0 * 60.0;
+
+/// Most frames extracted from one video unless configured otherwise
+pub const DEFAULT_MAX_TOTAL_FRAMES: usize = 10_000;
+
+/// Limits on how much of a video one extraction may process
+///
+/// Checked twice: against the probed duration and expected frame count before
+/// ffmpeg runs, and as a hard stop while frames are handed out, in case the
+/// probe underestimated.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct FrameBudget {
+    /// Longest video accepted, in seconds; unlimited when unset
+    pub max_duration_secs: Option<f64>,
+    /// Most frames one extraction may yield; unlimited when unset
+    pub max_frames: Option<usize>,
+}
+
+/// Why a video was refused by its [`FrameBudget`]
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum BudgetExceeded {
+    /// The video runs longer than `max_duration_secs`
+    #[error("video is {duration_secs:.1}s long, over the limit of {max_duration_secs}s")]
+    Duration {
+        /// Probed duration of the video
+        duration_secs: f64,
+        /// The budget's limit
+        max_duration_secs: f64,
+    },
+    /// The video yields more frames than `max_frames`
+    #[error("video yields more than {max_frames} frames (at least {frames})")]
+    Frames {
+        /// Frames expected or extracted so far
+        frames: usize,
+        /// The budget's limit
+        max_frames: usize,
+    },
+}
+
+impl FrameBudget {
+    /// [`DEFAULT_MAX_VIDEO_DURATION_SECS`] and [`DEFAULT_MAX_TOTAL_FRAMES`],
+    /// which `process_video_file` applies to files processed outside uploads
+    pub const DEFAULT_LIMITS: FrameBudget = FrameBudget {
+        max_duration_secs: Some(DEFAULT_MAX_VIDEO_DURATION_SECS),
+        max_frames: Some(DEFAULT_MAX_TOTAL_FRAMES),
+    };
+
+    /// Whether the budget limits anything at all
+    pub fn is_unlimited(&self) -> bool {
+        self.max_duration_secs.is_none() && self.max_frames.is_none()
+    }
+
+    /// Checks a video's probed duration, when known, and expected frame count
+    /// against the budget, before anything is extracted
+    pub fn check(&self, duration_secs: Option<f64>, frames: usize) -> std::result::Result<(), BudgetExceeded> {
+        if let (Some(duration_secs), Some(max_duration_secs)) = (duration_secs, self.max_duration_secs) {
+            if duration_secs > max_duration_secs {
+                return Err(BudgetExceeded::Duration { duration_secs, max_duration_secs });
+            }
+        }
+        self.admit(frames)
+    }
+
+    /// Checks that `extracted` frames are within the frame limit
+    pub fn admit(&self, extracted: usize) -> std::result::Result<(), BudgetExceeded> {
+        match self.max_frames {
+            Some(max_frames) if extracted > max_frames => {
+                Err(BudgetExceeded::Frames { frames: extracted, max_frames })
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
 /// Extracts frames from a video at specified intervals
 #[derive(Debug)]
 #[allow(dead_code)]
 pub struct FrameExtractor {
     input_path: String,
     interval_secs: f64,
+    video_stream_index: Option<usize>,
+    target_size: Option<(u32, u32)>,
+    selection: FrameSelection,
+    budget: FrameBudget,
+    min_entropy: Option<f32>,
 }
 
 impl FrameExtractor {
@@ -49,47 +147,250 @@ impl FrameExtractor {
         Self {
             input_path: input_path.as_ref().to_string_lossy().into_owned(),
             interval_secs,
+            video_stream_index: None,
+            target_size: None,
+            selection: FrameSelection::Interval,
+            budget: FrameBudget::default(),
+            min_entropy: None,
+        }
+    }
+
+    /// Selects which video stream to extract frames from.
+    ///
+    /// The index counts video streams only (as in ffmpeg's `0:v:N`), so files
+    /// carrying e.g. a cover-art stream next to the main stream can be pinned
+    /// to the right one. Defaults to the first video stream.
+    pub fn with_video_stream(mut self, index: usize) -> Self {
+        self.video_stream_index = Some(index);
+        self
+    }
+
+    /// Returns the index of the video stream frames are extracted from.
+    pub fn video_stream_index(&self) -> usize {
+        self.video_stream_index.unwrap_or(0)
+    }
+
+    /// Emits frames at exactly `width`x`height`.
+    ///
+    /// Scaling happens inside ffmpeg's filter graph in the same pass as frame
+    /// selection, keeping the aspect ratio and padding the remainder with
+    /// black, so no Rust-side resize is needed afterwards.
+    pub fn with_target_size(mut self, width: u32, height: u32) -> Self {
+        self.target_size = Some((width, height));
+        self
+    }
+
+    /// Returns the size frames are scaled to, if any.
+    pub fn target_size(&self) -> Option<(u32, u32)> {
+        self.target_size
+    }
+
+    /// Chooses which frames to extract; `interval_secs` only applies to
+    /// [`FrameSelection::Interval`].
+    pub fn with_selection(mut self, selection: FrameSelection) -> Self {
+        self.selection = selection;
+        self
+    }
+
+    /// Returns which frames are extracted.
+    pub fn selection(&self) -> &FrameSelection {
+        &self.selection
+    }
+
+    /// Refuses videos over `budget`, failing with [`BudgetExceeded`].
+    ///
+    /// File inputs are probed before extraction starts; streamed input can't
+    /// be probed, so it is only stopped once it yields too many frames.
+    pub fn with_budget(mut self, budget: FrameBudget) -> Self {
+        self.budget = budget;
+        self
+    }
+
+    /// Returns the limits extraction is held to.
+    pub fn budget(&self) -> FrameBudget {
+        self.budget
+    }
+
+    /// Drops frames whose [`frame_entropy`] is below `min_entropy` bits.
+    ///
+    /// Solid black or otherwise flat frames (intros, outros, fades) carry no
+    /// content and all hash alike; dropped frames are never handed to the
+    /// callback, and the remaining frames keep their own timestamps.
+    pub fn with_min_entropy(mut self, min_entropy: f32) -> Self {
+        self.min_entropy = Some(min_entropy);
+        self
+    }
+
+    /// Returns the entropy below which frames are dropped, if any.
+    pub fn min_entropy(&self) -> Option<f32> {
+        self.min_entropy
+    }
+
+    /// Whether `frame` passes the [`min_entropy`](Self::with_min_entropy) gate.
+    #[cfg_attr(not(feature = "video"), allow(dead_code))]
+    fn keeps(&self, frame: &DynamicImage) -> bool {
+        self.min_entropy.is_none_or(|min| frame_entropy(frame) >= min)
+    }
+
+    /// Interval between extracted frames, falling back to one second.
+    #[cfg_attr(not(feature = "video"), allow(dead_code))]
+    fn interval(&self) -> f64 {
+        if self.interval_secs > 0.0 { self.interval_secs } else { 1.0 }
+    }
+
+    /// Builds the `-vf` filter graph: frame selection, then optional scaling.
+    ///
+    /// Keyframes are selected by the decoder (`-skip_frame nokey`), so their
+    /// graph starts with `showinfo` instead, which logs each frame's timestamp.
+    /// EDL frames are selected by seeking and pass through unchanged.
+    #[cfg_attr(not(feature = "video"), allow(dead_code))]
+    fn filter_graph(&self) -> String {
+        let select = match self.selection {
+            FrameSelection::Interval => format!("fps={}", 1.0 / self.interval()),
+            FrameSelection::KeyframesOnly => "showinfo".to_string(),
+            FrameSelection::FromEdl(_) => "null".to_string(),
+        };
+        match self.target_size {
+            Some((w, h)) => format!(
+                "{select},scale={w}:{h}:force_original_aspect_ratio=decrease,pad={w}:{h}:(ow-iw)/2:(oh-ih)/2,setsar=1"
+            ),
+            None => select,
         }
     }
 
     #[cfg(feature = "video")]
     /// Extracts frames from the video at the specified interval
-    pub fn extract_frames<F>(&self, mut callback: F) -> Result<()>
+    pub fn extract_frames<F>(&self, callback: F) -> Result<()>
     where
         F: FnMut(DynamicImage, f64) -> Result<()>,
+    {
+        // Check if input file exists
+        if !Path::new(&self.input_path).exists() {
+            return Err(anyhow::anyhow!("Input file not found: {}", self.input_path));
+        }
+        self.check_budget()?;
+
+        self.extract_from(None::<std::io::Empty>, callback)
+    }
+
+    #[cfg(feature = "video")]
+    /// Checks the probed duration and expected frame count of `input_path` against the [`FrameBudget`]
+    ///
+    /// [`extract_frames`](Self::extract_frames) does this before decoding;
+    /// callers of [`extract_frames_from_reader`](Self::extract_frames_from_reader)
+    /// can do it once the whole file is available.
+    pub fn check_budget(&self) -> Result<()> {
+        if self.budget.is_unlimited() {
+            return Ok(());
+        }
+        // Some containers (such as WebM) record no duration; it is then
+        // unknown, and only the frame limit applies as frames come out
+        let duration = match self.selection {
+            FrameSelection::Interval | FrameSelection::KeyframesOnly => self.duration_secs().ok(),
+            FrameSelection::FromEdl(_) => None,
+        };
+        let expected = match (&self.selection, duration) {
+            (FrameSelection::Interval, None) => 0,
+            _ => self.expected_frame_count()?,
+        };
+        Ok(self.budget.check(duration, expected)?)
+    }
+
+    #[cfg(feature = "video")]
+    /// Extracts frames from `reader` instead of `input_path`, piping it to ffmpeg's stdin
+    ///
+    /// The input is read strictly front to back, so containers that need
+    /// seeking to be parsed (such as MP4 files with the index at the end) fail
+    /// with an error rather than yielding frames, as does interval extraction
+    /// from a stream ffmpeg reports errors reading.
+    pub fn extract_frames_from_reader<R, F>(&self, reader: R, callback: F) -> Result<()>
+    where
+        R: std::io::Read,
+        F: FnMut(DynamicImage, f64) -> Result<()>,
+    {
+        self.extract_from(Some(reader), callback)
+    }
+
+    /// Runs ffmpeg on `input_path`, or on `stdin` when given, and hands each frame to `callback`
+    #[cfg(feature = "video")]
+    fn extract_from<R, F>(&self, stdin: Option<R>, mut callback: F) -> Result<()>
+    where
+        R: std::io::Read,
+        F: FnMut(DynamicImage, f64) -> Result<()>,
     {
         // Check if FFmpeg is installed
         check_ffmpeg_installed()?;
         init_ffmpeg()?;
 
-        // Check if input file exists
-        if !Path::new(&self.input_path).exists() {
-            return Err(anyhow::anyhow!("Input file not found: {}", self.input_path));
+        if let FrameSelection::FromEdl(edl) = &self.selection {
+            if stdin.is_some() {
+                return Err(anyhow::anyhow!("EDL frame selection seeks, so it needs a file rather than a stream"));
+            }
+            return self.extract_at(&read_edl(edl)?, callback);
         }
 
         // Create a temporary directory for extracted frames
         let tmpdir = tempfile::tempdir()?;
         let out_pattern = tmpdir.path().join("frame_%05d.png");
 
-        // Compute fps filter string (frames per second)
-        let interval = if self.interval_secs > 0.0 { self.interval_secs } else { 1.0 };
-        let fps = 1.0 / interval;
-        let vf_filter = format!("fps={}", fps);
+        let interval = self.interval();
+        let vf_filter = self.filter_graph();
 
         // Run ffmpeg to extract frames
-        let status = Command::new("ffmpeg")
-            .arg("-hide_banner")
-            .arg("-loglevel").arg("error")
-            .arg("-i").arg(&self.input_path)
+        let mut command = Command::new("ffmpeg");
+        command.arg("-hide_banner");
+        match self.selection {
+            FrameSelection::Interval | FrameSelection::FromEdl(_) => {
+                command.arg("-loglevel").arg("error");
+            }
+            FrameSelection::KeyframesOnly => {
+                // `showinfo` logs at info level; the decoder drops non-key frames
+                command.arg("-loglevel").arg("info").arg("-skip_frame").arg("nokey");
+            }
+        }
+        let vsync = match self.selection {
+            FrameSelection::Interval | FrameSelection::FromEdl(_) => "vfr",
+            FrameSelection::KeyframesOnly => "passthrough",
+        };
+        let input = if stdin.is_some() { "pipe:0" } else { self.input_path.as_str() };
+        command
+            .arg("-i").arg(input)
+            .arg("-map").arg(format!("0:v:{}", self.video_stream_index()))
             .arg("-vf").arg(vf_filter)
-            .arg("-vsync").arg("vfr")
-            .arg(out_pattern.to_string_lossy().to_string())
-            .status()
-            .map_err(|e| anyhow::anyhow!("Failed to spawn ffmpeg: {}", e))?;
+            .arg("-vsync").arg(vsync);
+        if let Some(max_frames) = self.budget.max_frames {
+            // One frame past the budget is enough to tell it was exceeded
+            command.arg("-frames:v").arg((max_frames + 1).to_string());
+        }
+        command
+            .arg(out_pattern.to_string_lossy().to_string());
+
+        let (success, log) = match stdin {
+            None => {
+                let output = command
+                    .output()
+                    .map_err(|e| anyhow::anyhow!("Failed to spawn ffmpeg: {}", e))?;
+                (output.status.success(), String::from_utf8_lossy(&output.stderr).into_owned())
+            }
+            Some(reader) => {
+                let (success, log) = pipe_to_ffmpeg(&mut command, reader)?;
+                // Unreadable parts of a stream are logged as errors, but need
+                // not fail ffmpeg, which then yields too few frames
+                let logs_errors_only = matches!(self.selection, FrameSelection::Interval);
+                if success && logs_errors_only && !log.trim().is_empty() {
+                    return Err(anyhow::anyhow!("ffmpeg could not read the whole stream: {}", log.trim()));
+                }
+                (success, log)
+            }
+        };
 
-        if !status.success() {
+        if !success {
             return Err(anyhow::anyhow!("ffmpeg failed to extract frames"));
         }
+        let keyframe_times = match self.selection {
+            FrameSelection::Interval | FrameSelection::FromEdl(_) => None,
+            FrameSelection::KeyframesOnly => Some(showinfo_timestamps(&log)),
+        };
 
         // Read extracted frames, sorted by name
         let mut entries: Vec<_> = std::fs::read_dir(tmpdir.path())?
@@ -100,14 +401,92 @@ impl FrameExtractor {
         entries.sort();
 
         for (i, frame_path) in entries.iter().enumerate() {
+            self.budget.admit(i + 1)?;
             let img = image::open(frame_path)?;
-            let timestamp = (i as f64) * interval;
-            callback(img, timestamp)?;
+            let timestamp = match &keyframe_times {
+                Some(times) => times.get(i).copied().ok_or_else(|| {
+                    anyhow::anyhow!("ffmpeg reported no timestamp for keyframe {}", i)
+                })?,
+                None => (i as f64) * interval,
+            };
+            if self.keeps(&img) {
+                callback(img, timestamp)?;
+            }
         }
 
         Ok(())
     }
 
+    /// Extracts one frame at each of `times`, seeking to each in turn
+    #[cfg(feature = "video")]
+    fn extract_at<F>(&self, times: &[f64], mut callback: F) -> Result<()>
+    where
+        F: FnMut(DynamicImage, f64) -> Result<()>,
+    {
+        let tmpdir = tempfile::tempdir()?;
+        for (i, &time) in times.iter().enumerate() {
+            self.budget.admit(i + 1)?;
+            let frame_path = tmpdir.path().join(format!("frame_{:05}.png", i));
+            let output = Command::new("ffmpeg")
+                .args(["-hide_banner", "-loglevel", "error"])
+                .arg("-ss").arg(time.to_string())
+                .arg("-i").arg(&self.input_path)
+                .arg("-map").arg(format!("0:v:{}", self.video_stream_index()))
+                .arg("-vf").arg(self.filter_graph())
+                .arg("-frames:v").arg("1")
+                .arg(&frame_path)
+                .output()
+                .map_err(|e| anyhow::anyhow!("Failed to spawn ffmpeg: {}", e))?;
+
+            // Seeking past the end succeeds without writing a frame
+            if !output.status.success() || !frame_path.is_file() {
+                return Err(anyhow::anyhow!("ffmpeg could not extract a frame at {}s", time));
+            }
+            let img = image::open(&frame_path)?;
+            if self.keeps(&img) {
+                callback(img, time)?;
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "video")]
+    /// Estimates how many frames [`extract_frames`](Self::extract_frames) will yield, without decoding
+    ///
+    /// For [`FrameSelection::Interval`] the probed duration is divided by the
+    /// interval, which matches extraction to within one frame. For
+    /// [`FrameSelection::KeyframesOnly`] the keyframe packets are counted,
+    /// which is exact unless a keyframe fails to decode. Extraction checks its
+    /// [`FrameBudget`] against this estimate before decoding anything.
+    pub fn expected_frame_count(&self) -> Result<usize> {
+        let stream = format!("v:{}", self.video_stream_index());
+        match &self.selection {
+            FrameSelection::Interval => Ok((self.duration_secs()? / self.interval()).round().max(1.0) as usize),
+            FrameSelection::KeyframesOnly => {
+                let output = ffprobe(&["-select_streams", stream.as_str(), "-show_entries", "packet=flags"], &self.input_path)?;
+                Ok(output.lines().filter(|flags| flags.starts_with('K')).count())
+            }
+            FrameSelection::FromEdl(edl) => Ok(read_edl(edl)?.len()),
+        }
+    }
+
+    #[cfg(feature = "video")]
+    /// Probes the duration of the video, in seconds, without decoding
+    pub fn duration_secs(&self) -> Result<f64> {
+        let stream = format!("v:{}", self.video_stream_index());
+        let output = ffprobe(&["-select_streams", stream.as_str(), "-show_entries", "format=duration"], &self.input_path)?;
+        output
+            .trim()
+            .parse()
+            .map_err(|_| anyhow::anyhow!("ffprobe reported no duration for {}", self.input_path))
+    }
+
+    #[cfg(not(feature = "video"))]
+    /// Estimates how many frames extraction will yield (placeholder)
+    pub fn expected_frame_count(&self) -> Result<usize> {
+        Err(anyhow::anyhow!("Video processing not available - enable 'video' feature"))
+    }
+
     #[cfg(not(feature = "video"))]
     /// Extracts frames from the video at the specified interval (placeholder)
     pub fn extract_frames<F>(&self, mut _callback: F) -> Result<()>
@@ -116,6 +495,142 @@ impl FrameExtractor {
     {
         Err(anyhow::anyhow!("Video processing not available - enable 'video' feature"))
     }
+
+    #[cfg(not(feature = "video"))]
+    /// Extracts frames from `reader` instead of `input_path` (placeholder)
+    pub fn extract_frames_from_reader<R, F>(&self, _reader: R, mut _callback: F) -> Result<()>
+    where
+        R: std::io::Read,
+        F: FnMut(DynamicImage, f64) -> Result<()>,
+    {
+        Err(anyhow::anyhow!("Video processing not available - enable 'video' feature"))
+    }
+
+    #[cfg(not(feature = "video"))]
+    /// Checks `input_path` against the frame budget (placeholder)
+    pub fn check_budget(&self) -> Result<()> {
+        Err(anyhow::anyhow!("Video processing not available - enable 'video' feature"))
+    }
+}
+
+/// Runs `command` with `reader` copied to its stdin, returning whether it succeeded and its stderr
+///
+/// Stderr is drained on a separate thread so a chatty ffmpeg cannot block
+/// while the input is still being written. ffmpeg closing its stdin early is
+/// not an error by itself; its exit status decides.
+#[cfg(feature = "video")]
+fn pipe_to_ffmpeg<R: std::io::Read>(command: &mut Command, mut reader: R) -> Result<(bool, String)> {
+    use std::io::Read;
+    use std::process::Stdio;
+
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| anyhow::anyhow!("Failed to spawn ffmpeg: {}", e))?;
+
+    let mut stderr = child.stderr.take().ok_or_else(|| anyhow::anyhow!("ffmpeg stderr not captured"))?;
+    let log = std::thread::spawn(move || {
+        let mut log = String::new();
+        let _ = stderr.read_to_string(&mut log);
+        log
+    });
+
+    let mut stdin = child.stdin.take().ok_or_else(|| anyhow::anyhow!("ffmpeg stdin not captured"))?;
+    let copied = std::io::copy(&mut reader, &mut stdin);
+    drop(stdin);
+    let status = child.wait()?;
+    let log = log.join().map_err(|_| anyhow::anyhow!("ffmpeg log reader panicked"))?;
+
+    match copied {
+        Err(e) if e.kind() != std::io::ErrorKind::BrokenPipe => Err(e.into()),
+        _ => Ok((status.success(), log)),
+    }
+}
+
+/// Runs ffprobe on `input` with `args`, returning its CSV output
+#[cfg(feature = "video")]
+fn ffprobe(args: &[&str], input: &str) -> Result<String> {
+    let output = Command::new("ffprobe")
+        .args(["-v", "error"])
+        .args(args)
+        .args(["-of", "csv=p=0"])
+        .arg(input)
+        .output()
+        .map_err(|e| anyhow::anyhow!("Failed to spawn ffprobe: {}", e))?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!("ffprobe failed on {}", input));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Estimates how many frames extracting `path` with `selection` will yield
+///
+/// `interval_secs` only applies to [`FrameSelection::Interval`]. A thin
+/// wrapper over [`FrameExtractor::expected_frame_count`], which also honours
+/// the video stream index.
+pub fn expected_frame_count<P: AsRef<Path>>(path: P, selection: &FrameSelection, interval_secs: f64) -> Result<usize> {
+    FrameExtractor::new(path, interval_secs)
+        .with_selection(selection.clone())
+        .expected_frame_count()
+}
+
+/// Reads and parses an EDL or chapter file with [`parse_edl`]
+pub fn read_edl<P: AsRef<Path>>(path: P) -> Result<Vec<f64>> {
+    let path = path.as_ref();
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("Failed to read EDL {}: {}", path.display(), e))?;
+    Ok(parse_edl(&text))
+}
+
+/// Parses the start times, in seconds, listed in a simple EDL or chapter file
+///
+/// Each line starts with a time, either in seconds (`12.5`) or as
+/// `[HH:]MM:SS[.fff]`, optionally followed by whitespace and a title. Blank
+/// lines and lines starting with `#` are ignored; lines whose time does not
+/// parse are skipped with a warning. Times are returned in file order.
+pub fn parse_edl(text: &str) -> Vec<f64> {
+    text.lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|(number, line)| {
+            let field = line.split_whitespace().next().unwrap_or_default();
+            let time = parse_edl_time(field);
+            if time.is_none() {
+                log::warn!("Skipping malformed EDL line {}: {:?}", number, line);
+            }
+            time
+        })
+        .collect()
+}
+
+/// Parses `SS[.fff]`, `MM:SS[.fff]` or `HH:MM:SS[.fff]` into seconds
+fn parse_edl_time(field: &str) -> Option<f64> {
+    let parts: Vec<&str> = field.split(':').collect();
+    if parts.len() > 3 {
+        return None;
+    }
+    let (seconds, whole) = parts.split_last()?;
+    let mut total = seconds.parse::<f64>().ok().filter(|s| s.is_finite() && *s >= 0.0)?;
+    for (unit, part) in whole.iter().rev().enumerate() {
+        let value: u32 = part.parse().ok()?;
+        total += f64::from(value) * 60f64.powi(unit as i32 + 1);
+    }
+    Some(total)
+}
+
+/// Collects the `pts_time` of each frame logged by ffmpeg's `showinfo` filter, in order
+#[cfg_attr(not(feature = "video"), allow(dead_code))]
+fn showinfo_timestamps(log: &str) -> Vec<f64> {
+    log.lines()
+        .filter(|line| line.contains("Parsed_showinfo"))
+        .filter_map(|line| {
+            let rest = &line[line.find("pts_time:")? + "pts_time:".len()..];
+            rest.split_whitespace().next()?.parse().ok()
+        })
+        .collect()
 }
 
 #[cfg(feature = "video")]
@@ -138,6 +653,28 @@ pub fn process_video<P: AsRef<Path>>(_path: P, _interval_secs: f64) -> Result<Ve
     Err(anyhow::anyhow!("Video processing not available - enable 'video' feature"))
 }
 
+#[cfg(feature = "video")]
+/// Extracts frames every `interval_secs` from a video read from `reader`, such as stdin
+///
+/// Returns each frame with its timestamp. The bytes are piped to ffmpeg, so
+/// no seekable file is needed, but containers that must be seeked to be
+/// parsed (such as MP4 without `faststart`) fail; Matroska, WebM and MPEG-TS
+/// stream fine.
+pub fn extract_frames_from_reader<R: std::io::Read>(reader: R, interval_secs: f64) -> Result<Vec<(DynamicImage, f64)>> {
+    let mut frames = Vec::new();
+    FrameExtractor::new("pipe:0", interval_secs).extract_frames_from_reader(reader, |frame, timestamp| {
+        frames.push((frame, timestamp));
+        Ok(())
+    })?;
+    Ok(frames)
+}
+
+#[cfg(not(feature = "video"))]
+/// Extracts frames from a video read from `reader` (placeholder)
+pub fn extract_frames_from_reader<R: std::io::Read>(_reader: R, _interval_secs: f64) -> Result<Vec<(DynamicImage, f64)>> {
+    Err(anyhow::anyhow!("Video processing not available - enable 'video' feature"))
+}
+
 #[cfg(feature = "video")]
 /// Extract frames from video
 pub fn extract_frames<P: AsRef<Path>>(path: P, interval_secs: f64) -> Result<Vec<DynamicImage>> {
@@ -150,6 +687,58 @@ pub fn extract_frames<P: AsRef<Path>>(_path: P, _interval_secs: f64) -> Result<V
     Err(anyhow::anyhow!("Video processing not available - enable 'video' feature"))
 }
 
+/// Shannon entropy, in bits, of a frame's luminance histogram
+///
+/// Ranges from 0 for a single flat colour, such as a black intro frame, up to
+/// 8 when all 256 luminance levels are equally common.
+pub fn frame_entropy(img: &DynamicImage) -> f32 {
+    let luma = img.to_luma8();
+    let mut histogram = [0u64; 256];
+    for pixel in luma.pixels() {
+        histogram[pixel.0[0] as usize] += 1;
+    }
+    let total = luma.pixels().len() as f64;
+    if total == 0.0 {
+        return 0.0;
+    }
+    let entropy: f64 = histogram
+        .iter()
+        .filter(|&&count| count > 0)
+        .map(|&count| {
+            let p = count as f64 / total;
+            -p * p.log2()
+        })
+        .sum();
+    entropy as f32
+}
+
+/// Default PDQ distance above which a frame is treated as a new keyframe
+pub const DEFAULT_KEYFRAME_THRESHOLD: u32 = 8;
+
+/// Selects the frames that start a new scene
+///
+/// The first frame is always a keyframe. Each later frame becomes a keyframe
+/// when its PDQ distance to the most recent keyframe exceeds `threshold`, so
+/// runs of near-identical frames collapse onto their first frame. Frames whose
+/// hashes cannot be compared are kept. Returns the keyframe indices in order.
+pub fn keyframe_indices(pdq_hashes: &[String], threshold: u32) -> Vec<usize> {
+    let mut keyframes: Vec<usize> = Vec::new();
+
+    for (i, hash) in pdq_hashes.iter().enumerate() {
+        let is_keyframe = match keyframes.last() {
+            None => true,
+            Some(&last) => crate::core::hash::pdq_distance(&pdq_hashes[last], hash)
+                .map(|distance| distance > threshold)
+                .unwrap_or(true),
+        };
+        if is_keyframe {
+            keyframes.push(i);
+        }
+    }
+
+    keyframes
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -174,4 +763,340 @@ mod tests {
             assert!(frame_count > 0);
         }
     }
+
+    #[cfg(feature = "video")]
+    #[test]
+    fn test_extracts_from_selected_video_stream() {
+        if check_ffmpeg_installed().is_err() {
+            return;
+        }
+
+        // Build a file with a red main stream and a small blue secondary stream
+        let dir = tempfile::tempdir().unwrap();
+        let video = dir.path().join("multi.mkv");
+        let status = Command::new("ffmpeg")
+            .args(["-hide_banner", "-loglevel", "error"])
+            .args(["-f", "lavfi", "-i", "color=c=red:s=64x64:d=2"])
+            .args(["-f", "lavfi", "-i", "color=c=blue:s=32x32:d=2"])
+            .args(["-map", "0", "-map", "1", "-c:v", "ffv1"])
+            .arg(&video)
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        let first_pixels = |extractor: FrameExtractor| {
+            let mut pixels = Vec::new();
+            extractor
+                .extract_frames(|frame, _| {
+                    pixels.push(frame.to_rgb8().get_pixel(0, 0).0);
+                    Ok(())
+                })
+                .unwrap();
+            pixels
+        };
+
+        let main = first_pixels(FrameExtractor::new(&video, 1.0));
+        assert!(!main.is_empty());
+        assert!(main.iter().all(|p| p[0] > 200 && p[2] < 50));
+
+        let secondary = first_pixels(FrameExtractor::new(&video, 1.0).with_video_stream(1));
+        assert!(!secondary.is_empty());
+        assert!(secondary.iter().all(|p| p[2] > 200 && p[0] < 50));
+    }
+
+    #[test]
+    fn test_filter_graph_scales_in_same_pass() {
+        let plain = FrameExtractor::new("in.mp4", 0.5);
+        assert_eq!(plain.filter_graph(), "fps=2");
+
+        let scaled = FrameExtractor::new("in.mp4", 0.5).with_target_size(640, 360);
+        let graph = scaled.filter_graph();
+        assert!(graph.starts_with("fps=2,scale=640:360"));
+        assert!(graph.contains("pad=640:360"));
+    }
+
+    #[cfg(feature = "video")]
+    #[test]
+    fn test_frames_extracted_at_target_size() {
+        if check_ffmpeg_installed().is_err() {
+            return;
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let video = dir.path().join("clip.mkv");
+        let status = Command::new("ffmpeg")
+            .args(["-hide_banner", "-loglevel", "error"])
+            .args(["-f", "lavfi", "-i", "testsrc=s=320x240:d=2"])
+            .args(["-c:v", "ffv1"])
+            .arg(&video)
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        let mut sizes = Vec::new();
+        FrameExtractor::new(&video, 1.0)
+            .with_target_size(160, 90)
+            .extract_frames(|frame, _| {
+                sizes.push((frame.width(), frame.height()));
+                Ok(())
+            })
+            .unwrap();
+
+        assert!(!sizes.is_empty());
+        assert!(sizes.iter().all(|&size| size == (160, 90)));
+    }
+
+    #[test]
+    fn test_keyframe_selection_logs_timestamps() {
+        let extractor = FrameExtractor::new("in.mp4", 0.5).with_selection(FrameSelection::KeyframesOnly);
+        assert_eq!(extractor.filter_graph(), "showinfo");
+
+        let log = "\
+[Parsed_showinfo_0 @ 0x1] config in time_base: 1/25, frame_rate: 25/1
+[Parsed_showinfo_0 @ 0x1] n:   0 pts:      0 pts_time:0       duration: 1 iskey:1 type:I
+[Parsed_showinfo_0 @ 0x1] n:   1 pts:     10 pts_time:0.4     duration: 1 iskey:1 type:I
+frame=    2 fps=0.0 q=-0.0 Lsize=N/A time=00:00:00.44";
+        assert_eq!(showinfo_timestamps(log), vec![0.0, 0.4]);
+    }
+
+    #[cfg(feature = "video")]
+    #[test]
+    fn test_keyframes_only_matches_ffprobe_keyframe_count() {
+        if check_ffmpeg_installed().is_err() {
+            return;
+        }
+
+        // 3 seconds at 25 fps with a keyframe every 10 frames
+        let dir = tempfile::tempdir().unwrap();
+        let video = dir.path().join("gop.mkv");
+        let status = Command::new("ffmpeg")
+            .args(["-hide_banner", "-loglevel", "error"])
+            .args(["-f", "lavfi", "-i", "testsrc=s=160x120:r=25:d=3"])
+            .args(["-c:v", "mpeg4", "-g", "10"])
+            .arg(&video)
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        let probe = Command::new("ffprobe")
+            .args(["-v", "error", "-select_streams", "v:0", "-skip_frame", "nokey"])
+            .args(["-count_frames", "-show_entries", "stream=nb_read_frames", "-of", "csv=p=0"])
+            .arg(&video)
+            .output()
+            .unwrap();
+        let expected: usize = String::from_utf8_lossy(&probe.stdout).trim().parse().unwrap();
+
+        let mut timestamps = Vec::new();
+        FrameExtractor::new(&video, 1.0)
+            .with_selection(FrameSelection::KeyframesOnly)
+            .extract_frames(|_, timestamp| {
+                timestamps.push(timestamp);
+                Ok(())
+            })
+            .unwrap();
+
+        assert_eq!(timestamps.len(), expected);
+        assert_eq!(timestamps[0], 0.0);
+        assert!((timestamps[1] - 0.4).abs() < 1e-6);
+    }
+
+    #[cfg(feature = "video")]
+    #[test]
+    fn test_expected_frame_count_matches_interval_extraction() {
+        if check_ffmpeg_installed().is_err() {
+            return;
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let video = dir.path().join("clip.mp4");
+        let status = Command::new("ffmpeg")
+            .args(["-hide_banner", "-loglevel", "error"])
+            .args(["-f", "lavfi", "-i", "testsrc=s=160x120:r=25:d=3.3"])
+            .arg(&video)
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        for interval in [0.5, 1.0, 0.7] {
+            let expected = expected_frame_count(&video, &FrameSelection::Interval, interval).unwrap();
+            let mut extracted = 0usize;
+            FrameExtractor::new(&video, interval)
+                .extract_frames(|_, _| {
+                    extracted += 1;
+                    Ok(())
+                })
+                .unwrap();
+            assert!(expected.abs_diff(extracted) <= 1, "interval {}: expected {}, extracted {}", interval, expected, extracted);
+        }
+    }
+
+    #[cfg(feature = "video")]
+    #[test]
+    fn test_extracts_frames_from_a_reader() {
+        if check_ffmpeg_installed().is_err() {
+            return;
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let video = dir.path().join("clip.mkv");
+        let status = Command::new("ffmpeg")
+            .args(["-hide_banner", "-loglevel", "error"])
+            .args(["-f", "lavfi", "-i", "testsrc=s=160x120:r=25:d=3"])
+            .args(["-c:v", "ffv1"])
+            .arg(&video)
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        let bytes = std::fs::read(&video).unwrap();
+        let piped = extract_frames_from_reader(std::io::Cursor::new(bytes), 1.0).unwrap();
+        let from_path = process_video(&video, 1.0).unwrap();
+
+        assert!(!piped.is_empty());
+        assert_eq!(piped.len(), from_path.len());
+        assert!(piped.iter().enumerate().all(|(i, (_, t))| *t == i as f64));
+        for ((frame, _), expected) in piped.iter().zip(&from_path) {
+            assert_eq!(frame.to_rgb8(), expected.to_rgb8());
+        }
+    }
+
+    #[test]
+    fn test_frame_budget_rejects_long_or_frame_heavy_videos() {
+        let budget = FrameBudget { max_duration_secs: Some(60.0), max_frames: Some(100) };
+
+        assert_eq!(budget.check(Some(59.5), 100), Ok(()));
+        assert_eq!(
+            budget.check(Some(61.0), 10),
+            Err(BudgetExceeded::Duration { duration_secs: 61.0, max_duration_secs: 60.0 })
+        );
+        assert_eq!(budget.check(None, 101), Err(BudgetExceeded::Frames { frames: 101, max_frames: 100 }));
+        assert!(budget.admit(100).is_ok() && budget.admit(101).is_err());
+        assert!(FrameBudget::default().check(Some(1e9), usize::MAX).is_ok());
+    }
+
+    #[test]
+    fn test_min_entropy_drops_black_frames_and_keeps_detailed_ones() {
+        let black = DynamicImage::ImageRgb8(image::RgbImage::new(64, 64));
+        let detailed = DynamicImage::ImageRgb8(image::RgbImage::from_fn(64, 64, |x, y| {
+            image::Rgb([(x * 4) as u8, (y * 4) as u8, ((x * y) % 256) as u8])
+        }));
+        assert_eq!(frame_entropy(&black), 0.0);
+        assert!(frame_entropy(&detailed) > 4.0, "{}", frame_entropy(&detailed));
+
+        let extractor = FrameExtractor::new("clip.mp4", 1.0).with_min_entropy(1.0);
+        assert!(!extractor.keeps(&black));
+        assert!(extractor.keeps(&detailed));
+        assert!(FrameExtractor::new("clip.mp4", 1.0).keeps(&black));
+    }
+
+    #[cfg(feature = "video")]
+    #[test]
+    fn test_clip_over_frame_budget_is_rejected_before_extraction() {
+        if check_ffmpeg_installed().is_err() {
+            return;
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let video = dir.path().join("clip.mkv");
+        let status = Command::new("ffmpeg")
+            .args(["-hide_banner", "-loglevel", "error"])
+            .args(["-f", "lavfi", "-i", "testsrc=s=160x120:r=25:d=10"])
+            .args(["-c:v", "ffv1"])
+            .arg(&video)
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        let budget = FrameBudget { max_duration_secs: None, max_frames: Some(4) };
+        let mut extracted = 0usize;
+        let error = FrameExtractor::new(&video, 1.0)
+            .with_budget(budget)
+            .extract_frames(|_, _| {
+                extracted += 1;
+                Ok(())
+            })
+            .unwrap_err();
+
+        assert!(matches!(error.downcast_ref::<BudgetExceeded>(), Some(BudgetExceeded::Frames { max_frames: 4, .. })));
+        assert_eq!(extracted, 0);
+
+        // Streamed input can't be probed, so the hard stop catches it instead
+        let bytes = std::fs::read(&video).unwrap();
+        let error = FrameExtractor::new("pipe:0", 1.0)
+            .with_budget(budget)
+            .extract_frames_from_reader(std::io::Cursor::new(bytes), |_, _| {
+                extracted += 1;
+                Ok(())
+            })
+            .unwrap_err();
+        assert!(error.downcast_ref::<BudgetExceeded>().is_some());
+        assert_eq!(extracted, 4);
+    }
+
+    #[test]
+    fn test_parse_edl_skips_malformed_lines() {
+        let edl = "# chapters\n0.5 Intro\n\n00:01.25\tScene two\nsoon Outro\n1:00:02 Credits\n-3 Negative\n";
+        assert_eq!(parse_edl(edl), vec![0.5, 1.25, 3602.0]);
+    }
+
+    #[cfg(feature = "video")]
+    #[test]
+    fn test_edl_selection_extracts_at_listed_times() {
+        if check_ffmpeg_installed().is_err() {
+            return;
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let video = dir.path().join("clip.mkv");
+        let status = Command::new("ffmpeg")
+            .args(["-hide_banner", "-loglevel", "error"])
+            .args(["-f", "lavfi", "-i", "testsrc=s=160x120:r=25:d=4"])
+            .args(["-c:v", "ffv1"])
+            .arg(&video)
+            .status()
+            .unwrap();
+        assert!(status.success());
+        let edl = dir.path().join("cuts.edl");
+        std::fs::write(&edl, "0.4 Intro\nnot-a-time Broken\n00:01.2 Middle\n3.0 End\n").unwrap();
+
+        let extractor = FrameExtractor::new(&video, 1.0).with_selection(FrameSelection::FromEdl(edl));
+        let mut timestamps = Vec::new();
+        extractor
+            .extract_frames(|_, timestamp| {
+                timestamps.push(timestamp);
+                Ok(())
+            })
+            .unwrap();
+
+        assert_eq!(timestamps, vec![0.4, 1.2, 3.0]);
+        assert_eq!(extractor.expected_frame_count().unwrap(), 3);
+    }
+
+    #[test]
+    fn test_keyframes_on_mostly_static_clip() {
+        use crate::core::hash::compute_pdq_hash;
+        use image::{Rgb, RgbImage};
+
+        // Coarse pseudo-random blocks give each scene distinct low frequencies
+        let scene = |seed: u32, x: u32, y: u32| {
+            let cell = (x / 8) * 8 + (y / 8) + seed * 64;
+            (cell.wrapping_mul(2654435761) >> 24) % 200
+        };
+
+        // 30 frames of a slowly brightening static scene with one cut at frame 20
+        let hashes: Vec<String> = (0..30u32)
+            .map(|i| {
+                let frame = RgbImage::from_fn(64, 64, |x, y| {
+                    let v = if i < 20 { scene(1, x, y) + i } else { scene(2, x, y) };
+                    Rgb([v as u8; 3])
+                });
+                compute_pdq_hash(&DynamicImage::ImageRgb8(frame)).unwrap()
+            })
+            .collect();
+
+        let keyframes = keyframe_indices(&hashes, DEFAULT_KEYFRAME_THRESHOLD);
+
+        assert_eq!(keyframes, vec![0, 20]);
+        assert!(keyframes.len() * 10 <= hashes.len());
+    }
 }
@@ -0,0 +1,151 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use notify::{EventKind, RecursiveMode, Watcher};
+use tokio::sync::mpsc;
+
+use crate::error::{AppError, Result, ResultExt};
+use crate::models::manifest::MediaManifest;
+
+/// Quiet period after the last event before a file is considered for processing
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// How often pending files are checked for settling
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A file seen by the watcher that has not been processed yet
+#[derive(Debug)]
+struct PendingFile {
+    last_event: Instant,
+    last_size: Option<u64>,
+}
+
+/// Returns true for names that belong to in-progress or hidden files
+fn is_ignored(path: &Path) -> bool {
+    let name = match path.file_name().and_then(|n| n.to_str()) {
+        Some(name) => name,
+        None => return true,
+    };
+    name.starts_with('.') || name.ends_with(".tmp") || name.ends_with(".part") || name.ends_with('~')
+}
+
+/// Watches `dir` and processes media files as they appear
+///
+/// Each created or modified file is processed with [`crate::process_file`]
+/// once events for it have stopped for a short debounce period and its size
+/// has stopped changing, so files still being written are not picked up.
+/// Hidden and temporary files (`.tmp`, `.part`) are ignored. Every result,
+/// including processing errors, is passed to `sink`.
+///
+/// This runs until the underlying watcher shuts down; it is meant to be
+/// spawned as a background task.
+pub async fn watch_directory<P: AsRef<Path>>(
+    dir: P,
+    sink: impl Fn(Result<MediaManifest>) + Send,
+) -> Result<()> {
+    let dir = dir.as_ref().to_path_buf();
+    let (tx, mut rx) = mpsc::unbounded_channel::<PathBuf>();
+
+    let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+        if let Ok(event) = res {
+            if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+                for path in event.paths {
+                    let _ = tx.send(path);
+                }
+            }
+        }
+    })
+    .context("failed to create directory watcher")?;
+    watcher
+        .watch(&dir, RecursiveMode::NonRecursive)
+        .with_context(|| format!("failed to watch {}", dir.display()))?;
+    log::info!("Watching {} for new media", dir.display());
+
+    let mut pending: HashMap<PathBuf, PendingFile> = HashMap::new();
+    let mut ticker = tokio::time::interval(POLL_INTERVAL);
+
+    loop {
+        tokio::select! {
+            event = rx.recv() => match event {
+                Some(path) if !is_ignored(&path) => {
+                    let entry = pending.entry(path).or_insert(PendingFile {
+                        last_event: Instant::now(),
+                        last_size: None,
+                    });
+                    entry.last_event = Instant::now();
+                }
+                Some(_) => {}
+                None => return Ok(()),
+            },
+            _ = ticker.tick() => {
+                let mut ready = Vec::new();
+                pending.retain(|path, entry| {
+                    if entry.last_event.elapsed() < DEBOUNCE {
+                        return true;
+                    }
+                    let size = match std::fs::metadata(path) {
+                        Ok(meta) if meta.is_file() => meta.len(),
+                        // Removed or not a regular file: stop tracking it
+                        _ => return false,
+                    };
+                    // Process only once the size matched on two consecutive polls
+                    if size > 0 && entry.last_size == Some(size) {
+                        ready.push(path.clone());
+                        false
+                    } else {
+                        entry.last_size = Some(size);
+                        true
+                    }
+                });
+
+                for path in ready {
+                    log::debug!("Processing watched file: {}", path.display());
+                    let result = tokio::task::spawn_blocking(move || crate::process_file(&path))
+                        .await
+                        .map_err(AppError::from)
+                        .and_then(|r| r);
+                    sink(result);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::manifest::MediaType;
+
+    #[tokio::test]
+    async fn test_new_file_is_processed() {
+        let dir = tempfile::tempdir().unwrap();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+
+        let watched = dir.path().to_path_buf();
+        let task = tokio::spawn(async move {
+            watch_directory(watched, move |result| {
+                let _ = tx.send(result);
+            })
+            .await
+        });
+
+        // Give the watcher a moment to register before dropping the file in
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        image::RgbImage::from_pixel(8, 8, image::Rgb([1, 2, 3]))
+            .save(dir.path().join("dropped.png"))
+            .unwrap();
+        std::fs::write(dir.path().join("ignored.part"), b"partial").unwrap();
+
+        let manifest = tokio::time::timeout(Duration::from_secs(10), rx.recv())
+            .await
+            .expect("watched file was not processed")
+            .unwrap()
+            .unwrap();
+        task.abort();
+
+        assert_eq!(manifest.file_name, "dropped.png");
+        assert_eq!(manifest.media_type, MediaType::Image);
+        assert!(rx.try_recv().is_err());
+    }
+}
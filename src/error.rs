@@ -46,6 +46,10 @@ pub enum AppError {
     #[error("Authentication error: {0}")]
     Auth(String),
     
+    /// A file exists but the process is not allowed to access it
+    #[error("Permission denied: {0}")]
+    PermissionDenied(String),
+    
     /// Rate limiting errors
     #[error("Rate limit exceeded: {message} (retry after {retry_after:?}s)")]
     RateLimit {
@@ -55,6 +59,26 @@ pub enum AppError {
         retry_after: Option<u64>,
     },
     
+    /// Hashes produced by incompatible versions of the same algorithm
+    #[error("{algorithm} version mismatch: v{left} vs v{right}")]
+    VersionMismatch {
+        /// Name of the algorithm, e.g. `"pdq"`.
+        algorithm: String,
+        /// Version behind the first hash.
+        left: u32,
+        /// Version behind the second hash.
+        right: u32,
+    },
+    
+    /// A service the request depends on, such as the embedding service, could not be used
+    #[error("{service} failed: {detail}")]
+    ExternalService {
+        /// Name of the service, e.g. `"embedding service"`.
+        service: String,
+        /// What went wrong talking to it.
+        detail: String,
+    },
+    
     /// Upload errors
     #[error("Upload error: {0}")]
     UploadError(String),
@@ -65,7 +89,7 @@ pub enum AppError {
 }
 
 /// Standard error response format
-#[derive(Serialize)]
+#[derive(Serialize, schemars::JsonSchema)]
 #[derive(Debug)]
 pub struct ErrorResponse {
     /// Error code (HTTP status code)
@@ -78,6 +102,21 @@ pub struct ErrorResponse {
 }
 
 impl AppError {
+    /// Classifies an I/O error that occurred while accessing `path`
+    ///
+    /// Missing and unreadable files map to `NotFound` and `PermissionDenied`
+    /// (naming the path) so callers can tell them apart; anything else stays
+    /// an `Io` error.
+    pub fn from_io_at(err: std::io::Error, path: &std::path::Path) -> Self {
+        match err.kind() {
+            std::io::ErrorKind::NotFound => Self::NotFound(path.display().to_string()),
+            std::io::ErrorKind::PermissionDenied => {
+                Self::PermissionDenied(format!("{}: {}", path.display(), err))
+            }
+            _ => Self::Io(err),
+        }
+    }
+
     #[cfg(feature = "web")]
     /// Get the HTTP status code for this error
     pub fn status_code(&self) -> StatusCode {
@@ -86,7 +125,10 @@ impl AppError {
             Self::Validation(_) => StatusCode::BAD_REQUEST,
             Self::NotFound(_) => StatusCode::NOT_FOUND,
             Self::Auth(_) => StatusCode::UNAUTHORIZED,
+            Self::PermissionDenied(_) => StatusCode::FORBIDDEN,
+            Self::VersionMismatch { .. } => StatusCode::CONFLICT,
             Self::RateLimit { .. } => StatusCode::TOO_MANY_REQUESTS,
+            Self::ExternalService { .. } => StatusCode::BAD_GATEWAY,
             _ => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
@@ -153,8 +195,13 @@ impl From<tokio::task::JoinError> for AppError {
 }
 
 impl From<anyhow::Error> for AppError {
+    /// Unwraps an `AppError` carried through `anyhow`, so its kind survives;
+    /// anything else becomes `Internal`.
     fn from(err: anyhow::Error) -> Self {
-        AppError::Internal(err.to_string())
+        match err.downcast::<AppError>() {
+            Ok(err) => err,
+            Err(err) => AppError::Internal(err.to_string()),
+        }
     }
 }
 
@@ -54,24 +54,36 @@ mod utils;
 // Public API exports
 pub use crate::{
     error::{AppError, Result, ResultExt},
-    models::manifest::{MediaManifest, MediaType},
+    core::archive::{process_zip, process_zip_entry, process_zip_with, zip_media_entries, ZipEntries, ZipLimits},
+    core::cache::DecodeCache,
+    core::embeddings::{embed_checked, DefaultEmbedding, EmbeddingFuture, EmbeddingProvider, EmbeddingRegistry, DEFAULT_EMBEDDING_MODEL},
+    core::fetch::FetchLimits,
+    core::index::BkTree,
+    core::jobs::{run_job, ItemStatus, Job, JobItem, JobProgress, JobStore},
+    core::perceptual::{compute_all_perceptual, AlgorithmRegistry, PerceptualAlgorithm},
+    core::similarity::{compute_color_histogram, diff_images, embedding_similarity, find_similar, histogram_similarity, image_in_video, pdq_distance_matrix, pool_frame_embeddings, video_sequence_similarity, video_similarity_score, ColorSignature, DiffReport, SequenceMatch},
+    core::store::{reconcile, reconcile_with_policy, AuditEntry, AuditOutcome, DirManifestStore, ManifestStore, OrphanPolicy, ReconcileReport, AUDIT_LOG_FILE, PDQ_INDEX_FILE},
+    core::watch::watch_directory,
+    models::bundle::{ManifestBundle, SigningKey, VerifyingKey},
+    models::manifest::{FieldDiff, FrameSource, ManifestDetail, MediaManifest, MediaType, VerificationExplanation, VerificationReport, VerifyOptions, VerifyProgress, PARTIAL_DECODE_KEY, TOO_SMALL_FOR_PDQ_KEY, WEBP_LOSSLESS_KEY},
+    utils::{format_timestamp, sanitize_filename, TIMESTAMP_FORMAT},
 };
 
 #[cfg(feature = "web")]
 pub use crate::{
-    api::{create_router, health_check, handlers::{upload_file, verify_manifest}},
-    state::{AppState, Config},
+    api::{cors_layer, create_router, create_router_with_config, health_check, handlers::{audit, capabilities, closest, compare, distance_matrix, download_file, find_duplicates, frame_match, hash_file, image_diff, job_status, list_manifests, openapi, perceptual_all, search_similar, status, stream_file, submit_job, upload_archive, upload_file, verify_manifest, verify_manifest_progress, video_compare, video_similarity}},
+    state::{AppState, Config, DrainReport, InFlight, LoadStatus, ProcessingProfile},
 };
 
 #[cfg(feature = "hashing")]
-pub use crate::core::hash::{self, compute_sha3_256, compute_pdq_hash, compute_file_hash, sha3_256};
+pub use crate::core::hash::{self, compute_sha3_256, compute_pdq_hash, compute_pdq_hash_config, compute_file_hash, pdq_locality_key, sha3_256, IncrementalFileHash, PdqConfig};
 
 #[cfg(feature = "embeddings")]
 pub use crate::core::embeddings::{self, EmbeddingModel};
 
 #[cfg(feature = "video")]
 pub use crate::{
-    core::video::{self, extract_frames, process_video, check_ffmpeg_installed},
+    core::video::{self, expected_frame_count, extract_frames, extract_frames_from_reader, frame_entropy, parse_edl, BudgetExceeded, FrameBudget, process_video, read_edl, check_ffmpeg_installed, FrameSelection},
     state::VideoConfig,
 };
 
@@ -144,20 +156,63 @@ pub fn init() -> Result<()> {
 ///
 /// # Errors
 ///
-/// Returns an error if the file cannot be read, processed, or if any hashing operation fails.
+/// Returns `AppError::NotFound` if `path` does not exist, `AppError::InvalidInput`
+/// if it is a directory, or an error if the file cannot be read, processed, or if
+/// any hashing operation fails.
 pub fn process_image<P: AsRef<std::path::Path>>(path: P) -> Result<MediaManifest> {
     let path = path.as_ref();
     log::debug!("Processing image: {}", path.display());
+    require_file(path)?;
     
     // Read the file
     let data = std::fs::read(path)
-        .map_err(error::AppError::Io)?;
+        .map_err(|e| AppError::from_io_at(e, path))?;
+    
+    let file_name = path.file_name()
+        .and_then(|n| n.to_str())
+        .map(String::from)
+        .unwrap_or_else(|| "unknown".to_string());
+    
+    process_image_bytes(&file_name, &data)
+}
+
+/// Process an in-memory image and generate a manifest
+///
+/// # Arguments
+///
+/// * `file_name` - Name recorded in the manifest and used in error messages
+/// * `data` - Encoded image bytes
+///
+/// # Errors
+///
+/// Returns `AppError::Validation` if `data` is empty, `AppError::InvalidInput`
+/// if the image cannot be decoded, or an error if any hashing operation fails.
+pub fn process_image_bytes(file_name: &str, data: &[u8]) -> Result<MediaManifest> {
+    process_image_bytes_with(file_name, data, crate::core::hash::MIN_PDQ_DIMENSION)
+}
+
+/// Like [`process_image_bytes`], skipping the PDQ hash of images smaller than
+/// `min_pdq_dimension` pixels across, e.g. `Config::min_pdq_dimension`
+pub fn process_image_bytes_with(file_name: &str, data: &[u8], min_pdq_dimension: u32) -> Result<MediaManifest> {
+    if data.is_empty() {
+        return Err(AppError::Validation("empty file".to_string()));
+    }
     
     // Compute hashes
-    let sha3_hash = crate::core::hash::compute_sha3_256(&data)?;
-    // Load image for PDQ hash computation
-    let image = image::load_from_memory(&data)?;
-    let pdq_hash = crate::core::hash::compute_pdq_hash(&image)?;
+    let sha3_hash = crate::core::hash::compute_sha3_256(data)?;
+    // Load image for PDQ hash computation, salvaging what a damaged file still holds
+    let (image, partial) = crate::core::hash::decode_lenient(data)
+        .map_err(|e| AppError::InvalidInput(format!("Failed to decode image {}: {}", file_name, e)))?;
+    if partial {
+        log::warn!("Image {} was only partially decoded", file_name);
+    }
+    // Images too small to hash meaningfully get no perceptual hash at all
+    let too_small = crate::core::hash::too_small_for_pdq(&image, min_pdq_dimension);
+    let pdq_hash = if too_small { None } else { Some(crate::core::hash::compute_pdq_hash(&image)?) };
+    let oriented = crate::core::hash::apply_orientation(image.clone(), crate::core::hash::exif_orientation(data));
+    let content_hash = crate::core::hash::compute_content_hash(&oriented)?;
+    // Animations record their frame count, which verification expects back
+    let frame_count = crate::core::hash::image_frame_count(data).ok().filter(|&count| count > 1);
     
     // Generate embeddings if feature is enabled
     #[cfg(feature = "embeddings")]
@@ -170,52 +225,121 @@ pub fn process_image<P: AsRef<std::path::Path>>(path: P) -> Result<MediaManifest
     let embedding: Option<Vec<f32>> = None;
     
     // Create and return
-    let file_name = path.file_name()
-        .and_then(|n| n.to_str())
-        .map(String::from)
-        .unwrap_or_else(|| "unknown".to_string());
-    
     let file_size = data.len() as u64;
-    let now = chrono::Utc::now().to_rfc3339();
+    let now = crate::utils::now_timestamp();
     
     Ok(MediaManifest {
         media_type: MediaType::Image,
-        file_name,
+        file_name: file_name.to_string(),
+        relative_path: None,
         file_size,
         created_at: now.clone(),
         modified_at: now,
         sha3_256_hash: sha3_hash,
-        pdq_hash: Some(pdq_hash),
+        pdq_format: pdq_hash.as_deref().and_then(|h| hash::PdqFormat::detect(h).ok()),
+        perceptual_algorithm: None,
+        algorithm_versions: crate::models::manifest::current_algorithm_versions(
+            pdq_hash.is_some().then_some(crate::core::perceptual::DEFAULT_ALGORITHM),
+        ),
+        pdq_hash,
+        color_signature: Some(ColorSignature::compute(&image)),
+        color_histogram: Some(crate::core::similarity::compute_color_histogram(&image, crate::core::similarity::DEFAULT_HISTOGRAM_BINS)),
+        content_hash: Some(content_hash),
+        stream_hash: crate::core::hash::stream_hash(data),
+        frame_source: None,
+        frames_merkle_root: None,
+        frame_count,
         frames: None,
-        metadata: serde_json::Value::Null,
+        metadata: {
+            let mut metadata = serde_json::Map::new();
+            if partial {
+                metadata.insert(crate::models::manifest::PARTIAL_DECODE_KEY.to_string(), serde_json::Value::Bool(true));
+            }
+            if too_small {
+                metadata.insert(crate::models::manifest::TOO_SMALL_FOR_PDQ_KEY.to_string(), serde_json::Value::Bool(true));
+            }
+            if let Some(lossless) = crate::core::hash::webp_is_lossless(data) {
+                metadata.insert(crate::models::manifest::WEBP_LOSSLESS_KEY.to_string(), serde_json::Value::Bool(lossless));
+            }
+            if metadata.is_empty() { serde_json::Value::Null } else { serde_json::Value::Object(metadata) }
+        },
+        derived_from: None,
+        embedding_dim: None,
+        embedding_model: None,
+        video_embedding: None,
     })
 }
 
+/// Download an image from `url` and generate a manifest for it
+///
+/// The download is bounded by `limits` (see [`FetchLimits`]); the manifest's
+/// file name is the last segment of the URL's path.
+///
+/// # Errors
+///
+/// Returns the errors of [`core::fetch::fetch_media`] for a download that is
+/// refused or fails, and those of [`process_image_bytes`] for the image itself.
+pub async fn process_image_url(url: &str, limits: &FetchLimits) -> Result<MediaManifest> {
+    let (file_name, data) = crate::core::fetch::fetch_media(url, limits).await?;
+    tokio::task::spawn_blocking(move || process_image_bytes(&file_name, &data)).await?
+}
+
 /// Process a video file and generate a manifest with frame information
 ///
+/// Extraction is held to [`FrameBudget::DEFAULT_LIMITS`], so archives, batch
+/// jobs and watched directories can't run away with an overlong video; see
+/// [`process_video_file_with`] for other limits.
+///
 /// # Arguments
 ///
 /// * `path` - Path to the video file
 ///
 /// # Errors
 ///
-/// Returns an error if the file cannot be read, processed, or if any hashing operation fails.
+/// Returns `AppError::NotFound` if `path` does not exist, `AppError::InvalidInput`
+/// if it is a directory, `AppError::Validation` if the video is over the
+/// budget, or an error if the file cannot be read, processed, or if any
+/// hashing operation fails.
 #[cfg(feature = "video")]
 pub fn process_video_file<P: AsRef<std::path::Path>>(path: P) -> Result<MediaManifest> {
+    process_video_file_with(path, FrameBudget::DEFAULT_LIMITS)
+}
+
+/// Like [`process_video_file`], with frame extraction held to `budget`
+///
+/// # Errors
+///
+/// The errors of [`process_video_file`].
+#[cfg(feature = "video")]
+pub fn process_video_file_with<P: AsRef<std::path::Path>>(path: P, budget: FrameBudget) -> Result<MediaManifest> {
     
     let path = path.as_ref();
     log::debug!("Processing video: {}", path.display());
+    require_file(path)?;
     
     // Read the file
     let data = std::fs::read(path)
-        .map_err(error::AppError::Io)?;
+        .map_err(|e| AppError::from_io_at(e, path))?;
+    if data.is_empty() {
+        return Err(AppError::Validation("empty file".to_string()));
+    }
     
     // Compute hashes
     let sha3_hash = hash::compute_sha3_256(&data)?;
     
     // Extract frames and process them
     let _frames_dir = tempfile::tempdir()?;
-    let frames = video::extract_frames(path, 1.0)?; // 1 frame per second
+    let mut frames = Vec::new();
+    video::FrameExtractor::new(path, 1.0) // 1 frame per second
+        .with_budget(budget)
+        .extract_frames(|frame, _timestamp| {
+            frames.push(frame);
+            Ok(())
+        })
+        .map_err(|e| match e.downcast::<video::BudgetExceeded>() {
+            Ok(exceeded) => AppError::Validation(exceeded.to_string()),
+            Err(e) => e.into(),
+        })?;
     
     // Process each frame
     let mut frame_manifests = Vec::new();
@@ -236,19 +360,566 @@ pub fn process_video_file<P: AsRef<std::path::Path>>(path: P) -> Result<MediaMan
         .map(String::from)
         .unwrap_or_else(|| "unknown".to_string());
     
-    let now = chrono::Utc::now().to_rfc3339();
+    let now = crate::utils::now_timestamp();
     
     let manifest = MediaManifest {
         media_type: MediaType::Video,
         file_name,
+        relative_path: None,
         file_size: data.len() as u64,
         created_at: now.clone(),
         modified_at: now,
         sha3_256_hash: sha3_hash,
         pdq_hash: None, // Videos don't have a single PDQ hash
+        perceptual_algorithm: None,
+        algorithm_versions: crate::models::manifest::current_algorithm_versions(Some(crate::core::perceptual::DEFAULT_ALGORITHM)),
+        pdq_format: Some(hash::PdqFormat::Hex),
+        color_signature: None,
+        color_histogram: None,
+        content_hash: None,
+        stream_hash: None,
+        frame_source: Some(crate::models::manifest::FrameSource::VideoFfmpeg),
+        frames_merkle_root: None,
+        frame_count: None,
         frames: Some(frame_manifests),
         metadata: serde_json::Value::Null,
+        derived_from: None,
+        embedding_dim: None,
+        embedding_model: None,
+        video_embedding: None,
     };
     
     Ok(manifest)
 }
+
+/// Checks that `path` names a file, so a directory gets a clear error instead
+/// of an opaque read or decode failure
+fn require_file(path: &std::path::Path) -> Result<()> {
+    let metadata = std::fs::metadata(path).map_err(|e| AppError::from_io_at(e, path))?;
+    if metadata.is_dir() {
+        return Err(AppError::InvalidInput(format!(
+            "expected a file, got a directory: {}",
+            path.display()
+        )));
+    }
+    Ok(())
+}
+
+/// Process a multi-page document and generate a manifest with one frame per page
+///
+/// TIFF pages are decoded directly; PDF pages are rendered first (requires the
+/// `pdf` feature). Each page's PDQ hash is recorded in `frames`, with the page
+/// number as its timestamp.
+///
+/// # Arguments
+///
+/// * `file_name` - Name recorded in the manifest; its extension selects the format
+/// * `data` - Encoded document bytes
+///
+/// # Errors
+///
+/// Returns `AppError::Validation` if `data` is empty, `AppError::InvalidInput`
+/// if the document cannot be decoded, or an error if any hashing operation fails.
+pub fn process_document_bytes(file_name: &str, data: &[u8]) -> Result<MediaManifest> {
+    if data.is_empty() {
+        return Err(AppError::Validation("empty file".to_string()));
+    }
+    
+    let sha3_hash = crate::core::hash::compute_sha3_256(data)?;
+    let (frame_source, pages) = crate::core::document::page_frames(file_name, data)?;
+    let now = crate::utils::now_timestamp();
+    
+    Ok(MediaManifest {
+        media_type: MediaType::Document,
+        file_name: file_name.to_string(),
+        relative_path: None,
+        file_size: data.len() as u64,
+        created_at: now.clone(),
+        modified_at: now,
+        sha3_256_hash: sha3_hash,
+        pdq_hash: None, // Documents are hashed per page
+        perceptual_algorithm: None,
+        algorithm_versions: crate::models::manifest::current_algorithm_versions(Some(crate::core::perceptual::DEFAULT_ALGORITHM)),
+        pdq_format: Some(hash::PdqFormat::Hex),
+        color_signature: None,
+        color_histogram: None,
+        content_hash: None,
+        stream_hash: None,
+        frame_source: Some(frame_source),
+        frames_merkle_root: None,
+        frame_count: None,
+        frames: Some(pages),
+        metadata: serde_json::Value::Null,
+        derived_from: None,
+        embedding_dim: None,
+        embedding_model: None,
+        video_embedding: None,
+    })
+}
+
+/// Process any media file, dispatching on its extension
+///
+/// Images go through [`process_image`], videos through `process_video_file`
+/// (requires the `video` feature) and documents through
+/// [`process_document_bytes`]. Other files get a manifest carrying only
+/// their size and SHA3-256 hash.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read or processed, or if it is a
+/// video and the `video` feature is disabled.
+pub fn process_file<P: AsRef<std::path::Path>>(path: P) -> Result<MediaManifest> {
+    let path = path.as_ref();
+    
+    match MediaType::from_path(path) {
+        MediaType::Image => process_image(path),
+        #[cfg(feature = "video")]
+        MediaType::Video => process_video_file(path),
+        #[cfg(not(feature = "video"))]
+        MediaType::Video => Err(AppError::Config(
+            "Video processing not available - enable 'video' feature".to_string(),
+        )),
+        MediaType::Document => {
+            let data = std::fs::read(path).map_err(|e| AppError::from_io_at(e, path))?;
+            let file_name = path.file_name()
+                .and_then(|n| n.to_str())
+                .map(String::from)
+                .unwrap_or_else(|| "unknown".to_string());
+            process_document_bytes(&file_name, &data)
+        }
+        MediaType::Other => {
+            let metadata = std::fs::metadata(path).map_err(|e| AppError::from_io_at(e, path))?;
+            if metadata.len() == 0 {
+                return Err(AppError::Validation("empty file".to_string()));
+            }
+            
+            let file_name = path.file_name()
+                .and_then(|n| n.to_str())
+                .map(String::from)
+                .unwrap_or_else(|| "unknown".to_string());
+            let sha3_hash = crate::core::hash::compute_file_hash(path)?;
+            
+            MediaManifest::new(file_name, path, MediaType::Other, sha3_hash, None, None, None)
+        }
+    }
+}
+
+/// Process every file directly inside `dir` in parallel, in path order
+///
+/// Entries are sorted by path before dispatch, and results come back in that
+/// order however the work is scheduled. Hidden files and subdirectories are
+/// skipped. A file that fails, or panics while being processed, only gets an
+/// error entry (a panic becomes `AppError::Internal`); the other files are
+/// unaffected. Files are spread over the rayon pool with the `hashing`
+/// feature and processed one after another without it.
+///
+/// # Errors
+///
+/// Returns an error only if `dir` cannot be listed.
+pub fn process_directory_parallel<P: AsRef<std::path::Path>>(
+    dir: P,
+) -> Result<Vec<(std::path::PathBuf, Result<MediaManifest>)>> {
+    Ok(process_each(directory_files(dir.as_ref())?, |path| process_file(path)))
+}
+
+/// Counts from [`process_directory_incremental`]
+#[derive(Debug, Default)]
+pub struct BatchSummary {
+    /// Files whose manifest was (re)computed and saved
+    pub processed: usize,
+    /// Files left alone because their stored manifest is still current
+    pub skipped: usize,
+    /// Files that could not be processed or saved, with the reason
+    pub failed: Vec<(std::path::PathBuf, AppError)>,
+}
+
+/// Process the files in `dir` that are new or changed since their manifest was stored
+///
+/// A file counts as unchanged when `store` already holds a manifest for the
+/// same relative path with the same `file_size` and a `modified_at` equal to
+/// the file's modification time. Everything else is processed as in
+/// [`process_directory_parallel`] and saved to `store`, with `relative_path`
+/// set to its path under `dir` and `modified_at` set to its modification time
+/// so the next run can skip it. With `recursive`, subdirectories are walked
+/// too; hidden files and directories are always skipped. Since the store is
+/// keyed by file name, files sharing a name with another file in the same run
+/// are reported as failures rather than overwriting each other.
+///
+/// # Errors
+///
+/// Returns an error only if `dir` cannot be listed or the store cannot be read.
+pub fn process_directory_incremental<P: AsRef<std::path::Path>>(
+    dir: P,
+    recursive: bool,
+    store: &dyn ManifestStore,
+) -> Result<BatchSummary> {
+    let dir = dir.as_ref();
+    let paths = if recursive { directory_files_recursive(dir)? } else { directory_files(dir)? };
+    let stored: std::collections::HashMap<String, MediaManifest> = store
+        .manifests()?
+        .into_iter()
+        .map(|m| (m.relative_path.clone().unwrap_or_else(|| m.file_name.clone()), m))
+        .collect();
+    let mut name_counts = std::collections::HashMap::<std::ffi::OsString, usize>::new();
+    for path in &paths {
+        *name_counts.entry(path.file_name().unwrap_or_default().to_os_string()).or_default() += 1;
+    }
+
+    let mut summary = BatchSummary::default();
+    let mut changed = Vec::new();
+    for path in paths {
+        if name_counts[path.file_name().unwrap_or_default()] > 1 {
+            let message = format!("another file in {} has the same name", dir.display());
+            summary.failed.push((path, AppError::Validation(message)));
+            continue;
+        }
+        let metadata = match std::fs::metadata(&path) {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                let error = AppError::from_io_at(e, &path);
+                summary.failed.push((path, error));
+                continue;
+            }
+        };
+        let relative = relative_key(dir, &path);
+        let modified_at = metadata.modified().ok().map(|t| format_timestamp(&chrono::DateTime::<chrono::Utc>::from(t)));
+        let current = stored.get(&relative).is_some_and(|m| {
+            m.file_size == metadata.len() && modified_at.as_deref() == Some(m.modified_at.as_str())
+        });
+        if current {
+            summary.skipped += 1;
+        } else {
+            changed.push((path, relative, modified_at));
+        }
+    }
+
+    // process_each keeps input order, so results line up with `changed`
+    let (paths, keys): (Vec<_>, Vec<_>) = changed
+        .into_iter()
+        .map(|(path, relative, modified_at)| (path, (relative, modified_at)))
+        .unzip();
+    for ((path, result), (relative, modified_at)) in process_each(paths, |path| process_file(path)).into_iter().zip(keys) {
+        let saved = result.and_then(|manifest| {
+            let mut manifest = manifest.with_relative_path(relative);
+            if let Some(modified_at) = modified_at {
+                manifest.modified_at = modified_at;
+            }
+            store.save(&manifest)
+        });
+        match saved {
+            Ok(()) => summary.processed += 1,
+            Err(e) => summary.failed.push((path, e)),
+        }
+    }
+    log::info!(
+        "Incremental pass over {}: {} processed, {} skipped, {} failed",
+        dir.display(),
+        summary.processed,
+        summary.skipped,
+        summary.failed.len()
+    );
+    Ok(summary)
+}
+
+/// `path` relative to `dir` with `/` separators, as stored in `relative_path`
+fn relative_key(dir: &std::path::Path, path: &std::path::Path) -> String {
+    let relative = path.strip_prefix(dir).unwrap_or(path);
+    relative
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Lists the regular, non-hidden files directly inside `dir`, sorted by path
+pub(crate) fn directory_files(dir: &std::path::Path) -> Result<Vec<std::path::PathBuf>> {
+    let mut paths = Vec::new();
+    for entry in std::fs::read_dir(dir).map_err(|e| AppError::from_io_at(e, dir))? {
+        let entry = entry?;
+        if entry.file_type()?.is_file() && !entry.file_name().to_string_lossy().starts_with('.') {
+            paths.push(entry.path());
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+/// Like [`directory_files`], also descending into non-hidden subdirectories
+fn directory_files_recursive(dir: &std::path::Path) -> Result<Vec<std::path::PathBuf>> {
+    let mut paths = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(current) = pending.pop() {
+        for entry in std::fs::read_dir(&current).map_err(|e| AppError::from_io_at(e, &current))? {
+            let entry = entry?;
+            if entry.file_name().to_string_lossy().starts_with('.') {
+                continue;
+            }
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                pending.push(entry.path());
+            } else if file_type.is_file() {
+                paths.push(entry.path());
+            }
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+/// Runs `process` on every path, keeping input order and turning panics into errors
+fn process_each<F>(
+    paths: Vec<std::path::PathBuf>,
+    process: F,
+) -> Vec<(std::path::PathBuf, Result<MediaManifest>)>
+where
+    F: Fn(&std::path::Path) -> Result<MediaManifest> + Sync,
+{
+    let run = |path: std::path::PathBuf| {
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| process(&path)))
+            .unwrap_or_else(|panic| {
+                let message = panic
+                    .downcast_ref::<&str>()
+                    .map(|s| s.to_string())
+                    .or_else(|| panic.downcast_ref::<String>().cloned())
+                    .unwrap_or_else(|| "unknown panic".to_string());
+                Err(AppError::Internal(format!("processing {} panicked: {}", path.display(), message)))
+            });
+        (path, result)
+    };
+
+    // Indexed parallel iterators collect in input order
+    #[cfg(feature = "hashing")]
+    {
+        use rayon::prelude::*;
+        paths.into_par_iter().map(run).collect()
+    }
+    #[cfg(not(feature = "hashing"))]
+    {
+        paths.into_iter().map(run).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_panicking_file_becomes_an_error_in_place() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths: Vec<_> = ["a.bin", "b.bin", "c.bin"].iter().map(|name| dir.path().join(name)).collect();
+        for path in &paths {
+            std::fs::write(path, path.to_string_lossy().as_bytes()).unwrap();
+        }
+
+        let results = process_each(paths.clone(), |path| {
+            if path.ends_with("b.bin") {
+                panic!("decoder blew up");
+            }
+            process_file(path)
+        });
+
+        assert_eq!(results.iter().map(|(path, _)| path.clone()).collect::<Vec<_>>(), paths);
+        assert!(results[0].1.is_ok() && results[2].1.is_ok());
+        match &results[1].1 {
+            Err(AppError::Internal(message)) => assert!(message.contains("decoder blew up"), "{}", message),
+            other => panic!("expected an internal error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_incremental_rerun_over_unchanged_directory_processes_nothing() {
+        let dir = tempfile::tempdir().unwrap();
+        let manifests = tempfile::tempdir().unwrap();
+        let store = DirManifestStore::new(manifests.path());
+        image::RgbImage::from_fn(64, 64, |x, y| image::Rgb([(x * 4) as u8, (y * 4) as u8, 128]))
+            .save(dir.path().join("photo.png"))
+            .unwrap();
+        std::fs::create_dir(dir.path().join("nested")).unwrap();
+        std::fs::write(dir.path().join("nested").join("notes.bin"), b"first draft").unwrap();
+
+        let first = process_directory_incremental(dir.path(), true, &store).unwrap();
+        assert_eq!((first.processed, first.skipped), (2, 0), "{:?}", first.failed);
+        let relative_paths: Vec<_> = store.manifests().unwrap().into_iter().filter_map(|m| m.relative_path).collect();
+        assert_eq!(relative_paths, ["nested/notes.bin", "photo.png"]);
+
+        let second = process_directory_incremental(dir.path(), true, &store).unwrap();
+        assert_eq!((second.processed, second.skipped), (0, 2), "{:?}", second.failed);
+
+        std::fs::write(dir.path().join("nested").join("notes.bin"), b"second, longer draft").unwrap();
+        let third = process_directory_incremental(dir.path(), true, &store).unwrap();
+        assert_eq!((third.processed, third.skipped), (1, 1), "{:?}", third.failed);
+    }
+
+    #[test]
+    fn test_truncated_jpeg_is_hashed_and_flagged_partial() {
+        let img = image::RgbImage::from_fn(128, 128, |x, y| image::Rgb([(x * 2) as u8, (y * 2) as u8, ((x ^ y) * 3) as u8]));
+        let mut jpeg = std::io::Cursor::new(Vec::new());
+        image::DynamicImage::ImageRgb8(img)
+            .write_to(&mut jpeg, image::ImageOutputFormat::Jpeg(90))
+            .unwrap();
+        let jpeg = jpeg.into_inner();
+        let truncated = &jpeg[..jpeg.len() * 2 / 3];
+        assert!(image::load_from_memory(truncated).is_err());
+
+        let whole = process_image_bytes("whole.jpg", &jpeg).unwrap();
+        assert!(whole.metadata.is_null());
+
+        let partial = process_image_bytes("truncated.jpg", truncated).unwrap();
+        assert!(partial.pdq_hash.is_some());
+        assert_eq!(partial.metadata["partial_decode"], true);
+
+        // Nothing past the header survives: the decode error stands
+        assert!(matches!(process_image_bytes("header.jpg", &jpeg[..20]), Err(AppError::InvalidInput(_))));
+    }
+
+    #[test]
+    fn test_truncated_files_verify_against_their_own_manifests() {
+        let img = image::RgbImage::from_fn(96, 96, |x, y| image::Rgb([(x * 2) as u8, (y * 2) as u8, ((x ^ y) * 3) as u8]));
+        let dir = tempfile::tempdir().unwrap();
+        for format in [image::ImageOutputFormat::Jpeg(90), image::ImageOutputFormat::Png] {
+            let mut encoded = std::io::Cursor::new(Vec::new());
+            image::DynamicImage::ImageRgb8(img.clone()).write_to(&mut encoded, format).unwrap();
+            let encoded = encoded.into_inner();
+            let path = dir.path().join("truncated");
+            std::fs::write(&path, &encoded[..encoded.len() * 2 / 3]).unwrap();
+
+            let manifest = process_image(&path).unwrap();
+            assert_eq!(manifest.metadata[PARTIAL_DECODE_KEY], true);
+            let report = manifest.verify_report(&path).unwrap();
+            assert!(report.is_valid, "{:?}", report.reason);
+            assert!(manifest.verify_report_in_cached(dir.path(), &crate::core::cache::DecodeCache::new(4)).unwrap().is_valid);
+        }
+    }
+
+    #[test]
+    fn test_tiny_image_gets_no_pdq_hash_and_still_verifies() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tiny.png");
+        image::RgbImage::from_fn(2, 2, |x, y| image::Rgb([(x * 200) as u8, (y * 200) as u8, 128]))
+            .save(&path)
+            .unwrap();
+
+        let manifest = process_image(&path).unwrap();
+        assert_eq!(manifest.pdq_hash, None);
+        assert_eq!(manifest.pdq_format, None);
+        assert_eq!(manifest.metadata[TOO_SMALL_FOR_PDQ_KEY], true);
+        // The perceptual check is skipped; the file hash still has to match
+        assert!(manifest.verify(&path).unwrap());
+        std::fs::write(&path, b"replaced").unwrap();
+        assert!(!manifest.verify(&path).unwrap());
+    }
+
+    #[test]
+    fn test_configured_pdq_minimum_is_honoured() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("small.png");
+        image::RgbImage::from_fn(32, 32, |x, y| image::Rgb([(x * 8) as u8, (y * 8) as u8, 128]))
+            .save(&path)
+            .unwrap();
+        let data = std::fs::read(&path).unwrap();
+
+        assert!(process_image_bytes_with("small.png", &data, 16).unwrap().pdq_hash.is_some());
+        let mut manifest = process_image_bytes_with("small.png", &data, 64).unwrap();
+        assert_eq!(manifest.pdq_hash, None);
+        assert_eq!(manifest.metadata[TOO_SMALL_FOR_PDQ_KEY], true);
+
+        manifest.recompute_perceptual_with(&path, 16).unwrap();
+        assert!(manifest.pdq_hash.is_some());
+        assert!(manifest.metadata.get(TOO_SMALL_FOR_PDQ_KEY).is_none());
+        manifest.recompute_perceptual_with(&path, 64).unwrap();
+        assert_eq!(manifest.pdq_hash, None);
+    }
+
+    #[test]
+    fn test_lossless_webp_has_the_content_hash_of_a_png_of_the_same_pixels() {
+        use image::codecs::webp::WebPEncoder;
+
+        let img = image::RgbaImage::from_fn(40, 30, |x, y| image::Rgba([(x * 6) as u8, (y * 8) as u8, ((x ^ y) * 5) as u8, (255 - x * 3) as u8]));
+        let mut png = std::io::Cursor::new(Vec::new());
+        image::DynamicImage::ImageRgba8(img.clone())
+            .write_to(&mut png, image::ImageOutputFormat::Png)
+            .unwrap();
+        let mut webp = Vec::new();
+        WebPEncoder::new_lossless(&mut webp)
+            .encode(img.as_raw(), img.width(), img.height(), image::ColorType::Rgba8)
+            .unwrap();
+
+        let from_png = process_image_bytes("pixels.png", &png.into_inner()).unwrap();
+        let from_webp = process_image_bytes("pixels.webp", &webp).unwrap();
+        assert!(from_png.content_hash.is_some());
+        assert_eq!(from_webp.content_hash, from_png.content_hash);
+        assert_eq!(from_webp.metadata[WEBP_LOSSLESS_KEY], true);
+        assert!(from_png.metadata.get(WEBP_LOSSLESS_KEY).is_none());
+
+        // A lossy bitstream, alone or alongside an alpha chunk, makes no such claim
+        let riff = |chunks: &[(&[u8; 4], &[u8])]| {
+            let mut body = b"WEBP".to_vec();
+            for (kind, data) in chunks {
+                body.extend_from_slice(*kind);
+                body.extend_from_slice(&(data.len() as u32).to_le_bytes());
+                body.extend_from_slice(data);
+                body.resize(body.len() + data.len() % 2, 0);
+            }
+            [b"RIFF".as_slice(), &(body.len() as u32).to_le_bytes(), &body].concat()
+        };
+        assert_eq!(hash::webp_is_lossless(&webp), Some(true));
+        assert_eq!(hash::webp_is_lossless(&riff(&[(b"VP8 ", &[0; 9])])), Some(false));
+        assert_eq!(hash::webp_is_lossless(&riff(&[(b"VP8X", &[0; 10]), (b"ALPH", &[0; 3]), (b"VP8 ", &[0; 9])])), Some(false));
+        assert_eq!(hash::webp_is_lossless(b"\x89PNG\r\n\x1a\n"), None);
+    }
+
+    #[test]
+    fn test_deeply_nested_webp_animation_frames_do_not_overflow_the_stack() {
+        use image::codecs::webp::WebPEncoder;
+
+        let img = image::RgbaImage::from_fn(16, 16, |x, y| image::Rgba([(x * 16) as u8, (y * 16) as u8, 0, 255]));
+        let mut webp = Vec::new();
+        WebPEncoder::new_lossless(&mut webp)
+            .encode(img.as_raw(), img.width(), img.height(), image::ColorType::Rgba8)
+            .unwrap();
+
+        // 200k ANMF chunks, each nested in the previous, around a lossy bitstream
+        let depth = 200_000;
+        let innermost = [b"VP8 ".as_slice(), &10u32.to_le_bytes(), &[0; 10]].concat();
+        for level in 0..depth {
+            let size = 16 + (depth - level - 1) * 24 + innermost.len();
+            webp.extend_from_slice(b"ANMF");
+            webp.extend_from_slice(&(size as u32).to_le_bytes());
+            webp.extend_from_slice(&[0; 16]);
+        }
+        webp.extend_from_slice(&innermost);
+
+        // Only frames at the top level count, so the nested lossy bitstream is ignored
+        assert_eq!(hash::webp_is_lossless(&webp), Some(true));
+        let manifest = process_image_bytes("deep.webp", &webp).unwrap();
+        assert_eq!(manifest.metadata[WEBP_LOSSLESS_KEY], true);
+    }
+
+    #[test]
+    fn test_directory_and_missing_paths_get_typed_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let folder = dir.path().join("photos.png");
+        std::fs::create_dir(&folder).unwrap();
+        let missing = dir.path().join("missing.png");
+
+        match process_image(&folder) {
+            Err(AppError::InvalidInput(message)) => {
+                assert!(message.contains("expected a file, got a directory"), "{}", message);
+                assert!(message.contains("photos.png"), "{}", message);
+            }
+            other => panic!("expected an invalid input error, got {:?}", other),
+        }
+        match process_image(&missing) {
+            Err(AppError::NotFound(message)) => assert!(message.contains("missing.png"), "{}", message),
+            other => panic!("expected a not found error, got {:?}", other),
+        }
+
+        #[cfg(feature = "video")]
+        {
+            let folder = dir.path().join("clips.mp4");
+            std::fs::create_dir(&folder).unwrap();
+            assert!(matches!(process_video_file(&folder), Err(AppError::InvalidInput(_))));
+            assert!(matches!(process_video_file(dir.path().join("missing.mp4")), Err(AppError::NotFound(_))));
+        }
+    }
+}
@@ -8,26 +8,92 @@
 
 //! Main entry point for the ImageChain application
 
-use std::{net::SocketAddr, path::PathBuf};
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
 
 use axum::{
-    http::{header, Method},
+    body::Body,
+    http::{header, Request},
     routing::get,
-    extract::DefaultBodyLimit,
     Router,
 };
 use dotenv::dotenv;
+use hyper_util::{
+    rt::{TokioExecutor, TokioIo},
+    server::{conn::auto, graceful::GracefulShutdown},
+    service::TowerToHyperService,
+};
 use tower_http::{
     compression::CompressionLayer,
-    cors::{Any, CorsLayer},
-    limit::RequestBodyLimitLayer,
     propagate_header::PropagateHeaderLayer,
-    request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer},
+    request_id::{MakeRequestUuid, PropagateRequestIdLayer, RequestId, SetRequestIdLayer},
     trace::TraceLayer,
 };
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+use tracing_subscriber::{
+    fmt::MakeWriter,
+    layer::SubscriberExt,
+    registry::LookupSpan,
+    util::SubscriberInitExt,
+    EnvFilter, Layer,
+};
+
+use imagechain::{
+    core::report::{junit_xml, rehash_dir, verify_dir},
+    core::store::{reconcile_with_policy, DirManifestStore, ManifestStore, OrphanPolicy},
+    create_router_with_config, health_check, watch_directory, AppError, AppState, Config, Result,
+};
+
+/// Output format for log lines
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LogFormat {
+    /// Human-readable output for local development
+    Pretty,
+    /// One JSON object per line for log aggregators
+    Json,
+}
 
-use imagechain::{create_router, health_check, AppState, Config, Result};
+impl LogFormat {
+    /// Read the format from the `LOG_FORMAT` environment variable (`pretty|json`)
+    fn from_env() -> Self {
+        match std::env::var("LOG_FORMAT") {
+            Ok(v) if v.eq_ignore_ascii_case("json") => Self::Json,
+            _ => Self::Pretty,
+        }
+    }
+}
+
+/// Build the formatting layer for the given format and writer
+fn fmt_layer<S, W>(format: LogFormat, writer: W) -> Box<dyn Layer<S> + Send + Sync>
+where
+    S: tracing::Subscriber + for<'a> LookupSpan<'a>,
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    match format {
+        LogFormat::Pretty => tracing_subscriber::fmt::layer()
+            .with_writer(writer)
+            .with_target(true)
+            .with_ansi(true)
+            .with_level(true)
+            .with_file(true)
+            .with_line_number(true)
+            .boxed(),
+        LogFormat::Json => tracing_subscriber::fmt::layer()
+            .json()
+            .with_writer(writer)
+            .with_current_span(true)
+            .with_span_list(true)
+            .flatten_event(true)
+            .with_target(true)
+            .with_file(true)
+            .with_line_number(true)
+            .boxed(),
+    }
+}
 
 /// Initialize logging and tracing
 fn init_logging() {
@@ -36,45 +102,404 @@ fn init_logging() {
         .or_else(|_| EnvFilter::try_new("info"))
         .unwrap();
 
-    let fmt_layer = tracing_subscriber::fmt::layer()
-        .with_target(true)
-        .with_ansi(true)
-        .with_level(true)
-        .with_file(true)
-        .with_line_number(true);
-
     tracing_subscriber::registry()
         .with(env_filter)
-        .with(fmt_layer)
+        .with(fmt_layer(LogFormat::from_env(), std::io::stdout))
         .init();
 }
 
 /// Parse configuration from environment variables
+///
+/// Settings that cannot be parsed are logged and fall back to their defaults.
 fn parse_config() -> Config {
-    let upload_dir = std::env::var("UPLOAD_DIR")
+    let (config, problems) = config_from(|key| std::env::var(key));
+    for problem in problems {
+        log::warn!("Ignoring {}", problem);
+    }
+    config
+}
+
+/// Builds the configuration from the variables `var` looks up
+///
+/// Also returns a description of every setting that was present but could
+/// not be used; those settings keep their defaults.
+fn config_from<F>(var: F) -> (Config, Vec<String>)
+where
+    F: Fn(&str) -> std::result::Result<String, std::env::VarError>,
+{
+    let mut problems = Vec::new();
+    let upload_dir = var("UPLOAD_DIR")
         .map(PathBuf::from)
         .unwrap_or_else(|_| PathBuf::from("uploads"));
 
-    let max_upload_size = std::env::var("MAX_UPLOAD_SIZE")
-        .ok()
-        .and_then(|s| s.parse().ok())
+    let max_upload_size = parse_var(&var, "MAX_UPLOAD_SIZE", &mut problems)
         .unwrap_or(500 * 1024 * 1024); // Default to 500MB for video files
 
-    Config {
+    let download_secret = var("DOWNLOAD_URL_SECRET")
+        .ok()
+        .filter(|s| !s.is_empty());
+
+    let defaults = Config::default();
+
+    let upload_temp_dir = var("UPLOAD_TEMP_DIR")
+        .ok()
+        .filter(|s| !s.is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(|| defaults.upload_temp_dir.clone());
+
+    let max_concurrent_jobs = parse_var(&var, "MAX_CONCURRENT_JOBS", &mut problems)
+        .unwrap_or(defaults.max_concurrent_jobs);
+
+    let reject_when_busy = parse_flag(&var, "REJECT_WHEN_BUSY", &mut problems)
+        .unwrap_or(defaults.reject_when_busy);
+
+    let parallel_hashing = parse_flag(&var, "PARALLEL_HASHING", &mut problems)
+        .unwrap_or(defaults.parallel_hashing);
+
+    let concurrent_pipeline = parse_flag(&var, "CONCURRENT_PIPELINE", &mut problems)
+        .unwrap_or(defaults.concurrent_pipeline);
+
+    let fast_stub_hashes = parse_flag(&var, "FAST_STUB_HASHES", &mut problems)
+        .unwrap_or(defaults.fast_stub_hashes);
+
+    let closest_match_threshold = parse_var(&var, "CLOSEST_MATCH_THRESHOLD", &mut problems)
+        .unwrap_or(defaults.closest_match_threshold);
+
+    let min_pdq_dimension = parse_var(&var, "MIN_PDQ_DIMENSION", &mut problems)
+        .unwrap_or(defaults.min_pdq_dimension);
+
+    let max_distance_matrix_size = parse_var(&var, "MAX_DISTANCE_MATRIX_SIZE", &mut problems)
+        .unwrap_or(defaults.max_distance_matrix_size);
+
+    let max_video_duration_secs = parse_var(&var, "MAX_VIDEO_DURATION_SECS", &mut problems)
+        .unwrap_or(defaults.max_video_duration_secs);
+
+    let max_total_frames = parse_var(&var, "MAX_TOTAL_FRAMES", &mut problems)
+        .unwrap_or(defaults.max_total_frames);
+
+    let max_download_bytes = parse_var(&var, "MAX_DOWNLOAD_BYTES", &mut problems)
+        .unwrap_or(defaults.max_download_bytes);
+
+    let download_timeout = parse_var(&var, "DOWNLOAD_TIMEOUT_SECS", &mut problems)
+        .map(Duration::from_secs)
+        .unwrap_or(defaults.download_timeout);
+
+    let block_private_addresses = parse_flag(&var, "BLOCK_PRIVATE_ADDRESSES", &mut problems)
+        .unwrap_or(defaults.block_private_addresses);
+
+    let profiles = match var("PROCESSING_PROFILES") {
+        Ok(json) if !json.trim().is_empty() => serde_json::from_str(&json).unwrap_or_else(|e| {
+            problems.push(format!("invalid PROCESSING_PROFILES: {}", e));
+            defaults.profiles.clone()
+        }),
+        _ => defaults.profiles.clone(),
+    };
+
+    let watch_dir = var("WATCH_DIR")
+        .ok()
+        .filter(|s| !s.is_empty())
+        .map(PathBuf::from);
+
+    let cors_allowed_origins = var("CORS_ALLOWED_ORIGINS")
+        .map(|s| {
+            s.split(',')
+                .map(str::trim)
+                .filter(|origin| !origin.is_empty())
+                .map(String::from)
+                .collect()
+        })
+        .unwrap_or_else(|_| defaults.cors_allowed_origins.clone());
+
+    let cors_allow_credentials = parse_flag(&var, "CORS_ALLOW_CREDENTIALS", &mut problems)
+        .unwrap_or(defaults.cors_allow_credentials);
+
+    let shutdown_timeout = parse_var(&var, "SHUTDOWN_TIMEOUT_SECS", &mut problems)
+        .map(Duration::from_secs)
+        .unwrap_or(defaults.shutdown_timeout);
+
+    let quarantine_dir = var("QUARANTINE_DIR")
+        .ok()
+        .filter(|s| !s.is_empty())
+        .map(PathBuf::from);
+
+    let max_json_body_size = parse_var(&var, "MAX_JSON_BODY_SIZE", &mut problems)
+        .unwrap_or(defaults.max_json_body_size);
+
+    let request_timeout = parse_var(&var, "REQUEST_TIMEOUT_SECS", &mut problems)
+        .map(Duration::from_secs)
+        .or(defaults.request_timeout);
+
+    let http1_keep_alive = parse_flag(&var, "HTTP1_KEEP_ALIVE", &mut problems)
+        .unwrap_or(defaults.http1_keep_alive);
+
+    let http2_keep_alive_interval = parse_var(&var, "HTTP2_KEEP_ALIVE_SECS", &mut problems)
+        .map(Duration::from_secs)
+        .or(defaults.http2_keep_alive_interval);
+
+    let manifest_dir = var("MANIFEST_DIR")
+        .ok()
+        .filter(|s| !s.is_empty())
+        .map(PathBuf::from);
+
+    let orphan_policy = match var("ORPHAN_POLICY") {
+        Ok(v) if v.eq_ignore_ascii_case("delete") => OrphanPolicy::Delete,
+        Ok(v) if v.eq_ignore_ascii_case("log") => OrphanPolicy::Log,
+        Ok(v) if !v.trim().is_empty() => {
+            problems.push(format!("invalid ORPHAN_POLICY {:?}: expected log or delete", v));
+            defaults.orphan_policy
+        }
+        _ => defaults.orphan_policy,
+    };
+
+    let jobs_dir = var("JOBS_DIR")
+        .ok()
+        .filter(|s| !s.is_empty())
+        .map(PathBuf::from);
+
+    let decode_cache_size = parse_var(&var, "DECODE_CACHE_SIZE", &mut problems)
+        .unwrap_or(defaults.decode_cache_size);
+
+    let config = Config {
         upload_dir,
+        upload_temp_dir,
         max_upload_size,
-        ..Default::default()
+        max_json_body_size,
+        download_secret,
+        max_concurrent_jobs,
+        reject_when_busy,
+        parallel_hashing,
+        concurrent_pipeline,
+        fast_stub_hashes,
+        closest_match_threshold,
+        min_pdq_dimension,
+        max_distance_matrix_size,
+        max_video_duration_secs,
+        max_total_frames,
+        max_download_bytes,
+        download_timeout,
+        block_private_addresses,
+        profiles,
+        watch_dir,
+        shutdown_timeout,
+        cors_allowed_origins,
+        cors_allow_credentials,
+        decode_cache_size,
+        quarantine_dir,
+        manifest_dir,
+        orphan_policy,
+        jobs_dir,
+        request_timeout,
+        http1_keep_alive,
+        http2_keep_alive_interval,
+        ..defaults
+    };
+    (config, problems)
+}
+
+/// Parses the variable `key`, if set and not blank
+///
+/// A value that doesn't parse is described in `problems` and treated as unset.
+fn parse_var<T, F>(var: &F, key: &str, problems: &mut Vec<String>) -> Option<T>
+where
+    T: std::str::FromStr,
+    T::Err: std::fmt::Display,
+    F: Fn(&str) -> std::result::Result<String, std::env::VarError>,
+{
+    let value = var(key).ok().filter(|value| !value.trim().is_empty())?;
+    match value.trim().parse() {
+        Ok(parsed) => Some(parsed),
+        Err(e) => {
+            problems.push(format!("invalid {} {:?}: {}", key, value, e));
+            None
+        }
+    }
+}
+
+/// Parses the flag `key` as `1`/`true` or `0`/`false`, if set and not blank
+///
+/// Any other value is described in `problems` and treated as unset.
+fn parse_flag<F>(var: &F, key: &str, problems: &mut Vec<String>) -> Option<bool>
+where
+    F: Fn(&str) -> std::result::Result<String, std::env::VarError>,
+{
+    let value = var(key).ok().filter(|value| !value.trim().is_empty())?;
+    match value.trim() {
+        "1" => Some(true),
+        "0" => Some(false),
+        flag if flag.eq_ignore_ascii_case("true") => Some(true),
+        flag if flag.eq_ignore_ascii_case("false") => Some(false),
+        _ => {
+            problems.push(format!("invalid {} {:?}: expected true or false", key, value));
+            None
+        }
+    }
+}
+
+/// Re-reads the config file at `path` and applies it to `state`
+///
+/// Variables set in the file override `environment`, the process environment
+/// as it was before the file was first loaded; a variable removed from the
+/// file falls back to it, or to its default. The current configuration stays
+/// in effect if the file cannot be read or holds a setting that cannot be used.
+fn reload_config_file(state: &AppState, path: &Path, environment: &HashMap<String, String>) -> Result<()> {
+    let unreadable = |e: dotenv::Error| AppError::Config(format!("cannot read {}: {}", path.display(), e));
+    let mut overrides = HashMap::new();
+    // `from_path` never replaces variables that are already set, as every
+    // variable from the startup load now is, so read the entries instead
+    #[allow(deprecated)]
+    let entries = dotenv::from_path_iter(path).map_err(unreadable)?;
+    for entry in entries {
+        let (key, value) = entry.map_err(unreadable)?;
+        overrides.insert(key, value);
+    }
+
+    let (config, problems) = config_from(|key| {
+        overrides
+            .get(key)
+            .or_else(|| environment.get(key))
+            .cloned()
+            .ok_or(std::env::VarError::NotPresent)
+    });
+    if let Some(problem) = problems.into_iter().next() {
+        return Err(AppError::Config(problem));
+    }
+    state.reload_config(config)
+}
+
+/// Reloads the config file at `path` into `state` on every SIGHUP
+///
+/// The handler is installed before this returns, so a SIGHUP sent afterwards
+/// no longer terminates the process. Open connections are unaffected.
+/// `environment` is as for [`reload_config_file`].
+#[cfg(unix)]
+fn spawn_reload_on_hangup(
+    state: Arc<AppState>,
+    path: PathBuf,
+    environment: HashMap<String, String>,
+) -> std::io::Result<tokio::task::JoinHandle<()>> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangups = signal(SignalKind::hangup())?;
+    Ok(tokio::spawn(async move {
+        while hangups.recv().await.is_some() {
+            match reload_config_file(&state, &path, &environment) {
+                Ok(()) => log::info!("Reloaded configuration from {}", path.display()),
+                Err(e) => log::error!("Kept the current configuration, reload failed: {}", e),
+            }
+        }
+    }))
+}
+
+/// Usage of the `verify-dir` subcommand
+const VERIFY_DIR_USAGE: &str = "usage: imagechain verify-dir <dir> [--manifests <dir>] [--junit <file>]";
+
+/// Runs the `verify-dir` subcommand over the arguments that follow it
+///
+/// Verifies every manifest in the manifests directory (default: `<dir>`)
+/// against the files in `<dir>` and optionally writes a JUnit XML report.
+/// Returns whether every file verified.
+fn verify_dir_command(args: &[String]) -> Result<bool> {
+    let mut files_dir: Option<PathBuf> = None;
+    let mut manifests_dir: Option<PathBuf> = None;
+    let mut junit: Option<PathBuf> = None;
+    
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--manifests" => manifests_dir = args.next().map(PathBuf::from),
+            "--junit" => junit = args.next().map(PathBuf::from),
+            _ if files_dir.is_none() && !arg.starts_with("--") => files_dir = Some(PathBuf::from(arg)),
+            _ => {
+                return Err(AppError::InvalidInput(format!(
+                    "unexpected argument {}\n{}",
+                    arg, VERIFY_DIR_USAGE
+                )))
+            }
+        }
     }
+    
+    let files_dir = files_dir.ok_or_else(|| AppError::InvalidInput(VERIFY_DIR_USAGE.to_string()))?;
+    let manifests_dir = manifests_dir.unwrap_or_else(|| files_dir.clone());
+    
+    let results = verify_dir(&files_dir, &manifests_dir)?;
+    if let Some(junit) = junit {
+        std::fs::write(&junit, junit_xml("imagechain.verify", &results))?;
+        log::info!("Wrote JUnit report to {}", junit.display());
+    }
+    
+    let passed = results
+        .iter()
+        .filter(|(_, result)| matches!(result, Ok(report) if report.is_valid))
+        .count();
+    log::info!("{} of {} files verified", passed, results.len());
+    Ok(passed == results.len())
+}
+
+/// Usage of the `rehash-perceptual` subcommand
+const REHASH_USAGE: &str = "usage: imagechain rehash-perceptual <dir> [--manifests <dir>]";
+
+/// Runs the `rehash-perceptual` subcommand over the arguments that follow it
+///
+/// Recomputes the PDQ hashes of every manifest in the manifests directory
+/// (default: `<dir>`) from the files in `<dir>`, leaving crypto hashes alone.
+/// Returns whether every manifest was updated.
+fn rehash_perceptual_command(args: &[String]) -> Result<bool> {
+    let mut files_dir: Option<PathBuf> = None;
+    let mut manifests_dir: Option<PathBuf> = None;
+    
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--manifests" => manifests_dir = args.next().map(PathBuf::from),
+            _ if files_dir.is_none() && !arg.starts_with("--") => files_dir = Some(PathBuf::from(arg)),
+            _ => {
+                return Err(AppError::InvalidInput(format!(
+                    "unexpected argument {}\n{}",
+                    arg, REHASH_USAGE
+                )))
+            }
+        }
+    }
+    
+    let files_dir = files_dir.ok_or_else(|| AppError::InvalidInput(REHASH_USAGE.to_string()))?;
+    let manifests_dir = manifests_dir.unwrap_or_else(|| files_dir.clone());
+    
+    let results = rehash_dir(&files_dir, &manifests_dir, parse_config().min_pdq_dimension)?;
+    for (name, result) in &results {
+        if let Err(e) = result {
+            log::error!("Failed to rehash {}: {}", name, e);
+        }
+    }
+    
+    let updated = results.iter().filter(|(_, result)| result.is_ok()).count();
+    log::info!("{} of {} manifests rehashed", updated, results.len());
+    Ok(updated == results.len())
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    // Load environment variables from .env file if it exists
-    dotenv().ok();
+    // Load environment variables from CONFIG_FILE, or a .env file if it exists,
+    // remembering the environment without them for reloads
+    let environment: HashMap<String, String> = std::env::vars().collect();
+    let config_file = match std::env::var("CONFIG_FILE") {
+        Ok(path) => dotenv::from_path(&path).ok().map(|()| PathBuf::from(path)),
+        Err(_) => dotenv().ok(),
+    };
     
     // Initialize logging
     init_logging();
     
+    // CLI subcommands run instead of the server
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("verify-dir") {
+        let all_verified = verify_dir_command(&args[1..])?;
+        std::process::exit(if all_verified { 0 } else { 1 });
+    }
+    if args.first().map(String::as_str) == Some("rehash-perceptual") {
+        let all_updated = rehash_perceptual_command(&args[1..])?;
+        std::process::exit(if all_updated { 0 } else { 1 });
+    }
+    
     // Parse configuration
     let config = parse_config();
     
@@ -85,67 +510,279 @@ async fn main() -> Result<()> {
     
     log::info!("Upload directory: {}", config.upload_dir.display());
     log::info!("Max upload size: {} bytes", config.max_upload_size);
+    log::info!("Max JSON body size: {} bytes", config.max_json_body_size);
+    log::info!("Max concurrent jobs: {}", config.max_concurrent_jobs);
     
-    // Store max upload size before moving config
-    let max_upload_size = config.max_upload_size;
+    // Process files dropped into the watch directory in the background
+    if let Some(watch_dir) = config.watch_dir.clone() {
+        tokio::spawn(async move {
+            let result = watch_directory(watch_dir, |result| match result {
+                Ok(manifest) => log::info!(
+                    "Processed {} ({})",
+                    manifest.file_name,
+                    manifest.sha3_256_hash
+                ),
+                Err(e) => log::warn!("Failed to process watched file: {}", e),
+            })
+            .await;
+            if let Err(e) = result {
+                log::error!("Directory watcher stopped: {}", e);
+            }
+        });
+    }
     
     // Initialize application state
     let state = AppState::with_config(config);
-    
-    // Set up CORS
-    let cors = CorsLayer::new()
-        .allow_origin(Any)
-        .allow_methods([
-            Method::GET,
-            Method::POST,
-            Method::PUT,
-            Method::DELETE,
-            Method::OPTIONS,
-        ])
-        .allow_headers([
-            header::CONTENT_TYPE,
-            header::AUTHORIZATION,
-            header::ACCEPT,
-        ])
-        .allow_credentials(false);
+
+    // Bring persisted manifests and uploaded files back in line, then reload the index
+    let config = state.config();
+    if let Some(manifest_dir) = &config.manifest_dir {
+        let store = DirManifestStore::new(manifest_dir);
+        let report = reconcile_with_policy(&store, &config.upload_dir, config.orphan_policy)?;
+        log::info!(
+            "Reconciled manifests: {} orphaned uploads, {} orphaned manifests",
+            report.orphan_files.len(),
+            report.orphan_manifests.len()
+        );
+        let saved = store.load_index().unwrap_or_else(|e| {
+            log::warn!("Ignoring unreadable saved PDQ index: {}", e);
+            None
+        });
+        if state.restore_pdq_index(&store.manifests()?, saved)? {
+            log::info!("Loaded saved PDQ index");
+        } else {
+            log::info!("Rebuilt PDQ index from manifests");
+        }
+    }
+
+    // Pick up batch jobs interrupted by the last shutdown
+    let resumed = state.resume_jobs()?;
+    if resumed > 0 {
+        log::info!("Resumed {} interrupted batch jobs", resumed);
+    }
     
     // Build our application with routes and middleware
     let app = Router::new()
         // Public health check endpoint
         .route("/health", get(health_check))
-        // API routes with rate limiting
-        .merge(create_router())
-        // Add middleware
-        .layer(cors)
-        .layer(TraceLayer::new_for_http())
-        .layer(SetRequestIdLayer::x_request_id(MakeRequestUuid))
-        .layer(PropagateRequestIdLayer::x_request_id())
+        // API routes, with CORS from the configuration
+        .merge(create_router_with_config(&config)?);
+    // Add middleware
+    let app = with_request_ids(app)
         .layer(PropagateHeaderLayer::new(header::HeaderName::from_static(
             "x-request-id",
         )))
-        .layer(DefaultBodyLimit::max(max_upload_size as usize))
-        .layer(RequestBodyLimitLayer::new(max_upload_size as usize))
         .layer(CompressionLayer::new());
     
-    // Get the port from environment or use default
-    let port = std::env::var("PORT")
-        .ok()
-        .and_then(|p| p.parse().ok())
-        .unwrap_or(3000);
+    // Pick up edits to the config file on SIGHUP
+    #[cfg(unix)]
+    if let Some(path) = config_file {
+        spawn_reload_on_hangup(state.clone(), path.clone(), environment)?;
+        log::info!("Reloading configuration from {} on SIGHUP", path.display());
+    }
+    #[cfg(not(unix))]
+    let _ = (config_file, environment);
     
-    let addr = SocketAddr::from(([0, 0, 0, 0], port));
-    log::info!("Server listening on http://{}", addr);
+    // Listen where BIND says, or on 0.0.0.0:PORT
+    let bind = BindAddr::from_env()?;
+    let listener = Listener::bind(&bind).await?;
+    log::info!("Server listening on {}", bind);
     
-    // Start the server (axum 0.7)
-    let listener = tokio::net::TcpListener::bind(addr).await?;
-    axum::serve(listener, app.with_state(state))
-        .with_graceful_shutdown(shutdown_signal())
-        .await?;
+    // Start the server
+    serve(listener, app.with_state(state.clone()), &config).await;
+    
+    // Let background jobs finish writing before the runtime goes away
+    let shutdown_timeout = state.config().shutdown_timeout;
+    let report = state.drain_jobs(shutdown_timeout).await;
+    if report.aborted > 0 {
+        log::warn!(
+            "Drained {} background jobs, abandoned {} still running after {:?}",
+            report.drained,
+            report.aborted,
+            shutdown_timeout
+        );
+    } else {
+        log::info!("Drained {} background jobs", report.drained);
+    }
+    
+    // Save the index so the next startup can skip rebuilding it
+    if let Some(manifest_dir) = &config.manifest_dir {
+        let index = state.pdq_index.read().unwrap_or_else(|e| e.into_inner());
+        match DirManifestStore::new(manifest_dir).save_index(&index) {
+            Ok(()) => log::info!("Saved PDQ index of {} hashes", index.len()),
+            Err(e) => log::warn!("Failed to save PDQ index: {}", e),
+        }
+    }
     
     log::info!("Server shutdown complete");
     Ok(())
 }
 
+/// Permissions of the Unix socket file: read/write for the owner and group
+#[cfg(unix)]
+const UNIX_SOCKET_MODE: u32 = 0o660;
+
+/// Where the server accepts connections
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum BindAddr {
+    /// A TCP `host:port`
+    Tcp(SocketAddr),
+    /// A Unix domain socket at this path
+    #[cfg(unix)]
+    Unix(PathBuf),
+}
+
+impl BindAddr {
+    /// Read the address from `BIND`, falling back to `0.0.0.0:PORT` (port 3000 by default)
+    fn from_env() -> Result<Self> {
+        match std::env::var("BIND") {
+            Ok(value) if !value.trim().is_empty() => Self::parse(value.trim()),
+            _ => {
+                let port = std::env::var("PORT")
+                    .ok()
+                    .and_then(|p| p.parse().ok())
+                    .unwrap_or(3000);
+                Ok(Self::Tcp(SocketAddr::from(([0, 0, 0, 0], port))))
+            }
+        }
+    }
+
+    /// Parse `unix:/path/to.sock` or `host:port`
+    fn parse(value: &str) -> Result<Self> {
+        if let Some(path) = value.strip_prefix("unix:") {
+            #[cfg(unix)]
+            return Ok(Self::Unix(PathBuf::from(path)));
+            #[cfg(not(unix))]
+            return Err(AppError::Config(format!("Unix sockets are not supported here: {}", path)));
+        }
+        value
+            .parse()
+            .map(Self::Tcp)
+            .map_err(|e| AppError::Config(format!("invalid BIND address {:?}: {}", value, e)))
+    }
+}
+
+impl std::fmt::Display for BindAddr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Tcp(addr) => write!(f, "http://{}", addr),
+            #[cfg(unix)]
+            Self::Unix(path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+/// A bound TCP or Unix listener
+#[derive(Debug)]
+enum Listener {
+    Tcp(tokio::net::TcpListener),
+    #[cfg(unix)]
+    Unix(tokio::net::UnixListener),
+}
+
+#[cfg(unix)]
+type Stream = tokio_util::either::Either<tokio::net::TcpStream, tokio::net::UnixStream>;
+#[cfg(not(unix))]
+type Stream = tokio::net::TcpStream;
+
+impl Listener {
+    /// Bind `addr`; a stale socket file at a Unix path is replaced
+    async fn bind(addr: &BindAddr) -> Result<Self> {
+        match addr {
+            BindAddr::Tcp(addr) => Ok(Self::Tcp(tokio::net::TcpListener::bind(addr).await?)),
+            #[cfg(unix)]
+            BindAddr::Unix(path) => {
+                use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+
+                if std::fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_socket()) {
+                    std::fs::remove_file(path)?;
+                }
+                let listener = tokio::net::UnixListener::bind(path)?;
+                std::fs::set_permissions(path, std::fs::Permissions::from_mode(UNIX_SOCKET_MODE))?;
+                Ok(Self::Unix(listener))
+            }
+        }
+    }
+
+    async fn accept(&self) -> std::io::Result<Stream> {
+        match self {
+            #[cfg(unix)]
+            Self::Tcp(listener) => Ok(tokio_util::either::Either::Left(listener.accept().await?.0)),
+            #[cfg(not(unix))]
+            Self::Tcp(listener) => Ok(listener.accept().await?.0),
+            #[cfg(unix)]
+            Self::Unix(listener) => Ok(tokio_util::either::Either::Right(listener.accept().await?.0)),
+        }
+    }
+}
+
+/// Tags each request with an `x-request-id` and logs it under a span carrying it
+///
+/// The id is the client's if it sent one, otherwise a fresh UUID, and is
+/// echoed on the response.
+fn with_request_ids<S: Clone + Send + Sync + 'static>(app: Router<S>) -> Router<S> {
+    // Later layers wrap earlier ones, so the id is set before the span is
+    // made and is already on the request when it is propagated
+    app.layer(PropagateRequestIdLayer::x_request_id())
+        .layer(TraceLayer::new_for_http().make_span_with(request_span))
+        .layer(SetRequestIdLayer::x_request_id(MakeRequestUuid))
+}
+
+/// The span a request is logged under, with its id as a field so it appears
+/// on every log line
+fn request_span(request: &Request<Body>) -> tracing::Span {
+    let request_id = request
+        .extensions()
+        .get::<RequestId>()
+        .and_then(|id| id.header_value().to_str().ok())
+        .unwrap_or_default();
+    tracing::info_span!(
+        "request",
+        method = %request.method(),
+        uri = %request.uri(),
+        request_id = %request_id,
+    )
+}
+
+/// Serves `app` until a shutdown signal, then waits for open connections
+///
+/// Each connection speaks HTTP/1.1 or HTTP/2 (detected from the client's
+/// preface, i.e. prior knowledge), with keep-alive behaviour taken from
+/// `config`.
+async fn serve(listener: Listener, app: Router, config: &Config) {
+    let mut builder = auto::Builder::new(TokioExecutor::new());
+    builder.http1().keep_alive(config.http1_keep_alive);
+    builder.http2().keep_alive_interval(config.http2_keep_alive_interval);
+
+    let graceful = GracefulShutdown::new();
+    let mut shutdown = std::pin::pin!(shutdown_signal());
+    loop {
+        let stream = tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok(stream) => stream,
+                Err(e) => {
+                    log::warn!("Failed to accept connection: {}", e);
+                    continue;
+                }
+            },
+            _ = &mut shutdown => break,
+        };
+
+        let service = TowerToHyperService::new(app.clone());
+        let connection = builder
+            .serve_connection_with_upgrades(TokioIo::new(stream), service)
+            .into_owned();
+        let connection = graceful.watch(connection);
+        tokio::spawn(async move {
+            if let Err(e) = connection.await {
+                log::debug!("Connection closed with error: {}", e);
+            }
+        });
+    }
+
+    graceful.shutdown().await;
+}
+
 /// Handle graceful shutdown
 async fn shutdown_signal() {
     let ctrl_c = async {
@@ -181,3 +818,195 @@ async fn shutdown_signal() {
         },
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// Writer that captures log output in memory
+    #[derive(Clone, Default)]
+    struct Capture(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for Capture {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<'a> MakeWriter<'a> for Capture {
+        type Writer = Capture;
+
+        fn make_writer(&'a self) -> Self::Writer {
+            self.clone()
+        }
+    }
+
+    #[test]
+    fn test_bind_addr_parsing() {
+        assert_eq!(
+            BindAddr::parse("127.0.0.1:8080").unwrap(),
+            BindAddr::Tcp(SocketAddr::from(([127, 0, 0, 1], 8080)))
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            BindAddr::parse("unix:/run/imagechain.sock").unwrap(),
+            BindAddr::Unix(PathBuf::from("/run/imagechain.sock"))
+        );
+        assert!(BindAddr::parse("localhost").is_err());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_serves_health_over_unix_socket() {
+        use std::os::unix::fs::PermissionsExt;
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("imagechain.sock");
+        let listener = Listener::bind(&BindAddr::Unix(path.clone())).await.unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, UNIX_SOCKET_MODE);
+
+        let app = Router::new().route("/health", get(health_check));
+        let server = tokio::spawn(async move { serve(listener, app, &Config::default()).await });
+
+        let mut stream = tokio::net::UnixStream::connect(&path).await.unwrap();
+        stream
+            .write_all(b"GET /health HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        server.abort();
+
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_sighup_reloads_the_config_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("imagechain.env");
+        std::fs::write(&path, "CLOSEST_MATCH_THRESHOLD=12\nUPLOAD_DIR=elsewhere\n").unwrap();
+        let state = AppState::new();
+        spawn_reload_on_hangup(state.clone(), path.clone(), HashMap::new()).unwrap();
+
+        let hangup = || {
+            let pid = std::process::id().to_string();
+            assert!(std::process::Command::new("kill").args(["-HUP", &pid]).status().unwrap().success());
+        };
+        let threshold_becomes = |expected: u32| {
+            let state = state.clone();
+            async move {
+                for _ in 0..200 {
+                    if state.config().closest_match_threshold == expected {
+                        return true;
+                    }
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                }
+                false
+            }
+        };
+
+        hangup();
+        assert!(threshold_becomes(12).await);
+        // Settings fixed at startup keep their startup values
+        assert_eq!(state.config().upload_dir, Config::default().upload_dir);
+
+        // A setting that cannot be used keeps the whole old configuration
+        std::fs::write(&path, "CLOSEST_MATCH_THRESHOLD=20\nPROCESSING_PROFILES={not json\n").unwrap();
+        hangup();
+        assert!(!threshold_becomes(20).await);
+
+        std::fs::write(&path, "CLOSEST_MATCH_THRESHOLD=20\n").unwrap();
+        hangup();
+        assert!(threshold_becomes(20).await);
+
+        // A variable removed from the file goes back to its default
+        std::fs::write(&path, "").unwrap();
+        hangup();
+        assert!(threshold_becomes(Config::default().closest_match_threshold).await);
+    }
+
+    #[test]
+    fn test_every_unusable_setting_is_reported() {
+        let (config, problems) = config_from(|key| match key {
+            "MAX_TOTAL_FRAMES" => Ok("lots".to_string()),
+            "REJECT_WHEN_BUSY" => Ok("maybe".to_string()),
+            "ORPHAN_POLICY" => Ok("shred".to_string()),
+            "CLOSEST_MATCH_THRESHOLD" => Ok(" 12 ".to_string()),
+            "PARALLEL_HASHING" => Ok("TRUE".to_string()),
+            "DECODE_CACHE_SIZE" => Ok(String::new()),
+            _ => Err(std::env::VarError::NotPresent),
+        });
+        assert_eq!(problems.len(), 3, "{:?}", problems);
+        for key in ["MAX_TOTAL_FRAMES", "REJECT_WHEN_BUSY", "ORPHAN_POLICY"] {
+            assert!(problems.iter().any(|problem| problem.contains(key)), "{:?}", problems);
+        }
+
+        let defaults = Config::default();
+        assert_eq!(config.max_total_frames, defaults.max_total_frames);
+        assert_eq!(config.reject_when_busy, defaults.reject_when_busy);
+        assert_eq!(config.decode_cache_size, defaults.decode_cache_size);
+        assert_eq!(config.closest_match_threshold, 12);
+        assert!(config.parallel_hashing);
+    }
+
+    #[test]
+    fn test_json_format_emits_parseable_lines() {
+        let capture = Capture::default();
+        let subscriber = tracing_subscriber::registry()
+            .with(fmt_layer(LogFormat::Json, capture.clone()));
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("request", request_id = "req-123");
+            let _guard = span.enter();
+            tracing::info!(file_size = 42, "processed upload");
+        });
+
+        let output = String::from_utf8(capture.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 1);
+
+        let line: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(line["message"], "processed upload");
+        assert_eq!(line["file_size"], 42);
+        assert_eq!(line["span"]["request_id"], "req-123");
+    }
+
+    #[tokio::test]
+    async fn test_requests_without_an_id_get_a_generated_one() {
+        use tower::ServiceExt;
+
+        let capture = Capture::default();
+        let subscriber = tracing_subscriber::registry()
+            .with(fmt_layer(LogFormat::Json, capture.clone()));
+        let _default = tracing::subscriber::set_default(subscriber);
+
+        let app = with_request_ids(Router::new().route(
+            "/",
+            get(|| async {
+                tracing::info!("handled");
+            }),
+        ));
+        let response = app
+            .oneshot(Request::get("/").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let request_id = response.headers()["x-request-id"].to_str().unwrap().to_string();
+        assert!(uuid::Uuid::parse_str(&request_id).is_ok(), "{}", request_id);
+
+        let output = String::from_utf8(capture.0.lock().unwrap().clone()).unwrap();
+        let handled = output
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+            .find(|line| line["message"] == "handled")
+            .unwrap();
+        assert_eq!(handled["span"]["request_id"], request_id.as_str());
+    }
+}
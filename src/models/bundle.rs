@@ -0,0 +1,127 @@
+use std::path::Path;
+
+use ed25519_dalek::{Signature, Signer, Verifier};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::error::Result;
+use crate::models::manifest::MediaManifest;
+
+pub use ed25519_dalek::{SigningKey, VerifyingKey};
+
+/// A manifest packaged with an Ed25519 signature over it, for distribution next to the file
+///
+/// The signature covers the manifest's canonical JSON (keys sorted), so the
+/// bundle can be reformatted or re-serialized without invalidating it.
+/// [`verify`](Self::verify) only proves the manifest was signed by the key in
+/// `public_key`; check that key against one you trust to establish who signed it.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ManifestBundle {
+    /// The signed manifest
+    pub manifest: MediaManifest,
+    /// Hex-encoded Ed25519 signature over the manifest's canonical JSON
+    pub signature: String,
+    /// Hex-encoded Ed25519 public key the signature verifies with
+    pub public_key: String,
+}
+
+impl ManifestBundle {
+    /// Signs `manifest` with `signing_key`
+    pub fn sign(manifest: MediaManifest, signing_key: &SigningKey) -> Result<Self> {
+        let signature = signing_key.sign(&canonical_json(&manifest)?);
+        Ok(Self {
+            manifest,
+            signature: hex::encode(signature.to_bytes()),
+            public_key: hex::encode(signing_key.verifying_key().to_bytes()),
+        })
+    }
+
+    /// The key the bundle claims to be signed with, if `public_key` is a valid one
+    pub fn verifying_key(&self) -> Option<VerifyingKey> {
+        let bytes: [u8; 32] = hex::decode(&self.public_key).ok()?.try_into().ok()?;
+        VerifyingKey::from_bytes(&bytes).ok()
+    }
+
+    /// Whether `signature` is a valid signature of the manifest by `public_key`
+    ///
+    /// A signature or key that does not decode counts as invalid.
+    pub fn signature_is_valid(&self) -> Result<bool> {
+        let (Some(key), Some(signature)) = (self.verifying_key(), self.decoded_signature()) else {
+            return Ok(false);
+        };
+        Ok(key.verify(&canonical_json(&self.manifest)?, &signature).is_ok())
+    }
+
+    /// Checks the signature and that the file at `file_path` matches the manifest
+    ///
+    /// The file is only hashed once the signature checks out.
+    pub fn verify<P: AsRef<Path>>(&self, file_path: P) -> Result<bool> {
+        Ok(self.signature_is_valid()? && self.manifest.verify(file_path)?)
+    }
+
+    /// Serializes the bundle to a single JSON document
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// Deserializes a bundle written by [`to_json`](Self::to_json)
+    ///
+    /// The manifest is taken as signed; unlike [`MediaManifest::from_json`],
+    /// old PDQ formats are not migrated, since that would break the signature.
+    pub fn from_json(json: &str) -> Result<Self> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    fn decoded_signature(&self) -> Option<Signature> {
+        let bytes: [u8; 64] = hex::decode(&self.signature).ok()?.try_into().ok()?;
+        Some(Signature::from_bytes(&bytes))
+    }
+}
+
+impl MediaManifest {
+    /// Packages the manifest with an Ed25519 signature by `signing_key`; see [`ManifestBundle`]
+    pub fn to_bundle(&self, signing_key: &SigningKey) -> Result<ManifestBundle> {
+        ManifestBundle::sign(self.clone(), signing_key)
+    }
+}
+
+/// The bytes a bundle's signature covers: the manifest's JSON with keys sorted
+fn canonical_json(manifest: &MediaManifest) -> Result<Vec<u8>> {
+    Ok(serde_json::to_value(manifest)?.to_string().into_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bundle_verifies_until_file_or_manifest_is_tampered_with() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("photo.png");
+        image::RgbImage::from_fn(64, 64, |x, y| image::Rgb([(x * 4) as u8, (y * 4) as u8, 128]))
+            .save(&path)
+            .unwrap();
+        let manifest = crate::process_image(&path).unwrap();
+        let key = SigningKey::from_bytes(&[7; 32]);
+
+        let bundle = manifest.to_bundle(&key).unwrap();
+        let reloaded = ManifestBundle::from_json(&bundle.to_json().unwrap()).unwrap();
+        assert!(reloaded.verify(&path).unwrap());
+        assert_eq!(reloaded.verifying_key(), Some(key.verifying_key()));
+
+        // A forged manifest no longer matches the signature
+        let mut forged = reloaded.clone();
+        forged.manifest.file_size += 1;
+        assert!(!forged.signature_is_valid().unwrap());
+        forged.signature = "not hex".to_string();
+        assert!(!forged.signature_is_valid().unwrap());
+
+        // A tampered file fails even though the signature holds
+        let mut bytes = std::fs::read(&path).unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xff;
+        std::fs::write(&path, bytes).unwrap();
+        assert!(reloaded.signature_is_valid().unwrap());
+        assert!(!reloaded.verify(&path).unwrap());
+    }
+}
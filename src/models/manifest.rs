@@ -1,22 +1,190 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::path::Path;
-use log::{info, warn};
-use crate::error::Result;
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Component, Path};
+use std::sync::Arc;
+use std::time::Instant;
+use image::DynamicImage;
+use log::{debug, info, warn};
+use crate::core::hash::{
+    apply_orientation, compute_content_hash, exif_orientation, pdq_bits_as, pdq_to_hex, PdqFormat,
+};
+use crate::core::cache::DecodeCache;
+use crate::core::similarity::ColorSignature;
+use crate::error::{AppError, Result};
+
+/// Default maximum PDQ distance for a re-extracted frame to still match.
+pub const DEFAULT_FRAME_TOLERANCE: u32 = 8;
+
+/// Algorithm name marking a manifest whose hashes are placeholders; see [`MediaManifest::stub`].
+pub const STUB_ALGORITHM: &str = "stub";
+
+/// Key under `metadata` holding client-supplied metadata; see [`MediaManifest::set_user_metadata`].
+pub const USER_METADATA_KEY: &str = "user";
+
+/// Metadata flag set on image manifests left without a perceptual hash because
+/// the image is smaller than the minimum dimension; see [`crate::core::hash::too_small_for_pdq`].
+pub const TOO_SMALL_FOR_PDQ_KEY: &str = "too_small_for_pdq";
+
+/// Metadata flag on WebP image manifests: `true` for lossless files, whose
+/// `content_hash` equals that of a PNG with the same pixels, `false` for lossy ones.
+pub const WEBP_LOSSLESS_KEY: &str = "webp_lossless";
+
+/// Metadata flag set on image manifests of truncated or slightly corrupt
+/// files; see [`crate::core::hash::decode_lenient`]. Their files are decoded
+/// the same lenient way when verified.
+pub const PARTIAL_DECODE_KEY: &str = "partial_decode";
+
+/// Version assumed for an algorithm a manifest records no version of, as in
+/// manifests written before versions were recorded.
+pub const UNVERSIONED_ALGORITHM: u32 = 1;
+
+/// Versions of the algorithms this build hashes with, keyed by name.
+///
+/// Always covers `"sha3_256"`; `perceptual` names the registered perceptual
+/// algorithm behind a manifest's `pdq_hash` or frame hashes, if it has any.
+pub fn current_algorithm_versions(perceptual: Option<&str>) -> BTreeMap<String, u32> {
+    let mut versions = BTreeMap::from([("sha3_256".to_string(), crate::core::hash::SHA3_256_VERSION)]);
+    if let Some(algorithm) = perceptual.and_then(|name| crate::core::perceptual::algorithm(Some(name)).ok()) {
+        versions.insert(algorithm.name().to_string(), algorithm.version());
+    }
+    versions
+}
+
+/// Fails with `AppError::VersionMismatch` unless the two versions of `algorithm` agree.
+fn version_check(algorithm: &str, left: u32, right: u32) -> Result<()> {
+    if left == right {
+        return Ok(());
+    }
+    Err(AppError::VersionMismatch { algorithm: algorithm.to_string(), left, right })
+}
 
 /// Represents the type of media file.
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)] // <-- add PartialEq and Eq
+///
+/// Serialized in lowercase (`"image"`); the PascalCase names written by older
+/// manifests are still accepted.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, JsonSchema)] // <-- add PartialEq and Eq
+#[serde(rename_all = "lowercase")]
 pub enum MediaType {
     /// Represents an image file.
+    #[serde(alias = "Image")]
     Image,
     /// Represents a video file.
+    #[serde(alias = "Video")]
     Video,
+    /// Represents a multi-page document (TIFF or PDF).
+    Document,
     /// Represents any other file type.
+    #[serde(alias = "Other")]
     Other,
 }
 
+/// File extensions treated as images.
+pub const IMAGE_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "gif", "bmp", "webp"];
+
+/// File extensions treated as videos.
+pub const VIDEO_EXTENSIONS: &[&str] = &["mp4", "avi", "mov", "mkv", "webm"];
+
+/// File extensions treated as multi-page documents.
+pub const DOCUMENT_EXTENSIONS: &[&str] = &["tif", "tiff", "pdf"];
+
+impl MediaType {
+    /// Classifies a file by its extension (case-insensitive, without the dot).
+    pub fn from_extension(extension: &str) -> Self {
+        let extension = extension.to_lowercase();
+        if IMAGE_EXTENSIONS.contains(&extension.as_str()) {
+            MediaType::Image
+        } else if VIDEO_EXTENSIONS.contains(&extension.as_str()) {
+            MediaType::Video
+        } else if DOCUMENT_EXTENSIONS.contains(&extension.as_str()) {
+            MediaType::Document
+        } else {
+            MediaType::Other
+        }
+    }
+
+    /// Classifies a file by the extension of its path.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Self {
+        path.as_ref()
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(Self::from_extension)
+            .unwrap_or(MediaType::Other)
+    }
+}
+
+
+/// Outcome of verifying a file against its manifest.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct VerificationReport {
+    /// Whether the file matches the manifest.
+    pub is_valid: bool,
+    /// Why verification failed, e.g. which hash did not match.
+    pub reason: Option<String>,
+    /// Expected and recomputed values; only filled in by
+    /// [`MediaManifest::verify_report_explained`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub explanation: Option<VerificationExplanation>,
+}
+
+impl VerificationReport {
+    /// A failed report giving `reason`, which is logged.
+    fn failed(reason: String) -> Self {
+        warn!("Verification failed: {}", reason);
+        Self { is_valid: false, reason: Some(reason), explanation: None }
+    }
+}
+
+/// The values verification compares, as recorded in the manifest and as
+/// recomputed from the file, for diagnosing failures.
+///
+/// Recomputed values are `None` when they could not be computed, e.g. the
+/// file is missing or no longer decodes as an image.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct VerificationExplanation {
+    /// File size recorded in the manifest.
+    pub expected_file_size: u64,
+    /// Size of the file on disk.
+    pub actual_file_size: Option<u64>,
+    /// SHA3-256 hash recorded in the manifest.
+    pub expected_sha3_256: String,
+    /// SHA3-256 hash of the file on disk.
+    pub actual_sha3_256: Option<String>,
+    /// Perceptual hash recorded in the manifest (images only), in hex.
+    pub expected_pdq: Option<String>,
+    /// Perceptual hash recomputed from the file with the manifest's algorithm.
+    pub actual_pdq: Option<String>,
+}
+
+/// A top-level manifest field whose value differs between two manifests.
+///
+/// Values are in their JSON form; a field missing from one side is `null`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FieldDiff {
+    /// Name of the field as it appears in the manifest JSON.
+    pub field: String,
+    /// Value in the manifest `diff` was called on.
+    pub left: serde_json::Value,
+    /// Value in the other manifest.
+    pub right: serde_json::Value,
+}
+
+/// Why a [`MediaManifest::stub`] manifest never verifies.
+const STUB_REJECTION: &str = "stub manifest: hashes are placeholders and cannot be verified";
+
+/// Fields that change every time a file is processed, ignored by [`MediaManifest::content_eq`].
+const VOLATILE_FIELDS: &[&str] = &["created_at", "modified_at"];
+
+/// Fields naming where a file is stored rather than what it contains, left out
+/// of [`MediaManifest::fingerprint`] along with [`VOLATILE_FIELDS`].
+const LOCATION_FIELDS: &[&str] = &["file_name", "relative_path"];
+
+/// Number of SHA3-256 digest bytes kept in a fingerprint (32 base32 characters).
+pub const FINGERPRINT_BYTES: usize = 20;
 
 /// Contains information about a single frame extracted from a video.
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
 pub struct FrameInfo {
     /// The timestamp of the frame in seconds from the start of the video.
     pub timestamp_secs: f64,
@@ -26,27 +194,268 @@ pub struct FrameInfo {
     pub embedding: Option<Vec<f32>>,
 }
 
+/// Which extractor produced a manifest's `frames`.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, JsonSchema)]
+pub enum FrameSource {
+    /// Frames sampled from a video by FFmpeg.
+    VideoFfmpeg,
+    /// Frames decoded from an animated GIF.
+    GifDecoder,
+    /// Frames decoded from an animated WebP.
+    AnimatedWebp,
+    /// Frames taken at caller-supplied timestamps.
+    ExplicitTimestamps,
+    /// Pages decoded from a multi-page TIFF; timestamps are page numbers.
+    TiffPages,
+    /// Pages rendered from a PDF; timestamps are page numbers.
+    PdfPages,
+}
+
+/// How much per-frame data a video manifest keeps.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ManifestDetail {
+    /// Every frame's timestamp, PDQ hash and optional embedding.
+    #[default]
+    Full,
+    /// Only the frame count and the Merkle root of the frame hashes.
+    Minimal,
+}
+
+/// Options for frame-level verification of videos.
+#[derive(Debug, Clone)]
+pub struct VerifyOptions {
+    /// Maximum PDQ distance for a re-extracted frame to match its manifest entry.
+    pub frame_tolerance: u32,
+    /// Seconds between extracted frames; defaults to the manifest's `frame_interval_secs` metadata.
+    pub frame_interval_secs: Option<f64>,
+    /// Match frames by timestamp instead of by position: a recorded frame
+    /// matches any re-extracted frame taken within this many seconds of it
+    /// whose PDQ hash is within `frame_tolerance`. Absorbs timestamp drift
+    /// between ffmpeg runs; `None` matches frames in order.
+    pub timestamp_tolerance_secs: Option<f64>,
+}
+
+impl Default for VerifyOptions {
+    fn default() -> Self {
+        Self {
+            frame_tolerance: DEFAULT_FRAME_TOLERANCE,
+            frame_interval_secs: None,
+            timestamp_tolerance_secs: None,
+        }
+    }
+}
+
+/// Progress of a frame-by-frame verification.
+#[derive(Debug, Clone, Serialize)]
+pub struct VerifyProgress {
+    /// Number of frames compared so far.
+    pub frames_checked: usize,
+    /// Number of frames recorded in the manifest.
+    pub frames_total: usize,
+    /// Number of frames that did not match so far.
+    pub mismatches: usize,
+    /// Milliseconds since verification started.
+    pub elapsed_ms: u64,
+}
+
+/// Compares re-extracted frames against a manifest's frames, reporting progress.
+struct FrameChecker<'a, F: FnMut(VerifyProgress)> {
+    expected: &'a [FrameInfo],
+    tolerance: u32,
+    timestamp_tolerance: Option<f64>,
+    checked: usize,
+    mismatches: usize,
+    started: Instant,
+    progress: F,
+}
+
+impl<'a, F: FnMut(VerifyProgress)> FrameChecker<'a, F> {
+    fn report(&mut self) {
+        let update = VerifyProgress {
+            frames_checked: self.checked,
+            frames_total: self.expected.len(),
+            mismatches: self.mismatches,
+            elapsed_ms: self.started.elapsed().as_millis() as u64,
+        };
+        (self.progress)(update);
+    }
+
+    fn new(expected: &'a [FrameInfo], opts: &VerifyOptions, started: Instant, progress: F) -> Self {
+        Self {
+            expected,
+            tolerance: opts.frame_tolerance,
+            timestamp_tolerance: opts.timestamp_tolerance_secs,
+            checked: 0,
+            mismatches: 0,
+            started,
+            progress,
+        }
+    }
+
+    /// Whether every recorded frame has been matched or given up on.
+    fn is_done(&self) -> bool {
+        self.checked >= self.expected.len()
+    }
+
+    /// Checks the next re-extracted frame, taken `timestamp` seconds in if known.
+    ///
+    /// Without a timestamp tolerance (or timestamp), frames are matched to
+    /// recorded frames in order and frames beyond the manifest are ignored.
+    /// With one, the next recorded frame matches the first frame within the
+    /// tolerance of its timestamp whose hash is close enough, and is a
+    /// mismatch once frames move past that window; frames matching no
+    /// recorded frame are skipped.
+    fn check(&mut self, frame: &DynamicImage, timestamp: Option<f64>) -> Result<()> {
+        let frame_started = Instant::now();
+        let (Some(window), Some(timestamp)) = (self.timestamp_tolerance, timestamp) else {
+            if let Some(expected) = self.expected.get(self.checked) {
+                let matches = self.matches(frame, expected)?;
+                self.record(matches, frame_started);
+            }
+            return Ok(());
+        };
+
+        while let Some(expected) = self.expected.get(self.checked) {
+            if timestamp <= expected.timestamp_secs + window {
+                break;
+            }
+            self.record(false, frame_started);
+        }
+        if let Some(expected) = self.expected.get(self.checked) {
+            if timestamp >= expected.timestamp_secs - window && self.matches(frame, expected)? {
+                self.record(true, frame_started);
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether `frame`'s PDQ hash is within the tolerance of `expected`'s.
+    fn matches(&self, frame: &DynamicImage, expected: &FrameInfo) -> Result<bool> {
+        let pdq = crate::core::hash::compute_pdq_hash(frame)?;
+        Ok(crate::core::hash::pdq_distance(&pdq, &expected.pdq_hash)
+            .map(|distance| distance <= self.tolerance)
+            .unwrap_or(false))
+    }
+
+    /// Records the verdict on the next recorded frame and reports progress.
+    fn record(&mut self, matches: bool, frame_started: Instant) {
+        if !matches {
+            warn!("Frame at {}s does not match the manifest", self.expected[self.checked].timestamp_secs);
+            self.mismatches += 1;
+        }
+        self.checked += 1;
+        debug!(
+            "Checked frame {}/{} in {:?}",
+            self.checked,
+            self.expected.len(),
+            frame_started.elapsed()
+        );
+        self.report();
+    }
+
+    /// Counts frames that were never produced as mismatches and returns the mismatch count.
+    fn finish(mut self) -> usize {
+        if self.checked < self.expected.len() {
+            self.mismatches += self.expected.len() - self.checked;
+            self.checked = self.expected.len();
+            self.report();
+        }
+        info!(
+            "Frame verification finished in {:?} with {} mismatches",
+            self.started.elapsed(),
+            self.mismatches
+        );
+        self.mismatches
+    }
+}
+
 /// A manifest containing metadata and hashes for a media file.
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
 pub struct MediaManifest {
     /// The type of media (e.g., Image or Video).
     pub media_type: MediaType,
     /// The original name of the file.
     pub file_name: String,
+    /// Path of the file relative to a base directory, used by `verify_in`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub relative_path: Option<String>,
     /// The size of the file in bytes.
     pub file_size: u64,
-    /// The creation timestamp of the file (RFC 3339 format).
+    /// The creation timestamp of the file (RFC 3339 in UTC with milliseconds; see [`crate::format_timestamp`]).
     pub created_at: String,
-    /// The last modification timestamp of the file (RFC 3339 format).
+    /// The last modification timestamp of the file (RFC 3339 in UTC with milliseconds).
     pub modified_at: String,
     /// The SHA3-256 hash of the file content.
     pub sha3_256_hash: String,
-    /// The PDQ perceptual hash of the image (for images only).
+    /// The perceptual hash of the image (for images only): PDQ unless
+    /// `perceptual_algorithm` names another algorithm.
     pub pdq_hash: Option<String>,
+    /// Name of the registered perceptual algorithm that computed `pdq_hash`;
+    /// absent means PDQ.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub perceptual_algorithm: Option<String>,
+    /// Version of each hash algorithm used, keyed by name (`"sha3_256"`,
+    /// `"pdq"`, ...); hashes from different versions are not comparable.
+    /// Algorithms without an entry are [`UNVERSIONED_ALGORITHM`].
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub algorithm_versions: BTreeMap<String, u32>,
+    /// Representation of `pdq_hash` and the frame hashes; absent in manifests
+    /// written before hex hashes, which are migrated on load.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pdq_format: Option<PdqFormat>,
+    /// Average colour and coarse colour grid (for images only), used to
+    /// pre-filter perceptual searches.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color_signature: Option<ColorSignature>,
+    /// Per-channel colour histogram (for images only), see
+    /// [`compute_color_histogram`](crate::core::similarity::compute_color_histogram);
+    /// separates recoloured images that PDQ considers identical.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color_histogram: Option<Vec<f32>>,
+    /// SHA3-256 hash of the decoded, orientation-normalized RGBA pixels (for
+    /// images only); survives lossless re-encoding, unlike `sha3_256_hash`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_hash: Option<String>,
+    /// SHA3-256 hash of the encoded picture data with metadata (EXIF, XMP,
+    /// comments) left out (JPEG, PNG and WebP only); lets verification tell a
+    /// metadata edit from changed pixels.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stream_hash: Option<String>,
     /// Information about extracted frames (for videos only).
     pub frames: Option<Vec<FrameInfo>>,
+    /// How `frames` were extracted; absent when there are no frames.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub frame_source: Option<FrameSource>,
+    /// Merkle root over the frames' PDQ hashes, in order (see
+    /// [`merkle_root`](crate::core::hash::merkle_root)); set by minimal
+    /// manifests, which drop `frames` and verify against the root instead.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub frames_merkle_root: Option<String>,
+    /// Number of frames covered by `frames_merkle_root`; for animated images,
+    /// the number of frames in the animation.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub frame_count: Option<usize>,
     /// Arbitrary JSON metadata associated with the file.
     pub metadata: serde_json::Value,
+    /// SHA3-256 hash of the parent file this one was derived from (e.g. a thumbnail's source).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub derived_from: Option<String>,
+    /// Length of the embedding vectors recorded for this file, in
+    /// `metadata.embedding` or on its frames; absent when none were computed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub embedding_dim: Option<usize>,
+    /// Name of the embedding provider that computed those vectors (see
+    /// [`EmbeddingRegistry`](crate::core::embeddings::EmbeddingRegistry));
+    /// absent when none were computed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub embedding_model: Option<String>,
+    /// Mean of a video's frame embeddings (see
+    /// [`pool_frame_embeddings`](crate::core::similarity::pool_frame_embeddings)),
+    /// a single vector for video-level similarity search that survives
+    /// [`into_minimal`](Self::into_minimal); absent when no frame was embedded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub video_embedding: Option<Vec<f32>>,
 }
 
 impl MediaManifest {
@@ -71,128 +480,1218 @@ impl MediaManifest {
         
         let modified_at: chrono::DateTime<chrono::Utc> = file_metadata.modified()?.into();
         let file_size = file_metadata.len();
+        let has_perceptual = pdq_hash.is_some() || frames.is_some();
+        let algorithm_versions = current_algorithm_versions(
+            has_perceptual.then_some(crate::core::perceptual::DEFAULT_ALGORITHM),
+        );
 
         Ok(Self {
             media_type,
             file_name,
+            relative_path: None,
             file_size,
-            created_at: created_at.to_rfc3339(),
-            modified_at: modified_at.to_rfc3339(),
+            created_at: crate::utils::format_timestamp(&created_at),
+            modified_at: crate::utils::format_timestamp(&modified_at),
             sha3_256_hash,
+            pdq_format: pdq_hash.as_deref().and_then(|h| PdqFormat::detect(h).ok()),
             pdq_hash,
+            perceptual_algorithm: None,
+            algorithm_versions,
+            color_signature: None,
+            color_histogram: None,
+            content_hash: None,
+            stream_hash: None,
+            frame_source: None,
+            frames_merkle_root: None,
+            frame_count: None,
             frames,
             metadata: metadata.unwrap_or(serde_json::Value::Null),
+            derived_from: None,
+            embedding_dim: None,
+            embedding_model: None,
+            video_embedding: None,
         })
     }
 
+    /// Records client-supplied metadata (album, owner, license, ...) under
+    /// [`USER_METADATA_KEY`] in `metadata`.
+    ///
+    /// Computed fields stay untouched: only the `user` key is replaced. A
+    /// `Null` `metadata` becomes an object; any other non-object value is kept
+    /// under `value` alongside it.
+    pub fn set_user_metadata(&mut self, user: serde_json::Map<String, serde_json::Value>) {
+        self.metadata_fields().insert(USER_METADATA_KEY.to_string(), serde_json::Value::Object(user));
+    }
+
+    /// `metadata` as an object, converted as [`set_user_metadata`](Self::set_user_metadata) describes
+    fn metadata_fields(&mut self) -> &mut serde_json::Map<String, serde_json::Value> {
+        if !self.metadata.is_object() {
+            let fields = match std::mem::take(&mut self.metadata) {
+                serde_json::Value::Null => serde_json::Map::new(),
+                other => serde_json::Map::from_iter([("value".to_string(), other)]),
+            };
+            self.metadata = serde_json::Value::Object(fields);
+        }
+        self.metadata.as_object_mut().expect("metadata is an object")
+    }
+
+    /// Creates a manifest for a file derived from this one, such as a thumbnail.
+    ///
+    /// The new file is hashed (including its PDQ hash for images) and the
+    /// returned manifest records this manifest's SHA3-256 hash in `derived_from`,
+    /// forming an auditable provenance chain.
+    ///
+    /// The server has no thumbnail or sanitize endpoint yet, so `derived_from`
+    /// is only set for library callers that derive files themselves.
+    pub fn derive<P: AsRef<Path>>(&self, new_file: P, media_type: MediaType) -> Result<Self> {
+        let path = new_file.as_ref();
+        let file_name = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .map(String::from)
+            .unwrap_or_else(|| "unknown".to_string());
+
+        let sha3_256_hash = crate::core::hash::compute_file_hash(path)?;
+        let data = if media_type == MediaType::Image {
+            Some(std::fs::read(path)?)
+        } else {
+            None
+        };
+        let image = data.as_deref().map(image::load_from_memory).transpose()?;
+        let pdq_hash = image
+            .as_ref()
+            .map(crate::core::hash::compute_pdq_hash)
+            .transpose()?;
+        let content_hash = match (&data, &image) {
+            (Some(data), Some(image)) => {
+                let oriented = apply_orientation(image.clone(), exif_orientation(data));
+                Some(compute_content_hash(&oriented)?)
+            }
+            _ => None,
+        };
+
+        let mut derived = Self::new(file_name, path, media_type, sha3_256_hash, pdq_hash, None, None)?;
+        derived.color_signature = image.as_ref().map(ColorSignature::compute);
+        derived.color_histogram = image.as_ref().map(|image| {
+            crate::core::similarity::compute_color_histogram(image, crate::core::similarity::DEFAULT_HISTOGRAM_BINS)
+        });
+        derived.content_hash = content_hash;
+        derived.stream_hash = data.as_deref().and_then(crate::core::hash::stream_hash);
+        derived.derived_from = Some(self.sha3_256_hash.clone());
+        Ok(derived)
+    }
+
+    /// Creates a manifest with deterministic placeholder hashes, for exercising pipelines cheaply.
+    ///
+    /// Nothing is read beyond the file's metadata: the SHA3-256 field (and, for
+    /// images, the PDQ field) is derived from `name` and the file size alone, so
+    /// the same name and size always give the same hashes. `perceptual_algorithm`
+    /// is [`STUB_ALGORITHM`], and every verification of a stub manifest fails.
+    pub fn stub<P: AsRef<Path>>(file_name: String, path: P, media_type: MediaType, name: &str) -> Result<Self> {
+        let path = path.as_ref();
+        let size = std::fs::metadata(path).map_err(|e| AppError::from_io_at(e, path))?.len();
+
+        // FNV-1a over the name and size; only needs to be stable, not secure
+        let seed = name.bytes().chain(size.to_le_bytes());
+        let digest = seed.fold(0xcbf29ce484222325u64, |hash, byte| (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3));
+        let pdq_hash = (media_type == MediaType::Image).then(|| format!("{:016x}", digest));
+
+        let mut manifest = Self::new(file_name, path, media_type, format!("{:016x}", digest).repeat(4), pdq_hash, None, None)?;
+        manifest.perceptual_algorithm = Some(STUB_ALGORITHM.to_string());
+        manifest.algorithm_versions = BTreeMap::from([(STUB_ALGORITHM.to_string(), UNVERSIONED_ALGORITHM)]);
+        Ok(manifest)
+    }
+
+    /// Whether the hashes are placeholders from [`stub`](Self::stub).
+    pub fn is_stub(&self) -> bool {
+        self.perceptual_algorithm.as_deref() == Some(STUB_ALGORITHM)
+    }
+
+    /// Lists the fields whose values differ from `other`, in field-name order.
+    pub fn diff(&self, other: &Self) -> Vec<FieldDiff> {
+        let as_object = |manifest: &Self| match serde_json::to_value(manifest) {
+            Ok(serde_json::Value::Object(fields)) => fields,
+            _ => serde_json::Map::new(),
+        };
+        let (left, right) = (as_object(self), as_object(other));
+
+        let fields: std::collections::BTreeSet<&String> = left.keys().chain(right.keys()).collect();
+        fields
+            .into_iter()
+            .filter_map(|field| {
+                let (a, b) = (left.get(field), right.get(field));
+                (a != b).then(|| FieldDiff {
+                    field: field.clone(),
+                    left: a.cloned().unwrap_or(serde_json::Value::Null),
+                    right: b.cloned().unwrap_or(serde_json::Value::Null),
+                })
+            })
+            .collect()
+    }
+
+    /// Whether `other` describes the same content, ignoring processing timestamps.
+    ///
+    /// Useful for regression tests and change detection, where re-processing
+    /// the same file always produces new `created_at`/`modified_at` values.
+    pub fn content_eq(&self, other: &Self) -> bool {
+        self.diff(other)
+            .iter()
+            .all(|diff| VOLATILE_FIELDS.contains(&diff.field.as_str()))
+    }
+
+    /// A short, stable id for the manifest's content.
+    ///
+    /// The SHA3-256 of the manifest's canonical JSON (keys sorted, without
+    /// timestamps, file name or relative path), truncated to
+    /// [`FINGERPRINT_BYTES`] and encoded as lowercase unpadded base32. Processing
+    /// the same file twice yields the same fingerprint, so it can serve as a
+    /// database key; any change to a hash or other content field changes it.
+    pub fn fingerprint(&self) -> String {
+        let mut fields = match serde_json::to_value(self) {
+            Ok(serde_json::Value::Object(fields)) => fields,
+            _ => serde_json::Map::new(),
+        };
+        for field in VOLATILE_FIELDS.iter().chain(LOCATION_FIELDS) {
+            fields.remove(*field);
+        }
+        let canonical = serde_json::Value::Object(fields).to_string();
+
+        let digest = crate::core::hash::compute_sha3_256(canonical.as_bytes())
+            .ok()
+            .and_then(|hash| hex::decode(hash).ok())
+            .unwrap_or_default();
+        base32_lower(&digest[..digest.len().min(FINGERPRINT_BYTES)])
+    }
+
     /// Serializes the manifest to a pretty-printed JSON string.
     pub fn to_json(&self) -> Result<String> {
         Ok(serde_json::to_string_pretty(self)?)
     }
 
+    /// Serializes the manifest to JSON without whitespace.
+    ///
+    /// This is what [`save`](Self::save) writes; use [`to_json`](Self::to_json)
+    /// for output meant to be read by people.
+    pub fn to_json_compact(&self) -> Result<String> {
+        Ok(serde_json::to_string(self)?)
+    }
+
     /// Deserializes a `MediaManifest` from a JSON string.
+    ///
+    /// Binary-string PDQ hashes from older manifests are migrated to hex.
     pub fn from_json(json_str: &str) -> Result<Self> {
-        Ok(serde_json::from_str(json_str)?)
+        let mut manifest: Self = serde_json::from_str(json_str)?;
+        manifest.migrate_pdq_format();
+        Ok(manifest)
     }
 
-    /// Verifies the integrity of a file against the manifest.
+    /// Rewrites binary-string PDQ hashes (image and frames) as hex.
     ///
-    /// This checks the file size and SHA3-256 hash.
-    pub fn verify<P: AsRef<Path>>(&self, file_path: P) -> Result<bool> {
+    /// Manifests without a `pdq_format` marker predate hex hashes, so their
+    /// hashes are read as binary. Hashes that don't parse are left untouched,
+    /// as are manifests hashed by a [custom algorithm](Self::custom_perceptual_algorithm),
+    /// whose hashes are in that algorithm's own format.
+    pub fn migrate_pdq_format(&mut self) {
+        if self.pdq_format == Some(PdqFormat::Hex) || self.custom_perceptual_algorithm().is_some() {
+            return;
+        }
+        let to_hex = |hash: &mut String| {
+            if let Ok(hex) = pdq_bits_as(hash, PdqFormat::Binary).and_then(|_| pdq_to_hex(hash)) {
+                *hash = hex;
+            }
+        };
+
+        if let Some(hash) = self.pdq_hash.as_mut() {
+            to_hex(hash);
+        }
+        for frame in self.frames.iter_mut().flatten() {
+            to_hex(&mut frame.pdq_hash);
+        }
+        if self.pdq_hash.is_some() || self.frames.as_ref().is_some_and(|f| !f.is_empty()) {
+            self.pdq_format = Some(PdqFormat::Hex);
+        }
+    }
+
+    /// Normalizes the stored image PDQ hash to hex, honouring `pdq_format`.
+    fn pdq_hash_hex(&self, hash: &str) -> Result<String> {
+        let format = match self.pdq_format {
+            Some(format) => format,
+            None => PdqFormat::detect(hash)?,
+        };
+        let bits: String = pdq_bits_as(hash, format)?
+            .into_iter()
+            .map(|bit| if bit { '1' } else { '0' })
+            .collect();
+        Ok(pdq_to_hex(&bits)?)
+    }
+
+    /// Writes the manifest as JSON to `path`, atomically replacing any existing file.
+    ///
+    /// The JSON goes to a temporary file in the same directory, is synced to
+    /// disk and then renamed over `path`, so a crash mid-write leaves either
+    /// the previous manifest or the new one in place, never a truncated file.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let dir = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+
+        let mut temp = tempfile::Builder::new()
+            .prefix(".manifest-")
+            .suffix(".tmp")
+            .tempfile_in(dir)?;
+        temp.write_all(self.to_json_compact()?.as_bytes())?;
+        temp.as_file().sync_all()?;
+        temp.persist(path).map_err(|e| e.error)?;
+
+        // Sync the directory so the rename itself survives a crash
+        #[cfg(unix)]
+        std::fs::File::open(dir)?.sync_all()?;
+
+        debug!("Saved manifest for {} to {}", self.file_name, path.display());
+        Ok(())
+    }
+
+    /// Reads a manifest previously written with [`save`](Self::save).
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::from_json(&std::fs::read_to_string(path)?)
+    }
+
+    /// Sets the path of the file relative to the base directory it is stored in.
+    pub fn with_relative_path<S: Into<String>>(mut self, relative_path: S) -> Self {
+        self.relative_path = Some(relative_path.into());
+        self
+    }
+
+    /// Verifies the integrity of a file located relative to `base_dir`.
+    ///
+    /// The file is resolved as `base_dir.join(relative_path)`, falling back to
+    /// `file_name` when no relative path is stored. Absolute paths and paths
+    /// containing `..` are rejected with `AppError::Validation`.
+    pub fn verify_in<P: AsRef<Path>>(&self, base_dir: P) -> Result<bool> {
+        let path = self.resolve_in(base_dir.as_ref())?;
+        self.verify(path)
+    }
+
+    /// Resolves the manifest's file inside `base_dir`, rejecting path traversal.
+    fn resolve_in(&self, base_dir: &Path) -> Result<std::path::PathBuf> {
+        let relative = self.relative_path.as_deref().unwrap_or(&self.file_name);
+        let is_contained = Path::new(relative)
+            .components()
+            .all(|c| matches!(c, Component::Normal(_) | Component::CurDir));
+
+        if relative.is_empty() || !is_contained {
+            return Err(AppError::Validation(format!(
+                "manifest path escapes the base directory: {}",
+                relative
+            )));
+        }
+
+        Ok(base_dir.join(relative))
+    }
+
+    /// Verifies a file including its video frames, reporting progress per frame.
+    ///
+    /// This runs [`verify`](Self::verify) first; for videos with recorded frames
+    /// it then re-extracts frames and compares each one's PDQ hash with the
+    /// manifest, calling `progress` after every frame. Minimal manifests are
+    /// checked against `frames_merkle_root` instead, without progress updates.
+    /// Requires the `video` feature for videos with frames.
+    pub fn verify_with_progress<P, F>(&self, file_path: P, opts: &VerifyOptions, progress: F) -> Result<bool>
+    where
+        P: AsRef<Path>,
+        F: FnMut(VerifyProgress),
+    {
+        Ok(self.verify_report_with_progress(file_path, opts, progress)?.is_valid)
+    }
+
+    /// Like [`verify_with_progress`](Self::verify_with_progress), but also says why verification failed.
+    pub fn verify_report_with_progress<P, F>(
+        &self,
+        file_path: P,
+        opts: &VerifyOptions,
+        progress: F,
+    ) -> Result<VerificationReport>
+    where
+        P: AsRef<Path>,
+        F: FnMut(VerifyProgress),
+    {
         let path = file_path.as_ref();
-        info!("Verifying file at path: {:?}", path);
+        let started = Instant::now();
+        let report = self.verify_report(path)?;
+        if !report.is_valid {
+            return Ok(report);
+        }
+        self.check_current()?;
 
-        if !path.exists() {
-            warn!("Verification failed: path does not exist.");
-            return Ok(false);
+        if let (MediaType::Video, None, Some(root), Some(count)) =
+            (&self.media_type, &self.frames, &self.frames_merkle_root, self.frame_count)
+        {
+            // Minimal manifests have no per-frame entries to report progress against
+            let hashes = self.frame_hashes(path, opts.frame_interval_secs, count)?;
+            if !self.root_matches(root, &hashes)? {
+                return Ok(VerificationReport::failed("frames Merkle root mismatch".to_string()));
+            }
+            return Ok(report);
         }
 
-        if !path.is_file() {
-            warn!("Verification failed: path is not a file.");
-            return Ok(false);
+        let expected = match (&self.media_type, &self.frames) {
+            (MediaType::Video, Some(frames)) if !frames.is_empty() => frames,
+            _ => return Ok(report),
+        };
+
+        let extractor = self.frame_extractor(path, opts.frame_interval_secs);
+        let mut checker = FrameChecker::new(expected, opts, started, progress);
+        extractor.extract_frames(|frame, timestamp| Ok(checker.check(&frame, Some(timestamp))?))?;
+
+        match checker.finish() {
+            0 => Ok(report),
+            mismatches => Ok(VerificationReport::failed(format!(
+                "{} of {} frames did not match",
+                mismatches,
+                expected.len()
+            ))),
         }
+    }
 
-        let metadata = std::fs::metadata(path)?;
-        if metadata.len() != self.file_size {
-            warn!(
-                "Verification failed: size mismatch. Expected: {}, Found: {}",
-                self.file_size,
-                metadata.len()
-            );
-            return Ok(false);
+    /// Replaces `frames` with their count and Merkle root, for storage-constrained clients.
+    ///
+    /// The root covers the frames' PDQ hashes in order, so the video still
+    /// verifies exactly (see [`verify_frames_root`](Self::verify_frames_root)),
+    /// but tolerance-based per-frame matching and frame search are no longer
+    /// possible. Manifests without frames are returned unchanged.
+    pub fn into_minimal(mut self) -> Result<Self> {
+        self.seal_frames()?;
+        self.frames = None;
+        Ok(self)
+    }
+
+    /// Records the count and Merkle root of `frames` while keeping the frames.
+    ///
+    /// The root is the one [`into_minimal`](Self::into_minimal) stores, so
+    /// [`verify_frames_integrity`](Self::verify_frames_integrity) can later
+    /// detect edits to the stored frames. Manifests without frames are
+    /// left unchanged.
+    pub fn seal_frames(&mut self) -> Result<()> {
+        if let Some(frames) = &self.frames {
+            self.frames_merkle_root = Some(crate::core::hash::merkle_root(&Self::frame_hex_hashes(frames)?)?);
+            self.frame_count = Some(frames.len());
+        }
+        Ok(())
+    }
+
+    /// Whether the stored `frames` still match the stored `frames_merkle_root`.
+    ///
+    /// This only checks the manifest against itself, so it needs no video
+    /// file: it catches frames that were added, removed, reordered or had
+    /// their PDQ hash edited after [`seal_frames`](Self::seal_frames). Returns
+    /// `false` when the manifest lacks either the frames or the root.
+    pub fn verify_frames_integrity(&self) -> bool {
+        let (Some(frames), Some(root)) = (&self.frames, &self.frames_merkle_root) else {
+            return false;
+        };
+        if self.frame_count != Some(frames.len()) {
+            return false;
         }
+        Self::frame_hex_hashes(frames)
+            .and_then(|hashes| Ok(crate::core::hash::merkle_root(&hashes)?))
+            .is_ok_and(|computed| crate::core::hash::secure_hash_eq(&computed, root))
+    }
+
+    /// The frames' PDQ hashes in hex, the form Merkle roots are built over.
+    fn frame_hex_hashes(frames: &[FrameInfo]) -> Result<Vec<String>> {
+        Ok(frames
+            .iter()
+            .map(|frame| pdq_to_hex(&frame.pdq_hash))
+            .collect::<anyhow::Result<Vec<_>>>()?)
+    }
+
+    /// Checks already-decoded frames against `frames_merkle_root`.
+    ///
+    /// The first `frame_count` frames are hashed in order; fewer frames, or a
+    /// different root, fail. Returns `false` if the manifest has no root.
+    pub fn verify_frames_root<I>(&self, frames: I) -> Result<bool>
+    where
+        I: IntoIterator<Item = DynamicImage>,
+    {
+        let (Some(root), Some(count)) = (&self.frames_merkle_root, self.frame_count) else {
+            return Ok(false);
+        };
+        let hashes = frames
+            .into_iter()
+            .take(count)
+            .map(|frame| crate::core::hash::compute_pdq_hash(&frame))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        self.root_matches(root, &hashes)
+    }
 
-        let file_hash = crate::core::hash::compute_file_hash(path)?;
-        if file_hash != self.sha3_256_hash {
+    /// Whether `hashes` are exactly the frames the root was built from.
+    fn root_matches(&self, root: &str, hashes: &[String]) -> Result<bool> {
+        if Some(hashes.len()) != self.frame_count {
             warn!(
-                "Verification failed: SHA3 hash mismatch. Expected: {}, Found: {}",
-                self.sha3_256_hash,
-                file_hash
+                "Video yielded {} frames but the manifest's root covers {:?}",
+                hashes.len(),
+                self.frame_count
             );
             return Ok(false);
         }
+        let computed = crate::core::hash::merkle_root(hashes)?;
+        Ok(crate::core::hash::secure_hash_eq(&computed, root))
+    }
 
-        if self.media_type == MediaType::Image {
-            if let Some(pdq_hash) = &self.pdq_hash {
-                let img = image::open(path)?;
-                let computed_pdq_hash = crate::core::hash::compute_pdq_hash(&img)?;
-                if pdq_hash != &computed_pdq_hash {
-                    warn!(
-                        "Verification failed: PDQ hash mismatch. Expected: {}, Found: {}",
-                        pdq_hash,
-                        computed_pdq_hash
-                    );
-                    return Ok(false);
+    /// PDQ hashes of the first `limit` frames re-extracted from the video at `path`.
+    fn frame_hashes(&self, path: &Path, interval_secs: Option<f64>, limit: usize) -> Result<Vec<String>> {
+        let mut hashes = Vec::new();
+        self.frame_extractor(path, interval_secs).extract_frames(|frame, _timestamp| {
+            if hashes.len() < limit {
+                hashes.push(crate::core::hash::compute_pdq_hash(&frame)?);
+            }
+            Ok(())
+        })?;
+        Ok(hashes)
+    }
+
+    /// Builds a frame extractor with the settings recorded in `metadata` at upload time.
+    fn frame_extractor(&self, path: &Path, interval_secs: Option<f64>) -> crate::core::video::FrameExtractor {
+        let interval = interval_secs
+            .or_else(|| self.metadata.get("frame_interval_secs").and_then(|v| v.as_f64()))
+            .unwrap_or(1.0);
+        let stream = self
+            .metadata
+            .get("video_stream_index")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(0) as usize;
+        let frame_size = |key: &str| self.metadata.get(key).and_then(|v| v.as_u64()).map(|v| v as u32);
+
+        let mut extractor = crate::core::video::FrameExtractor::new(path, interval).with_video_stream(stream);
+        if let (Some(w), Some(h)) = (frame_size("frame_width"), frame_size("frame_height")) {
+            extractor = extractor.with_target_size(w, h);
+        }
+        // Frames dropped at upload must be dropped again, or the rest would shift
+        if let Some(min_entropy) = self.metadata.get("min_entropy").and_then(|v| v.as_f64()) {
+            extractor = extractor.with_min_entropy(min_entropy as f32);
+        }
+        extractor
+    }
+
+    /// Recomputes the perceptual hashes from the file at `path`, in place.
+    ///
+    /// Images get a fresh `pdq_hash`, or none with [`TOO_SMALL_FOR_PDQ_KEY`]
+    /// set if they are smaller than [`MIN_PDQ_DIMENSION`](crate::core::hash::MIN_PDQ_DIMENSION).
+    /// Videos with recorded frames are
+    /// re-extracted with the settings in `metadata` and each frame's PDQ hash is
+    /// replaced in order, keeping timestamps and embeddings; minimal manifests
+    /// get a fresh `frames_merkle_root` instead. The file is not
+    /// verified first and `sha3_256_hash` is left untouched; `modified_at` is
+    /// set to the current time and the recorded PDQ version to this build's. Requires the `video` feature for videos with
+    /// frames. Documents get fresh per-page hashes and must still have as many
+    /// pages as `frames` records.
+    pub fn recompute_perceptual(&mut self, path: &Path) -> Result<()> {
+        self.recompute_perceptual_with(path, crate::core::hash::MIN_PDQ_DIMENSION)
+    }
+
+    /// Like [`recompute_perceptual`](Self::recompute_perceptual), with images
+    /// smaller than `min_pdq_dimension` pixels across getting no PDQ hash.
+    pub fn recompute_perceptual_with(&mut self, path: &Path, min_pdq_dimension: u32) -> Result<()> {
+        match self.media_type {
+            MediaType::Image => {
+                let image = image::open(path)?;
+                if crate::core::hash::too_small_for_pdq(&image, min_pdq_dimension) {
+                    self.pdq_hash = None;
+                    self.metadata_fields().insert(TOO_SMALL_FOR_PDQ_KEY.to_string(), serde_json::Value::Bool(true));
+                } else {
+                    self.pdq_hash = Some(crate::core::hash::compute_pdq_hash(&image)?);
+                    if let serde_json::Value::Object(fields) = &mut self.metadata {
+                        fields.remove(TOO_SMALL_FOR_PDQ_KEY);
+                    }
+                }
+            }
+            MediaType::Video if self.frames.is_none() && self.frames_merkle_root.is_some() => {
+                let count = self.frame_count.unwrap_or(0);
+                let hashes = self.frame_hashes(path, None, count)?;
+                if hashes.len() < count {
+                    return Err(AppError::Validation(format!(
+                        "video yielded {} frames but the manifest records {}",
+                        hashes.len(),
+                        count
+                    )));
+                }
+                self.frames_merkle_root = Some(crate::core::hash::merkle_root(&hashes)?);
+            }
+            MediaType::Video => {
+                let recorded = self.frames.as_ref().map_or(0, Vec::len);
+                if recorded == 0 {
+                    return Ok(());
+                }
+
+                let mut hashes = Vec::with_capacity(recorded);
+                self.frame_extractor(path, None).extract_frames(|frame, _timestamp| {
+                    hashes.push(crate::core::hash::compute_pdq_hash(&frame)?);
+                    Ok(())
+                })?;
+                if hashes.len() < recorded {
+                    return Err(AppError::Validation(format!(
+                        "video yielded {} frames but the manifest records {}",
+                        hashes.len(),
+                        recorded
+                    )));
+                }
+
+                for (frame, hash) in self.frames.iter_mut().flatten().zip(hashes) {
+                    frame.pdq_hash = hash;
+                }
+            }
+            MediaType::Document => {
+                let data = std::fs::read(path)?;
+                let (_, pages) = crate::core::document::page_frames(&self.file_name, &data)?;
+                let recorded = self.frames.as_ref().map_or(0, Vec::len);
+                if pages.len() != recorded {
+                    return Err(AppError::Validation(format!(
+                        "document has {} pages but the manifest records {}",
+                        pages.len(),
+                        recorded
+                    )));
+                }
+
+                for (frame, page) in self.frames.iter_mut().flatten().zip(pages) {
+                    frame.pdq_hash = page.pdq_hash;
                 }
             }
+            MediaType::Other => return Ok(()),
+        }
+        // Keep a root stored next to the frames in step with them
+        if self.frames.is_some() && self.frames_merkle_root.is_some() {
+            self.seal_frames()?;
         }
 
-        info!("Verification successful.");
-        Ok(true)
+        self.pdq_format = Some(PdqFormat::Hex);
+        self.algorithm_versions.insert(
+            crate::core::perceptual::DEFAULT_ALGORITHM.to_string(),
+            crate::core::hash::PDQ_VERSION,
+        );
+        self.modified_at = crate::utils::now_timestamp();
+        Ok(())
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use chrono::Utc;
+    /// Like [`recompute_perceptual_with`](Self::recompute_perceptual_with), resolving the file as [`verify_in`](Self::verify_in) does.
+    pub fn recompute_perceptual_in<P: AsRef<Path>>(&mut self, base_dir: P, min_pdq_dimension: u32) -> Result<()> {
+        let path = self.resolve_in(base_dir.as_ref())?;
+        self.recompute_perceptual_with(&path, min_pdq_dimension)
+    }
 
-    #[test]
-    fn test_manifest_verification() {
-        // Create a temporary image file
-        let file = tempfile::Builder::new().suffix(".png").tempfile().unwrap();
-        let img = image::RgbImage::new(10, 10);
-        img.save(file.path()).unwrap();
+    /// Like [`verify_with_progress`](Self::verify_with_progress), resolving the file as [`verify_in`](Self::verify_in) does.
+    pub fn verify_in_with_progress<P, F>(&self, base_dir: P, opts: &VerifyOptions, progress: F) -> Result<bool>
+    where
+        P: AsRef<Path>,
+        F: FnMut(VerifyProgress),
+    {
+        Ok(self.verify_report_in_with_progress(base_dir, opts, progress)?.is_valid)
+    }
 
-        let file_hash = crate::core::hash::compute_file_hash(file.path()).unwrap();
-        let pdq_hash = crate::core::hash::compute_pdq_hash(&image::open(file.path()).unwrap()).unwrap();
+    /// Like [`verify_report_with_progress`](Self::verify_report_with_progress), resolving the file as [`verify_in`](Self::verify_in) does.
+    pub fn verify_report_in_with_progress<P, F>(
+        &self,
+        base_dir: P,
+        opts: &VerifyOptions,
+        progress: F,
+    ) -> Result<VerificationReport>
+    where
+        P: AsRef<Path>,
+        F: FnMut(VerifyProgress),
+    {
+        let path = self.resolve_in(base_dir.as_ref())?;
+        self.verify_report_with_progress(path, opts, progress)
+    }
 
-        let manifest = MediaManifest::new(
-            "test_image.png".to_string(),
-            file.path(),
-            MediaType::Image,
-            file_hash,
-            Some(pdq_hash),
-            None,
-            None,
-        ).unwrap();
+    /// Compares already-decoded frames with the manifest's frames, reporting progress.
+    ///
+    /// Frames are matched to manifest entries in order. Missing frames count as
+    /// mismatches; frames beyond those recorded are ignored. Returns `true` if
+    /// every recorded frame matched within `opts.frame_tolerance`.
+    pub fn verify_frames_with_progress<I, F>(&self, frames: I, opts: &VerifyOptions, progress: F) -> Result<bool>
+    where
+        I: IntoIterator<Item = DynamicImage>,
+        F: FnMut(VerifyProgress),
+    {
+        self.check_frames(frames.into_iter().map(|frame| (frame, None)), opts, progress)
+    }
 
-        // Verification should pass
-        assert!(manifest.verify(file.path()).unwrap());
+    /// Like [`verify_frames_with_progress`](Self::verify_frames_with_progress),
+    /// for frames paired with the timestamp they were taken at.
+    ///
+    /// With `opts.timestamp_tolerance_secs` set, frames are matched to
+    /// manifest entries by timestamp rather than by position, so frames that
+    /// drifted by up to the tolerance still match.
+    pub fn verify_timed_frames_with_progress<I, F>(&self, frames: I, opts: &VerifyOptions, progress: F) -> Result<bool>
+    where
+        I: IntoIterator<Item = (DynamicImage, f64)>,
+        F: FnMut(VerifyProgress),
+    {
+        self.check_frames(frames.into_iter().map(|(frame, timestamp)| (frame, Some(timestamp))), opts, progress)
     }
-    
-    #[test]
-    fn test_manifest_serialization() {
-        let manifest = MediaManifest {
-            media_type: MediaType::Image,
-            file_name: "test.jpg".to_string(),
-            file_size: 1024,
-            created_at: Utc::now().to_rfc3339(),
-            modified_at: Utc::now().to_rfc3339(),
-            sha3_256_hash: "test_hash".to_string(),
-            pdq_hash: Some("pdq_hash".to_string()),
+
+    fn check_frames<I, F>(&self, frames: I, opts: &VerifyOptions, progress: F) -> Result<bool>
+    where
+        I: Iterator<Item = (DynamicImage, Option<f64>)>,
+        F: FnMut(VerifyProgress),
+    {
+        let expected = self.frames.as_deref().unwrap_or(&[]);
+        let mut checker = FrameChecker::new(expected, opts, Instant::now(), progress);
+        for (frame, timestamp) in frames {
+            if checker.is_done() {
+                break;
+            }
+            checker.check(&frame, timestamp)?;
+        }
+        Ok(checker.finish() == 0)
+    }
+
+    /// Verifies the integrity of a file against the manifest.
+    ///
+    /// This checks the file size and SHA3-256 hash.
+    pub fn verify<P: AsRef<Path>>(&self, file_path: P) -> Result<bool> {
+        Ok(self.verify_report(file_path)?.is_valid)
+    }
+
+    /// Like [`verify`](Self::verify), but also says why verification failed.
+    pub fn verify_report<P: AsRef<Path>>(&self, file_path: P) -> Result<VerificationReport> {
+        self.verify_report_with(file_path.as_ref(), None)
+    }
+
+    /// Like [`verify_report`](Self::verify_report), decoding images through `cache`.
+    pub fn verify_report_cached<P: AsRef<Path>>(&self, file_path: P, cache: &DecodeCache) -> Result<VerificationReport> {
+        self.verify_report_with(file_path.as_ref(), Some(cache))
+    }
+
+    /// Like [`verify_report`](Self::verify_report), also recording the expected
+    /// and recomputed values in `explanation`.
+    ///
+    /// Every value is recomputed, even past the first mismatch, so this hashes
+    /// the file again on top of verifying it; use it when diagnosing failures.
+    pub fn verify_report_explained<P: AsRef<Path>>(&self, file_path: P) -> Result<VerificationReport> {
+        let path = file_path.as_ref();
+        let mut report = self.verify_report_with(path, None)?;
+        report.explanation = Some(self.explain(path)?);
+        Ok(report)
+    }
+
+    /// Like [`verify_report_explained`](Self::verify_report_explained), resolving the file as [`verify_in`](Self::verify_in) does.
+    pub fn verify_report_explained_in<P: AsRef<Path>>(&self, base_dir: P) -> Result<VerificationReport> {
+        let path = self.resolve_in(base_dir.as_ref())?;
+        self.verify_report_explained(path)
+    }
+
+    /// Recomputes the values verification compares, next to the expected ones.
+    pub fn explain(&self, path: &Path) -> Result<VerificationExplanation> {
+        let expected_pdq = match (&self.media_type, &self.pdq_hash) {
+            (MediaType::Image, Some(hash)) if self.custom_perceptual_algorithm().is_none() => {
+                Some(self.pdq_hash_hex(hash).unwrap_or_else(|_| hash.clone()))
+            }
+            (MediaType::Image, Some(hash)) => Some(hash.clone()),
+            _ => None,
+        };
+        let mut explanation = VerificationExplanation {
+            expected_file_size: self.file_size,
+            actual_file_size: None,
+            expected_sha3_256: self.sha3_256_hash.clone(),
+            actual_sha3_256: None,
+            expected_pdq,
+            actual_pdq: None,
+        };
+        if !path.is_file() {
+            return Ok(explanation);
+        }
+
+        explanation.actual_file_size = Some(std::fs::metadata(path)?.len());
+        explanation.actual_sha3_256 = Some(crate::core::hash::compute_file_hash(path)?);
+        if explanation.expected_pdq.is_some() {
+            let algorithm = crate::core::perceptual::algorithm(self.custom_perceptual_algorithm()).ok();
+            explanation.actual_pdq = algorithm
+                .zip(image::open(path).ok())
+                .and_then(|(algorithm, img)| algorithm.hash(&img).ok());
+        }
+        Ok(explanation)
+    }
+
+    /// Like [`verify_in`](Self::verify_in), decoding images through `cache`.
+    pub fn verify_in_cached<P: AsRef<Path>>(&self, base_dir: P, cache: &DecodeCache) -> Result<bool> {
+        let path = self.resolve_in(base_dir.as_ref())?;
+        Ok(self.verify_report_cached(path, cache)?.is_valid)
+    }
+
+    /// Like [`verify_report_in`](Self::verify_report_in), decoding images through `cache`.
+    pub fn verify_report_in_cached<P: AsRef<Path>>(&self, base_dir: P, cache: &DecodeCache) -> Result<VerificationReport> {
+        let path = self.resolve_in(base_dir.as_ref())?;
+        self.verify_report_cached(path, cache)
+    }
+
+    /// Name of the algorithm behind `pdq_hash` when it is not the default PDQ.
+    pub fn custom_perceptual_algorithm(&self) -> Option<&str> {
+        self.perceptual_algorithm
+            .as_deref()
+            .filter(|name| *name != crate::core::perceptual::DEFAULT_ALGORITHM)
+    }
+
+    /// Name of the algorithm behind `pdq_hash` and the frame hashes, if there are any.
+    pub fn perceptual_algorithm_name(&self) -> Option<&str> {
+        (self.pdq_hash.is_some() || self.frames.is_some() || self.frames_merkle_root.is_some())
+            .then(|| self.custom_perceptual_algorithm().unwrap_or(crate::core::perceptual::DEFAULT_ALGORITHM))
+    }
+
+    /// Recorded version of `algorithm`, or [`UNVERSIONED_ALGORITHM`] if none was recorded.
+    pub fn algorithm_version(&self, algorithm: &str) -> u32 {
+        self.algorithm_versions.get(algorithm).copied().unwrap_or(UNVERSIONED_ALGORITHM)
+    }
+
+    /// Checks that this manifest's perceptual hashes can be compared with `other`'s.
+    ///
+    /// Fails with `AppError::VersionMismatch` when both use the same perceptual
+    /// algorithm at different versions, since the distance between such hashes
+    /// is meaningless. Manifests without perceptual hashes always pass.
+    pub fn check_comparable(&self, other: &MediaManifest) -> Result<()> {
+        match (self.perceptual_algorithm_name(), other.perceptual_algorithm_name()) {
+            (Some(name), Some(other_name)) if name == other_name => {
+                version_check(name, self.algorithm_version(name), other.algorithm_version(name))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Like [`check_comparable`](Self::check_comparable), against hashes computed by this build.
+    ///
+    /// Algorithms that are no longer registered are not checked.
+    pub fn check_current(&self) -> Result<()> {
+        let Some(name) = self.perceptual_algorithm_name() else {
+            return Ok(());
+        };
+        match crate::core::perceptual::algorithm(Some(name)) {
+            Ok(algorithm) => version_check(name, self.algorithm_version(name), algorithm.version()),
+            Err(_) => Ok(()),
+        }
+    }
+
+    /// Qualifies a byte-level mismatch when the file's `stream_hash` still matches.
+    fn metadata_only_note(&self, bytes: &[u8]) -> &'static str {
+        let Some(expected) = &self.stream_hash else {
+            return "";
+        };
+        match crate::core::hash::stream_hash(bytes) {
+            Some(found) if crate::core::hash::secure_hash_eq(&found, expected) => {
+                ", but only metadata changed (image stream unchanged)"
+            }
+            _ => "",
+        }
+    }
+
+    fn verify_report_with(&self, path: &Path, cache: Option<&DecodeCache>) -> Result<VerificationReport> {
+        info!("Verifying file at path: {:?}", path);
+
+        if !path.exists() {
+            return Ok(VerificationReport::failed("path does not exist".to_string()));
+        }
+
+        if !path.is_file() {
+            return Ok(VerificationReport::failed("path is not a file".to_string()));
+        }
+
+        if self.is_stub() {
+            return Ok(VerificationReport::failed(STUB_REJECTION.to_string()));
+        }
+
+        // Only images are read into memory, and only when their size is
+        // plausible: a metadata-only edit changes it by a little at most
+        // a PNG may also carry its own manifest, which verification skips over
+        let size = std::fs::metadata(path).map_err(|e| AppError::from_io_at(e, path))?.len();
+        let plausible = size == self.file_size
+            || (self.stream_hash.is_some() && size <= self.file_size.saturating_mul(2))
+            || (size <= self.file_size.saturating_add(crate::core::container::MAX_EMBEDDED_MANIFEST_SIZE)
+                && starts_like_png(path)?);
+        if self.media_type == MediaType::Image && plausible {
+            let bytes = std::fs::read(path).map_err(|e| AppError::from_io_at(e, path))?;
+            return match cache {
+                Some(cache) if !self.is_partial_decode() => self.verify_bytes_with(&bytes, || cache.open(path)),
+                _ => self.verify_bytes(&bytes),
+            };
+        }
+
+        if size != self.file_size {
+            return Ok(VerificationReport::failed(format!(
+                "size mismatch. Expected: {}, Found: {}",
+                self.file_size, size
+            )));
+        }
+        let file_hash = crate::core::hash::compute_file_hash(path).map_err(|e| match e.downcast::<std::io::Error>() {
+            Ok(e) => AppError::from_io_at(e, path),
+            Err(e) => e.into(),
+        })?;
+        if !crate::core::hash::secure_hash_eq(&file_hash, &self.sha3_256_hash) {
+            return Ok(VerificationReport::failed(format!(
+                "SHA3 hash mismatch. Expected: {}, Found: {}",
+                self.sha3_256_hash, file_hash
+            )));
+        }
+
+        info!("Verification successful.");
+        Ok(VerificationReport { is_valid: true, reason: None, explanation: None })
+    }
+
+    /// Verifies in-memory file contents against the manifest.
+    ///
+    /// The same checks as [`verify_report`](Self::verify_report), for callers
+    /// that already hold the bytes: size and SHA3-256 over the slice and, for
+    /// images, the frame count and perceptual hash of the decoded picture. A
+    /// manifest embedded in a PNG is left out of the size and hash.
+    /// Manifests flagged with [`PARTIAL_DECODE_KEY`] decode the picture as
+    /// leniently as it was decoded when the manifest was created.
+    pub fn verify_bytes(&self, bytes: &[u8]) -> Result<VerificationReport> {
+        self.verify_bytes_with(bytes, || Ok(Arc::new(self.decode_image(bytes)?)))
+    }
+
+    /// Whether the manifest was made from a file that only partially decoded.
+    fn is_partial_decode(&self) -> bool {
+        self.metadata.get(PARTIAL_DECODE_KEY).and_then(serde_json::Value::as_bool) == Some(true)
+    }
+
+    /// Decodes an image file the way it was decoded when the manifest was created.
+    fn decode_image(&self, bytes: &[u8]) -> anyhow::Result<DynamicImage> {
+        if self.is_partial_decode() {
+            Ok(crate::core::hash::decode_lenient(bytes)?.0)
+        } else {
+            Ok(image::load_from_memory(bytes)?)
+        }
+    }
+
+    /// [`verify_bytes`](Self::verify_bytes), with images decoded by `decode` only when needed.
+    fn verify_bytes_with<F>(&self, bytes: &[u8], decode: F) -> Result<VerificationReport>
+    where
+        F: FnOnce() -> anyhow::Result<Arc<DynamicImage>>,
+    {
+        let fail = |reason: String| Ok(VerificationReport::failed(reason));
+
+        if self.is_stub() {
+            return fail(STUB_REJECTION.to_string());
+        }
+
+        let content = crate::core::container::manifest_content(bytes);
+        let bytes = content.as_ref();
+
+        if bytes.len() as u64 != self.file_size {
+            return fail(format!(
+                "size mismatch{}. Expected: {}, Found: {}",
+                self.metadata_only_note(bytes),
+                self.file_size,
+                bytes.len()
+            ));
+        }
+
+        let file_hash = crate::core::hash::compute_sha3_256(bytes)?;
+        if !crate::core::hash::secure_hash_eq(&file_hash, &self.sha3_256_hash) {
+            return fail(format!(
+                "SHA3 hash mismatch{}. Expected: {}, Found: {}",
+                self.metadata_only_note(bytes),
+                self.sha3_256_hash,
+                file_hash
+            ));
+        }
+
+        if self.media_type == MediaType::Image {
+            if let Some(reason) = self.frame_count_mismatch(bytes)? {
+                return fail(reason);
+            }
+            if let Some(pdq_hash) = &self.pdq_hash {
+                if let Err(e) = self.check_current() {
+                    return fail(e.to_string());
+                }
+                let img = decode()?;
+                if let Some(name) = self.custom_perceptual_algorithm() {
+                    let algorithm = match crate::core::perceptual::algorithm(Some(name)) {
+                        Ok(algorithm) => algorithm,
+                        Err(e) => return fail(e.to_string()),
+                    };
+                    let computed = algorithm.hash(&img)?;
+                    if algorithm.compare(pdq_hash, &computed)? != 0 {
+                        return fail(format!(
+                            "{} hash mismatch. Expected: {}, Found: {}",
+                            name,
+                            pdq_hash,
+                            computed
+                        ));
+                    }
+                } else {
+                    let computed_pdq_hash = crate::core::hash::compute_pdq_hash(&img)?;
+                    if !crate::core::hash::secure_hash_eq(&self.pdq_hash_hex(pdq_hash)?, &computed_pdq_hash) {
+                        return fail(format!(
+                            "PDQ hash mismatch. Expected: {}, Found: {}",
+                            pdq_hash,
+                            computed_pdq_hash
+                        ));
+                    }
+                }
+            }
+        }
+
+        info!("Verification successful.");
+        Ok(VerificationReport { is_valid: true, reason: None, explanation: None })
+    }
+
+    /// Verifies that a file shows the same picture as the manifest, whatever its encoding.
+    ///
+    /// Unlike [`verify`](Self::verify), this compares `content_hash` instead of
+    /// the file bytes, so a lossless format migration (say, JPEG pixels saved
+    /// as WebP) still verifies. With a `tolerance`, a file whose pixels differ
+    /// is also accepted if its PDQ hash is within that many bits of the
+    /// manifest's, which covers lossy migrations.
+    ///
+    /// Returns `AppError::Validation` if the manifest has no content hash.
+    pub fn verify_content<P: AsRef<Path>>(&self, file_path: P, tolerance: Option<u32>) -> Result<bool> {
+        let path = file_path.as_ref();
+        let expected = self
+            .content_hash
+            .as_deref()
+            .ok_or_else(|| AppError::Validation("manifest has no content hash".to_string()))?;
+
+        if self.is_stub() {
+            warn!("{}", STUB_REJECTION);
+            return Ok(false);
+        }
+
+        let data = std::fs::read(path).map_err(|e| AppError::from_io_at(e, path))?;
+        if let Some(reason) = self.frame_count_mismatch(&data)? {
+            warn!("{}", reason);
+            return Ok(false);
+        }
+        let image = self
+            .decode_image(&data)
+            .map_err(|e| AppError::InvalidInput(format!("Failed to decode image {}: {}", path.display(), e)))?;
+        let oriented = apply_orientation(image.clone(), exif_orientation(&data));
+        let content_hash = compute_content_hash(&oriented)?;
+        if crate::core::hash::secure_hash_eq(&content_hash, expected) {
+            return Ok(true);
+        }
+
+        match (tolerance, &self.pdq_hash) {
+            (Some(max_distance), Some(pdq_hash)) => {
+                let computed = crate::core::hash::compute_pdq_hash(&image)?;
+                let distance = crate::core::hash::pdq_distance(&self.pdq_hash_hex(pdq_hash)?, &computed)?;
+                debug!("Content hash differs; PDQ distance {} (tolerance {})", distance, max_distance);
+                Ok(distance <= max_distance)
+            }
+            _ => {
+                warn!("Content hash mismatch. Expected: {}, Found: {}", expected, content_hash);
+                Ok(false)
+            }
+        }
+    }
+
+    /// Number of frames an image manifest expects its file to have.
+    ///
+    /// Still images record no frames (or a single one) and expect 1; animated
+    /// images expect the `frame_count` recorded when they were processed.
+    fn expected_image_frames(&self) -> usize {
+        self.frame_count
+            .or_else(|| self.frames.as_ref().map(Vec::len))
+            .unwrap_or(0)
+            .max(1)
+    }
+
+    /// Describes how an image file's frame count differs from the manifest's, if it does.
+    ///
+    /// Verification only hashes the first frame, so without this an animation
+    /// whose first frame matches a still image's would pass for it. Partially
+    /// decoded files whose frames cannot be counted are not checked.
+    fn frame_count_mismatch(&self, data: &[u8]) -> Result<Option<String>> {
+        let expected = self.expected_image_frames();
+        let found = match crate::core::hash::image_frame_count(data) {
+            Ok(found) => found,
+            Err(_) if self.is_partial_decode() => return Ok(None),
+            Err(e) => return Err(AppError::InvalidInput(format!("Failed to count frames: {}", e))),
+        };
+        Ok((found != expected).then(|| format!("frame count mismatch. Expected: {}, Found: {}", expected, found)))
+    }
+
+    /// Like [`verify_report`](Self::verify_report), resolving the file as [`verify_in`](Self::verify_in) does.
+    pub fn verify_report_in<P: AsRef<Path>>(&self, base_dir: P) -> Result<VerificationReport> {
+        let path = self.resolve_in(base_dir.as_ref())?;
+        self.verify_report(path)
+    }
+
+    /// Locates the manifest's file among the files directly inside `dir` and verifies it.
+    ///
+    /// The file is first looked up as [`verify_in`](Self::verify_in) does. If
+    /// it is missing or fails verification, every other regular file is a
+    /// candidate, so a renamed file is still found by its SHA3-256 hash; only
+    /// candidates of the recorded `file_size` are hashed, bounding the scan.
+    /// Returns the first matching file with its report, or the named file's
+    /// failed report if nothing matched, or `None` if there was neither.
+    pub fn verify_in_dir(&self, dir: &Path) -> Result<Option<(std::path::PathBuf, VerificationReport)>> {
+        let named = self.resolve_in(dir).ok().filter(|path| path.is_file());
+        let named_report = match &named {
+            Some(path) => {
+                let report = self.verify_report(path)?;
+                if report.is_valid {
+                    return Ok(Some((path.clone(), report)));
+                }
+                Some(report)
+            }
+            None => None,
+        };
+
+        let mut candidates = Vec::new();
+        for entry in std::fs::read_dir(dir).map_err(|e| AppError::from_io_at(e, dir))? {
+            let entry = entry?;
+            let path = entry.path();
+            if entry.file_type()?.is_file() && Some(&path) != named.as_ref() && entry.metadata()?.len() == self.file_size {
+                candidates.push(path);
+            }
+        }
+        candidates.sort();
+        debug!("{} size-matching candidates for {} in {}", candidates.len(), self.file_name, dir.display());
+
+        for path in candidates {
+            if crate::core::hash::secure_hash_eq(&crate::core::hash::compute_file_hash(&path)?, &self.sha3_256_hash) {
+                let report = self.verify_report(&path)?;
+                return Ok(Some((path, report)));
+            }
+        }
+        Ok(named.zip(named_report))
+    }
+}
+
+/// Whether the file at `path` begins like a PNG, which may carry an embedded manifest.
+fn starts_like_png(path: &Path) -> Result<bool> {
+    use std::io::Read;
+    let mut head = [0u8; 8];
+    let mut file = std::fs::File::open(path).map_err(|e| AppError::from_io_at(e, path))?;
+    let read = file.read(&mut head).map_err(|e| AppError::from_io_at(e, path))?;
+    Ok(crate::core::container::is_png(&head[..read]))
+}
+
+/// RFC 4648 base32 in lowercase, without padding.
+fn base32_lower(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 32] = b"abcdefghijklmnopqrstuvwxyz234567";
+    let mut out = String::with_capacity(data.len().div_ceil(5) * 8);
+    let (mut buffer, mut bits) = (0u16, 0u32);
+    for &byte in data {
+        buffer = (buffer << 8) | u16::from(byte);
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            out.push(ALPHABET[usize::from((buffer >> bits) & 0x1f)] as char);
+        }
+    }
+    if bits > 0 {
+        out.push(ALPHABET[usize::from((buffer << (5 - bits)) & 0x1f)] as char);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    #[test]
+    fn test_manifest_verification() {
+        // Create a temporary image file
+        let file = tempfile::Builder::new().suffix(".png").tempfile().unwrap();
+        let img = image::RgbImage::new(10, 10);
+        img.save(file.path()).unwrap();
+
+        let file_hash = crate::core::hash::compute_file_hash(file.path()).unwrap();
+        let pdq_hash = crate::core::hash::compute_pdq_hash(&image::open(file.path()).unwrap()).unwrap();
+
+        let manifest = MediaManifest::new(
+            "test_image.png".to_string(),
+            file.path(),
+            MediaType::Image,
+            file_hash,
+            Some(pdq_hash),
+            None,
+            None,
+        ).unwrap();
+
+        // Verification should pass
+        assert!(manifest.verify(file.path()).unwrap());
+    }
+    
+    #[test]
+    fn test_binary_pdq_manifest_migrates_to_hex() {
+        let file = tempfile::Builder::new().suffix(".png").tempfile().unwrap();
+        image::RgbImage::from_fn(32, 32, |x, y| image::Rgb([(x * 8) as u8, (y * 8) as u8, 0]))
+            .save(file.path())
+            .unwrap();
+
+        let file_hash = crate::core::hash::compute_file_hash(file.path()).unwrap();
+        let hex = crate::core::hash::compute_pdq_hash(&image::open(file.path()).unwrap()).unwrap();
+        let manifest = MediaManifest::new(
+            "legacy.png".to_string(),
+            file.path(),
+            MediaType::Image,
+            file_hash,
+            Some(hex.clone()),
+            None,
+            None,
+        ).unwrap();
+        assert_eq!(manifest.pdq_format, Some(PdqFormat::Hex));
+
+        // A manifest written before hex hashes: binary string, no format marker
+        let mut legacy = serde_json::to_value(&manifest).unwrap();
+        legacy["pdq_hash"] = crate::core::hash::pdq_to_binary(&hex).unwrap().into();
+        legacy.as_object_mut().unwrap().remove("pdq_format");
+
+        let unmigrated: MediaManifest = serde_json::from_value(legacy.clone()).unwrap();
+        assert!(unmigrated.verify(file.path()).unwrap());
+
+        let migrated = MediaManifest::from_json(&legacy.to_string()).unwrap();
+        assert_eq!(migrated.pdq_hash.as_deref(), Some(hex.as_str()));
+        assert_eq!(migrated.pdq_format, Some(PdqFormat::Hex));
+        assert!(migrated.verify(file.path()).unwrap());
+    }
+
+    #[test]
+    fn test_custom_algorithm_hashes_are_not_migrated() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), b"custom").unwrap();
+        let mut manifest = MediaManifest::new("custom.png".to_string(), file.path(), MediaType::Image, "0".repeat(64), None, None, None).unwrap();
+        // A binary-looking hash from a custom algorithm, with no format marker
+        let custom_hash = "01".repeat(crate::core::hash::PDQ_HASH_BITS / 2);
+        manifest.pdq_hash = Some(custom_hash.clone());
+        manifest.pdq_format = None;
+        manifest.perceptual_algorithm = Some("mean-luma".to_string());
+
+        let loaded = MediaManifest::from_json(&serde_json::to_string(&manifest).unwrap()).unwrap();
+        assert_eq!(loaded.pdq_hash, Some(custom_hash));
+        assert_eq!(loaded.pdq_format, None);
+    }
+
+    #[test]
+    fn test_manifest_serialization() {
+        let manifest = MediaManifest {
+            media_type: MediaType::Image,
+            file_name: "test.jpg".to_string(),
+            relative_path: None,
+            file_size: 1024,
+            created_at: Utc::now().to_rfc3339(),
+            modified_at: Utc::now().to_rfc3339(),
+            sha3_256_hash: "test_hash".to_string(),
+            pdq_hash: Some("pdq_hash".to_string()),
+            perceptual_algorithm: None,
+            algorithm_versions: BTreeMap::new(),
+            pdq_format: None,
+            color_signature: None,
+            color_histogram: None,
+            content_hash: None,
+            stream_hash: None,
+            frame_source: None,
+            frames_merkle_root: None,
+            frame_count: None,
             frames: None,
             metadata: serde_json::json!({}),
+            derived_from: None,
+            embedding_dim: None,
+            embedding_model: None,
+            video_embedding: None,
         };
         
         let json = manifest.to_json().unwrap();
@@ -201,4 +1700,672 @@ mod tests {
         assert_eq!(deserialized.file_name, "test.jpg");
         assert_eq!(deserialized.sha3_256_hash, "test_hash");
     }
+
+    #[test]
+    fn test_verify_bytes_accepts_intact_and_rejects_corrupted_bytes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("photo.png");
+        image::RgbImage::from_fn(64, 64, |x, y| image::Rgb([(x * 4) as u8, (y * 4) as u8, 128]))
+            .save(&path)
+            .unwrap();
+        let manifest = crate::process_image(&path).unwrap();
+        let mut bytes = std::fs::read(&path).unwrap();
+
+        let report = manifest.verify_bytes(&bytes).unwrap();
+        assert!(report.is_valid, "{:?}", report.reason);
+        assert_eq!(report, manifest.verify_report(&path).unwrap());
+
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xff;
+        let report = manifest.verify_bytes(&bytes).unwrap();
+        assert!(!report.is_valid);
+        assert!(report.reason.unwrap().starts_with("SHA3 hash mismatch"));
+
+        let report = manifest.verify_bytes(&bytes[..last]).unwrap();
+        assert!(report.reason.unwrap().starts_with("size mismatch"));
+    }
+
+    #[test]
+    fn test_stub_manifests_are_fast_deterministic_and_never_verify() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("big.png");
+        std::fs::write(&path, vec![0u8; 1024 * 1024]).unwrap();
+
+        let stub = MediaManifest::stub("big.png".to_string(), &path, MediaType::Image, "big.png").unwrap();
+        assert!(stub.is_stub());
+        assert_eq!(stub.perceptual_algorithm.as_deref(), Some(STUB_ALGORITHM));
+        assert_eq!(stub.sha3_256_hash.len(), 64);
+        assert_eq!(stub.pdq_hash.as_ref().map(String::len), Some(16));
+        assert_eq!(stub.file_size, 1024 * 1024);
+        let again = MediaManifest::stub("copy.png".to_string(), &path, MediaType::Image, "big.png").unwrap();
+        assert_eq!(again.sha3_256_hash, stub.sha3_256_hash);
+
+        // The contents are never read: other bytes of the same size give the same stub
+        let other = dir.path().join("other.png");
+        std::fs::write(&other, (0..1024 * 1024).map(|i| (i * 31 % 251) as u8).collect::<Vec<u8>>()).unwrap();
+        let unread = MediaManifest::stub("big.png".to_string(), &other, MediaType::Image, "big.png").unwrap();
+        assert_eq!(unread.sha3_256_hash, stub.sha3_256_hash);
+        assert_eq!(unread.pdq_hash, stub.pdq_hash);
+
+        let report = stub.verify_report(&path).unwrap();
+        assert!(!report.is_valid);
+        assert_eq!(report.reason.as_deref(), Some(STUB_REJECTION));
+        assert!(!MediaManifest::from_json(&stub.to_json().unwrap()).unwrap().verify(&path).unwrap());
+    }
+
+    #[test]
+    fn test_compact_json_is_smaller_and_round_trips() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), b"compact me").unwrap();
+        let hash = crate::core::hash::compute_file_hash(file.path()).unwrap();
+        let manifest = MediaManifest::new("a.bin".to_string(), file.path(), MediaType::Other, hash, None, None, None).unwrap();
+
+        let compact = manifest.to_json_compact().unwrap();
+        assert!(compact.len() < manifest.to_json().unwrap().len());
+        assert!(!compact.contains('\n'));
+        assert!(MediaManifest::from_json(&compact).unwrap().diff(&manifest).is_empty());
+    }
+
+    #[test]
+    fn test_renamed_file_is_found_by_hash_in_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let original = dir.path().join("original.bin");
+        std::fs::write(&original, b"the real contents").unwrap();
+        let hash = crate::core::hash::compute_file_hash(&original).unwrap();
+        let manifest = MediaManifest::new("original.bin".to_string(), &original, MediaType::Other, hash, None, None, None).unwrap();
+
+        let (found, report) = manifest.verify_in_dir(dir.path()).unwrap().unwrap();
+        assert_eq!((found, report.is_valid), (original.clone(), true));
+
+        // Renamed, with a same-size impostor and an unrelated file alongside it
+        let renamed = dir.path().join("IMG_0001.bin");
+        std::fs::rename(&original, &renamed).unwrap();
+        std::fs::write(dir.path().join("aaa-impostor.bin"), b"the fake contents").unwrap();
+        std::fs::write(dir.path().join("other.bin"), b"something else entirely").unwrap();
+        let (found, report) = manifest.verify_in_dir(dir.path()).unwrap().unwrap();
+        assert_eq!((found, report.is_valid), (renamed.clone(), true));
+
+        // Digests compare as hashes, not strings
+        let mut uppercase = manifest.clone();
+        uppercase.sha3_256_hash = uppercase.sha3_256_hash.to_uppercase();
+        assert_eq!(uppercase.verify_in_dir(dir.path()).unwrap().unwrap().0, renamed);
+
+        std::fs::remove_file(&renamed).unwrap();
+        assert_eq!(manifest.verify_in_dir(dir.path()).unwrap(), None);
+    }
+
+    #[test]
+    fn test_content_eq_ignores_timestamps() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), b"same bytes").unwrap();
+        let hash = crate::core::hash::compute_file_hash(file.path()).unwrap();
+        let first = MediaManifest::new("a.bin".to_string(), file.path(), MediaType::Other, hash, None, None, None).unwrap();
+        let mut second = first.clone();
+        second.created_at = "2020-01-01T00:00:00+00:00".to_string();
+        second.modified_at = "2020-01-02T00:00:00+00:00".to_string();
+
+        assert!(first.content_eq(&second));
+        let fields: Vec<String> = first.diff(&second).into_iter().map(|d| d.field).collect();
+        assert_eq!(fields, vec!["created_at", "modified_at"]);
+
+        second.sha3_256_hash = "0".repeat(64);
+        assert!(!first.content_eq(&second));
+        let diff = first.diff(&second);
+        assert!(diff.iter().any(|d| d.field == "sha3_256_hash" && d.right == "0".repeat(64)));
+    }
+
+    #[test]
+    fn test_identical_content_shares_a_fingerprint() {
+        let dir = tempfile::tempdir().unwrap();
+        let manifest_for = |name: &str, data: &[u8]| {
+            let path = dir.path().join(name);
+            std::fs::write(&path, data).unwrap();
+            let hash = crate::core::hash::compute_file_hash(&path).unwrap();
+            MediaManifest::new(name.to_string(), &path, MediaType::Other, hash, None, None, None).unwrap()
+        };
+        let first = manifest_for("a.bin", b"same bytes");
+        let mut second = manifest_for("b.bin", b"same bytes");
+        second.created_at = "2020-01-01T00:00:00+00:00".to_string();
+        let third = manifest_for("c.bin", b"other bytes");
+
+        assert_eq!(first.fingerprint(), second.fingerprint());
+        assert_ne!(first.fingerprint(), third.fingerprint());
+        assert_eq!(first.fingerprint().len(), 32);
+        assert!(first.fingerprint().chars().all(|c| c.is_ascii_lowercase() || ('2'..='7').contains(&c)));
+        assert_eq!(base32_lower(b"foobar"), "mzxw6ytboi");
+    }
+
+    #[test]
+    fn test_comparing_pdq_versions_is_a_version_mismatch() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), b"pixels").unwrap();
+        let hash = crate::core::hash::compute_file_hash(file.path()).unwrap();
+        let v1 = MediaManifest::new("a.png".to_string(), file.path(), MediaType::Image, hash, Some("a50f3cff00817e12".to_string()), None, None).unwrap();
+        let mut v2 = v1.clone();
+        v2.algorithm_versions.insert("pdq".to_string(), 2);
+
+        assert_eq!(v1.algorithm_version("pdq"), crate::core::hash::PDQ_VERSION);
+        assert!(v1.check_comparable(&v1.clone()).is_ok());
+        let err = v1.check_comparable(&v2).unwrap_err();
+        assert!(matches!(&err, AppError::VersionMismatch { algorithm, left: 1, right: 2 } if algorithm == "pdq"));
+        assert_eq!(err.to_string(), "pdq version mismatch: v1 vs v2");
+
+        // Manifests written before versions were recorded count as v1
+        let mut unversioned = v1.clone();
+        unversioned.algorithm_versions.clear();
+        assert!(unversioned.check_comparable(&v1).is_ok());
+        assert!(crate::core::similarity::find_similar(&v1, [&v2], 64, None, None).is_empty());
+    }
+
+    #[test]
+    fn test_media_type_wire_format() {
+        for wire in ["\"image\"", "\"Image\""] {
+            let media_type: MediaType = serde_json::from_str(wire).unwrap();
+            assert_eq!(media_type, MediaType::Image);
+        }
+        assert_eq!(serde_json::to_string(&MediaType::Video).unwrap(), "\"video\"");
+        assert!(serde_json::from_str::<MediaType>("\"IMAGE\"").is_err());
+    }
+
+    #[test]
+    fn test_save_is_atomic() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("photo.manifest.json");
+
+        let mut manifest = MediaManifest {
+            media_type: MediaType::Image,
+            file_name: "photo.jpg".to_string(),
+            relative_path: None,
+            file_size: 1024,
+            created_at: Utc::now().to_rfc3339(),
+            modified_at: Utc::now().to_rfc3339(),
+            sha3_256_hash: "first_hash".to_string(),
+            pdq_hash: None,
+            perceptual_algorithm: None,
+            algorithm_versions: BTreeMap::new(),
+            pdq_format: None,
+            color_signature: None,
+            color_histogram: None,
+            content_hash: None,
+            stream_hash: None,
+            frame_source: None,
+            frames_merkle_root: None,
+            frame_count: None,
+            frames: None,
+            metadata: serde_json::json!({}),
+            derived_from: None,
+            embedding_dim: None,
+            embedding_model: None,
+            video_embedding: None,
+        };
+        manifest.save(&path).unwrap();
+
+        // A writer that crashed mid-save leaves only its temp file behind
+        let partial = &manifest.to_json().unwrap()[..20];
+        std::fs::write(dir.path().join(".manifest-crashed.tmp"), partial).unwrap();
+        assert_eq!(MediaManifest::load(&path).unwrap().sha3_256_hash, "first_hash");
+
+        manifest.sha3_256_hash = "second_hash".to_string();
+        manifest.save(&path).unwrap();
+        assert_eq!(MediaManifest::load(&path).unwrap().sha3_256_hash, "second_hash");
+
+        // Successful saves don't leave temp files of their own
+        let leftovers = std::fs::read_dir(dir.path())
+            .unwrap()
+            .filter(|e| e.as_ref().unwrap().file_name() != "photo.manifest.json")
+            .count();
+        assert_eq!(leftovers, 1);
+    }
+
+    #[test]
+    fn test_explained_report_carries_expected_and_actual_hashes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("photo.png");
+        image::RgbImage::from_fn(32, 32, |x, y| image::Rgb([(x * 8) as u8, (y * 8) as u8, 40]))
+            .save(&path)
+            .unwrap();
+        let mut manifest = crate::process_image(&path).unwrap();
+        let expected_sha3 = "0".repeat(64);
+        manifest.sha3_256_hash = expected_sha3.clone();
+
+        assert!(manifest.verify_report(&path).unwrap().explanation.is_none());
+        let report = manifest.verify_report_explained(&path).unwrap();
+
+        assert!(!report.is_valid);
+        let explanation = report.explanation.unwrap();
+        assert_eq!(explanation.expected_sha3_256, expected_sha3);
+        assert_eq!(explanation.actual_sha3_256, Some(crate::core::hash::compute_file_hash(&path).unwrap()));
+        assert_eq!(explanation.actual_file_size, Some(explanation.expected_file_size));
+        assert!(explanation.expected_pdq.is_some());
+        assert_eq!(explanation.actual_pdq, explanation.expected_pdq);
+    }
+
+    #[test]
+    fn test_verify_in_nested_relative_path() {
+        let base = tempfile::tempdir().unwrap();
+        let nested = base.path().join("2024").join("album");
+        std::fs::create_dir_all(&nested).unwrap();
+        let file_path = nested.join("photo.bin");
+        std::fs::write(&file_path, b"nested content").unwrap();
+
+        let file_hash = crate::core::hash::compute_file_hash(&file_path).unwrap();
+        let manifest = MediaManifest::new(
+            "photo.bin".to_string(),
+            &file_path,
+            MediaType::Other,
+            file_hash,
+            None,
+            None,
+            None,
+        )
+        .unwrap()
+        .with_relative_path("2024/album/photo.bin");
+
+        assert!(manifest.verify_in(base.path()).unwrap());
+
+        // The manifest stays valid after relocating the whole tree
+        let moved = tempfile::tempdir().unwrap();
+        let moved_nested = moved.path().join("2024").join("album");
+        std::fs::create_dir_all(&moved_nested).unwrap();
+        std::fs::copy(&file_path, moved_nested.join("photo.bin")).unwrap();
+        assert!(manifest.verify_in(moved.path()).unwrap());
+    }
+
+    #[test]
+    fn test_verify_in_rejects_path_traversal() {
+        let base = tempfile::tempdir().unwrap();
+        let mut manifest = MediaManifest {
+            media_type: MediaType::Other,
+            file_name: "secret.txt".to_string(),
+            relative_path: Some("../secret.txt".to_string()),
+            file_size: 0,
+            created_at: Utc::now().to_rfc3339(),
+            modified_at: Utc::now().to_rfc3339(),
+            sha3_256_hash: "hash".to_string(),
+            pdq_hash: None,
+            perceptual_algorithm: None,
+            algorithm_versions: BTreeMap::new(),
+            pdq_format: None,
+            color_signature: None,
+            color_histogram: None,
+            content_hash: None,
+            stream_hash: None,
+            frame_source: None,
+            frames_merkle_root: None,
+            frame_count: None,
+            frames: None,
+            metadata: serde_json::Value::Null,
+            derived_from: None,
+            embedding_dim: None,
+            embedding_model: None,
+            video_embedding: None,
+        };
+
+        assert!(matches!(manifest.verify_in(base.path()), Err(AppError::Validation(_))));
+
+        manifest.relative_path = Some("/etc/passwd".to_string());
+        assert!(matches!(manifest.verify_in(base.path()), Err(AppError::Validation(_))));
+    }
+
+    #[test]
+    fn test_derive_records_parent_hash() {
+        let dir = tempfile::tempdir().unwrap();
+        let parent_path = dir.path().join("original.png");
+        image::RgbImage::from_pixel(64, 64, image::Rgb([200, 10, 10]))
+            .save(&parent_path)
+            .unwrap();
+        let thumb_path = dir.path().join("thumb.png");
+        image::open(&parent_path).unwrap().thumbnail(16, 16).save(&thumb_path).unwrap();
+
+        let parent_hash = crate::core::hash::compute_file_hash(&parent_path).unwrap();
+        let parent = MediaManifest::new(
+            "original.png".to_string(),
+            &parent_path,
+            MediaType::Image,
+            parent_hash.clone(),
+            None,
+            None,
+            None,
+        ).unwrap();
+
+        let derived = parent.derive(&thumb_path, MediaType::Image).unwrap();
+
+        assert_eq!(derived.derived_from, Some(parent_hash));
+        assert_eq!(derived.file_name, "thumb.png");
+        assert!(derived.pdq_hash.is_some());
+        assert!(derived.verify(&thumb_path).unwrap());
+    }
+
+    #[test]
+    fn test_recompute_perceptual_keeps_crypto_hash() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("photo.png");
+        image::RgbImage::from_fn(64, 48, |x, y| image::Rgb([(x * 4) as u8, (y * 5) as u8, 120]))
+            .save(&path)
+            .unwrap();
+
+        let mut manifest = crate::process_image(&path).unwrap();
+        let fresh_pdq = manifest.pdq_hash.clone();
+        // Pretend the hash came from an older PDQ implementation
+        manifest.pdq_hash = Some("0".repeat(16));
+        manifest.modified_at = "2020-01-01T00:00:00+00:00".to_string();
+        let sha3 = manifest.sha3_256_hash.clone();
+
+        manifest.recompute_perceptual(&path).unwrap();
+
+        assert_eq!(manifest.sha3_256_hash, sha3);
+        assert_eq!(manifest.pdq_hash, fresh_pdq);
+        assert_ne!(manifest.modified_at, "2020-01-01T00:00:00+00:00");
+    }
+
+    #[cfg(feature = "hashing")]
+    #[test]
+    fn test_content_hash_survives_format_migration() {
+        use image::codecs::{jpeg::JpegEncoder, webp::WebPEncoder};
+        use image::{ColorType, ImageFormat};
+
+        let dir = tempfile::tempdir().unwrap();
+        let source = image::RgbImage::from_fn(160, 120, |x, y| {
+            let cell = (x / 10) * 16 + (y / 10);
+            let v = ((cell.wrapping_mul(2654435761) >> 24) % 256) as u8;
+            image::Rgb([v, 255 - v, v / 2])
+        });
+        let jpeg_path = dir.path().join("photo.jpg");
+        DynamicImage::ImageRgb8(source)
+            .save_with_format(&jpeg_path, ImageFormat::Jpeg)
+            .unwrap();
+        let original = crate::process_image(&jpeg_path).unwrap();
+
+        // Lossless migration: the decoded JPEG pixels saved as WebP
+        let decoded = image::open(&jpeg_path).unwrap().to_rgba8();
+        let (w, h) = decoded.dimensions();
+        let webp_path = dir.path().join("photo.webp");
+        let mut webp = Vec::new();
+        WebPEncoder::new_lossless(&mut webp)
+            .encode(decoded.as_raw(), w, h, ColorType::Rgba8)
+            .unwrap();
+        std::fs::write(&webp_path, webp).unwrap();
+
+        let migrated = crate::process_image(&webp_path).unwrap();
+        assert!(original.content_hash.is_some());
+        assert_eq!(original.content_hash, migrated.content_hash);
+        assert!(!original.verify(&webp_path).unwrap());
+        assert!(original.verify_content(&webp_path, None).unwrap());
+
+        // Lossy migration only verifies within a PDQ tolerance
+        let lossy_path = dir.path().join("photo-q40.jpg");
+        let mut lossy = Vec::new();
+        JpegEncoder::new_with_quality(&mut lossy, 40)
+            .encode(decoded.as_raw(), w, h, ColorType::Rgba8)
+            .unwrap();
+        std::fs::write(&lossy_path, lossy).unwrap();
+        assert!(!original.verify_content(&lossy_path, None).unwrap());
+        assert!(original.verify_content(&lossy_path, Some(DEFAULT_FRAME_TOLERANCE)).unwrap());
+    }
+
+    #[test]
+    fn test_animation_posing_as_still_image_fails_verification() {
+        use image::codecs::gif::GifEncoder;
+        use image::{Delay, Frame, RgbaImage};
+
+        let dir = tempfile::tempdir().unwrap();
+        let checkers = RgbaImage::from_fn(64, 64, |x, y| {
+            if (x / 8 + y / 8) % 2 == 0 { image::Rgba([0, 0, 0, 255]) } else { image::Rgba([255, 255, 255, 255]) }
+        });
+        let still_path = dir.path().join("still.png");
+        checkers.save(&still_path).unwrap();
+        let manifest = crate::process_image(&still_path).unwrap();
+        assert!(manifest.verify_content(&still_path, None).unwrap());
+
+        // Same first frame, followed by a second one
+        let mut gif = Vec::new();
+        GifEncoder::new(&mut gif)
+            .encode_frames([
+                Frame::from_parts(checkers, 0, 0, Delay::from_numer_denom_ms(100, 1)),
+                Frame::from_parts(RgbaImage::from_pixel(64, 64, image::Rgba([255, 0, 0, 255])), 0, 0, Delay::from_numer_denom_ms(100, 1)),
+            ])
+            .unwrap();
+        assert_eq!(crate::core::hash::image_frame_count(&gif).unwrap(), 2);
+        std::fs::write(&still_path, &gif).unwrap();
+
+        assert!(!manifest.verify_content(&still_path, None).unwrap());
+        let mut forged = manifest.clone();
+        forged.file_size = gif.len() as u64;
+        forged.sha3_256_hash = crate::core::hash::compute_file_hash(&still_path).unwrap();
+        let report = forged.verify_report(&still_path).unwrap();
+        assert!(!report.is_valid);
+        assert_eq!(report.reason.as_deref(), Some("frame count mismatch. Expected: 1, Found: 2"));
+    }
+
+    #[test]
+    fn test_unmodified_animated_gif_verifies() {
+        use image::codecs::gif::GifEncoder;
+        use image::{Delay, Frame, RgbaImage};
+
+        let dir = tempfile::tempdir().unwrap();
+        let frame = |shade: u8| {
+            let img = RgbaImage::from_fn(64, 64, |x, y| image::Rgba([shade ^ (x * 4) as u8, (y * 4) as u8, shade, 255]));
+            Frame::from_parts(img, 0, 0, Delay::from_numer_denom_ms(100, 1))
+        };
+        let mut gif = Vec::new();
+        GifEncoder::new(&mut gif).encode_frames([frame(0), frame(90), frame(180)]).unwrap();
+        let path = dir.path().join("animated.gif");
+        std::fs::write(&path, &gif).unwrap();
+
+        let manifest = crate::process_image(&path).unwrap();
+        assert_eq!(manifest.frame_count, Some(3));
+        assert!(manifest.verify_report(&path).unwrap().is_valid);
+        assert!(manifest.verify_content(&path, None).unwrap());
+
+        // Still images keep recording no count
+        let still = dir.path().join("still.png");
+        RgbaImage::from_pixel(64, 64, image::Rgba([1, 2, 3, 255])).save(&still).unwrap();
+        assert_eq!(crate::process_image(&still).unwrap().frame_count, None);
+    }
+
+    #[test]
+    fn test_minimal_manifest_verifies_by_merkle_root() {
+        let frame = |seed: u32| {
+            DynamicImage::ImageRgb8(image::RgbImage::from_fn(32, 32, |x, y| {
+                let cell = (x / 4) * 8 + (y / 4) + seed * 64;
+                image::Rgb([((cell.wrapping_mul(2654435761) >> 24) % 256) as u8; 3])
+            }))
+        };
+        let frames: Vec<FrameInfo> = (0..120)
+            .map(|i| FrameInfo {
+                timestamp_secs: i as f64,
+                pdq_hash: crate::core::hash::compute_pdq_hash(&frame(i)).unwrap(),
+                embedding: None,
+            })
+            .collect();
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), b"video bytes").unwrap();
+        let hash = crate::core::hash::compute_file_hash(file.path()).unwrap();
+        let full = MediaManifest::new("clip.mp4".to_string(), file.path(), MediaType::Video, hash, None, Some(frames), None).unwrap();
+
+        let minimal = full.clone().into_minimal().unwrap();
+        assert!(minimal.frames.is_none());
+        assert_eq!(minimal.frame_count, Some(120));
+        assert!(minimal.to_json().unwrap().len() * 20 < full.to_json().unwrap().len());
+
+        assert!(minimal.verify_frames_root((0..120).map(frame)).unwrap());
+        assert!(!minimal.verify_frames_root((0..119).map(frame)).unwrap());
+        assert!(!minimal.verify_frames_root((0..120).map(|i| frame(if i == 60 { 999 } else { i }))).unwrap());
+        // Reordered frames produce a different root
+        assert!(!minimal.verify_frames_root((0..120).rev().map(frame)).unwrap());
+    }
+
+    #[test]
+    fn test_frames_integrity_detects_an_edited_frame() {
+        let frames: Vec<FrameInfo> = (0..8u64)
+            .map(|i| FrameInfo {
+                timestamp_secs: i as f64,
+                pdq_hash: format!("{:016x}", i.wrapping_mul(0x9E37_79B9_7F4A_7C15)),
+                embedding: None,
+            })
+            .collect();
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), b"video bytes").unwrap();
+        let hash = crate::core::hash::compute_file_hash(file.path()).unwrap();
+        let mut manifest =
+            MediaManifest::new("clip.mp4".to_string(), file.path(), MediaType::Video, hash, None, Some(frames), None).unwrap();
+        assert!(!manifest.verify_frames_integrity());
+
+        manifest.seal_frames().unwrap();
+        assert!(manifest.verify_frames_integrity());
+        assert_eq!(manifest.frames_merkle_root, manifest.clone().into_minimal().unwrap().frames_merkle_root);
+        drop(file);
+        assert!(manifest.verify_frames_integrity());
+
+        let mut tampered = manifest.clone();
+        tampered.frames.as_mut().unwrap()[3].pdq_hash = "ffffffffffffffff".to_string();
+        assert!(!tampered.verify_frames_integrity());
+
+        let mut truncated = manifest;
+        truncated.frames.as_mut().unwrap().pop();
+        assert!(!truncated.verify_frames_integrity());
+    }
+
+    #[test]
+    fn test_frame_verification_reports_progress() {
+        let frame = |seed: u32| {
+            DynamicImage::ImageRgb8(image::RgbImage::from_fn(64, 64, |x, y| {
+                let cell = (x / 8) * 8 + (y / 8) + seed * 64;
+                image::Rgb([((cell.wrapping_mul(2654435761) >> 24) % 256) as u8; 3])
+            }))
+        };
+        let frames: Vec<FrameInfo> = (0..4)
+            .map(|i| FrameInfo {
+                timestamp_secs: i as f64,
+                pdq_hash: crate::core::hash::compute_pdq_hash(&frame(i)).unwrap(),
+                embedding: None,
+            })
+            .collect();
+        let manifest = MediaManifest {
+            media_type: MediaType::Video,
+            file_name: "clip.mp4".to_string(),
+            relative_path: None,
+            file_size: 0,
+            created_at: Utc::now().to_rfc3339(),
+            modified_at: Utc::now().to_rfc3339(),
+            sha3_256_hash: "hash".to_string(),
+            pdq_hash: None,
+            perceptual_algorithm: None,
+            algorithm_versions: BTreeMap::new(),
+            pdq_format: None,
+            color_signature: None,
+            color_histogram: None,
+            content_hash: None,
+            stream_hash: None,
+            frame_source: Some(FrameSource::VideoFfmpeg),
+            frames_merkle_root: None,
+            frame_count: None,
+            frames: Some(frames),
+            metadata: serde_json::Value::Null,
+            derived_from: None,
+            embedding_dim: None,
+            embedding_model: None,
+            video_embedding: None,
+        };
+        let opts = VerifyOptions::default();
+
+        let mut updates = Vec::new();
+        let valid = manifest
+            .verify_frames_with_progress((0..4).map(frame), &opts, |p| updates.push(p))
+            .unwrap();
+        assert!(valid);
+        assert_eq!(updates.len(), 4);
+        assert_eq!(updates.iter().map(|p| p.frames_checked).collect::<Vec<_>>(), vec![1, 2, 3, 4]);
+        assert!(updates.iter().all(|p| p.frames_total == 4 && p.mismatches == 0));
+
+        // A swapped frame and a missing frame both show up as mismatches
+        let mut updates = Vec::new();
+        let valid = manifest
+            .verify_frames_with_progress([0, 9, 2].into_iter().map(frame), &opts, |p| updates.push(p))
+            .unwrap();
+        assert!(!valid);
+        assert_eq!(updates[1].mismatches, 1);
+        assert_eq!(updates.last().unwrap().mismatches, 2);
+        assert_eq!(updates.last().unwrap().frames_checked, 4);
+    }
+
+    /// A video manifest holding just `frames`, for frame-matching tests
+    fn frames_manifest(frames: Vec<FrameInfo>) -> MediaManifest {
+        MediaManifest {
+            media_type: MediaType::Video,
+            file_name: "clip.mp4".to_string(),
+            relative_path: None,
+            file_size: 0,
+            created_at: Utc::now().to_rfc3339(),
+            modified_at: Utc::now().to_rfc3339(),
+            sha3_256_hash: "hash".to_string(),
+            pdq_hash: None,
+            perceptual_algorithm: None,
+            algorithm_versions: BTreeMap::new(),
+            pdq_format: None,
+            color_signature: None,
+            color_histogram: None,
+            content_hash: None,
+            stream_hash: None,
+            frame_source: Some(FrameSource::VideoFfmpeg),
+            frames_merkle_root: None,
+            frame_count: None,
+            frames: Some(frames),
+            metadata: serde_json::Value::Null,
+            derived_from: None,
+            embedding_dim: None,
+            embedding_model: None,
+            video_embedding: None,
+        }
+    }
+
+    #[test]
+    fn test_frame_extractor_reapplies_recorded_min_entropy() {
+        let mut manifest = frames_manifest(Vec::new());
+        assert_eq!(manifest.frame_extractor(Path::new("clip.mp4"), None).min_entropy(), None);
+
+        manifest.metadata = serde_json::json!({ "frame_interval_secs": 1.0, "min_entropy": 2.5 });
+        assert_eq!(manifest.frame_extractor(Path::new("clip.mp4"), None).min_entropy(), Some(2.5));
+
+        manifest.metadata = serde_json::json!({ "min_entropy": null });
+        assert_eq!(manifest.frame_extractor(Path::new("clip.mp4"), None).min_entropy(), None);
+    }
+
+    #[test]
+    fn test_timestamp_tolerance_absorbs_drift_in_re_extracted_frames() {
+        let frame = |seed: u32| {
+            DynamicImage::ImageRgb8(image::RgbImage::from_fn(64, 64, |x, y| {
+                let cell = (x / 8) * 8 + (y / 8) + seed * 64;
+                image::Rgb([((cell.wrapping_mul(2654435761) >> 24) % 256) as u8; 3])
+            }))
+        };
+        let manifest = frames_manifest(
+            (0..4)
+                .map(|i| FrameInfo {
+                    timestamp_secs: f64::from(i) * 0.5,
+                    pdq_hash: crate::core::hash::compute_pdq_hash(&frame(i)).unwrap(),
+                    embedding: None,
+                })
+                .collect(),
+        );
+
+        // Re-extraction lands 40ms later and yields an extra frame up front
+        let drifted = || {
+            std::iter::once((frame(7), 0.0)).chain((0..4).map(|i| (frame(i), f64::from(i) * 0.5 + 0.04)))
+        };
+        let tolerant = VerifyOptions { timestamp_tolerance_secs: Some(0.05), ..VerifyOptions::default() };
+        let mut updates = Vec::new();
+        assert!(manifest.verify_timed_frames_with_progress(drifted(), &tolerant, |p| updates.push(p)).unwrap());
+        assert_eq!(updates.last().unwrap().frames_checked, 4);
+        assert_eq!(updates.last().unwrap().mismatches, 0);
+
+        // Matching by position is thrown off by the extra frame
+        assert!(!manifest.verify_timed_frames_with_progress(drifted(), &VerifyOptions::default(), |_| {}).unwrap());
+
+        // Drift beyond the tolerance is still a mismatch
+        let strict = VerifyOptions { timestamp_tolerance_secs: Some(0.01), ..VerifyOptions::default() };
+        let mut updates = Vec::new();
+        assert!(!manifest.verify_timed_frames_with_progress(drifted(), &strict, |p| updates.push(p)).unwrap());
+        assert_eq!(updates.last().unwrap().mismatches, 4);
+    }
 }
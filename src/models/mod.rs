@@ -3,3 +3,6 @@
 /// Defines the `MediaManifest` and related data structures.
 pub mod manifest;
 
+/// Signed manifest bundles for distribution.
+pub mod bundle;
+
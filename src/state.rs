@@ -1,21 +1,132 @@
+use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
 
-use crate::core::embeddings::EmbeddingModel;
+use serde::{Deserialize, Serialize};
+
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio::task::JoinHandle;
+use tokio_util::task::TaskTracker;
+
+#[cfg(feature = "web")]
+use chrono::{DateTime, Utc};
+
+use crate::core::cache::DecodeCache;
+use crate::core::embeddings::{EmbeddingModel, EmbeddingProvider, EmbeddingRegistry};
+use crate::core::index::BkTree;
+use crate::core::jobs::{run_job, Job, JobStore};
+use crate::core::store::OrphanPolicy;
+use crate::error::{AppError, Result};
+use crate::models::manifest::{ManifestDetail, MediaManifest};
+
+/// Seconds a client is asked to wait when the processing queue is full
+const BUSY_RETRY_AFTER_SECS: u64 = 1;
 
 /// Configuration for the application
 #[derive(Clone, Debug)]
 pub struct Config {
     /// Base directory for file uploads
     pub upload_dir: PathBuf,
+    /// Directory uploads are written to while they stream in, before moving to `upload_dir`
+    pub upload_temp_dir: PathBuf,
     /// Maximum file size in bytes
     pub max_upload_size: u64,
+    /// Maximum body size in bytes for routes taking JSON (verify, video
+    /// comparison, jobs); file-carrying routes get `max_upload_size`
+    pub max_json_body_size: usize,
     /// Allowed file extensions for uploads
     pub allowed_extensions: Vec<String>,
     /// Video processing configuration
     pub video: VideoConfig,
     /// Embedding model configuration
     pub embedding_model: EmbeddingModel,
+    /// Secret used to sign download links; signed downloads are disabled when unset
+    pub download_secret: Option<String>,
+    /// Maximum number of uploads processed at the same time
+    pub max_concurrent_jobs: usize,
+    /// Reject uploads beyond `max_concurrent_jobs` instead of queueing them
+    pub reject_when_busy: bool,
+    /// Parallelize the PDQ DCT across threads; hashes are identical either way
+    pub parallel_hashing: bool,
+    /// Run an image upload's perceptual hashes and embedding concurrently;
+    /// the manifest is identical either way
+    pub concurrent_pipeline: bool,
+    /// Produce placeholder manifests without hashing (see `MediaManifest::stub`),
+    /// for testing downstream pipelines; they never verify
+    pub fast_stub_hashes: bool,
+    /// Maximum PDQ distance for `/api/closest` to report a stored image as a match
+    pub closest_match_threshold: u32,
+    /// Smallest width and height, in pixels, of an uploaded image that gets a
+    /// PDQ hash; smaller images are flagged `too_small_for_pdq` instead
+    pub min_pdq_dimension: u32,
+    /// Most images `/api/distance-matrix` compares in one request, bounding
+    /// its quadratic number of comparisons
+    pub max_distance_matrix_size: usize,
+    /// Longest video, in seconds, an upload may extract frames from; longer
+    /// videos are rejected before extraction starts
+    pub max_video_duration_secs: f64,
+    /// Most frames extracted from one uploaded video, whatever the interval
+    pub max_total_frames: usize,
+    /// Largest body, in bytes, downloaded when ingesting media from a URL
+    pub max_download_bytes: u64,
+    /// Time limit for a whole download when ingesting media from a URL
+    pub download_timeout: Duration,
+    /// Refuse to download from hosts on private, loopback or link-local addresses
+    pub block_private_addresses: bool,
+    /// Named upload defaults, selected with the `profile` upload parameter
+    pub profiles: HashMap<String, ProcessingProfile>,
+    /// Directory to watch for new media, processed in the background when set
+    pub watch_dir: Option<PathBuf>,
+    /// How long shutdown waits for background jobs before abandoning them
+    pub shutdown_timeout: Duration,
+    /// Origins allowed to make cross-origin requests; empty allows any origin
+    pub cors_allowed_origins: Vec<String>,
+    /// Allow cross-origin requests with credentials (requires explicit origins)
+    pub cors_allow_credentials: bool,
+    /// Number of decoded images kept for reuse across operations; 0 disables the cache
+    pub decode_cache_size: usize,
+    /// Directory where uploads failing integrity checks are kept; they are deleted when unset
+    pub quarantine_dir: Option<PathBuf>,
+    /// Directory upload manifests are persisted to; manifests are not kept when unset
+    pub manifest_dir: Option<PathBuf>,
+    /// What startup does with uploads and manifests that have lost their counterpart
+    pub orphan_policy: OrphanPolicy,
+    /// Directory batch jobs read their sources from and keep their state in;
+    /// `/api/jobs` is disabled when unset
+    pub jobs_dir: Option<PathBuf>,
+    /// Time limit for requests on non-upload routes; unlimited when unset
+    pub request_timeout: Option<Duration>,
+    /// Keep HTTP/1.1 connections open between requests
+    pub http1_keep_alive: bool,
+    /// Interval between HTTP/2 keep-alive pings; no pings when unset
+    pub http2_keep_alive_interval: Option<Duration>,
+}
+
+/// Upload defaults for a kind of media, such as short clips or surveillance footage
+///
+/// Each option set here fills in the upload parameter of the same name when a
+/// request selecting the profile leaves it unset; options the request sets win.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProcessingProfile {
+    /// Include image and frame embeddings
+    pub include_embeddings: Option<bool>,
+    /// Seconds between extracted video frames
+    pub frame_interval_secs: Option<f64>,
+    /// Cap on the number of processed video frames
+    pub max_frames: Option<usize>,
+    /// Extract video frames at all
+    pub extract_frames: Option<bool>,
+    /// Embed only frames that start a new scene
+    pub embed_keyframes_only: Option<bool>,
+    /// Which video stream to extract frames from
+    pub video_stream_index: Option<usize>,
+    /// Registered perceptual algorithm used for images
+    pub algorithm: Option<String>,
+    /// How much per-frame data video manifests keep
+    pub detail: Option<ManifestDetail>,
 }
 
 /// Video processing configuration
@@ -31,17 +142,79 @@ pub struct VideoConfig {
     pub codec: String,
 }
 
+impl Config {
+    /// Checks for settings no server could run with
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError::Config` naming the first invalid setting.
+    pub fn validate(&self) -> Result<()> {
+        let invalid = |message: &str| Err(AppError::Config(message.to_string()));
+        if self.max_upload_size == 0 {
+            return invalid("max_upload_size must be positive");
+        }
+        if self.max_json_body_size == 0 {
+            return invalid("max_json_body_size must be positive");
+        }
+        if self.video.target_width == 0 || self.video.target_height == 0 {
+            return invalid("video target size must be positive");
+        }
+        Ok(())
+    }
+
+    /// Limits for ingesting media from URLs, e.g. with [`crate::process_image_url`]
+    pub fn fetch_limits(&self) -> crate::core::fetch::FetchLimits {
+        crate::core::fetch::FetchLimits {
+            max_bytes: self.max_download_bytes,
+            timeout: self.download_timeout,
+            block_private_addresses: self.block_private_addresses,
+            ..Default::default()
+        }
+    }
+}
+
 impl Default for Config {
     fn default() -> Self {
         Self {
             upload_dir: PathBuf::from("uploads"),
+            upload_temp_dir: std::env::temp_dir(),
             max_upload_size: 100 * 1024 * 1024, // 100MB
-            allowed_extensions: vec!["jpg", "jpeg", "png", "webp", "mp4", "mov", "avi"]
+            max_json_body_size: 1024 * 1024, // 1MB
+            allowed_extensions: vec!["jpg", "jpeg", "png", "webp", "mp4", "mov", "avi", "tif", "tiff", "pdf"]
                 .into_iter()
                 .map(String::from)
                 .collect(),
             video: VideoConfig::default(),
             embedding_model: EmbeddingModel::default(),
+            download_secret: None,
+            max_concurrent_jobs: std::thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(4),
+            reject_when_busy: false,
+            parallel_hashing: false,
+            concurrent_pipeline: true,
+            fast_stub_hashes: false,
+            closest_match_threshold: 10,
+            min_pdq_dimension: crate::core::hash::MIN_PDQ_DIMENSION,
+            max_distance_matrix_size: 100,
+            max_video_duration_secs: crate::core::video::DEFAULT_MAX_VIDEO_DURATION_SECS,
+            max_total_frames: crate::core::video::DEFAULT_MAX_TOTAL_FRAMES,
+            max_download_bytes: 100 * 1024 * 1024, // 100MB
+            download_timeout: Duration::from_secs(30),
+            block_private_addresses: true,
+            profiles: HashMap::new(),
+            watch_dir: None,
+            shutdown_timeout: Duration::from_secs(30),
+            cors_allowed_origins: Vec::new(),
+            cors_allow_credentials: false,
+            decode_cache_size: 0,
+            quarantine_dir: None,
+            manifest_dir: None,
+            orphan_policy: OrphanPolicy::Log,
+            jobs_dir: None,
+            request_timeout: None,
+            http1_keep_alive: true,
+            http2_keep_alive_interval: None,
         }
     }
 }
@@ -61,31 +234,406 @@ impl Default for VideoConfig {
 #[derive(Clone)]
 #[derive(Debug)]
 pub struct AppState {
-    /// Application configuration
-    pub config: Config,
+    /// Application configuration; see [`config`](Self::config)
+    config: Arc<RwLock<Arc<Config>>>,
     /// Shared embedding model instance
     pub embedding_model: EmbeddingModel,
+    /// Embedding providers uploads select with the `model` parameter
+    pub embedding_providers: Arc<RwLock<EmbeddingRegistry>>,
+    /// Slots bounding the number of concurrent processing jobs
+    pub jobs: Arc<Semaphore>,
+    /// PDQ index over processed images, keyed by stored file name
+    pub pdq_index: Arc<RwLock<BkTree<String>>>,
+    /// Manifests of the images in `pdq_index`, keyed the same way
+    pub indexed_manifests: Arc<RwLock<HashMap<String, MediaManifest>>>,
+    /// Background jobs that shutdown waits for
+    pub background: TaskTracker,
+    /// Decoded images reused across operations on the same file
+    pub decode_cache: Arc<DecodeCache>,
+    /// Requests waiting for a slot in `jobs`
+    pub queued_jobs: Arc<AtomicUsize>,
+    /// Upload requests being received or processed
+    pub uploads_in_progress: Arc<AtomicUsize>,
+    /// When the state was created, i.e. when the server started
+    pub started_at: Instant,
+    /// Ids of the batch jobs currently running
+    pub running_jobs: Arc<Mutex<HashSet<String>>>,
+}
+
+/// Live load figures reported by `/api/status`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct LoadStatus {
+    /// Processing slots currently held
+    pub active_jobs: usize,
+    /// Requests waiting for a processing slot
+    pub queued_jobs: usize,
+    /// Upload requests being received or processed, queued or not
+    pub uploads_in_progress: usize,
+    /// Seconds since the server started
+    pub uptime_secs: u64,
+}
+
+/// Counts itself in a shared gauge until dropped
+#[derive(Debug)]
+pub struct InFlight(Arc<AtomicUsize>);
+
+impl InFlight {
+    fn new(gauge: &Arc<AtomicUsize>) -> Self {
+        gauge.fetch_add(1, Ordering::SeqCst);
+        Self(gauge.clone())
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Outcome of waiting for background jobs at shutdown
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DrainReport {
+    /// Jobs that finished within the timeout
+    pub drained: usize,
+    /// Jobs still running when the timeout expired
+    pub aborted: usize,
 }
 
 impl AppState {
     /// Create a new application state with default configuration
     pub fn new() -> Arc<Self> {
-        let config = Config::default();
-        let embedding_model = config.embedding_model.clone();
-        
-        Arc::new(Self {
-            config,
-            embedding_model,
-        })
+        Self::with_config(Config::default())
     }
     
     /// Create a new application state with custom configuration
     pub fn with_config(config: Config) -> Arc<Self> {
         let embedding_model = config.embedding_model.clone();
+        let jobs = Arc::new(Semaphore::new(config.max_concurrent_jobs.max(1)));
+        let decode_cache = Arc::new(DecodeCache::new(config.decode_cache_size));
         
         Arc::new(Self {
-            config,
+            config: Arc::new(RwLock::new(Arc::new(config))),
             embedding_model,
+            embedding_providers: Arc::new(RwLock::new(EmbeddingRegistry::default())),
+            jobs,
+            pdq_index: Arc::new(RwLock::new(BkTree::new())),
+            indexed_manifests: Arc::new(RwLock::new(HashMap::new())),
+            background: TaskTracker::new(),
+            decode_cache,
+            queued_jobs: Arc::new(AtomicUsize::new(0)),
+            uploads_in_progress: Arc::new(AtomicUsize::new(0)),
+            started_at: Instant::now(),
+            running_jobs: Arc::new(Mutex::new(HashSet::new())),
         })
     }
+    
+    /// The configuration in effect, as of the last [`reload_config`](Self::reload_config)
+    ///
+    /// Take a fresh snapshot per request rather than holding one, so reloads
+    /// reach long-lived tasks too.
+    pub fn config(&self) -> Arc<Config> {
+        self.config.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Replace the configuration in effect, e.g. after the config file changed
+    ///
+    /// Requests already running keep the snapshot they started with. Settings
+    /// that shaped the server at startup (listener and router limits, job
+    /// slots, CORS, caches and the upload, manifest, jobs and watch
+    /// directories) keep their startup values until a restart; everything
+    /// else takes effect for the next request.
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError::Config` and keeps the current configuration if
+    /// `config` fails [`Config::validate`].
+    pub fn reload_config(&self, config: Config) -> Result<()> {
+        config.validate()?;
+        let mut current = self.config.write().unwrap_or_else(|e| e.into_inner());
+        *current = Arc::new(Config {
+            upload_dir: current.upload_dir.clone(),
+            max_upload_size: current.max_upload_size,
+            max_json_body_size: current.max_json_body_size,
+            embedding_model: current.embedding_model.clone(),
+            max_concurrent_jobs: current.max_concurrent_jobs,
+            watch_dir: current.watch_dir.clone(),
+            cors_allowed_origins: current.cors_allowed_origins.clone(),
+            cors_allow_credentials: current.cors_allow_credentials,
+            decode_cache_size: current.decode_cache_size,
+            manifest_dir: current.manifest_dir.clone(),
+            orphan_policy: current.orphan_policy,
+            jobs_dir: current.jobs_dir.clone(),
+            request_timeout: current.request_timeout,
+            http1_keep_alive: current.http1_keep_alive,
+            http2_keep_alive_interval: current.http2_keep_alive_interval,
+            ..config
+        });
+        Ok(())
+    }
+
+    /// Makes `provider` selectable by name with the upload `model` parameter,
+    /// replacing any provider of the same name
+    pub fn register_embedding_provider<P: EmbeddingProvider + 'static>(&self, provider: P) {
+        self.embedding_providers.write().unwrap_or_else(|e| e.into_inner()).register(provider);
+    }
+
+    /// Reserve a processing slot, bounded by `Config::max_concurrent_jobs`
+    ///
+    /// When all slots are taken this waits for one to free up, or fails with
+    /// `AppError::RateLimit` if `Config::reject_when_busy` is set. The slot is
+    /// released when the returned permit is dropped.
+    pub async fn acquire_job_slot(&self) -> Result<OwnedSemaphorePermit> {
+        if self.config().reject_when_busy {
+            return self.jobs.clone().try_acquire_owned().map_err(|_| AppError::RateLimit {
+                message: "too many uploads are being processed".to_string(),
+                retry_after: Some(BUSY_RETRY_AFTER_SECS),
+            });
+        }
+        if let Ok(permit) = self.jobs.clone().try_acquire_owned() {
+            return Ok(permit);
+        }
+
+        let _queued = InFlight::new(&self.queued_jobs);
+        self.jobs
+            .clone()
+            .acquire_owned()
+            .await
+            .map_err(|e| AppError::Internal(format!("job queue closed: {}", e)))
+    }
+
+    /// Count an upload request in [`LoadStatus::uploads_in_progress`] until the guard is dropped
+    pub fn track_upload(&self) -> InFlight {
+        InFlight::new(&self.uploads_in_progress)
+    }
+
+    /// Current job, queue and upload counts
+    pub fn load_status(&self) -> LoadStatus {
+        LoadStatus {
+            active_jobs: self.config().max_concurrent_jobs.max(1) - self.jobs.available_permits(),
+            queued_jobs: self.queued_jobs.load(Ordering::SeqCst),
+            uploads_in_progress: self.uploads_in_progress.load(Ordering::SeqCst),
+            uptime_secs: self.started_at.elapsed().as_secs(),
+        }
+    }
+    
+    /// Run blocking work (hashing, extraction) as a tracked background job
+    ///
+    /// Unlike a bare `spawn_blocking`, the job is awaited by
+    /// [`drain_jobs`](Self::drain_jobs) at shutdown, even if the request that
+    /// started it has already completed.
+    pub fn spawn_blocking_job<F, T>(&self, job: F) -> JoinHandle<T>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        self.background.spawn_blocking(job)
+    }
+    
+    /// Stop accepting background jobs and wait up to `timeout` for running ones
+    ///
+    /// Jobs still running after the timeout are abandoned when the runtime
+    /// shuts down; they are reported as aborted.
+    pub async fn drain_jobs(&self, timeout: Duration) -> DrainReport {
+        self.background.close();
+        let running = self.background.len();
+        
+        if tokio::time::timeout(timeout, self.background.wait()).await.is_ok() {
+            return DrainReport { drained: running, aborted: 0 };
+        }
+        
+        let aborted = self.background.len();
+        DrainReport { drained: running - aborted, aborted }
+    }
+    
+    /// Run a batch job in the background unless it is already running
+    ///
+    /// The job holds one processing slot while it works through its items and
+    /// records each one as it finishes, so a job cut short by shutdown resumes
+    /// where it stopped (see [`resume_jobs`](Self::resume_jobs)). Returns
+    /// whether the job was started; it never is when `Config::jobs_dir` is unset.
+    pub fn start_job(self: &Arc<Self>, mut job: Job) -> bool {
+        let Some(root) = self.config().jobs_dir.clone() else {
+            return false;
+        };
+        if !self.running_jobs.lock().unwrap_or_else(|e| e.into_inner()).insert(job.id.clone()) {
+            return false;
+        }
+
+        let state = self.clone();
+        self.background.spawn(async move {
+            let id = job.id.clone();
+            let run = async {
+                // Background jobs wait for a slot even when uploads are rejected
+                let slot = state
+                    .jobs
+                    .clone()
+                    .acquire_owned()
+                    .await
+                    .map_err(|e| AppError::Internal(format!("job queue closed: {}", e)))?;
+                state
+                    .spawn_blocking_job(move || {
+                        let _slot = slot;
+                        run_job(&root, &JobStore::new(&root), &mut job)
+                    })
+                    .await?
+            };
+            if let Err(e) = run.await {
+                log::error!("Batch job {} stopped: {}", id, e);
+            }
+            state.running_jobs.lock().unwrap_or_else(|e| e.into_inner()).remove(&id);
+        });
+        true
+    }
+
+    /// Restart the saved batch jobs that still have pending items, e.g. on startup
+    ///
+    /// Jobs whose only unfinished items failed are left alone until they are
+    /// resubmitted. Returns the number of jobs restarted.
+    pub fn resume_jobs(self: &Arc<Self>) -> Result<usize> {
+        let Some(root) = self.config().jobs_dir.clone() else {
+            return Ok(0);
+        };
+        let pending = JobStore::new(root)
+            .jobs()?
+            .into_iter()
+            .filter(|job| job.progress().pending > 0)
+            .filter(|job| self.start_job(job.clone()))
+            .count();
+        Ok(pending)
+    }
+
+    /// Add a manifest's PDQ hash to the perceptual index; manifests without one,
+    /// or hashed with another perceptual algorithm or PDQ version, are ignored
+    pub fn index_manifest(&self, manifest: &MediaManifest) -> Result<()> {
+        if manifest.custom_perceptual_algorithm().is_some() || manifest.check_current().is_err() {
+            return Ok(());
+        }
+        if let Some(hash) = &manifest.pdq_hash {
+            let mut index = self.pdq_index.write().unwrap_or_else(|e| e.into_inner());
+            index.insert(manifest.file_name.clone(), hash.clone())?;
+            self.indexed_manifests
+                .write()
+                .unwrap_or_else(|e| e.into_inner())
+                .insert(manifest.file_name.clone(), manifest.clone());
+        }
+        Ok(())
+    }
+    
+    /// Replace the perceptual index with one built from `manifests`, e.g. on startup
+    pub fn rebuild_pdq_index<'a, I>(&self, manifests: I) -> Result<()>
+    where
+        I: IntoIterator<Item = &'a MediaManifest>,
+    {
+        self.restore_pdq_index(manifests, None)?;
+        Ok(())
+    }
+
+    /// Replace the perceptual index with `saved` if it indexes exactly the
+    /// hashes of `manifests`, or with one rebuilt from them if it is missing or stale
+    ///
+    /// Returns whether `saved` was used.
+    pub fn restore_pdq_index<'a, I>(&self, manifests: I, saved: Option<BkTree<String>>) -> Result<bool>
+    where
+        I: IntoIterator<Item = &'a MediaManifest>,
+    {
+        let manifests: Vec<&MediaManifest> = manifests
+            .into_iter()
+            .filter(|m| m.pdq_hash.is_some() && m.custom_perceptual_algorithm().is_none() && m.check_current().is_ok())
+            .collect();
+        let indexed: HashMap<String, MediaManifest> =
+            manifests.iter().map(|m| (m.file_name.clone(), (*m).clone())).collect();
+        let current = |index: &BkTree<String>| {
+            let mut seen = HashSet::new();
+            index.len() == indexed.len()
+                && index.iter().all(|(id, hash)| {
+                    seen.insert(id) && indexed.get(id).and_then(|m| m.pdq_hash.as_deref()) == Some(hash)
+                })
+        };
+        let (index, reused) = match saved {
+            Some(saved) if current(&saved) => (saved, true),
+            _ => (BkTree::from_manifests(manifests.iter().map(|m| (m.file_name.clone(), *m)))?, false),
+        };
+        *self.pdq_index.write().unwrap_or_else(|e| e.into_inner()) = index;
+        *self.indexed_manifests.write().unwrap_or_else(|e| e.into_inner()) = indexed;
+        Ok(reused)
+    }
+    
+    /// Create a signed download link for a stored file that expires at `expires_at`
+    ///
+    /// The returned path and query string are accepted by
+    /// `GET /api/manifest/:id/download` until the expiry time passes.
+    #[cfg(feature = "web")]
+    pub fn sign_download_url(&self, id: &str, expires_at: DateTime<Utc>, secret: &[u8]) -> String {
+        crate::api::signing::signed_download_url(id, expires_at, secret)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_job_slots_reject_when_busy() {
+        let state = AppState::with_config(Config {
+            max_concurrent_jobs: 2,
+            reject_when_busy: true,
+            ..Default::default()
+        });
+
+        let _first = state.acquire_job_slot().await.unwrap();
+        let _second = state.acquire_job_slot().await.unwrap();
+        let third = state.acquire_job_slot().await;
+
+        assert!(matches!(third, Err(AppError::RateLimit { retry_after: Some(_), .. })));
+    }
+
+    #[tokio::test]
+    async fn test_drain_waits_for_running_job() {
+        let state = AppState::new();
+        let (started_tx, started_rx) = std::sync::mpsc::channel();
+        let finished = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        
+        let job_finished = finished.clone();
+        state.spawn_blocking_job(move || {
+            started_tx.send(()).unwrap();
+            std::thread::sleep(Duration::from_millis(200));
+            job_finished.store(true, std::sync::atomic::Ordering::SeqCst);
+        });
+        started_rx.recv().unwrap();
+        
+        let report = state.drain_jobs(Duration::from_secs(5)).await;
+        
+        assert_eq!(report, DrainReport { drained: 1, aborted: 0 });
+        assert!(finished.load(std::sync::atomic::Ordering::SeqCst));
+    }
+    
+    #[tokio::test]
+    async fn test_drain_reports_jobs_past_timeout() {
+        let state = AppState::new();
+        state.spawn_blocking_job(|| std::thread::sleep(Duration::from_millis(500)));
+        
+        let report = state.drain_jobs(Duration::from_millis(20)).await;
+        
+        assert_eq!(report, DrainReport { drained: 0, aborted: 1 });
+    }
+    
+    #[tokio::test]
+    async fn test_job_slots_queue_when_busy() {
+        let state = AppState::with_config(Config {
+            max_concurrent_jobs: 1,
+            ..Default::default()
+        });
+
+        let first = state.acquire_job_slot().await.unwrap();
+
+        // The second job waits while the first holds the only slot
+        let waiting = tokio::time::timeout(Duration::from_millis(50), state.acquire_job_slot()).await;
+        assert!(waiting.is_err());
+
+        drop(first);
+        let second = tokio::time::timeout(Duration::from_millis(50), state.acquire_job_slot()).await;
+        assert!(second.unwrap().is_ok());
+        assert_eq!(state.load_status().queued_jobs, 0);
+    }
 }
@@ -38,6 +38,34 @@ pub(crate) fn validate_file_extension(filename: &str, allowed_extensions: &[&str
     false
 }
 
+/// Reduce a client-supplied file name to a single safe path component
+///
+/// Keeps only the part after the last `/` or `\`, so `../x` and `/etc/passwd`
+/// become `x` and `passwd`, removes control characters such as NUL, and trims
+/// surrounding whitespace and leading dots so the result can never be `.` or
+/// `..`. The result may be empty, which callers should reject.
+pub fn sanitize_filename(name: &str) -> String {
+    let last = name.rsplit(['/', '\\']).next().unwrap_or("");
+    let printable: String = last.chars().filter(|c| !c.is_control()).collect();
+    printable.trim().trim_start_matches('.').trim().to_string()
+}
+
+/// Format of every stored timestamp: UTC, with millisecond precision
+pub const TIMESTAMP_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.3fZ";
+
+/// Formats `time` as a canonical timestamp, in [`TIMESTAMP_FORMAT`]
+///
+/// The time is converted to UTC and truncated to milliseconds, so the same
+/// instant gives the same string whatever timezone it was taken in.
+pub fn format_timestamp<Tz: chrono::TimeZone>(time: &chrono::DateTime<Tz>) -> String {
+    time.with_timezone(&chrono::Utc).format(TIMESTAMP_FORMAT).to_string()
+}
+
+/// The current time as a canonical timestamp; see [`format_timestamp`]
+pub(crate) fn now_timestamp() -> String {
+    format_timestamp(&chrono::Utc::now())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -69,4 +97,32 @@ mod tests {
         assert!(!validate_file_extension("test.txt", &allowed));
         assert!(!validate_file_extension("test", &allowed));
     }
+
+    #[test]
+    fn test_sanitize_filename() {
+        assert_eq!(sanitize_filename("photo.jpg"), "photo.jpg");
+        assert_eq!(sanitize_filename("../x"), "x");
+        assert_eq!(sanitize_filename("../../etc/passwd"), "passwd");
+        assert_eq!(sanitize_filename("..\\..\\windows\\win.ini"), "win.ini");
+        assert_eq!(sanitize_filename("/etc/shadow"), "shadow");
+        assert_eq!(sanitize_filename("C:\\temp\\a.png"), "a.png");
+        assert_eq!(sanitize_filename("evil\0.png"), "evil.png");
+        assert_eq!(sanitize_filename("line\nbreak\r.gif"), "linebreak.gif");
+        assert_eq!(sanitize_filename(".."), "");
+        assert_eq!(sanitize_filename("dir/"), "");
+        assert_eq!(sanitize_filename(" .hidden.png "), "hidden.png");
+    }
+
+    #[test]
+    fn test_same_instant_formats_identically_in_every_timezone() {
+        use chrono::{FixedOffset, TimeZone, Utc};
+
+        let instant = Utc.timestamp_opt(1_700_000_000, 123_456_789).unwrap();
+        let tokyo = instant.with_timezone(&FixedOffset::east_opt(9 * 3600).unwrap());
+        let new_york = instant.with_timezone(&FixedOffset::west_opt(5 * 3600).unwrap());
+
+        assert_ne!(tokyo.to_rfc3339(), new_york.to_rfc3339());
+        assert_eq!(format_timestamp(&tokyo), "2023-11-14T22:13:20.123Z");
+        assert_eq!(format_timestamp(&tokyo), format_timestamp(&new_york));
+    }
 }
@@ -0,0 +1,1329 @@
+use axum::{
+    body::{to_bytes, Body},
+    http::{Request, StatusCode},
+    Router,
+};
+use tower::ServiceExt;
+
+use imagechain::{create_router, create_router_with_config, AppState, Config};
+
+const BOUNDARY: &str = "imagechain-test-boundary";
+
+/// Config keeping uploads and their temp files under `dir`, so tests never
+/// write to the working directory or the shared temp directory
+fn isolated_config(dir: &tempfile::TempDir) -> Config {
+    Config {
+        upload_dir: dir.path().join("uploads"),
+        upload_temp_dir: dir.path().join("tmp"),
+        ..Config::default()
+    }
+}
+
+/// Number of entries in `path`, counting a missing directory as empty
+fn entry_count(path: &std::path::Path) -> usize {
+    std::fs::read_dir(path).map_or(0, |entries| entries.count())
+}
+
+fn app(dir: &tempfile::TempDir) -> Router {
+    create_router().with_state(AppState::with_config(isolated_config(dir)))
+}
+
+/// Builds a multipart request with a single `file` field
+fn upload_request(uri: &str, file_name: &str, data: &[u8]) -> Request<Body> {
+    let mut body = Vec::new();
+    body.extend_from_slice(format!("--{}\r\n", BOUNDARY).as_bytes());
+    body.extend_from_slice(
+        format!(
+            "Content-Disposition: form-data; name=\"file\"; filename=\"{}\"\r\n",
+            file_name
+        )
+        .as_bytes(),
+    );
+    body.extend_from_slice(b"Content-Type: application/octet-stream\r\n\r\n");
+    body.extend_from_slice(data);
+    body.extend_from_slice(format!("\r\n--{}--\r\n", BOUNDARY).as_bytes());
+
+    Request::builder()
+        .method("POST")
+        .uri(uri)
+        .header(
+            "content-type",
+            format!("multipart/form-data; boundary={}", BOUNDARY),
+        )
+        .body(Body::from(body))
+        .unwrap()
+}
+
+async fn json_body(response: axum::response::Response) -> serde_json::Value {
+    let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    serde_json::from_slice(&bytes).unwrap()
+}
+
+#[tokio::test]
+async fn test_upload_zero_byte_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let response = app(&dir)
+        .oneshot(upload_request("/api/upload", "empty.png", b""))
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body = json_body(response).await;
+    assert!(body["message"].as_str().unwrap().contains("empty file"));
+}
+
+#[tokio::test]
+async fn test_upload_truncated_image() {
+    let dir = tempfile::tempdir().unwrap();
+    let response = app(&dir)
+        .oneshot(upload_request("/api/upload", "broken.png", &[0x89]))
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body = json_body(response).await;
+    assert!(body["message"].as_str().unwrap().contains("broken.png"));
+}
+
+#[tokio::test]
+async fn test_upload_sanitizes_traversal_file_names() {
+    let dir = tempfile::tempdir().unwrap();
+    let response = app(&dir)
+        .oneshot(upload_request("/api/upload", "../../escape.bin", b"payload"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let stored = json_body(response).await["data"]["file_name"].as_str().unwrap().to_string();
+    assert!(!stored.contains(['/', '\\']) && !stored.starts_with('.'), "{}", stored);
+    assert!(dir.path().join("uploads").join(&stored).is_file());
+
+    for name in ["..", "/tmp/", "..\\.."] {
+        let response = app(&dir).oneshot(upload_request("/api/upload", name, b"payload")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", name);
+    }
+}
+
+#[cfg(feature = "video")]
+#[tokio::test]
+async fn test_video_manifest_records_frame_source() {
+    if imagechain::check_ffmpeg_installed().is_err() {
+        return;
+    }
+    let dir = tempfile::tempdir().unwrap();
+    let video = dir.path().join("clip.mp4");
+    let status = std::process::Command::new("ffmpeg")
+        .args(["-hide_banner", "-loglevel", "error"])
+        .args(["-f", "lavfi", "-i", "testsrc=s=64x64:d=2:r=10"])
+        .args(["-c:v", "mpeg4", "-movflags", "+faststart"])
+        .arg(&video)
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let response = app(&dir)
+        .oneshot(upload_request("/api/upload", "clip.mp4", &std::fs::read(&video).unwrap()))
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = json_body(response).await;
+    assert_eq!(body["data"]["media_type"], "video");
+    assert!(!body["data"]["frames"].as_array().unwrap().is_empty());
+    assert_eq!(body["data"]["frame_source"], "VideoFfmpeg");
+}
+
+#[tokio::test]
+async fn test_video_without_extracted_frames_has_no_frame_source() {
+    let dir = tempfile::tempdir().unwrap();
+    let response = app(&dir)
+        .oneshot(upload_request("/api/upload?extract_frames=false", "clip.mp4", b"not decoded"))
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = json_body(response).await;
+    assert_eq!(body["data"]["media_type"], "video");
+    assert!(body["data"].get("frame_source").is_none());
+}
+
+#[tokio::test]
+async fn test_profile_fills_in_unset_upload_params() {
+    let dir = tempfile::tempdir().unwrap();
+    let surveillance = imagechain::ProcessingProfile {
+        frame_interval_secs: Some(5.0),
+        extract_frames: Some(false),
+        include_embeddings: Some(false),
+        ..Default::default()
+    };
+    let app = create_router().with_state(AppState::with_config(Config {
+        profiles: [("surveillance".to_string(), surveillance)].into(),
+        ..isolated_config(&dir)
+    }));
+    let upload = |uri: &'static str| {
+        let app = app.clone();
+        async move { app.oneshot(upload_request(uri, "clip.mp4", b"not decoded")).await.unwrap() }
+    };
+
+    let response = upload("/api/upload?profile=surveillance").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let metadata = json_body(response).await["data"]["metadata"].clone();
+    assert_eq!(metadata["frame_interval_secs"], 5.0);
+    assert_eq!(metadata["extracted_frames"], false);
+
+    // Parameters in the request win over the profile
+    let response = upload("/api/upload?profile=surveillance&frame_interval_secs=2").await;
+    assert_eq!(json_body(response).await["data"]["metadata"]["frame_interval_secs"], 2.0);
+
+    let response = upload("/api/upload?profile=unknown").await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_hash_endpoint_matches_processing_without_storing() {
+    let dir = tempfile::tempdir().unwrap();
+    let img = image::RgbImage::from_fn(96, 64, |x, y| {
+        let cell = (x / 8) * 16 + (y / 8) + 0x417;
+        let v = ((cell.wrapping_mul(2654435761) >> 24) % 256) as u8;
+        image::Rgb([v, 255 - v, v / 2])
+    });
+    let mut png = std::io::Cursor::new(Vec::new());
+    image::DynamicImage::ImageRgb8(img)
+        .write_to(&mut png, image::ImageOutputFormat::Png)
+        .unwrap();
+    let png = png.into_inner();
+
+    let state = AppState::with_config(isolated_config(&dir));
+    let response = create_router()
+        .with_state(state.clone())
+        .oneshot(upload_request("/api/hash", "fingerprint.png", &png))
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = json_body(response).await;
+    let expected = imagechain::process_image_bytes("fingerprint.png", &png).unwrap();
+    assert_eq!(body["data"]["sha3_256"], expected.sha3_256_hash);
+    assert_eq!(body["data"]["pdq"], expected.pdq_hash.unwrap());
+
+    assert!(state.pdq_index.read().unwrap().is_empty());
+    assert_eq!(entry_count(&dir.path().join("uploads")), 0, "hashed file was written to the uploads directory");
+    assert_eq!(entry_count(&dir.path().join("tmp")), 0);
+}
+
+#[tokio::test]
+async fn test_hash_endpoint_streams_non_image_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let data: Vec<u8> = (0..300_000u32).map(|i| (i.wrapping_mul(2654435761) >> 24) as u8).collect();
+
+    let response = create_router()
+        .with_state(AppState::with_config(isolated_config(&dir)))
+        .oneshot(upload_request("/api/hash", "notes.bin", &data))
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = json_body(response).await;
+    assert_eq!(body["data"]["sha3_256"], imagechain::compute_sha3_256(&data).unwrap());
+    assert!(body["data"]["pdq"].is_null());
+}
+
+/// Hashes an image to its mean luma, compared by absolute difference
+struct MeanLuma;
+
+impl imagechain::PerceptualAlgorithm for MeanLuma {
+    fn name(&self) -> &str {
+        "mean-luma"
+    }
+
+    fn hash(&self, img: &image::DynamicImage) -> anyhow::Result<String> {
+        let luma = img.to_luma8();
+        let sum: u64 = luma.pixels().map(|p| p.0[0] as u64).sum();
+        Ok(format!("{}", sum / (luma.len() as u64).max(1)))
+    }
+
+    fn compare(&self, a: &str, b: &str) -> anyhow::Result<u32> {
+        Ok(a.parse::<u32>()?.abs_diff(b.parse::<u32>()?))
+    }
+}
+
+#[tokio::test]
+async fn test_upload_selects_registered_perceptual_algorithm() {
+    let dir = tempfile::tempdir().unwrap();
+    imagechain::core::perceptual::register(MeanLuma);
+    let mut png = std::io::Cursor::new(Vec::new());
+    image::DynamicImage::ImageRgb8(image::RgbImage::from_pixel(8, 8, image::Rgb([60, 60, 60])))
+        .write_to(&mut png, image::ImageOutputFormat::Png)
+        .unwrap();
+    let png = png.into_inner();
+
+    let response = app(&dir)
+        .oneshot(upload_request("/api/upload?algorithm=mean-luma", "grey.png", &png))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let manifest = json_body(response).await["data"].clone();
+    assert_eq!(manifest["perceptual_algorithm"], "mean-luma");
+    assert_eq!(manifest["pdq_hash"], "60");
+
+    let response = app(&dir)
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/api/verify")
+                .header("content-type", "application/json")
+                .body(Body::from(manifest.to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(json_body(response).await["data"]["is_valid"], true);
+
+    let response = app(&dir)
+        .oneshot(upload_request("/api/upload?algorithm=phash", "grey.png", &png))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+/// Embeds every image as a constant vector, to tell providers apart
+struct ConstantEmbedding(&'static str, f32);
+
+impl imagechain::EmbeddingProvider for ConstantEmbedding {
+    fn name(&self) -> &str {
+        self.0
+    }
+
+    fn embed<'a>(&'a self, _img: &'a image::DynamicImage) -> imagechain::EmbeddingFuture<'a> {
+        Box::pin(async move { Ok(Some(vec![self.1; 4])) })
+    }
+}
+
+#[tokio::test]
+async fn test_upload_records_selected_embedding_model() {
+    let dir = tempfile::tempdir().unwrap();
+    let state = AppState::with_config(isolated_config(&dir));
+    state.register_embedding_provider(ConstantEmbedding("fast", 0.25));
+    state.register_embedding_provider(ConstantEmbedding("clip", 0.75));
+    let router = create_router().with_state(state);
+    let mut png = std::io::Cursor::new(Vec::new());
+    image::DynamicImage::ImageRgb8(image::RgbImage::from_fn(32, 32, |x, y| image::Rgb([(x * 8) as u8, (y * 8) as u8, 30])))
+        .write_to(&mut png, image::ImageOutputFormat::Png)
+        .unwrap();
+    let png = png.into_inner();
+
+    let mut embeddings = Vec::new();
+    for model in ["fast", "clip"] {
+        let uri = format!("/api/upload?model={}", model);
+        let response = router.clone().oneshot(upload_request(&uri, "gradient.png", &png)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let manifest = json_body(response).await["data"].clone();
+        assert_eq!(manifest["embedding_model"], model);
+        assert_eq!(manifest["embedding_dim"], 4);
+        embeddings.push(manifest["metadata"]["embedding"].clone());
+    }
+    assert_eq!(embeddings[0], serde_json::json!([0.25, 0.25, 0.25, 0.25]));
+    assert_ne!(embeddings[0], embeddings[1]);
+
+    let response = router.oneshot(upload_request("/api/upload?model=missing", "gradient.png", &png)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert!(json_body(response).await["message"].as_str().unwrap().contains("missing"));
+}
+
+#[tokio::test]
+async fn test_upload_options_field_after_file_takes_effect() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut png = std::io::Cursor::new(Vec::new());
+    image::DynamicImage::ImageRgb8(image::RgbImage::from_fn(32, 32, |x, y| image::Rgb([(x * 8) as u8, (y * 8) as u8, 90])))
+        .write_to(&mut png, image::ImageOutputFormat::Png)
+        .unwrap();
+
+    let mut body = Vec::new();
+    body.extend_from_slice(format!("--{}\r\n", BOUNDARY).as_bytes());
+    body.extend_from_slice(b"Content-Disposition: form-data; name=\"file\"; filename=\"gradient.png\"\r\n");
+    body.extend_from_slice(b"Content-Type: image/png\r\n\r\n");
+    body.extend_from_slice(&png.into_inner());
+    body.extend_from_slice(format!("\r\n--{}\r\n", BOUNDARY).as_bytes());
+    body.extend_from_slice(b"Content-Disposition: form-data; name=\"options\"\r\n");
+    body.extend_from_slice(b"Content-Type: application/json\r\n\r\n");
+    body.extend_from_slice(br#"{"algorithm": "dhash"}"#);
+    body.extend_from_slice(format!("\r\n--{}--\r\n", BOUNDARY).as_bytes());
+
+    // The body's options override the (here unknown) algorithm in the query string
+    let response = app(&dir)
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/api/upload?algorithm=phash")
+                .header("content-type", format!("multipart/form-data; boundary={}", BOUNDARY))
+                .body(Body::from(body))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(json_body(response).await["data"]["perceptual_algorithm"], "dhash");
+}
+
+#[tokio::test]
+async fn test_upload_meta_lands_under_metadata_user() {
+    let dir = tempfile::tempdir().unwrap();
+    let upload_with_meta = |meta: &str| {
+        let mut body = Vec::new();
+        body.extend_from_slice(format!("--{}\r\n", BOUNDARY).as_bytes());
+        body.extend_from_slice(b"Content-Disposition: form-data; name=\"meta\"\r\n");
+        body.extend_from_slice(b"Content-Type: application/json\r\n\r\n");
+        body.extend_from_slice(meta.as_bytes());
+        body.extend_from_slice(format!("\r\n--{}\r\n", BOUNDARY).as_bytes());
+        body.extend_from_slice(b"Content-Disposition: form-data; name=\"file\"; filename=\"clip.mp4\"\r\n");
+        body.extend_from_slice(b"Content-Type: application/octet-stream\r\n\r\n");
+        body.extend_from_slice(b"not decoded");
+        body.extend_from_slice(format!("\r\n--{}--\r\n", BOUNDARY).as_bytes());
+        Request::builder()
+            .method("POST")
+            .uri("/api/upload?extract_frames=false")
+            .header("content-type", format!("multipart/form-data; boundary={}", BOUNDARY))
+            .body(Body::from(body))
+            .unwrap()
+    };
+
+    let response = app(&dir).oneshot(upload_with_meta(r#"{"owner":"alice"}"#)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let metadata = json_body(response).await["data"]["metadata"].clone();
+    assert_eq!(metadata["user"], serde_json::json!({ "owner": "alice" }));
+    // Computed fields sit alongside the client's
+    assert_eq!(metadata["extracted_frames"], false);
+
+    let response = app(&dir)
+        .oneshot(upload_request("/api/upload?meta_json=%7B%22album%22%3A%22trip%22%7D", "notes.bin", b"catalogued"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(json_body(response).await["data"]["metadata"]["user"]["album"], "trip");
+
+    for meta in ["42", r#"["alice"]"#] {
+        let response = app(&dir).oneshot(upload_with_meta(meta)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", meta);
+    }
+}
+
+#[tokio::test]
+async fn test_json_routes_get_a_smaller_body_limit_than_uploads() {
+    let dir = tempfile::tempdir().unwrap();
+    let big = vec![b'x'; 5 * 1024 * 1024];
+
+    let mut manifest = br#"{"padding": ""#.to_vec();
+    manifest.extend_from_slice(&big);
+    manifest.extend_from_slice(br#""}"#);
+    let response = app(&dir)
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/api/verify")
+                .header("content-type", "application/json")
+                .body(Body::from(manifest))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+    let response = app(&dir).oneshot(upload_request("/api/upload", "large.bin", &big)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(json_body(response).await["data"]["file_size"], big.len());
+}
+
+#[tokio::test]
+async fn test_perceptual_all_returns_every_builtin_algorithm() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut png = std::io::Cursor::new(Vec::new());
+    image::DynamicImage::ImageRgb8(image::RgbImage::from_fn(40, 40, |x, y| image::Rgb([(x * 6) as u8, (y * 6) as u8, 128])))
+        .write_to(&mut png, image::ImageOutputFormat::Png)
+        .unwrap();
+
+    let response = app(&dir)
+        .oneshot(upload_request("/api/perceptual-all", "gradient.png", &png.into_inner()))
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = json_body(response).await;
+    for name in ["pdq", "ahash", "dhash", "whash", "blockhash"] {
+        assert_eq!(body["data"][name].as_str().unwrap().len(), 16, "{}", name);
+    }
+}
+
+/// Serves `POST /embed` like the embedding service, counting the requests in `calls`
+async fn counting_embedding_service(calls: std::sync::Arc<std::sync::atomic::AtomicUsize>) -> String {
+    let app = Router::new().route(
+        "/embed",
+        axum::routing::post(move || async move {
+            calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            axum::Json(serde_json::json!({ "embedding": [0.25, 0.5, 0.75] }))
+        }),
+    );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    format!("http://{}", addr)
+}
+
+/// Sets an environment variable until dropped, then restores its previous value
+struct EnvGuard {
+    key: &'static str,
+    previous: Option<String>,
+}
+
+impl EnvGuard {
+    fn set(key: &'static str, value: &str) -> Self {
+        let previous = std::env::var(key).ok();
+        std::env::set_var(key, value);
+        Self { key, previous }
+    }
+}
+
+impl Drop for EnvGuard {
+    fn drop(&mut self) {
+        match &self.previous {
+            Some(value) => std::env::set_var(self.key, value),
+            None => std::env::remove_var(self.key),
+        }
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[serial_test::serial]
+async fn test_concurrent_pipeline_matches_sequential() {
+    let dir = tempfile::tempdir().unwrap();
+    let calls = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let _service = EnvGuard::set("EMBEDDING_SERVICE_URL", &counting_embedding_service(calls.clone()).await);
+
+    let img = image::RgbImage::from_fn(640, 480, |x, y| image::Rgb([(x % 251) as u8, (y % 241) as u8, ((x ^ y) % 256) as u8]));
+    let mut png = std::io::Cursor::new(Vec::new());
+    image::DynamicImage::ImageRgb8(img)
+        .write_to(&mut png, image::ImageOutputFormat::Png)
+        .unwrap();
+    let png = png.into_inner();
+
+    let upload = |concurrent_pipeline: bool| {
+        let app = create_router().with_state(AppState::with_config(Config { concurrent_pipeline, ..isolated_config(&dir) }));
+        let request = upload_request("/api/upload?include_embeddings=true", "pipeline.png", &png);
+        async move {
+            let response = app.oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let manifest: imagechain::MediaManifest = serde_json::from_value(json_body(response).await["data"].clone()).unwrap();
+            manifest
+        }
+    };
+
+    let sequential = upload(false).await;
+    assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 1);
+    let concurrent = upload(true).await;
+    assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 2);
+
+    assert_eq!(sequential.metadata["embedding"], serde_json::json!([0.25, 0.5, 0.75]));
+    let changed: Vec<String> = sequential.diff(&concurrent).into_iter().map(|d| d.field).collect();
+    assert!(
+        changed.iter().all(|field| ["file_name", "created_at", "modified_at"].contains(&field.as_str())),
+        "pipelines disagree on {:?}",
+        changed
+    );
+}
+
+#[tokio::test]
+async fn test_upload_over_limit_is_rejected_and_cleaned_up() {
+    let dir = tempfile::tempdir().unwrap();
+    let limit = 4096;
+    let state = AppState::with_config(Config { max_upload_size: limit, ..isolated_config(&dir) });
+    let data = vec![0x5a; limit as usize + 1];
+
+    let response = create_router()
+        .with_state(state)
+        .oneshot(upload_request("/api/upload", "oversized.bin", &data))
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body = json_body(response).await;
+    assert!(body["message"].as_str().unwrap().contains("maximum upload size"));
+
+    assert!(dir.path().join("tmp").is_dir(), "upload was not staged in the configured temp dir");
+    assert_eq!(entry_count(&dir.path().join("tmp")), 0, "partial upload left behind");
+    assert_eq!(entry_count(&dir.path().join("uploads")), 0);
+}
+
+#[tokio::test]
+async fn test_corrupt_upload_is_quarantined() {
+    let dir = tempfile::tempdir().unwrap();
+    let quarantine = tempfile::tempdir().unwrap();
+    let state = AppState::with_config(Config {
+        quarantine_dir: Some(quarantine.path().to_path_buf()),
+        ..isolated_config(&dir)
+    });
+
+    let response = create_router()
+        .with_state(state)
+        .oneshot(upload_request("/api/upload", "corrupt.png", b"\x89PNG\r\n\x1a\nnot really"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let mut names: Vec<String> = std::fs::read_dir(quarantine.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    names.sort();
+    assert_eq!(names.len(), 2, "{:?}", names);
+    assert!(names[0].ends_with(".png"));
+    assert_eq!(names[1], format!("{}.reason.txt", names[0]));
+
+    let reason = std::fs::read_to_string(quarantine.path().join(&names[1])).unwrap();
+    assert!(reason.contains("corrupt.png"));
+    assert!(reason.contains("Failed to decode image"));
+}
+
+#[tokio::test]
+async fn test_stream_file_serves_byte_ranges() {
+    let dir = tempfile::tempdir().unwrap();
+    let state = AppState::with_config(Config {
+        download_secret: Some("range-secret".to_string()),
+        ..isolated_config(&dir)
+    });
+    std::fs::create_dir_all(dir.path().join("uploads")).unwrap();
+    let id = format!("range-{}.mp4", std::process::id());
+    let data: Vec<u8> = (0..=255u8).cycle().take(1000).collect();
+    std::fs::write(dir.path().join("uploads").join(&id), &data).unwrap();
+
+    let expires = chrono::Utc::now() + chrono::Duration::minutes(5);
+    let url = state
+        .sign_download_url(&id, expires, b"range-secret")
+        .replace("/download?", "/file?");
+    let get = |range: &str| {
+        Request::builder()
+            .uri(&url)
+            .header("range", range)
+            .body(Body::empty())
+            .unwrap()
+    };
+    let app = create_router().with_state(state.clone());
+
+    let response = app.clone().oneshot(get("bytes=100-199")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+    assert_eq!(response.headers()["content-range"], "bytes 100-199/1000");
+    assert_eq!(response.headers()["content-length"], "100");
+    assert_eq!(response.headers()["accept-ranges"], "bytes");
+    assert_eq!(response.headers()["content-type"], "video/mp4");
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    assert_eq!(&body[..], &data[100..200]);
+
+    let response = app.clone().oneshot(get("bytes=-10")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    assert_eq!(&body[..], &data[990..]);
+
+    let response = app.oneshot(get("bytes=1000-")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+    assert_eq!(response.headers()["content-range"], "bytes */1000");
+}
+
+#[tokio::test]
+async fn test_openapi_document() {
+    let dir = tempfile::tempdir().unwrap();
+    let response = app(&dir)
+        .oneshot(Request::get("/api/openapi.json").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let doc = json_body(response).await;
+    assert!(doc["openapi"].as_str().unwrap().starts_with("3."));
+    assert!(doc["paths"]["/api/upload"]["post"].is_object());
+    assert!(doc["components"]["schemas"]["MediaManifest"].is_object());
+}
+
+#[tokio::test]
+async fn test_cors_rejects_disallowed_origin() {
+    let config = Config {
+        cors_allowed_origins: vec!["https://app.example.com".to_string()],
+        cors_allow_credentials: true,
+        ..Default::default()
+    };
+    let app = create_router_with_config(&config)
+        .unwrap()
+        .with_state(AppState::with_config(config));
+
+    let preflight = |origin: &str| {
+        Request::builder()
+            .method("OPTIONS")
+            .uri("/api/upload")
+            .header("origin", origin)
+            .header("access-control-request-method", "POST")
+            .body(Body::empty())
+            .unwrap()
+    };
+
+    let allowed = app.clone().oneshot(preflight("https://app.example.com")).await.unwrap();
+    assert_eq!(
+        allowed.headers()["access-control-allow-origin"],
+        "https://app.example.com"
+    );
+    assert_eq!(allowed.headers()["access-control-allow-credentials"], "true");
+
+    let rejected = app.oneshot(preflight("https://evil.example.com")).await.unwrap();
+    assert!(rejected.headers().get("access-control-allow-origin").is_none());
+}
+
+#[tokio::test]
+async fn test_cors_defaults_to_any_origin() {
+    let preflight = || {
+        Request::builder()
+            .method("OPTIONS")
+            .uri("/api/upload")
+            .header("origin", "https://app.example.com")
+            .header("access-control-request-method", "POST")
+            .body(Body::empty())
+            .unwrap()
+    };
+
+    let response = create_router().with_state(AppState::new()).oneshot(preflight()).await.unwrap();
+    assert_eq!(response.headers()["access-control-allow-origin"], "*");
+
+    let config = Config::default();
+    let response = create_router_with_config(&config)
+        .unwrap()
+        .with_state(AppState::with_config(config))
+        .oneshot(preflight())
+        .await
+        .unwrap();
+    assert_eq!(response.headers()["access-control-allow-origin"], "*");
+}
+
+#[test]
+fn test_cors_credentials_require_origins() {
+    let config = Config {
+        cors_allow_credentials: true,
+        ..Default::default()
+    };
+    assert!(create_router_with_config(&config).is_err());
+
+    let wildcard = Config {
+        cors_allowed_origins: vec!["*".to_string()],
+        cors_allow_credentials: true,
+        ..Default::default()
+    };
+    assert!(create_router_with_config(&wildcard).is_err());
+}
+
+/// Encodes 8x8 blocks of pseudo-random greys as a 64x64 PNG, nudged by `noise`
+fn block_texture_png(seed: u32, noise: u8) -> Vec<u8> {
+    let img = image::RgbImage::from_fn(64, 64, |x, y| {
+        let block = (x / 8) * 8 + y / 8;
+        let shade = (block.wrapping_mul(2654435761).wrapping_add(seed.wrapping_mul(40503)) >> 13) as u8;
+        let shade = shade.saturating_add(noise * (((x + y) % 2) as u8));
+        image::Rgb([shade, shade, shade])
+    });
+    let mut png = std::io::Cursor::new(Vec::new());
+    image::DynamicImage::ImageRgb8(img)
+        .write_to(&mut png, image::ImageOutputFormat::Png)
+        .unwrap();
+    png.into_inner()
+}
+
+#[tokio::test]
+async fn test_closest_returns_nearest_stored_image() {
+    let dir = tempfile::tempdir().unwrap();
+    let app = create_router().with_state(AppState::with_config(isolated_config(&dir)));
+
+    let mut stored = Vec::new();
+    for seed in 1..=4 {
+        let response = app
+            .clone()
+            .oneshot(upload_request("/api/upload", &format!("texture-{}.png", seed), &block_texture_png(seed, 0)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        stored.push(json_body(response).await["data"]["file_name"].as_str().unwrap().to_string());
+    }
+
+    let response = app
+        .clone()
+        .oneshot(upload_request("/api/closest", "query.png", &block_texture_png(3, 6)))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = json_body(response).await;
+    assert_eq!(body["data"]["manifest"]["file_name"], stored[2].as_str());
+    assert!(body["data"]["distance"].as_u64().unwrap() <= 10);
+
+    let response = app
+        .oneshot(upload_request("/api/closest?threshold=0", "unrelated.png", &block_texture_png(99, 0)))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_distance_matrix_is_symmetric_with_a_zero_diagonal() {
+    let dir = tempfile::tempdir().unwrap();
+    let state = AppState::with_config(Config { max_distance_matrix_size: 3, ..isolated_config(&dir) });
+    let app = create_router().with_state(state);
+    let response = app
+        .clone()
+        .oneshot(upload_request("/api/upload", "stored.png", &block_texture_png(3, 0)))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let stored = json_body(response).await["data"]["file_name"].as_str().unwrap().to_string();
+
+    let matrix_request = |ids: &[&str]| {
+        let mut body = Vec::new();
+        for seed in [4, 7] {
+            body.extend_from_slice(format!("--{}\r\n", BOUNDARY).as_bytes());
+            body.extend_from_slice(
+                format!("Content-Disposition: form-data; name=\"file\"; filename=\"texture-{}.png\"\r\n", seed).as_bytes(),
+            );
+            body.extend_from_slice(b"Content-Type: image/png\r\n\r\n");
+            body.extend_from_slice(&block_texture_png(seed, 0));
+            body.extend_from_slice(b"\r\n");
+        }
+        for id in ids {
+            body.extend_from_slice(format!("--{}\r\n", BOUNDARY).as_bytes());
+            body.extend_from_slice(b"Content-Disposition: form-data; name=\"id\"\r\n\r\n");
+            body.extend_from_slice(id.as_bytes());
+            body.extend_from_slice(b"\r\n");
+        }
+        body.extend_from_slice(format!("--{}--\r\n", BOUNDARY).as_bytes());
+        Request::builder()
+            .method("POST")
+            .uri("/api/distance-matrix")
+            .header("content-type", format!("multipart/form-data; boundary={}", BOUNDARY))
+            .body(Body::from(body))
+            .unwrap()
+    };
+
+    let response = app.clone().oneshot(matrix_request(&[&stored])).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let data = json_body(response).await["data"].clone();
+    assert_eq!(data["order"], serde_json::json!(["texture-4.png", "texture-7.png", stored]));
+
+    let pdq = |seed| {
+        imagechain::compute_pdq_hash(&image::load_from_memory(&block_texture_png(seed, 0)).unwrap()).unwrap()
+    };
+    let hashes = [pdq(4), pdq(7), pdq(3)];
+    let distances: Vec<Vec<u64>> = serde_json::from_value(data["distances"].clone()).unwrap();
+    assert_eq!(distances.len(), 3);
+    for i in 0..3 {
+        assert_eq!(distances[i][i], 0);
+        for j in 0..3 {
+            assert_eq!(distances[i][j], distances[j][i]);
+            let expected = imagechain::hash::pdq_distance(&hashes[i], &hashes[j]).unwrap();
+            assert_eq!(distances[i][j], u64::from(expected), "{} vs {}", i, j);
+        }
+    }
+    assert!(distances[0][1] > 0 && distances[0][2] > 0);
+
+    let response = app.clone().oneshot(matrix_request(&["missing.png"])).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let response = app.oneshot(matrix_request(&[&stored, &stored])).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_verbose_compare_reports_a_diff_mask_matching_the_distance() {
+    let dir = tempfile::tempdir().unwrap();
+    let compare_request = |uri: &str| {
+        let mut body = Vec::new();
+        for (name, seed) in [("a", 3), ("b", 4)] {
+            body.extend_from_slice(format!("--{}\r\n", BOUNDARY).as_bytes());
+            body.extend_from_slice(
+                format!("Content-Disposition: form-data; name=\"{}\"; filename=\"{}.png\"\r\n", name, name).as_bytes(),
+            );
+            body.extend_from_slice(b"Content-Type: image/png\r\n\r\n");
+            body.extend_from_slice(&block_texture_png(seed, 0));
+            body.extend_from_slice(b"\r\n");
+        }
+        body.extend_from_slice(format!("--{}--\r\n", BOUNDARY).as_bytes());
+        Request::builder()
+            .method("POST")
+            .uri(uri)
+            .header("content-type", format!("multipart/form-data; boundary={}", BOUNDARY))
+            .body(Body::from(body))
+            .unwrap()
+    };
+
+    let response = app(&dir).oneshot(compare_request("/api/compare")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let plain = json_body(response).await["data"].clone();
+    assert!(plain.get("detail").is_none());
+
+    let response = app(&dir).oneshot(compare_request("/api/compare?verbose=true")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let data = json_body(response).await["data"].clone();
+    let distance = data["distance"].as_u64().unwrap();
+    assert_eq!(distance, plain["distance"].as_u64().unwrap());
+    assert!(distance > 0);
+
+    let detail = &data["detail"];
+    let mask = detail["diff_mask"].as_str().unwrap();
+    assert_eq!(mask.len(), 64);
+    assert_eq!(mask.chars().filter(|&c| c == '1').count() as u64, distance);
+    let (a, b) = (detail["a_binary"].as_str().unwrap(), detail["b_binary"].as_str().unwrap());
+    assert!(mask.chars().zip(a.chars().zip(b.chars())).all(|(m, (x, y))| (m == '1') == (x != y)));
+    assert_eq!(detail["a_hex"].as_str().unwrap().len(), 16);
+}
+
+/// Reads a newline-delimited JSON body into its lines
+async fn ndjson_lines(response: axum::response::Response) -> Vec<serde_json::Value> {
+    assert_eq!(response.headers()["content-type"], "application/x-ndjson");
+    let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    String::from_utf8(bytes.to_vec())
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn test_similarity_search_can_skip_recoloured_images() {
+    let dir = tempfile::tempdir().unwrap();
+    // The same disc on black, in green and in magenta of equal luminance
+    let disc = |colour: [u8; 3]| {
+        let img = image::RgbImage::from_fn(64, 64, |x, y| {
+            let (dx, dy) = (x as i32 - 24, y as i32 - 28);
+            if dx * dx + dy * dy < 300 { image::Rgb(colour) } else { image::Rgb([0, 0, 0]) }
+        });
+        let mut png = std::io::Cursor::new(Vec::new());
+        image::DynamicImage::ImageRgb8(img).write_to(&mut png, image::ImageOutputFormat::Png).unwrap();
+        png.into_inner()
+    };
+    let (green, magenta) = (disc([0, 150, 0]), disc([200, 0, 245]));
+
+    let app = create_router().with_state(AppState::with_config(isolated_config(&dir)));
+    let mut stored = std::collections::HashMap::new();
+    for (colour, png) in [("green", &green), ("magenta", &magenta)] {
+        let response = app.clone().oneshot(upload_request("/api/upload", "disc.png", png)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let file_name = json_body(response).await["data"]["file_name"].as_str().unwrap().to_string();
+        stored.insert(file_name, colour);
+    }
+    let found = |uri: &'static str| {
+        let app = app.clone();
+        let green = green.clone();
+        let stored = stored.clone();
+        async move {
+            let response = app.oneshot(upload_request(uri, "query.png", &green)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let lines = ndjson_lines(response).await;
+            let mut colours: Vec<&str> = lines
+                .iter()
+                .map(|line| stored[line["file_name"].as_str().unwrap()])
+                .collect();
+            colours.sort();
+            colours
+        }
+    };
+
+    assert_eq!(found("/api/search-similar?threshold=8").await, ["green", "magenta"]);
+    assert_eq!(found("/api/search-similar?threshold=8&min_histogram_similarity=0.9").await, ["green"]);
+    assert_eq!(found("/api/search-similar?threshold=8&limit=1&min_histogram_similarity=0.9").await, ["green"]);
+
+    let pairs = |uri: &'static str| {
+        let app = app.clone();
+        async move {
+            let request = Request::builder().uri(uri).body(Body::empty()).unwrap();
+            ndjson_lines(app.oneshot(request).await.unwrap()).await.len()
+        }
+    };
+    assert_eq!(pairs("/api/find-duplicates?threshold=8").await, 1);
+    assert_eq!(pairs("/api/find-duplicates?threshold=8&min_histogram_similarity=0.9").await, 0);
+}
+
+#[tokio::test]
+async fn test_similarity_streams_are_in_ascending_distance() {
+    let dir = tempfile::tempdir().unwrap();
+    let app = create_router().with_state(AppState::with_config(isolated_config(&dir)));
+    for (seed, noise) in [(1, 0), (1, 4), (1, 9), (2, 0), (2, 7), (3, 0)] {
+        let response = app
+            .clone()
+            .oneshot(upload_request("/api/upload", &format!("texture-{}-{}.png", seed, noise), &block_texture_png(seed, noise)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+    let distances = |lines: &[serde_json::Value]| -> Vec<u64> {
+        lines.iter().map(|line| line["distance"].as_u64().unwrap()).collect()
+    };
+    let ascending = |distances: &[u64]| distances.windows(2).all(|w| w[0] <= w[1]);
+
+    let response = app
+        .clone()
+        .oneshot(upload_request("/api/search-similar?threshold=256", "query.png", &block_texture_png(1, 2)))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let all = distances(&ndjson_lines(response).await);
+    assert_eq!(all.len(), 6);
+    assert!(ascending(&all), "{:?}", all);
+
+    let response = app
+        .clone()
+        .oneshot(upload_request("/api/search-similar?threshold=256&limit=2", "query.png", &block_texture_png(1, 2)))
+        .await
+        .unwrap();
+    assert_eq!(distances(&ndjson_lines(response).await), all[..2]);
+
+    let pairs = |uri: &str| {
+        let request = Request::builder().uri(uri).body(Body::empty()).unwrap();
+        let app = app.clone();
+        async move { ndjson_lines(app.oneshot(request).await.unwrap()).await }
+    };
+    let all_pairs = pairs("/api/find-duplicates?threshold=256").await;
+    assert_eq!(all_pairs.len(), 15);
+    assert!(all_pairs.iter().all(|pair| pair["a"].as_str() < pair["b"].as_str()));
+    let all_pairs = distances(&all_pairs);
+    assert!(ascending(&all_pairs), "{:?}", all_pairs);
+    assert_eq!(distances(&pairs("/api/find-duplicates?threshold=256&limit=3").await), all_pairs[..3]);
+
+    // Thresholds past the hash length are clamped instead of walked distance by distance
+    let huge = format!("threshold={}", u32::MAX);
+    assert_eq!(distances(&pairs(&format!("/api/find-duplicates?{}", huge)).await), all_pairs);
+    let response = app
+        .clone()
+        .oneshot(upload_request(&format!("/api/search-similar?{}", huge), "query.png", &block_texture_png(1, 2)))
+        .await
+        .unwrap();
+    assert_eq!(distances(&ndjson_lines(response).await), all);
+}
+
+#[tokio::test]
+async fn test_status_counts_active_jobs() {
+    let dir = tempfile::tempdir().unwrap();
+    let state = AppState::with_config(isolated_config(&dir));
+    let app = create_router().with_state(state.clone());
+    let status = |app: Router| async move {
+        let request = Request::builder().uri("/api/status").body(Body::empty()).unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        json_body(response).await["data"].clone()
+    };
+
+    let idle = status(app.clone()).await;
+    assert_eq!(idle["active_jobs"], 0);
+    assert_eq!(idle["queued_jobs"], 0);
+    assert_eq!(idle["uploads_in_progress"], 0);
+    assert!(idle["uptime_secs"].is_u64());
+
+    // A blocking job holding a processing slot until released
+    let slot = state.acquire_job_slot().await.unwrap();
+    let (release_tx, release_rx) = std::sync::mpsc::channel::<()>();
+    let job = state.spawn_blocking_job(move || {
+        let _slot = slot;
+        release_rx.recv().ok();
+    });
+
+    assert_eq!(status(app.clone()).await["active_jobs"], 1);
+
+    release_tx.send(()).unwrap();
+    job.await.unwrap();
+    assert_eq!(status(app).await["active_jobs"], 0);
+}
+
+#[tokio::test]
+async fn test_list_manifests_envelope_carries_paging_meta() {
+    let dir = tempfile::tempdir().unwrap();
+    let app = app(&dir);
+    for seed in 1..=2 {
+        let response = app
+            .clone()
+            .oneshot(upload_request("/api/upload", &format!("texture-{}.png", seed), &block_texture_png(seed, 0)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    let request = Request::builder().uri("/api/manifests?limit=1").body(Body::empty()).unwrap();
+    let response = app.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = json_body(response).await;
+    assert_eq!(body["success"], true);
+    assert_eq!(body["data"].as_array().unwrap().len(), 1);
+    assert_eq!(body["meta"], serde_json::json!({ "total": 2, "limit": 1, "offset": 0 }));
+}
+
+#[tokio::test]
+async fn test_batch_job_reports_progress_and_resubmits_idempotently() {
+    let dir = tempfile::tempdir().unwrap();
+    let jobs_dir = tempfile::tempdir().unwrap();
+    let batch = jobs_dir.path().join("batch");
+    std::fs::create_dir(&batch).unwrap();
+    for seed in 1..=2 {
+        std::fs::write(batch.join(format!("texture-{}.png", seed)), block_texture_png(seed, 0)).unwrap();
+    }
+    let app = create_router().with_state(AppState::with_config(Config {
+        jobs_dir: Some(jobs_dir.path().to_path_buf()),
+        ..isolated_config(&dir)
+    }));
+    let submit = |app: Router| async move {
+        let request = Request::builder()
+            .method("POST")
+            .uri("/api/jobs")
+            .header("content-type", "application/json")
+            .body(Body::from(r#"{"source":"batch"}"#))
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        json_body(response).await["data"].clone()
+    };
+
+    let job = submit(app.clone()).await;
+    assert_eq!(job["progress"]["total"], 2);
+    let id = job["id"].as_str().unwrap().to_string();
+
+    let mut report = serde_json::Value::Null;
+    for _ in 0..100 {
+        let request = Request::builder().uri(format!("/api/jobs/{}", id)).body(Body::empty()).unwrap();
+        report = json_body(app.clone().oneshot(request).await.unwrap()).await["data"].clone();
+        if report["progress"]["done"] == 2 && report["running"] == false {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+    assert_eq!(report["progress"]["done"], 2, "{}", report);
+    assert_eq!(report["items"][0]["manifest"]["file_name"], "texture-1.png");
+
+    let again = submit(app.clone()).await;
+    assert_eq!(again["id"], id.as_str());
+    assert_eq!(again["progress"]["done"], 2);
+    assert_eq!(again["running"], false);
+
+    let request = Request::builder().uri("/api/jobs/not-a-job").body(Body::empty()).unwrap();
+    assert_eq!(app.oneshot(request).await.unwrap().status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_each_verification_is_recorded_in_the_audit_log() {
+    let dir = tempfile::tempdir().unwrap();
+    let manifests = tempfile::tempdir().unwrap();
+    let state = AppState::with_config(Config { manifest_dir: Some(manifests.path().to_path_buf()), ..isolated_config(&dir) });
+    let app = create_router().with_state(state);
+    let response = app
+        .clone()
+        .oneshot(upload_request("/api/upload", "audited.png", &block_texture_png(3, 0)))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let manifest = json_body(response).await["data"].clone();
+    let id = manifest["file_name"].as_str().unwrap().to_string();
+
+    for uri in ["/api/verify", "/api/verify?explain=true"] {
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri(uri)
+                    .header("content-type", "application/json")
+                    .body(Body::from(manifest.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(json_body(response).await["data"]["is_valid"], true);
+    }
+
+    let audit = |id: &str| {
+        Request::builder().uri(format!("/api/audit?manifest_id={}", id)).body(Body::empty()).unwrap()
+    };
+    let response = app.clone().oneshot(audit(&id)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let entries = json_body(response).await["data"].clone();
+    let entries = entries.as_array().unwrap();
+    assert_eq!(entries.len(), 2);
+    for entry in entries {
+        assert_eq!(entry["manifest_id"], id.as_str());
+        assert_eq!(entry["outcome"], "valid");
+        assert_eq!(entry["report"]["is_valid"], true);
+        assert!(entry["file_path"].as_str().unwrap().ends_with(&id));
+    }
+
+    let response = app.clone().oneshot(audit("unknown.png")).await.unwrap();
+    assert_eq!(json_body(response).await["data"], serde_json::json!([]));
+
+    // A failed verification with progress records why it failed
+    let mut tampered = manifest.clone();
+    tampered["sha3_256_hash"] = serde_json::json!("0".repeat(64));
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/api/verify/progress")
+                .header("content-type", "application/json")
+                .body(Body::from(tampered.to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(ndjson_lines(response).await.last().unwrap()["is_valid"], false);
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri(format!("/api/audit?manifest_id={}&offset=2&limit=1", id))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let body = json_body(response).await;
+    assert_eq!(body["meta"], serde_json::json!({ "limit": 1, "offset": 2 }));
+    let entries = body["data"].as_array().unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0]["outcome"], "invalid");
+    assert!(entries[0]["report"]["reason"].as_str().unwrap().starts_with("SHA3 hash mismatch"));
+}
+
+#[tokio::test]
+async fn test_uploads_land_in_the_configured_upload_dir() {
+    let dir = tempfile::tempdir().unwrap();
+    let uploads = tempfile::tempdir().unwrap();
+    let manifests = tempfile::tempdir().unwrap();
+    let state = AppState::with_config(Config {
+        upload_dir: uploads.path().join("stored"),
+        manifest_dir: Some(manifests.path().to_path_buf()),
+        ..isolated_config(&dir)
+    });
+    let app = create_router().with_state(state);
+    let response = app
+        .clone()
+        .oneshot(upload_request("/api/upload", "elsewhere.png", &block_texture_png(5, 0)))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let manifest = json_body(response).await["data"].clone();
+    let stored = manifest["file_name"].as_str().unwrap().to_string();
+    assert!(uploads.path().join("stored").join(&stored).is_file());
+    assert!(!std::path::Path::new("uploads").join(&stored).exists());
+
+    // Startup reconciliation scans the same directory, so nothing is orphaned
+    let store = imagechain::DirManifestStore::new(manifests.path());
+    let report = imagechain::reconcile(&store, &uploads.path().join("stored")).unwrap();
+    assert!(report.orphan_files.is_empty() && report.orphan_manifests.is_empty(), "{:?}", report);
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/api/verify")
+                .header("content-type", "application/json")
+                .body(Body::from(manifest.to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(json_body(response).await["data"]["is_valid"], true);
+}
+
+#[tokio::test]
+async fn test_uploaded_animated_gif_verifies() {
+    let dir = tempfile::tempdir().unwrap();
+    use image::codecs::gif::GifEncoder;
+    use image::{Delay, Frame, RgbaImage};
+
+    let frame = |shade: u8| {
+        let img = RgbaImage::from_fn(64, 64, |x, y| image::Rgba([shade ^ (x * 4) as u8, (y * 4) as u8, shade, 255]));
+        Frame::from_parts(img, 0, 0, Delay::from_numer_denom_ms(100, 1))
+    };
+    let mut gif = Vec::new();
+    GifEncoder::new(&mut gif).encode_frames([frame(0), frame(120)]).unwrap();
+
+    let app = app(&dir);
+    let response = app.clone().oneshot(upload_request("/api/upload", "animated.gif", &gif)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let manifest = json_body(response).await["data"].clone();
+    assert_eq!(manifest["frame_count"], 2);
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/api/verify")
+                .header("content-type", "application/json")
+                .body(Body::from(manifest.to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(json_body(response).await["data"]["is_valid"], true);
+}
+
+#[tokio::test]
+async fn test_verify_rejects_manifests_with_too_many_frames() {
+    let dir = tempfile::tempdir().unwrap();
+    let app = app(&dir);
+    let response = app
+        .clone()
+        .oneshot(upload_request("/api/upload", "counted.png", &block_texture_png(6, 0)))
+        .await
+        .unwrap();
+    let mut manifest = json_body(response).await["data"].clone();
+    std::fs::remove_file(dir.path().join("uploads").join(manifest["file_name"].as_str().unwrap())).unwrap();
+    manifest["media_type"] = "video".into();
+    manifest["frames_merkle_root"] = "0".repeat(64).into();
+    manifest["frame_count"] = serde_json::json!(u64::MAX);
+
+    for uri in ["/api/verify", "/api/verify/progress"] {
+        let request = Request::builder()
+            .method("POST")
+            .uri(uri)
+            .header("content-type", "application/json")
+            .body(Body::from(manifest.to_string()))
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", uri);
+        assert!(json_body(response).await["message"].as_str().unwrap().contains("frames"));
+    }
+}
+
+#[tokio::test]
+async fn test_video_comparison_rejects_overlong_frame_lists() {
+    let dir = tempfile::tempdir().unwrap();
+    let video = |frames: usize| {
+        let frames: Vec<serde_json::Value> = (0..frames)
+            .map(|i| serde_json::json!({ "timestamp_secs": i as f64, "pdq_hash": format!("{:016x}", i * 7919) }))
+            .collect();
+        serde_json::json!({
+            "media_type": "video",
+            "file_name": "clip.mp4",
+            "file_size": 1,
+            "created_at": "2024-01-01T00:00:00Z",
+            "modified_at": "2024-01-01T00:00:00Z",
+            "sha3_256_hash": "0".repeat(64),
+            "pdq_hash": null,
+            "frames": frames,
+            "metadata": null,
+        })
+    };
+    let post = |uri: &str, a: serde_json::Value, b: serde_json::Value| {
+        Request::builder()
+            .method("POST")
+            .uri(uri)
+            .header("content-type", "application/json")
+            .body(Body::from(serde_json::json!({ "a": a, "b": b }).to_string()))
+            .unwrap()
+    };
+    let app = app(&dir);
+    for uri in ["/api/video-compare", "/api/video-similarity"] {
+        let response = app.clone().oneshot(post(uri, video(3), video(3))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK, "{}", uri);
+
+        let response = app.clone().oneshot(post(uri, video(3), video(2_001))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", uri);
+    }
+
+    let response = app.oneshot(post("/api/video-compare", video(3), video(3))).await.unwrap();
+    let body = json_body(response).await;
+    assert_eq!(body["data"]["matched"], 3);
+}
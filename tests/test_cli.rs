@@ -0,0 +1,71 @@
+use std::process::Command;
+
+use imagechain::{process_image, MediaManifest};
+
+#[test]
+fn test_verify_dir_writes_junit_report() {
+    let media = tempfile::tempdir().unwrap();
+    let manifests = tempfile::tempdir().unwrap();
+
+    for (name, shade) in [("good.png", 40u8), ("tampered.png", 200u8)] {
+        let path = media.path().join(name);
+        image::RgbImage::from_fn(16, 16, |x, y| image::Rgb([shade, (x * 16) as u8, (y * 16) as u8]))
+            .save(&path)
+            .unwrap();
+        process_image(&path)
+            .unwrap()
+            .save(manifests.path().join(format!("{}.json", name)))
+            .unwrap();
+    }
+
+    // Flip one byte so the size stays the same but the hash changes
+    let tampered = media.path().join("tampered.png");
+    let mut bytes = std::fs::read(&tampered).unwrap();
+    let last = bytes.len() - 1;
+    bytes[last] ^= 0xff;
+    std::fs::write(&tampered, bytes).unwrap();
+
+    let report = manifests.path().join("report.xml");
+    let status = Command::new(env!("CARGO_BIN_EXE_imagechain"))
+        .arg("verify-dir")
+        .arg(media.path())
+        .arg("--manifests")
+        .arg(manifests.path())
+        .arg("--junit")
+        .arg(&report)
+        .status()
+        .unwrap();
+
+    assert!(!status.success());
+    let xml = std::fs::read_to_string(&report).unwrap();
+    assert!(xml.contains("tests=\"2\" failures=\"1\" errors=\"0\""));
+    assert!(xml.contains("name=\"good.png\"/>"));
+    assert!(xml.contains("name=\"tampered.png\">"));
+    assert!(xml.contains("<failure message=\"SHA3 hash mismatch"));
+}
+
+#[test]
+fn test_rehash_perceptual_updates_manifests() {
+    let media = tempfile::tempdir().unwrap();
+    let path = media.path().join("photo.png");
+    image::RgbImage::from_fn(32, 32, |x, y| image::Rgb([(x * 8) as u8, 90, (y * 8) as u8]))
+        .save(&path)
+        .unwrap();
+
+    let mut manifest = process_image(&path).unwrap();
+    let fresh_pdq = manifest.pdq_hash.clone();
+    manifest.pdq_hash = Some("0".repeat(16));
+    let manifest_path = media.path().join("photo.png.json");
+    manifest.save(&manifest_path).unwrap();
+
+    let status = Command::new(env!("CARGO_BIN_EXE_imagechain"))
+        .arg("rehash-perceptual")
+        .arg(media.path())
+        .status()
+        .unwrap();
+
+    assert!(status.success());
+    let rehashed = MediaManifest::load(&manifest_path).unwrap();
+    assert_eq!(rehashed.pdq_hash, fresh_pdq);
+    assert_eq!(rehashed.sha3_256_hash, manifest.sha3_256_hash);
+}
@@ -81,3 +81,75 @@ fn test_manifest_creation() {
     assert_eq!(deserialized.media_type, MediaType::Image);
     assert_eq!(deserialized.sha3_256_hash, "test_hash");
 }
+
+#[test]
+fn test_process_image_bytes_rejects_empty_input() {
+    use imagechain::{process_image_bytes, AppError};
+
+    let result = process_image_bytes("empty.png", &[]);
+    assert!(matches!(result, Err(AppError::Validation(msg)) if msg == "empty file"));
+}
+
+#[test]
+fn test_process_image_bytes_names_undecodable_file() {
+    use imagechain::{process_image_bytes, AppError};
+
+    let result = process_image_bytes("one_byte.png", &[0x89]);
+    assert!(matches!(result, Err(AppError::InvalidInput(msg)) if msg.contains("one_byte.png")));
+}
+
+#[test]
+fn test_process_image_missing_file() {
+    use imagechain::{process_image, AppError};
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("missing.png");
+    let result = process_image(&path);
+    assert!(matches!(result, Err(AppError::NotFound(msg)) if msg.contains("missing.png")));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_process_image_unreadable_file() {
+    use imagechain::{process_image, AppError};
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("locked.png");
+    RgbImage::new(4, 4).save(&path).unwrap();
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o000)).unwrap();
+
+    // Privileged users (e.g. root in CI containers) bypass file permissions
+    if std::fs::read(&path).is_ok() {
+        return;
+    }
+
+    let result = process_image(&path);
+    assert!(matches!(result, Err(AppError::PermissionDenied(msg)) if msg.contains("locked.png")));
+}
+
+#[test]
+fn test_process_directory_parallel_keeps_path_order_past_bad_files() {
+    let dir = tempfile::tempdir().unwrap();
+    for (name, shade) in [("d.png", 200u8), ("a.png", 10), ("c.png", 90)] {
+        RgbImage::from_fn(32, 32, |x, y| image::Rgb([shade, (x * 8) as u8, (y * 8) as u8]))
+            .save(dir.path().join(name))
+            .unwrap();
+    }
+    // A PNG signature followed by garbage fails to decode
+    std::fs::write(dir.path().join("b.png"), b"\x89PNG\r\n\x1a\nnot really").unwrap();
+    std::fs::create_dir(dir.path().join("nested")).unwrap();
+
+    for _ in 0..3 {
+        let results = imagechain::process_directory_parallel(dir.path()).unwrap();
+        let names: Vec<_> = results
+            .iter()
+            .map(|(path, _)| path.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(names, ["a.png", "b.png", "c.png", "d.png"]);
+        assert!(results[1].1.is_err());
+        for i in [0, 2, 3] {
+            assert_eq!(results[i].1.as_ref().unwrap().file_name, names[i]);
+        }
+    }
+}